@@ -138,7 +138,11 @@ fn preprocess(spec: &str) -> Vec<Vec<String>> {
         .collect::<Vec<_>>()
 }
 
-fn codegen() -> Vec<(String, String, Vec<(String, String)>)> {
+/// Per ISA: module name, module source, and `(file name, contents)` of the
+/// lookup tables it includes.
+type GeneratedIsa = (String, String, Vec<(String, String)>);
+
+fn codegen() -> Vec<GeneratedIsa> {
     let operands = preprocess(RISCV_OPERANDS);
     let opcodes = preprocess(RISCV_OPCODES)
         .into_iter()
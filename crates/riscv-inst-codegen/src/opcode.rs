@@ -87,13 +87,13 @@ impl Opcode {
 
         let operands = args[1..]
             .iter()
-            .filter(|arg| arg.chars().next().map_or(false, char::is_alphabetic))
+            .filter(|arg| arg.chars().next().is_some_and(char::is_alphabetic))
             .cloned()
             .collect();
 
         let encodings = args[1..]
             .iter()
-            .filter(|arg| arg.chars().next().map_or(false, char::is_numeric))
+            .filter(|arg| arg.chars().next().is_some_and(char::is_numeric))
             .filter_map(BitEnc::parse)
             .collect();
 
@@ -0,0 +1,47 @@
+//! Embedder-visible knobs for the ELF auxiliary vector.
+//!
+//! Everything the loader cannot derive from the ELF image or the hart
+//! (identity, platform strings, signal stack size) lives here.
+
+/// Process credentials, as reported in the auxv (and to the `get*id`
+/// syscalls).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Credentials {
+    pub uid: u32,
+    pub euid: u32,
+    pub gid: u32,
+    pub egid: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct AuxvConfig {
+    pub credentials: Credentials,
+    /// `AT_SECURE`: tells libc to ignore `LD_*`-style environment overrides.
+    pub secure: bool,
+    /// `AT_HWCAP` override. `None` reports the extensions the hart executes.
+    pub hwcap: Option<u64>,
+    /// `AT_PLATFORM` string; omitted from the auxv when `None`.
+    pub platform: Option<String>,
+    /// `AT_EXECFN`, and the target of `/proc/self/exe`.
+    pub execfn: String,
+    /// `AT_MINSIGSTKSZ`.
+    pub minsigstksz: u64,
+}
+
+/// Linux's `MINSIGSTKSZ` for riscv without the vector extension.
+pub const DEFAULT_MINSIGSTKSZ: u64 = 2048;
+
+impl AuxvConfig {
+    /// Defaults for an rv`bits` process: root credentials, the hart's own
+    /// extensions, and platform `riscv<bits>`.
+    pub fn new(bits: u32) -> Self {
+        Self {
+            credentials: Credentials::default(),
+            secure: false,
+            hwcap: None,
+            platform: Some(format!("riscv{bits}")),
+            execfn: "/tmp/main".to_string(),
+            minsigstksz: DEFAULT_MINSIGSTKSZ,
+        }
+    }
+}
@@ -0,0 +1,72 @@
+//! Guest-visible randomness: `getrandom` and the `AT_RANDOM` auxv blob.
+//!
+//! Production runs draw from the host OS; test runs can pin a seed so that
+//! stack canaries, hash-map seeds, and anything else a guest derives from
+//! its entropy are identical from run to run.
+use std::{fs::File, io::Read};
+
+#[derive(Debug, Clone, Default)]
+pub enum Entropy {
+    /// Host OS randomness (`/dev/urandom`).
+    #[default]
+    Os,
+    /// A deterministic xoshiro256** stream.
+    Seeded(Xoshiro256),
+}
+
+impl Entropy {
+    pub fn seeded(seed: u64) -> Self {
+        Self::Seeded(Xoshiro256::new(seed))
+    }
+
+    pub fn fill(&mut self, buf: &mut [u8]) -> std::io::Result<()> {
+        match self {
+            Self::Os => File::open("/dev/urandom")?.read_exact(buf),
+            Self::Seeded(rng) => {
+                for chunk in buf.chunks_mut(8) {
+                    let word = rng.next_u64().to_le_bytes();
+                    chunk.copy_from_slice(&word[..chunk.len()]);
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// xoshiro256** (Blackman & Vigna). Not cryptographic: only meant to make
+/// guest runs reproducible.
+#[derive(Debug, Clone)]
+pub struct Xoshiro256 {
+    s: [u64; 4],
+}
+
+impl Xoshiro256 {
+    /// Expand `seed` with splitmix64, as the reference implementation
+    /// recommends (and which never yields the all-zero state).
+    pub fn new(seed: u64) -> Self {
+        let mut x = seed;
+        let mut splitmix = || {
+            x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = x;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        };
+        Self {
+            s: [splitmix(), splitmix(), splitmix(), splitmix()],
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let s = &mut self.s;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+}
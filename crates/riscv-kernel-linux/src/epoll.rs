@@ -0,0 +1,262 @@
+//! `epoll`: an interest list over open file descriptions.
+//!
+//! Items hold their description weakly, so closing the last descriptor for
+//! a file drops it from every interest list, as on Linux. Edge-triggered
+//! items report once per change of the file's readiness sequence (see
+//! [`Readiness::seq`]) rather than on true edges, which may yield a spurious
+//! wakeup but never loses one.
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex, Weak},
+    time::Duration,
+};
+
+use riscv_vm::{
+    error::MemoryAccess,
+    memory::{Memory, Pod},
+};
+
+use crate::{
+    fd::{lock, Fd, Node, OpenFile},
+    poll::{read_timespec, Readiness},
+    KernelXlen, MockLinux,
+};
+
+/// `struct epoll_event`. Only x86-64 packs it; on riscv `data` is aligned.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct EpollEvent {
+    events: u32,
+    _pad: u32,
+    data: u64,
+}
+// Safety: integers only; any bit pattern valid, padding is explicit.
+unsafe impl Pod for EpollEvent {}
+
+#[derive(Debug)]
+struct Item {
+    file: Weak<Mutex<OpenFile>>,
+    events: u32,
+    data: u64,
+    /// Readiness sequence last reported, for `EPOLLET`.
+    seen: Option<u64>,
+    /// Reported once under `EPOLLONESHOT`; idle until re-armed.
+    disarmed: bool,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Epoll {
+    /// Keyed by the descriptor the item was added under.
+    items: BTreeMap<i32, Item>,
+}
+
+/// Readiness of an item's file, or `None` once it has been closed.
+fn poll_item(item: &Item) -> Option<Readiness> {
+    let file = item.file.upgrade()?;
+    let readiness = lock(&file).node.poll();
+    Some(readiness)
+}
+
+/// The events an item reports for `readiness`: what it asked for plus
+/// errors and hangups.
+fn reported(item: &Item, readiness: Readiness) -> u32 {
+    readiness.mask & (item.events | libc_riscv32::EPOLLERR | libc_riscv32::EPOLLHUP)
+}
+
+impl Epoll {
+    /// Whether any item would be reported (for polling the epoll fd itself).
+    pub(crate) fn any_ready(&self) -> bool {
+        self.items.values().any(|item| {
+            !item.disarmed
+                && poll_item(item).is_some_and(|r| {
+                    reported(item, r) != 0
+                        && (item.events & libc_riscv32::EPOLLET == 0 || item.seen != Some(r.seq))
+                })
+        })
+    }
+
+    /// Collect up to `max` events, updating edge and one-shot state.
+    fn ready(&mut self, max: usize) -> Vec<EpollEvent> {
+        self.items.retain(|_, item| item.file.strong_count() > 0);
+        let mut events = Vec::new();
+        for item in self.items.values_mut() {
+            if events.len() == max {
+                break;
+            }
+            let Some(readiness) = poll_item(item).filter(|_| !item.disarmed) else {
+                continue;
+            };
+            let mask = reported(item, readiness);
+            if mask == 0 {
+                continue;
+            }
+            if item.events & libc_riscv32::EPOLLET != 0 {
+                if item.seen == Some(readiness.seq) {
+                    continue;
+                }
+                item.seen = Some(readiness.seq);
+            }
+            if item.events & libc_riscv32::EPOLLONESHOT != 0 {
+                item.disarmed = true;
+            }
+            events.push(EpollEvent {
+                events: mask,
+                _pad: 0,
+                data: item.data,
+            });
+        }
+        events
+    }
+}
+
+impl<X: KernelXlen, M: Memory> MockLinux<X, M> {
+    pub(crate) fn epoll_create1(&mut self, flags: u32) -> Result<u64, i32> {
+        if flags & !libc_riscv32::EPOLL_CLOEXEC != 0 {
+            return Err(libc_riscv32::EINVAL);
+        }
+        let file = OpenFile::new(
+            Node::Epoll(Arc::new(Mutex::new(Epoll::default()))),
+            libc_riscv32::O_RDWR,
+        );
+        Ok(self.fds.insert(Fd::new(file, flags != 0))? as u64)
+    }
+
+    /// The epoll instance behind `epfd`.
+    fn epoll(&self, epfd: i32) -> Result<Arc<Mutex<Epoll>>, i32> {
+        match &self.fds.get(epfd)?.lock().node {
+            Node::Epoll(epoll) => Ok(epoll.clone()),
+            _ => Err(libc_riscv32::EINVAL),
+        }
+    }
+
+    pub(crate) fn epoll_ctl(
+        &mut self,
+        mem: &M,
+        epfd: i32,
+        op: i32,
+        fd: i32,
+        event: u64,
+    ) -> Result<u64, i32> {
+        use libc_riscv32::{EPOLL_CTL_ADD, EPOLL_CTL_DEL, EPOLL_CTL_MOD};
+
+        let epoll = self.epoll(epfd)?;
+        let target = self.fds.get(fd)?;
+        match &target.lock().node {
+            _ if fd == epfd => return Err(libc_riscv32::EINVAL),
+            // Regular files and directories are always ready; Linux refuses
+            // to watch them.
+            Node::Inode { .. } => return Err(libc_riscv32::EPERM),
+            // Nested epoll instances are not supported.
+            Node::Epoll(_) => return Err(libc_riscv32::EINVAL),
+            _ => {}
+        }
+        let file = Arc::downgrade(&target.file);
+
+        let event = if op == EPOLL_CTL_DEL {
+            None
+        } else {
+            Some(
+                mem.slice::<EpollEvent>(event, 1)
+                    .map_err(|_| libc_riscv32::EFAULT)?[0],
+            )
+        };
+
+        let mut epoll = lock(&epoll);
+        // An item whose file has since been closed no longer exists, even if
+        // its descriptor number has been reused.
+        let live = epoll
+            .items
+            .get(&fd)
+            .is_some_and(|item| Weak::ptr_eq(&item.file, &file));
+        match (op, event) {
+            (EPOLL_CTL_ADD, Some(event)) => {
+                if live {
+                    return Err(libc_riscv32::EEXIST);
+                }
+                epoll.items.insert(
+                    fd,
+                    Item {
+                        file,
+                        events: event.events,
+                        data: event.data,
+                        seen: None,
+                        disarmed: false,
+                    },
+                );
+            }
+            (EPOLL_CTL_MOD, Some(event)) => {
+                let item = epoll
+                    .items
+                    .get_mut(&fd)
+                    .filter(|_| live)
+                    .ok_or(libc_riscv32::ENOENT)?;
+                item.events = event.events;
+                item.data = event.data;
+                item.seen = None;
+                item.disarmed = false;
+            }
+            (EPOLL_CTL_DEL, _) => {
+                if !live {
+                    return Err(libc_riscv32::ENOENT);
+                }
+                epoll.items.remove(&fd);
+            }
+            _ => return Err(libc_riscv32::EINVAL),
+        }
+        Ok(0)
+    }
+
+    fn epoll_wait(
+        &mut self,
+        mem: &mut M,
+        epfd: i32,
+        events: u64,
+        maxevents: i32,
+        timeout: Option<Duration>,
+    ) -> Result<u64, i32> {
+        let max = usize::try_from(maxevents)
+            .ok()
+            .filter(|&max| max > 0 && max <= i32::MAX as usize / size_of::<EpollEvent>())
+            .ok_or(libc_riscv32::EINVAL)?;
+        mem.check_range(
+            MemoryAccess::Store,
+            events,
+            (max * size_of::<EpollEvent>()) as u64,
+        )
+        .map_err(|_| libc_riscv32::EFAULT)?;
+        let epoll = self.epoll(epfd)?;
+
+        let ready = lock(&epoll).ready(max);
+        if ready.is_empty() {
+            self.park(timeout)?;
+        }
+        mem.copy_to(events, &ready)
+            .map_err(|_| libc_riscv32::EFAULT)?;
+        Ok(ready.len() as u64)
+    }
+
+    pub(crate) fn epoll_pwait(
+        &mut self,
+        mem: &mut M,
+        epfd: i32,
+        events: u64,
+        maxevents: i32,
+        timeout_ms: i32,
+    ) -> Result<u64, i32> {
+        // Negative means forever.
+        let timeout = u64::try_from(timeout_ms).ok().map(Duration::from_millis);
+        self.epoll_wait(mem, epfd, events, maxevents, timeout)
+    }
+
+    pub(crate) fn epoll_pwait2(
+        &mut self,
+        mem: &mut M,
+        epfd: i32,
+        events: u64,
+        maxevents: i32,
+        tsp: u64,
+    ) -> Result<u64, i32> {
+        let timeout = read_timespec(mem, tsp)?;
+        self.epoll_wait(mem, epfd, events, maxevents, timeout)
+    }
+}
@@ -0,0 +1,109 @@
+//! `eventfd`: a 64-bit counter behind a descriptor, used by async runtimes
+//! to wake their poll loop.
+use std::sync::{Arc, Mutex};
+
+use riscv_vm::memory::Memory;
+
+use crate::{
+    fd::{lock, Fd, Node, OpenFile},
+    poll::Readiness,
+    KernelXlen, MockLinux,
+};
+
+/// The counter saturates one below `u64::MAX`.
+const MAX: u64 = u64::MAX - 1;
+
+#[derive(Debug)]
+pub(crate) struct EventFd {
+    counter: u64,
+    semaphore: bool,
+    /// Bumped on every state change, for edge-triggered epoll.
+    seq: u64,
+}
+
+impl EventFd {
+    /// Take the counter (or one unit of it in semaphore mode), or `EAGAIN`
+    /// while it is zero.
+    fn read(&mut self) -> Result<u64, i32> {
+        if self.counter == 0 {
+            return Err(libc_riscv32::EAGAIN);
+        }
+        let value = if self.semaphore { 1 } else { self.counter };
+        self.counter -= value;
+        self.seq += 1;
+        Ok(value)
+    }
+
+    /// Add to the counter, or `EAGAIN` while that would pass the maximum.
+    fn write(&mut self, value: u64) -> Result<(), i32> {
+        if value == u64::MAX {
+            return Err(libc_riscv32::EINVAL);
+        }
+        if value > MAX - self.counter {
+            return Err(libc_riscv32::EAGAIN);
+        }
+        self.counter += value;
+        self.seq += 1;
+        Ok(())
+    }
+
+    pub(crate) fn poll(&self) -> Readiness {
+        let mut mask = 0;
+        if self.counter > 0 {
+            mask |= libc_riscv32::POLLIN;
+        }
+        if self.counter < MAX {
+            mask |= libc_riscv32::POLLOUT;
+        }
+        Readiness {
+            mask: mask as u32,
+            seq: self.seq,
+        }
+    }
+}
+
+impl<X: KernelXlen, M: Memory> MockLinux<X, M> {
+    pub(crate) fn eventfd2(&mut self, initval: u32, flags: u32) -> Result<u64, i32> {
+        use libc_riscv32::{EFD_CLOEXEC, EFD_NONBLOCK, EFD_SEMAPHORE};
+
+        if flags & !(EFD_CLOEXEC | EFD_NONBLOCK | EFD_SEMAPHORE) != 0 {
+            return Err(libc_riscv32::EINVAL);
+        }
+        let eventfd = EventFd {
+            counter: initval as u64,
+            semaphore: flags & EFD_SEMAPHORE != 0,
+            seq: 0,
+        };
+        let file = OpenFile::new(
+            Node::EventFd(Arc::new(Mutex::new(eventfd))),
+            libc_riscv32::O_RDWR | (flags & EFD_NONBLOCK),
+        );
+        Ok(self.fds.insert(Fd::new(file, flags & EFD_CLOEXEC != 0))? as u64)
+    }
+}
+
+/// `read` on an eventfd: exactly one `u64`.
+pub(crate) fn read<M: Memory>(
+    mem: &mut M,
+    eventfd: &Mutex<EventFd>,
+    buf: u64,
+    count: u64,
+) -> Result<u64, i32> {
+    if count < 8 {
+        return Err(libc_riscv32::EINVAL);
+    }
+    let value = lock(eventfd).read()?;
+    mem.store_at::<u64>(buf, value)
+        .map_err(|_| libc_riscv32::EFAULT)?;
+    Ok(8)
+}
+
+/// `write` on an eventfd: exactly one `u64`.
+pub(crate) fn write(eventfd: &Mutex<EventFd>, bytes: &[u8]) -> Result<u64, i32> {
+    let value: [u8; 8] = bytes
+        .get(..8)
+        .and_then(|b| b.try_into().ok())
+        .ok_or(libc_riscv32::EINVAL)?;
+    lock(eventfd).write(u64::from_le_bytes(value))?;
+    Ok(8)
+}
@@ -0,0 +1,240 @@
+//! The per-process file descriptor table.
+//!
+//! Descriptors point at shared open file descriptions (offset + status
+//! flags), so duplicated descriptors observe each other's seeks, as on
+//! Linux. Only the close-on-exec flag belongs to the descriptor itself.
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use riscv_vm::memory::Memory;
+
+use crate::{
+    epoll::Epoll, eventfd::EventFd, fs::Inode, pipe::PipeEnd, procfs::Device, socket::SocketFile,
+    KernelXlen, MockLinux,
+};
+
+/// Size of the descriptor table (`RLIMIT_NOFILE`).
+pub(crate) const MAX_FDS: usize = 1024;
+
+/// What an open file description refers to.
+#[derive(Debug, Clone)]
+pub(crate) enum Node {
+    Stdin,
+    Stdout,
+    Stderr,
+    /// A file or directory from the guest filesystem, with the absolute
+    /// path it was opened by.
+    Inode {
+        path: String,
+        inode: Arc<Mutex<Inode>>,
+    },
+    Pipe(PipeEnd),
+    EventFd(Arc<Mutex<EventFd>>),
+    Epoll(Arc<Mutex<Epoll>>),
+    Socket(Arc<Mutex<SocketFile>>),
+    Device(Device),
+}
+
+#[derive(Debug)]
+pub(crate) struct OpenFile {
+    pub(crate) node: Node,
+    /// Access mode and status flags (`O_RDWR`, `O_APPEND`, ...).
+    pub(crate) flags: u32,
+    pub(crate) offset: u64,
+}
+
+impl OpenFile {
+    pub(crate) fn new(node: Node, flags: u32) -> Self {
+        Self {
+            node,
+            flags,
+            offset: 0,
+        }
+    }
+
+    pub(crate) fn readable(&self) -> bool {
+        self.flags & libc_riscv32::O_ACCMODE != libc_riscv32::O_WRONLY
+    }
+
+    pub(crate) fn writable(&self) -> bool {
+        self.flags & libc_riscv32::O_ACCMODE != libc_riscv32::O_RDONLY
+    }
+
+    pub(crate) fn nonblock(&self) -> bool {
+        self.flags & libc_riscv32::O_NONBLOCK != 0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Fd {
+    pub(crate) file: Arc<Mutex<OpenFile>>,
+    /// `FD_CLOEXEC`. There is no exec, so this is only ever reported back.
+    pub(crate) cloexec: bool,
+}
+
+impl Fd {
+    pub(crate) fn new(file: OpenFile, cloexec: bool) -> Self {
+        Self {
+            file: Arc::new(Mutex::new(file)),
+            cloexec,
+        }
+    }
+
+    /// Another descriptor for the same open file description.
+    fn dup(&self, cloexec: bool) -> Self {
+        Self {
+            file: self.file.clone(),
+            cloexec,
+        }
+    }
+
+    pub(crate) fn lock(&self) -> MutexGuard<'_, OpenFile> {
+        lock(&self.file)
+    }
+}
+
+/// Lock a kernel object. The kernel never panics while holding a lock, so
+/// poisoning only follows a panic that already tore the machine down.
+pub(crate) fn lock<T>(m: &Mutex<T>) -> MutexGuard<'_, T> {
+    m.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[derive(Debug)]
+pub(crate) struct FdTable {
+    fds: Vec<Option<Fd>>,
+    /// Descriptors are below this (`RLIMIT_NOFILE`).
+    limit: usize,
+}
+
+impl FdTable {
+    /// A table with 0/1/2 open on the host's stdio.
+    pub(crate) fn with_stdio() -> Self {
+        let stdio = [
+            (Node::Stdin, libc_riscv32::O_RDONLY),
+            (Node::Stdout, libc_riscv32::O_WRONLY),
+            (Node::Stderr, libc_riscv32::O_WRONLY),
+        ];
+        Self {
+            fds: stdio
+                .into_iter()
+                .map(|(node, flags)| Some(Fd::new(OpenFile::new(node, flags), false)))
+                .collect(),
+            limit: MAX_FDS,
+        }
+    }
+
+    pub(crate) fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Bound new descriptors; those already open stay.
+    pub(crate) fn set_limit(&mut self, limit: u64) {
+        self.limit = limit.min(MAX_FDS as u64) as usize;
+    }
+
+    pub(crate) fn get(&self, fd: i32) -> Result<&Fd, i32> {
+        usize::try_from(fd)
+            .ok()
+            .and_then(|fd| self.fds.get(fd))
+            .and_then(Option::as_ref)
+            .ok_or(libc_riscv32::EBADF)
+    }
+
+    /// Install at the lowest free descriptor.
+    pub(crate) fn insert(&mut self, fd: Fd) -> Result<i32, i32> {
+        self.insert_from(0, fd)
+    }
+
+    /// Install at the lowest free descriptor `>= min`.
+    fn insert_from(&mut self, min: usize, fd: Fd) -> Result<i32, i32> {
+        let slot = (min..self.limit)
+            .find(|&slot| self.fds.get(slot).is_none_or(Option::is_none))
+            .ok_or(libc_riscv32::EMFILE)?;
+        self.set(slot, fd);
+        Ok(slot as i32)
+    }
+
+    /// Install at `slot`, closing whatever was there.
+    fn set(&mut self, slot: usize, fd: Fd) {
+        if self.fds.len() <= slot {
+            self.fds.resize_with(slot + 1, || None);
+        }
+        self.fds[slot] = Some(fd);
+    }
+
+    fn get_mut(&mut self, fd: i32) -> Result<&mut Fd, i32> {
+        usize::try_from(fd)
+            .ok()
+            .and_then(|fd| self.fds.get_mut(fd))
+            .and_then(Option::as_mut)
+            .ok_or(libc_riscv32::EBADF)
+    }
+
+    pub(crate) fn remove(&mut self, fd: i32) -> Result<Fd, i32> {
+        usize::try_from(fd)
+            .ok()
+            .and_then(|fd| self.fds.get_mut(fd))
+            .and_then(Option::take)
+            .ok_or(libc_riscv32::EBADF)
+    }
+}
+
+impl<X: KernelXlen, M: Memory> MockLinux<X, M> {
+    pub(crate) fn close(&mut self, fd: i32) -> Result<u64, i32> {
+        self.fds.remove(fd)?;
+        Ok(0)
+    }
+
+    pub(crate) fn dup(&mut self, oldfd: i32) -> Result<u64, i32> {
+        let fd = self.fds.get(oldfd)?.dup(false);
+        Ok(self.fds.insert(fd)? as u64)
+    }
+
+    pub(crate) fn dup3(&mut self, oldfd: i32, newfd: i32, flags: u32) -> Result<u64, i32> {
+        if flags & !libc_riscv32::O_CLOEXEC != 0 || oldfd == newfd {
+            return Err(libc_riscv32::EINVAL);
+        }
+        let fd = self.fds.get(oldfd)?.dup(flags != 0);
+        let slot = usize::try_from(newfd)
+            .ok()
+            .filter(|&slot| slot < self.fds.limit)
+            .ok_or(libc_riscv32::EBADF)?;
+        self.fds.set(slot, fd);
+        Ok(newfd as u64)
+    }
+
+    pub(crate) fn fcntl(&mut self, fd: i32, cmd: u32, arg: u64) -> Result<u64, i32> {
+        use libc_riscv32::*;
+
+        match cmd {
+            F_DUPFD | F_DUPFD_CLOEXEC => {
+                let min = usize::try_from(arg as i32)
+                    .ok()
+                    .filter(|&min| min < self.fds.limit)
+                    .ok_or(EINVAL)?;
+                let fd = self.fds.get(fd)?.dup(cmd == F_DUPFD_CLOEXEC);
+                Ok(self.fds.insert_from(min, fd)? as u64)
+            }
+            F_GETFD => Ok(if self.fds.get(fd)?.cloexec {
+                FD_CLOEXEC as u64
+            } else {
+                0
+            }),
+            F_SETFD => {
+                self.fds.get_mut(fd)?.cloexec = arg as u32 & FD_CLOEXEC != 0;
+                Ok(0)
+            }
+            F_GETFL => Ok(self.fds.get(fd)?.lock().flags as u64),
+            F_SETFL => {
+                // The access mode and creation flags are fixed at open.
+                const SETTABLE: u32 = O_APPEND | O_NONBLOCK | O_ASYNC | O_DIRECT | O_NOATIME;
+                let mut file = self.fds.get(fd)?.lock();
+                file.flags = (file.flags & !SETTABLE) | (arg as u32 & SETTABLE);
+                Ok(0)
+            }
+            _ => {
+                tracing::warn!("fcntl: unsupported command {cmd}");
+                Err(EINVAL)
+            }
+        }
+    }
+}
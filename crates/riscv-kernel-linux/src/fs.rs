@@ -0,0 +1,716 @@
+//! The guest filesystem and the path- and fd-based file syscalls over it.
+//!
+//! The filesystem is an in-memory tree the embedder populates (and reads
+//! results back from) through [`GuestFs`]; nothing here touches the host
+//! filesystem. Paths are stored normalized and absolute.
+use std::{
+    collections::BTreeMap,
+    io::Read,
+    sync::{Arc, Mutex},
+};
+
+use riscv_vm::{
+    error::MemoryAccess,
+    memory::{Memory, Pod},
+};
+
+use crate::{
+    eventfd,
+    fd::{lock, Fd, Node, OpenFile},
+    poll::park_unless,
+    procfs::Device,
+    KernelXlen, MockLinux,
+};
+
+/// Preferred I/O size reported for every file.
+const BLKSIZE: u32 = 4096;
+
+/// Largest a file may grow (`EFBIG` past it), as a filesystem's maximum
+/// file size. Files live in host memory, and a guest can seek anywhere
+/// before writing.
+pub(crate) const MAX_FILE_SIZE: u64 = 256 << 20;
+
+#[derive(Debug)]
+pub struct Inode {
+    pub(crate) ino: u64,
+    /// `st_mode`: file type and permission bits.
+    pub(crate) mode: u32,
+    /// Contents (regular files only).
+    pub(crate) data: Vec<u8>,
+}
+
+impl Inode {
+    pub(crate) fn is_dir(&self) -> bool {
+        self.mode & libc_riscv32::S_IFMT == libc_riscv32::S_IFDIR
+    }
+}
+
+#[derive(Debug)]
+pub struct GuestFs {
+    nodes: BTreeMap<String, Arc<Mutex<Inode>>>,
+    next_ino: u64,
+}
+
+impl Default for GuestFs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GuestFs {
+    /// An empty filesystem: just `/`.
+    pub fn new() -> Self {
+        let mut fs = Self {
+            nodes: BTreeMap::new(),
+            next_ino: 1,
+        };
+        fs.insert("/".to_string(), libc_riscv32::S_IFDIR | 0o755, vec![]);
+        fs
+    }
+
+    /// Create (or replace) a regular file, creating missing parent
+    /// directories.
+    pub fn add_file(&mut self, path: &str, contents: impl Into<Vec<u8>>) {
+        let path = normalize("/", path);
+        self.add_parents(&path);
+        self.insert(path, libc_riscv32::S_IFREG | 0o644, contents.into());
+    }
+
+    /// Create a directory (and any missing parents).
+    pub fn add_dir(&mut self, path: &str) {
+        let path = normalize("/", path);
+        self.add_parents(&path);
+        if !self.nodes.contains_key(&path) {
+            self.insert(path, libc_riscv32::S_IFDIR | 0o755, vec![]);
+        }
+    }
+
+    /// Contents of a regular file, e.g. to collect a guest's output.
+    pub fn read_file(&self, path: &str) -> Option<Vec<u8>> {
+        let inode = self.nodes.get(&normalize("/", path))?;
+        let inode = lock(inode);
+        (!inode.is_dir()).then(|| inode.data.clone())
+    }
+
+    fn add_parents(&mut self, path: &str) {
+        for (i, _) in path.match_indices('/').skip(1) {
+            let parent = &path[..i];
+            if !self.nodes.contains_key(parent) {
+                self.insert(parent.to_string(), libc_riscv32::S_IFDIR | 0o755, vec![]);
+            }
+        }
+    }
+
+    fn insert(&mut self, path: String, mode: u32, data: Vec<u8>) -> Arc<Mutex<Inode>> {
+        let inode = Arc::new(Mutex::new(Inode {
+            ino: self.next_ino,
+            mode,
+            data,
+        }));
+        self.next_ino += 1;
+        self.nodes.insert(path, inode.clone());
+        inode
+    }
+
+    /// Look up a normalized absolute path: `ENOTDIR` if a prefix is not a
+    /// directory, `ENOENT` if anything is missing.
+    pub(crate) fn lookup(&self, path: &str) -> Result<Arc<Mutex<Inode>>, i32> {
+        self.check_parents(path)?;
+        self.nodes.get(path).cloned().ok_or(libc_riscv32::ENOENT)
+    }
+
+    /// Create an empty regular file; the parent must exist.
+    pub(crate) fn create(&mut self, path: &str, mode: u32) -> Result<Arc<Mutex<Inode>>, i32> {
+        self.check_parents(path)?;
+        Ok(self.insert(
+            path.to_string(),
+            libc_riscv32::S_IFREG | (mode & 0o7777),
+            vec![],
+        ))
+    }
+
+    fn check_parents(&self, path: &str) -> Result<(), i32> {
+        for (i, _) in path.match_indices('/').skip(1) {
+            match self.nodes.get(&path[..i]) {
+                Some(parent) if lock(parent).is_dir() => {}
+                Some(_) => return Err(libc_riscv32::ENOTDIR),
+                None => return Err(libc_riscv32::ENOENT),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Resolve `path` against the absolute directory `base`, collapsing `.`,
+/// `..` and repeated slashes. `..` at the root stays at the root.
+pub(crate) fn normalize(base: &str, path: &str) -> String {
+    let mut parts: Vec<&str> = vec![];
+    let start = if path.starts_with('/') { "" } else { base };
+    for part in start.split('/').chain(path.split('/')) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    format!("/{}", parts.join("/"))
+}
+
+/// Width-neutral file metadata, rendered into `struct stat`/`struct statx`.
+pub(crate) struct FileStat {
+    pub(crate) ino: u64,
+    pub(crate) mode: u32,
+    pub(crate) nlink: u32,
+    pub(crate) uid: u32,
+    pub(crate) gid: u32,
+    pub(crate) size: u64,
+    pub(crate) rdev: (u32, u32),
+}
+
+impl FileStat {
+    fn blocks(&self) -> u64 {
+        self.size.div_ceil(512)
+    }
+}
+
+/// `struct statx`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct Statx {
+    pub(crate) mask: u32,
+    blksize: u32,
+    attributes: u64,
+    nlink: u32,
+    uid: u32,
+    gid: u32,
+    pub(crate) mode: u16,
+    _spare0: u16,
+    ino: u64,
+    pub(crate) size: u64,
+    blocks: u64,
+    attributes_mask: u64,
+    /// atime, btime, ctime, mtime: `{ i64 sec; u32 nsec; i32 reserved }`.
+    times: [[u64; 2]; 4],
+    rdev_major: u32,
+    rdev_minor: u32,
+    dev_major: u32,
+    dev_minor: u32,
+    mnt_id: u64,
+    dio_mem_align: u32,
+    dio_offset_align: u32,
+    _spare3: [u64; 12],
+}
+// Safety: integers only; any bit pattern valid, no padding.
+unsafe impl Pod for Statx {}
+
+/// `struct stat` (asm-generic, 64-bit). rv32 has no `fstat`: its libcs are
+/// time64-only and use `statx`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct Stat64 {
+    dev: u64,
+    ino: u64,
+    pub(crate) mode: u32,
+    nlink: u32,
+    uid: u32,
+    gid: u32,
+    rdev: u64,
+    _pad1: u64,
+    pub(crate) size: i64,
+    blksize: i32,
+    _pad2: i32,
+    blocks: i64,
+    /// atime, mtime, ctime: `{ long sec; unsigned long nsec }`.
+    times: [[u64; 2]; 3],
+    _unused: [u32; 2],
+}
+// Safety: as above.
+unsafe impl Pod for Stat64 {}
+
+/// Linux `new_encode_dev` for the old `st_dev`/`st_rdev` fields.
+fn encode_dev((major, minor): (u32, u32)) -> u64 {
+    ((major as u64 & 0xfff) << 8) | (minor as u64 & 0xff) | ((minor as u64 & !0xff) << 12)
+}
+
+/// The pty major: stdio reports as a terminal.
+const PTY_MAJOR: u32 = 136;
+
+impl<X: KernelXlen, M: Memory> MockLinux<X, M> {
+    /// The guest filesystem, e.g. to add input files before a run.
+    pub fn fs(&self) -> &GuestFs {
+        &self.fs
+    }
+
+    pub fn fs_mut(&mut self) -> &mut GuestFs {
+        &mut self.fs
+    }
+
+    pub fn with_fs(mut self, fs: GuestFs) -> Self {
+        self.fs = fs;
+        self
+    }
+
+    /// The guest's starting working directory (`/` by default); it should
+    /// exist in the guest filesystem.
+    pub fn with_cwd(mut self, cwd: &str) -> Self {
+        self.cwd = normalize("/", cwd);
+        self
+    }
+
+    pub fn cwd(&self) -> &str {
+        &self.cwd
+    }
+
+    fn read_path(&self, mem: &M, pathname: u64) -> Result<String, i32> {
+        let path = mem
+            .bytes_null_terminated(pathname, Some(libc_riscv32::PATH_MAX as u64))
+            .map_err(|_| libc_riscv32::EFAULT)?;
+        if path.len() >= libc_riscv32::PATH_MAX as usize {
+            return Err(libc_riscv32::ENAMETOOLONG);
+        }
+        String::from_utf8(path.to_vec()).map_err(|_| libc_riscv32::EINVAL)
+    }
+
+    /// The absolute directory a relative path under `dirfd` is resolved
+    /// against.
+    fn dir_base(&self, dirfd: i32) -> Result<String, i32> {
+        if dirfd == libc_riscv32::AT_FDCWD {
+            return Ok(self.cwd.clone());
+        }
+        match &self.fds.get(dirfd)?.lock().node {
+            Node::Inode { path, inode } if lock(inode).is_dir() => Ok(path.clone()),
+            _ => Err(libc_riscv32::ENOTDIR),
+        }
+    }
+
+    /// Normalize a guest path relative to `dirfd`.
+    pub(crate) fn resolve_at(&self, mem: &M, dirfd: i32, pathname: u64) -> Result<String, i32> {
+        let path = self.read_path(mem, pathname)?;
+        if path.is_empty() {
+            return Err(libc_riscv32::ENOENT);
+        }
+        if path.starts_with('/') {
+            return Ok(normalize("/", &path));
+        }
+        Ok(normalize(&self.dir_base(dirfd)?, &path))
+    }
+
+    pub(crate) fn openat(
+        &mut self,
+        mem: &M,
+        dirfd: i32,
+        pathname: u64,
+        flags: u32,
+        mode: u32,
+    ) -> Result<u64, i32> {
+        use libc_riscv32::{O_CLOEXEC, O_CREAT, O_DIRECTORY, O_EXCL, O_TRUNC};
+
+        let path = self.resolve_at(mem, dirfd, pathname)?;
+        tracing::trace!("openat: {path} flags={flags:#o} mode={mode:#o}");
+
+        if let Some(node) = self.special_node(mem, &path) {
+            if flags & O_CREAT != 0 && flags & O_EXCL != 0 {
+                return Err(libc_riscv32::EEXIST);
+            }
+            if flags & O_DIRECTORY != 0 {
+                return Err(libc_riscv32::ENOTDIR);
+            }
+            let file = OpenFile::new(node, flags & !(O_CREAT | O_EXCL | O_TRUNC | O_CLOEXEC));
+            // Generated files are read-only.
+            if file.writable() && matches!(file.node, Node::Inode { .. }) {
+                return Err(libc_riscv32::EACCES);
+            }
+            return Ok(self.fds.insert(Fd::new(file, flags & O_CLOEXEC != 0))? as u64);
+        }
+
+        let inode = match self.fs.lookup(&path) {
+            Ok(_) if flags & O_CREAT != 0 && flags & O_EXCL != 0 => {
+                return Err(libc_riscv32::EEXIST)
+            }
+            Ok(inode) => inode,
+            Err(libc_riscv32::ENOENT) if flags & O_CREAT != 0 => {
+                self.fs.create(&path, mode & !self.umask)?
+            }
+            Err(e) => return Err(e),
+        };
+
+        let file = OpenFile::new(
+            Node::Inode {
+                path,
+                inode: inode.clone(),
+            },
+            flags & !(O_CREAT | O_EXCL | O_TRUNC | O_CLOEXEC),
+        );
+        {
+            let mut inode = lock(&inode);
+            if inode.is_dir() {
+                if file.writable() {
+                    return Err(libc_riscv32::EISDIR);
+                }
+            } else if flags & O_DIRECTORY != 0 {
+                return Err(libc_riscv32::ENOTDIR);
+            } else if flags & O_TRUNC != 0 && file.writable() {
+                inode.data.clear();
+            }
+        }
+
+        Ok(self.fds.insert(Fd::new(file, flags & O_CLOEXEC != 0))? as u64)
+    }
+
+    pub(crate) fn read(&mut self, mem: &mut M, fd: i32, buf: u64, count: u64) -> Result<u64, i32> {
+        mem.check_range(MemoryAccess::Store, buf, count)
+            .map_err(|_| libc_riscv32::EFAULT)?;
+
+        let fd = self.fds.get(fd)?.clone();
+        let mut file = fd.lock();
+        if !file.readable() {
+            return Err(libc_riscv32::EBADF);
+        }
+        match &file.node {
+            Node::Stdin => {
+                if !self.passthrough_stdio {
+                    return Ok(0);
+                }
+                // `count` is guest-controlled: read through a bounded buffer.
+                let mut chunk = vec![0u8; count.min(64 << 10) as usize];
+                let n = std::io::stdin()
+                    .read(&mut chunk)
+                    .map_err(|_| libc_riscv32::EIO)?;
+                mem.copy_to(buf, &chunk[..n])
+                    .map_err(|_| libc_riscv32::EFAULT)?;
+                Ok(n as u64)
+            }
+            Node::Stdout | Node::Stderr => Err(libc_riscv32::EBADF),
+            Node::Pipe(pipe) => {
+                let mut chunk = vec![0u8; count.min(64 << 10) as usize];
+                let n = pipe
+                    .read(&mut chunk)
+                    .map_err(park_unless(file.nonblock()))?;
+                mem.copy_to(buf, &chunk[..n])
+                    .map_err(|_| libc_riscv32::EFAULT)?;
+                Ok(n as u64)
+            }
+            Node::EventFd(eventfd) => {
+                eventfd::read(mem, eventfd, buf, count).map_err(park_unless(file.nonblock()))
+            }
+            Node::Epoll(_) => Err(libc_riscv32::EINVAL),
+            Node::Device(Device::Null) => Ok(0),
+            Node::Device(Device::Zero) => {
+                mem.memset(buf, 0, count)
+                    .map_err(|_| libc_riscv32::EFAULT)?;
+                Ok(count)
+            }
+            Node::Device(Device::Random | Device::Urandom) => self.fill_random(mem, buf, count),
+            Node::Socket(sock) => {
+                let mut chunk = vec![0u8; count.min(64 << 10) as usize];
+                let n = lock(sock)
+                    .read(&mut chunk)
+                    .map_err(park_unless(file.nonblock()))?;
+                mem.copy_to(buf, &chunk[..n])
+                    .map_err(|_| libc_riscv32::EFAULT)?;
+                Ok(n as u64)
+            }
+            Node::Inode { inode, .. } => {
+                self.flush_shared(mem, inode, file.offset, file.offset.saturating_add(count));
+                let inode = lock(inode);
+                if inode.is_dir() {
+                    return Err(libc_riscv32::EISDIR);
+                }
+                let start = (file.offset.min(inode.data.len() as u64)) as usize;
+                let n = (inode.data.len() - start).min(count as usize);
+                mem.copy_to(buf, &inode.data[start..start + n])
+                    .map_err(|_| libc_riscv32::EFAULT)?;
+                drop(inode);
+                file.offset += n as u64;
+                Ok(n as u64)
+            }
+        }
+    }
+
+    /// Write already-fetched guest bytes to `fd`, as far as the output cap
+    /// allows.
+    pub(crate) fn write_bytes(&mut self, fd: i32, bytes: &[u8]) -> Result<u64, i32> {
+        self.fds.get(fd)?;
+        let bytes = &bytes[..self.output_allowance(bytes.len())?];
+        let written = self.write_file(fd, bytes)?;
+        self.output_bytes += written;
+        Ok(written)
+    }
+
+    fn write_file(&mut self, fd: i32, bytes: &[u8]) -> Result<u64, i32> {
+        let fd = self.fds.get(fd)?.clone();
+        let mut file = fd.lock();
+        if !file.writable() {
+            return Err(libc_riscv32::EBADF);
+        }
+        let append = file.flags & libc_riscv32::O_APPEND != 0;
+        match &file.node {
+            Node::Stdout => {
+                if self.passthrough_stdio {
+                    print!("{}", String::from_utf8_lossy(bytes));
+                }
+                Ok(bytes.len() as u64)
+            }
+            Node::Stderr => {
+                if self.passthrough_stdio {
+                    eprint!("{}", String::from_utf8_lossy(bytes));
+                }
+                Ok(bytes.len() as u64)
+            }
+            Node::Stdin => Err(libc_riscv32::EBADF),
+            Node::Pipe(pipe) => Ok(pipe.write(bytes).map_err(park_unless(file.nonblock()))? as u64),
+            Node::EventFd(eventfd) => {
+                eventfd::write(eventfd, bytes).map_err(park_unless(file.nonblock()))
+            }
+            Node::Epoll(_) => Err(libc_riscv32::EINVAL),
+            Node::Device(_) => Ok(bytes.len() as u64),
+            Node::Socket(sock) => Ok(lock(sock)
+                .write(bytes)
+                .map_err(park_unless(file.nonblock()))?
+                as u64),
+            Node::Inode { inode, .. } => {
+                let mut inode = lock(inode);
+                if inode.is_dir() {
+                    return Err(libc_riscv32::EISDIR);
+                }
+                let start = if append {
+                    inode.data.len() as u64
+                } else {
+                    file.offset
+                };
+                if bytes.is_empty() {
+                    return Ok(0);
+                }
+                let bytes = &bytes[..self.file_allowance(start, bytes.len())?];
+                if start >= MAX_FILE_SIZE {
+                    return Err(libc_riscv32::EFBIG);
+                }
+                // Short, as Linux writes up to the maximum size.
+                let bytes = &bytes[..bytes.len().min((MAX_FILE_SIZE - start) as usize)];
+                let start = start as usize;
+                let end = start + bytes.len();
+                if inode.data.len() < end {
+                    inode.data.resize(end, 0);
+                }
+                inode.data[start..end].copy_from_slice(bytes);
+                drop(inode);
+                file.offset = end as u64;
+                Ok(bytes.len() as u64)
+            }
+        }
+    }
+
+    /// Seek, returning the new offset.
+    fn seek(&mut self, fd: i32, offset: i64, whence: u32) -> Result<u64, i32> {
+        let fd = self.fds.get(fd)?.clone();
+        let mut file = fd.lock();
+        let inode = match &file.node {
+            Node::Inode { inode, .. } => inode,
+            // Devices accept and ignore any seek.
+            Node::Device(_) => return Ok(0),
+            _ => return Err(libc_riscv32::ESPIPE),
+        };
+        let base = match whence {
+            libc_riscv32::SEEK_SET => 0,
+            libc_riscv32::SEEK_CUR => file.offset,
+            libc_riscv32::SEEK_END => lock(inode).data.len() as u64,
+            _ => return Err(libc_riscv32::EINVAL),
+        };
+        let new = base
+            .checked_add_signed(offset)
+            .filter(|&o| o <= i64::MAX as u64)
+            .ok_or(libc_riscv32::EINVAL)?;
+        file.offset = new;
+        Ok(new)
+    }
+
+    pub(crate) fn lseek(&mut self, fd: i32, offset: u64, whence: u32) -> Result<u64, i32> {
+        self.seek(fd, offset as i64, whence)
+    }
+
+    /// rv32's `lseek` slot is `llseek`: a split 64-bit offset and the
+    /// result written through a pointer.
+    pub(crate) fn llseek(
+        &mut self,
+        mem: &mut M,
+        fd: i32,
+        offset_high: u64,
+        offset_low: u64,
+        result: u64,
+        whence: u32,
+    ) -> Result<u64, i32> {
+        let offset = ((offset_high as u32 as u64) << 32) | (offset_low as u32 as u64);
+        let new = self.seek(fd, offset as i64, whence)?;
+        mem.store_at::<u64>(result, new)
+            .map_err(|_| libc_riscv32::EFAULT)?;
+        Ok(0)
+    }
+
+    fn stat_node(&self, node: &Node) -> FileStat {
+        let creds = self.auxv.credentials;
+        let (ino, mode, size, rdev) = match node {
+            Node::Stdin | Node::Stdout | Node::Stderr => {
+                (0, libc_riscv32::S_IFCHR | 0o620, 0, (PTY_MAJOR, 0))
+            }
+            Node::Pipe(_) => (0, libc_riscv32::S_IFIFO | 0o600, 0, (0, 0)),
+            Node::Socket(_) => (0, libc_riscv32::S_IFSOCK | 0o777, 0, (0, 0)),
+            Node::Device(dev) => (0, libc_riscv32::S_IFCHR | 0o666, 0, dev.rdev()),
+            // Anonymous inodes have no file type.
+            Node::EventFd(_) | Node::Epoll(_) => (0, 0o600, 0, (0, 0)),
+            Node::Inode { inode, .. } => {
+                let inode = lock(inode);
+                (inode.ino, inode.mode, inode.data.len() as u64, (0, 0))
+            }
+        };
+        FileStat {
+            ino,
+            mode,
+            nlink: if mode & libc_riscv32::S_IFMT == libc_riscv32::S_IFDIR {
+                2
+            } else {
+                1
+            },
+            uid: creds.euid,
+            gid: creds.egid,
+            size,
+            rdev,
+        }
+    }
+
+    /// Metadata for `pathname` under `dirfd`, or for `dirfd` itself with
+    /// `AT_EMPTY_PATH` and an empty path.
+    fn stat_at(&self, mem: &M, dirfd: i32, pathname: u64, flags: u32) -> Result<FileStat, i32> {
+        let valid = libc_riscv32::AT_SYMLINK_NOFOLLOW
+            | libc_riscv32::AT_NO_AUTOMOUNT
+            | libc_riscv32::AT_EMPTY_PATH
+            | libc_riscv32::AT_STATX_SYNC_TYPE;
+        if flags & !valid != 0 {
+            return Err(libc_riscv32::EINVAL);
+        }
+        if flags & libc_riscv32::AT_EMPTY_PATH != 0 && self.read_path(mem, pathname)?.is_empty() {
+            if dirfd == libc_riscv32::AT_FDCWD {
+                let path = self.cwd.clone();
+                let inode = self.fs.lookup(&path)?;
+                return Ok(self.stat_node(&Node::Inode { path, inode }));
+            }
+            return Ok(self.stat_node(&self.fds.get(dirfd)?.lock().node));
+        }
+        let path = self.resolve_at(mem, dirfd, pathname)?;
+        if let Some(node) = self.special_node(mem, &path) {
+            return Ok(self.stat_node(&node));
+        }
+        let inode = self.fs.lookup(&path)?;
+        Ok(self.stat_node(&Node::Inode { path, inode }))
+    }
+
+    pub(crate) fn statx(
+        &mut self,
+        mem: &mut M,
+        dirfd: i32,
+        pathname: u64,
+        flags: u64,
+        _mask: u64,
+        statxbuf: u64,
+    ) -> Result<u64, i32> {
+        let st = self.stat_at(mem, dirfd, pathname, flags as u32)?;
+        let statx = Statx {
+            // Everything basic is always available, whatever was asked for.
+            mask: libc_riscv32::STATX_BASIC_STATS,
+            blksize: BLKSIZE,
+            attributes: 0,
+            nlink: st.nlink,
+            uid: st.uid,
+            gid: st.gid,
+            mode: st.mode as u16,
+            _spare0: 0,
+            ino: st.ino,
+            size: st.size,
+            blocks: st.blocks(),
+            attributes_mask: 0,
+            times: [[0; 2]; 4],
+            rdev_major: st.rdev.0,
+            rdev_minor: st.rdev.1,
+            dev_major: 0,
+            dev_minor: 0,
+            mnt_id: 0,
+            dio_mem_align: 0,
+            dio_offset_align: 0,
+            _spare3: [0; 12],
+        };
+        mem.copy_to(statxbuf, &[statx])
+            .map_err(|_| libc_riscv32::EFAULT)?;
+        Ok(0)
+    }
+
+    fn write_stat(&self, mem: &mut M, statbuf: u64, st: FileStat) -> Result<u64, i32> {
+        let stat = Stat64 {
+            dev: 0,
+            ino: st.ino,
+            mode: st.mode,
+            nlink: st.nlink,
+            uid: st.uid,
+            gid: st.gid,
+            rdev: encode_dev(st.rdev),
+            _pad1: 0,
+            size: st.size as i64,
+            blksize: BLKSIZE as i32,
+            _pad2: 0,
+            blocks: st.blocks() as i64,
+            times: [[0; 2]; 3],
+            _unused: [0; 2],
+        };
+        mem.copy_to(statbuf, &[stat])
+            .map_err(|_| libc_riscv32::EFAULT)?;
+        Ok(0)
+    }
+
+    pub(crate) fn fstat(&mut self, mem: &mut M, fd: i32, statbuf: u64) -> Result<u64, i32> {
+        let st = self.stat_node(&self.fds.get(fd)?.lock().node);
+        self.write_stat(mem, statbuf, st)
+    }
+
+    pub(crate) fn newfstatat(
+        &mut self,
+        mem: &mut M,
+        dirfd: i32,
+        pathname: u64,
+        statbuf: u64,
+        flags: u32,
+    ) -> Result<u64, i32> {
+        let st = self.stat_at(mem, dirfd, pathname, flags)?;
+        self.write_stat(mem, statbuf, st)
+    }
+
+    pub(crate) fn getcwd(&mut self, mem: &mut M, buf: u64, size: u64) -> Result<u64, i32> {
+        let mut cwd = self.cwd.clone().into_bytes();
+        cwd.push(0);
+        if size < cwd.len() as u64 {
+            return Err(libc_riscv32::ERANGE);
+        }
+        mem.copy_to(buf, &cwd).map_err(|_| libc_riscv32::EFAULT)?;
+        Ok(cwd.len() as u64)
+    }
+
+    pub(crate) fn chdir(&mut self, mem: &M, pathname: u64) -> Result<u64, i32> {
+        let path = self.resolve_at(mem, libc_riscv32::AT_FDCWD, pathname)?;
+        let inode = self.fs.lookup(&path)?;
+        if !lock(&inode).is_dir() {
+            return Err(libc_riscv32::ENOTDIR);
+        }
+        self.cwd = path;
+        Ok(0)
+    }
+
+    pub(crate) fn fchdir(&mut self, fd: i32) -> Result<u64, i32> {
+        self.cwd = self.dir_base(fd)?;
+        Ok(0)
+    }
+
+    pub(crate) fn umask(&mut self, mask: u32) -> Result<u64, i32> {
+        let old = self.umask;
+        self.umask = mask & 0o777;
+        Ok(old as u64)
+    }
+}
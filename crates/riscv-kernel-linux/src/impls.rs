@@ -6,39 +6,57 @@
 //! parameterized by `X::U`.
 use std::ffi::CString;
 
-use riscv_vm::memory::{Memory, Pod};
+use riscv_vm::{
+    error::MemoryAccess,
+    memory::{Memory, Pod},
+};
 
-use crate::{KernelXlen, MockLinux, PAGE_SIZE};
+use crate::{fd::Node, KernelXlen, MockLinux, CHUNK};
 
 #[repr(C)]
 #[derive(Clone, Copy)]
-struct IoVec<U> {
-    base: U,
-    len: U,
+pub(crate) struct IoVec<U> {
+    pub(crate) base: U,
+    pub(crate) len: U,
 }
 // Safety: width-sized integers only; any bit pattern valid, no padding.
 unsafe impl<U: Pod> Pod for IoVec<U> {}
 
+/// `struct rlimit`; `RLimit<u64>` is `struct rlimit64`.
 #[repr(C)]
-#[derive(Clone, Copy)]
-struct RLimit<U> {
-    rlim_cur: U,
-    rlim_max: U,
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RLimit<U> {
+    pub(crate) rlim_cur: U,
+    pub(crate) rlim_max: U,
 }
 // Safety: as above.
 unsafe impl<U: Pod> Pod for RLimit<U> {}
 
+pub(crate) type RLimits = [RLimit<u64>; libc_riscv32::RLIM_NLIMITS as usize];
+
+/// Limits a fresh process starts with: an 8 MiB stack, the descriptor
+/// table's size, and no limit on anything else.
+pub(crate) fn default_rlimits() -> RLimits {
+    let fixed = |limit: u64| RLimit {
+        rlim_cur: limit,
+        rlim_max: limit,
+    };
+    let mut limits = [fixed(libc_riscv32::RLIM64_INFINITY); libc_riscv32::RLIM_NLIMITS as usize];
+    limits[libc_riscv32::RLIMIT_STACK as usize] = fixed(crate::STACK_RESERVE);
+    limits[libc_riscv32::RLIMIT_NOFILE as usize] = fixed(crate::fd::MAX_FDS as u64);
+    limits
+}
+
 #[repr(C)]
 #[derive(Clone, Copy)]
-struct PollFd {
-    fd: i32,
-    events: i16,
-    revents: i16,
+struct HwProbePair {
+    key: i64,
+    value: u64,
 }
 // Safety: integers only; any bit pattern valid, no padding.
-unsafe impl Pod for PollFd {}
+unsafe impl Pod for HwProbePair {}
 
-impl<X: KernelXlen> MockLinux<X> {
+impl<X: KernelXlen, M: Memory> MockLinux<X, M> {
     pub(crate) fn ioctl(&mut self, _fd: i32, _request: u64) -> Result<u64, i32> {
         // just return 0 for now
         Ok(0)
@@ -46,7 +64,7 @@ impl<X: KernelXlen> MockLinux<X> {
 
     pub(crate) fn readlinkat(
         &mut self,
-        mem: &mut X::Memory,
+        mem: &mut M,
         _dirfd: i32,
         pathname: u64,
         buf: u64,
@@ -55,11 +73,11 @@ impl<X: KernelXlen> MockLinux<X> {
         let pathname = mem
             .bytes_null_terminated(pathname, None)
             .map_err(|_| libc_riscv32::EINVAL)?;
-        let pathname = std::str::from_utf8(pathname).map_err(|_| libc_riscv32::EINVAL)?;
+        let pathname = std::str::from_utf8(&pathname).map_err(|_| libc_riscv32::EINVAL)?;
 
         match pathname {
             "/proc/self/exe" => {
-                let fake_path = "/tmp/main".as_bytes();
+                let fake_path = self.auxv.execfn.as_bytes();
 
                 let cstr = CString::new(fake_path).map_err(|_| libc_riscv32::EINVAL)?;
                 let as_bytes = cstr.as_bytes_with_nul();
@@ -76,41 +94,59 @@ impl<X: KernelXlen> MockLinux<X> {
         }
     }
 
-    pub(crate) fn write(
+    /// `f` over the guest bytes `[buf, buf + len)` a [`CHUNK`] at a time,
+    /// so memories that copy ranges out (`Paged`) never copy a guest-sized
+    /// one. Ends at the first short take; a failure after some progress
+    /// reports the progress instead.
+    pub(crate) fn chunked(
         &mut self,
-        mem: &X::Memory,
-        fd: i32,
+        mem: &M,
         buf: u64,
-        count: u64,
+        len: u64,
+        mut f: impl FnMut(&mut Self, &[u8]) -> Result<u64, i32>,
     ) -> Result<u64, i32> {
-        let slice = mem.slice::<u8>(buf, count).map_err(|_| {
-            tracing::warn!("write: buffer read would overflow guest memory");
-            libc_riscv32::EFAULT
-        })?;
-
-        match fd {
-            1 => {
-                if self.passthrough_stdio {
-                    print!("{}", String::from_utf8_lossy(slice)); // stdout
+        // Faults up front, as one copy would.
+        mem.check_range(MemoryAccess::Load, buf, len)
+            .map_err(|_| libc_riscv32::EFAULT)?;
+        let mut done = 0;
+        loop {
+            let n = (len - done).min(CHUNK);
+            let bytes = mem
+                .slice::<u8>(buf + done, n)
+                .map_err(|_| libc_riscv32::EFAULT)?;
+            match f(self, &bytes) {
+                Ok(taken) => {
+                    done += taken;
+                    if taken < n || done == len {
+                        return Ok(done);
+                    }
                 }
-                Ok(count)
+                Err(_) if done > 0 => return Ok(done),
+                Err(e) => return Err(e),
             }
-            2 => {
-                if self.passthrough_stdio {
-                    eprint!("{}", String::from_utf8_lossy(slice)); // stderr
+        }
+    }
+
+    pub(crate) fn write(&mut self, mem: &mut M, fd: i32, buf: u64, count: u64) -> Result<u64, i32> {
+        let written = self
+            .chunked(mem, buf, count, |k, bytes| k.write_bytes(fd, bytes))
+            .inspect_err(|&e| {
+                if e == libc_riscv32::EFAULT {
+                    tracing::warn!("write: buffer read would overflow guest memory");
                 }
-                Ok(count)
-            }
-            _ => {
-                tracing::warn!("write: fd {fd} not supported");
-                Err(libc_riscv32::EBADF)
-            }
+            })?;
+        // Shared mappings of the file see the new bytes.
+        let file = self.fds.get(fd)?.clone();
+        let file = file.lock();
+        if let Node::Inode { inode, .. } = &file.node {
+            self.reload_shared(mem, inode, file.offset - written, file.offset);
         }
+        Ok(written)
     }
 
     pub(crate) fn writev(
         &mut self,
-        mem: &X::Memory,
+        mem: &mut M,
         fd: i32,
         iov: u64,
         iovcnt: i32,
@@ -121,12 +157,25 @@ impl<X: KernelXlen> MockLinux<X> {
 
         let iovs = mem
             .slice::<IoVec<X::U>>(iov, iovcnt as u64)
-            .map_err(|_| libc_riscv32::EFAULT)?;
-
-        let total = iovs.iter().try_fold(0u64, |total, iov| {
-            let count = self.write(mem, fd, X::to_u64(iov.base), X::to_u64(iov.len))?;
-            total.checked_add(count).ok_or(libc_riscv32::EINVAL)
-        })?;
+            .map_err(|_| libc_riscv32::EFAULT)?
+            .to_vec();
+
+        let mut total = 0u64;
+        for iov in iovs {
+            let len = X::to_u64(iov.len);
+            match self.write(mem, fd, X::to_u64(iov.base), len) {
+                Ok(count) => {
+                    total = total.checked_add(count).ok_or(libc_riscv32::EINVAL)?;
+                    if count < len {
+                        break;
+                    }
+                }
+                // Report what went out before the failure (a retry after a
+                // park would write it twice).
+                Err(_) if total > 0 => break,
+                Err(e) => return Err(e),
+            }
+        }
 
         Ok(total)
     }
@@ -135,7 +184,7 @@ impl<X: KernelXlen> MockLinux<X> {
         Ok(0x1)
     }
 
-    pub(crate) fn set_tid_address(&mut self, mem: &mut X::Memory, tidptr: u64) -> Result<u64, i32> {
+    pub(crate) fn set_tid_address(&mut self, mem: &mut M, tidptr: u64) -> Result<u64, i32> {
         let tid = self.gettid()?;
         mem.store_at::<u32>(tidptr, tid as u32)
             .map_err(|_| libc_riscv32::EFAULT)?;
@@ -150,7 +199,7 @@ impl<X: KernelXlen> MockLinux<X> {
     #[allow(clippy::too_many_arguments)]
     pub(crate) fn futex(
         &mut self,
-        mem: &mut X::Memory,
+        mem: &mut M,
         uaddr: u64,
         op: u32,
         val: u32,
@@ -205,7 +254,7 @@ impl<X: KernelXlen> MockLinux<X> {
 
     pub(crate) fn set_robust_list(
         &mut self,
-        _mem: &mut X::Memory,
+        _mem: &mut M,
         _head: u64,
         _len: u64,
     ) -> Result<u64, i32> {
@@ -218,7 +267,7 @@ impl<X: KernelXlen> MockLinux<X> {
 
     pub(crate) fn rt_sigaction(
         &mut self,
-        _mem: &X::Memory,
+        _mem: &M,
         _sig: u64,
         _act: u64,
         _oldact: u64,
@@ -230,7 +279,7 @@ impl<X: KernelXlen> MockLinux<X> {
 
     pub(crate) fn rt_sigprocmask(
         &mut self,
-        mem: &mut X::Memory,
+        mem: &mut M,
         _how: u32,
         _set: u64,
         oldset: u64,
@@ -244,138 +293,47 @@ impl<X: KernelXlen> MockLinux<X> {
         Ok(0)
     }
 
-    pub(crate) fn brk(&mut self, mem: &mut X::Memory, addr: u64) -> Result<u64, i32> {
-        // TODO: OOM detection/handling
-        let old_brk = self.brk;
-        let new_brk = addr;
-        tracing::trace!("brk: new_brk={:#x} cur_brk={old_brk:#x}", addr);
-
-        // brk(0) is used to query the current break
-        // brk returns the old program break on failure
-        if new_brk == 0 {
-            return Ok(old_brk);
-        }
-
-        // brk may not grow into the mmap region (rv32 layout) or the stack
-        // reservation (rv64 layout), nor shrink below the loaded image.
-        let limit = if X::MMAP_GROWS_DOWN {
-            self.mmap_cursor.min(self.brk_limit)
-        } else {
-            self.brk_limit
-        };
-        if new_brk >= limit || new_brk < self.brk_floor {
-            return Ok(old_brk);
-        }
-
-        if mem.grow_to(new_brk).is_err() {
-            return Ok(old_brk);
-        }
-
-        self.brk = new_brk;
-        // Zero memory
-        let base = new_brk.min(old_brk);
-        let size = new_brk.abs_diff(old_brk);
-        mem.memset(base, 0, size).map_err(|_| {
-            tracing::warn!("brk: failed to zero memory");
-            libc_riscv32::ENOMEM
-        })?;
-
-        // brk returns the new program break on success
-        Ok(new_brk)
+    fn rlimit(&self, resource: u32) -> Result<RLimit<u64>, i32> {
+        self.rlimits
+            .get(resource as usize)
+            .copied()
+            .ok_or(libc_riscv32::EINVAL)
     }
 
-    #[allow(clippy::too_many_arguments)]
-    pub(crate) fn mmap(
-        &mut self,
-        mem: &mut X::Memory,
-        addr: u64,
-        len: u64,
-        _prot: u64,
-        _flags: u64,
-        fd: i32,
-        _offset: u64,
-    ) -> Result<u64, i32> {
-        tracing::trace!(
-            "mmap: addr={addr:#x} len={len:#x} prot={_prot:#x} flags={_flags:#x} fd={fd} offset={_offset:#x}"
-        );
-
-        let Some(size) = len.checked_add(PAGE_SIZE - 1).map(|l| l & !(PAGE_SIZE - 1)) else {
-            return Err(libc_riscv32::ENOMEM);
-        };
-        if fd != -1 {
-            tracing::warn!("mmap: file mappings not supported");
-            return Err(libc_riscv32::ENOSYS);
-        }
-        if size == 0 {
+    fn set_rlimit(&mut self, resource: u32, new: RLimit<u64>) -> Result<(), i32> {
+        let old = self.rlimit(resource)?;
+        if new.rlim_cur > new.rlim_max {
             return Err(libc_riscv32::EINVAL);
         }
-
-        // Placement hints are advisory (POSIX): allocation always comes from
-        // the cursor, so hinted requests can never overlap the bump region.
-        let map_addr = if X::MMAP_GROWS_DOWN {
-            self.mmap_cursor.saturating_sub(size) & !(PAGE_SIZE - 1)
-        } else {
-            self.mmap_cursor
-        };
-
-        let Some(end) = map_addr.checked_add(size) else {
-            tracing::warn!("mmap: address overflow");
-            return Err(libc_riscv32::ENOMEM);
-        };
-
-        // The grows-down region bottoms out at the program break.
-        if X::MMAP_GROWS_DOWN && map_addr < self.brk {
-            tracing::warn!("mmap: region collided with brk");
-            return Err(libc_riscv32::ENOMEM);
+        // Raising a hard limit takes privilege, and never passes what the
+        // embedder allows (nor the descriptor table's size).
+        if new.rlim_max > old.rlim_max
+            && (self.auxv.credentials.euid != 0 || new.rlim_max > self.limits.ceiling(resource))
+        {
+            return Err(libc_riscv32::EPERM);
         }
-
-        if mem.grow_to(end).is_err() {
-            tracing::warn!("mmap: out of guest address space");
-            return Err(libc_riscv32::ENOMEM);
+        self.rlimits[resource as usize] = new;
+        match resource {
+            libc_riscv32::RLIMIT_NOFILE => self.fds.set_limit(new.rlim_cur),
+            libc_riscv32::RLIMIT_CPU => self.cpu_limit_changed = true,
+            _ => {}
         }
-
-        self.mmap_cursor = if X::MMAP_GROWS_DOWN { map_addr } else { end };
-
-        // Zero out the region
-        mem.memset(map_addr, 0, size).map_err(|_| {
-            tracing::warn!("mmap: failed to zero memory");
-            libc_riscv32::ENOMEM
-        })?;
-
-        tracing::debug!("mmap: returning region at {map_addr:#x} of size {size:#x}");
-
-        Ok(map_addr)
-    }
-
-    pub(crate) fn mprotect(
-        &mut self,
-        _mem: &X::Memory,
-        _addr: u64,
-        _len: u64,
-        _prot: u64,
-    ) -> Result<u64, i32> {
-        Ok(0)
+        Ok(())
     }
 
     pub(crate) fn getrlimit(
         &mut self,
-        mem: &mut X::Memory,
+        mem: &mut M,
         resource: u32,
         rlim_ptr: u64,
     ) -> Result<u64, i32> {
-        // All-ones at width == RLIM_INFINITY.
-        let infinity = X::from_i64(-1);
-        let rlim = match resource {
-            // RLIMIT_STACK = 3, 8MB of stack
-            3 => RLimit {
-                rlim_cur: X::from_u64(crate::STACK_RESERVE),
-                rlim_max: X::from_u64(crate::STACK_RESERVE),
-            },
-            // For other resources, return "unlimited"
-            _ => RLimit {
-                rlim_cur: infinity,
-                rlim_max: infinity,
-            },
+        // All-ones at width is RLIM_INFINITY; limits past it saturate.
+        let infinity = X::to_u64(X::from_i64(-1));
+        let at_width = |v: u64| X::from_u64(v.min(infinity));
+        let limit = self.rlimit(resource)?;
+        let rlim = RLimit {
+            rlim_cur: at_width(limit.rlim_cur),
+            rlim_max: at_width(limit.rlim_max),
         };
         mem.copy_to(rlim_ptr, &[rlim])
             .map_err(|_| libc_riscv32::EFAULT)?;
@@ -383,65 +341,207 @@ impl<X: KernelXlen> MockLinux<X> {
         Ok(0)
     }
 
-    pub(crate) fn riscv_hwprobe(
-        &mut self,
-        _mem: &X::Memory,
-        _pairs: u64,
-        _pair_count: u64,
-        _cpusetsize: u64,
-        _cpus: u64,
-        _flags: u64,
-    ) -> Result<u64, i32> {
-        Err(libc_riscv32::ENOSYS)
+    pub(crate) fn setrlimit(&mut self, mem: &M, resource: u32, rlim_ptr: u64) -> Result<u64, i32> {
+        let infinity = X::to_u64(X::from_i64(-1));
+        let widen = |v: X::U| match X::to_u64(v) {
+            v if v == infinity => libc_riscv32::RLIM64_INFINITY,
+            v => v,
+        };
+        let rlim = mem
+            .slice::<RLimit<X::U>>(rlim_ptr, 1)
+            .map_err(|_| libc_riscv32::EFAULT)?[0];
+        let new = RLimit {
+            rlim_cur: widen(rlim.rlim_cur),
+            rlim_max: widen(rlim.rlim_max),
+        };
+        self.set_rlimit(resource, new)?;
+        Ok(0)
     }
 
-    pub(crate) fn getrandom(
+    pub(crate) fn prlimit64(
         &mut self,
-        mem: &mut X::Memory,
-        buf: u64,
-        len: u64,
-        _flags: u64,
+        mem: &mut M,
+        pid: i32,
+        resource: u32,
+        new_limit: u64,
+        old_limit: u64,
     ) -> Result<u64, i32> {
-        // TODO: Stubbed to zero buffer
-        mem.memset(buf, 0, len).map_err(|_| libc_riscv32::EFAULT)?;
+        if !self.is_self(pid) {
+            return Err(libc_riscv32::ESRCH);
+        }
+        let old = self.rlimit(resource)?;
+        let new = match new_limit {
+            0 => None,
+            ptr => Some(
+                mem.slice::<RLimit<u64>>(ptr, 1)
+                    .map_err(|_| libc_riscv32::EFAULT)?[0],
+            ),
+        };
+        if let Some(new) = new {
+            self.set_rlimit(resource, new)?;
+        }
+        if old_limit != 0 {
+            mem.copy_to(old_limit, &[old])
+                .map_err(|_| libc_riscv32::EFAULT)?;
+        }
+        Ok(0)
+    }
 
-        Ok(len)
+    /// Answer a hwprobe key from what the hart executes, or `None` for keys
+    /// this kernel does not know (reported back as key `-1`).
+    fn hwprobe_value(&self, mem: &M, key: i64) -> Option<u64> {
+        use libc_riscv32::*;
+
+        let has = |letter: u8| X::EXTENSIONS & (1 << (letter - b'a')) != 0;
+        let misaligned = if X::MISALIGNED_FAST {
+            RISCV_HWPROBE_MISALIGNED_FAST
+        } else {
+            RISCV_HWPROBE_MISALIGNED_EMULATED
+        };
+        Some(match key {
+            // Not a real vendor: zero is "unknown/open-source".
+            RISCV_HWPROBE_KEY_MVENDORID | RISCV_HWPROBE_KEY_MARCHID | RISCV_HWPROBE_KEY_MIMPID => 0,
+            RISCV_HWPROBE_KEY_BASE_BEHAVIOR => {
+                if has(b'i') && has(b'm') && has(b'a') {
+                    RISCV_HWPROBE_BASE_BEHAVIOR_IMA
+                } else {
+                    0
+                }
+            }
+            RISCV_HWPROBE_KEY_IMA_EXT_0 => {
+                let mut ext = 0;
+                if has(b'f') && has(b'd') {
+                    ext |= RISCV_HWPROBE_IMA_FD;
+                }
+                if has(b'c') {
+                    ext |= RISCV_HWPROBE_IMA_C;
+                }
+                if has(b'v') {
+                    ext |= RISCV_HWPROBE_IMA_V;
+                }
+                ext
+            }
+            RISCV_HWPROBE_KEY_CPUPERF_0 | RISCV_HWPROBE_KEY_MISALIGNED_SCALAR_PERF => misaligned,
+            RISCV_HWPROBE_KEY_MISALIGNED_VECTOR_PERF => RISCV_HWPROBE_MISALIGNED_UNSUPPORTED,
+            RISCV_HWPROBE_KEY_ZICBOZ_BLOCK_SIZE => 0,
+            RISCV_HWPROBE_KEY_HIGHEST_VIRT_ADDRESS => mem.max_addr() - 1,
+            // No `time` CSR.
+            RISCV_HWPROBE_KEY_TIME_CSR_FREQ => 0,
+            _ => return None,
+        })
     }
 
-    pub(crate) fn statx(
+    pub(crate) fn riscv_hwprobe(
         &mut self,
-        _mem: &X::Memory,
-        _dirfd: i32,
-        _pathname: u64,
-        _flags: u64,
-        _mask: u64,
-        _statxbuf: u64,
+        mem: &mut M,
+        pairs: u64,
+        pair_count: u64,
+        cpusetsize: u64,
+        cpus: u64,
+        flags: u64,
     ) -> Result<u64, i32> {
-        // stubbed out
+        let mut probes = mem
+            .slice::<HwProbePair>(pairs, pair_count)
+            .map_err(|_| libc_riscv32::EFAULT)?
+            .to_vec();
+
+        // A single hart: CPU 0 is the only member of any cpu set. An empty
+        // set (or none at all) means "all online CPUs".
+        let cpu0_selected = || -> Result<bool, i32> {
+            if cpusetsize == 0 {
+                return Ok(true);
+            }
+            let first: u8 = mem.load_at(cpus).map_err(|_| libc_riscv32::EFAULT)?;
+            Ok(first & 1 != 0)
+        };
+
+        match flags as u32 {
+            0 => {
+                if cpusetsize != 0 && cpus == 0 {
+                    return Err(libc_riscv32::EFAULT);
+                }
+                for probe in &mut probes {
+                    match self.hwprobe_value(mem, probe.key) {
+                        Some(value) => probe.value = value,
+                        None => *probe = HwProbePair { key: -1, value: 0 },
+                    }
+                }
+                mem.copy_to(pairs, &probes)
+                    .map_err(|_| libc_riscv32::EFAULT)?;
+            }
+            libc_riscv32::RISCV_HWPROBE_WHICH_CPUS => {
+                // Inverse query: narrow the cpu set to CPUs matching every
+                // pair. Bitmask keys match when all requested bits are set.
+                if cpusetsize == 0 || cpus == 0 {
+                    return Err(libc_riscv32::EINVAL);
+                }
+                let matches = cpu0_selected()?
+                    && probes.iter().all(|probe| {
+                        let Some(value) = self.hwprobe_value(mem, probe.key) else {
+                            return false;
+                        };
+                        match probe.key {
+                            libc_riscv32::RISCV_HWPROBE_KEY_BASE_BEHAVIOR
+                            | libc_riscv32::RISCV_HWPROBE_KEY_IMA_EXT_0 => {
+                                value & probe.value == probe.value
+                            }
+                            _ => value == probe.value,
+                        }
+                    });
+                mem.memset(cpus, 0, cpusetsize)
+                    .map_err(|_| libc_riscv32::EFAULT)?;
+                if matches {
+                    mem.store_at::<u8>(cpus, 1)
+                        .map_err(|_| libc_riscv32::EFAULT)?;
+                }
+            }
+            _ => return Err(libc_riscv32::EINVAL),
+        }
+
         Ok(0)
     }
 
-    pub(crate) fn ppoll(
+    pub(crate) fn getrandom(
         &mut self,
-        mem: &X::Memory,
-        fds: u64,
-        nfds: u64,
-        _tsp: u64,
-        _sigmask: u64,
-        _sigsetsize: u64,
+        mem: &mut M,
+        buf: u64,
+        len: u64,
+        flags: u64,
     ) -> Result<u64, i32> {
-        // This method is called during CRT init for stdin/out/err
-        let fds = mem
-            .slice::<PollFd>(fds, nfds)
+        let valid =
+            libc_riscv32::GRND_NONBLOCK | libc_riscv32::GRND_RANDOM | libc_riscv32::GRND_INSECURE;
+        if flags & !(valid as u64) != 0 {
+            return Err(libc_riscv32::EINVAL);
+        }
+        // As Linux: the two pools are exclusive.
+        let both = (libc_riscv32::GRND_RANDOM | libc_riscv32::GRND_INSECURE) as u64;
+        if flags & both == both {
+            return Err(libc_riscv32::EINVAL);
+        }
+        self.fill_random(mem, buf, len)
+    }
+
+    /// Fill guest `[buf, buf + len)` from the entropy source.
+    pub(crate) fn fill_random(&mut self, mem: &mut M, buf: u64, len: u64) -> Result<u64, i32> {
+        // Validate the whole range up front so a bad buffer faults before
+        // any entropy is consumed.
+        mem.check_range(MemoryAccess::Store, buf, len)
             .map_err(|_| libc_riscv32::EFAULT)?;
-        for fd in fds {
-            if fd.fd > 2 {
-                // We don't support any other file descriptors
-                return Err(libc_riscv32::ENOSYS);
-            }
+
+        // Fill through a bounded host buffer: `len` is guest-controlled.
+        let mut chunk = [0u8; 256];
+        let mut off = 0;
+        while off < len {
+            let n = (len - off).min(chunk.len() as u64) as usize;
+            self.entropy.fill(&mut chunk[..n]).map_err(|e| {
+                tracing::warn!("getrandom: host entropy unavailable: {e}");
+                libc_riscv32::EIO
+            })?;
+            mem.copy_to(buf + off, &chunk[..n])
+                .map_err(|_| libc_riscv32::EFAULT)?;
+            off += n as u64;
         }
 
-        // We can just return 0, as stdin/out/err are always ready
-        Ok(0)
+        Ok(len)
     }
 }
@@ -1,15 +1,58 @@
+mod auxv;
+mod entropy;
+mod epoll;
+mod eventfd;
+mod fd;
+mod fs;
 mod impls;
+mod limits;
+mod mm;
+pub mod net;
+mod pipe;
+mod policy;
+mod poll;
+mod process;
+mod procfs;
+mod socket;
+mod suspend;
+mod trace;
+
+pub use auxv::{AuxvConfig, Credentials, DEFAULT_MINSIGSTKSZ};
+pub use entropy::{Entropy, Xoshiro256};
+pub use fs::GuestFs;
+pub use limits::{Limits, INSTRUCTIONS_PER_SECOND};
+pub use pipe::{PipeReader, PipeWriter};
+pub use policy::{Action, ArgMatch, SyscallInfo, SyscallPolicy, SyscallPolicy32, SyscallPolicy64};
+pub use suspend::run_async;
+pub use syscalls;
+pub use trace::{ArgValue, Strace, SyscallEvent, Tracer};
+
+use fd::FdTable;
+use impls::{default_rlimits, RLimits};
+use mm::Vmas;
+use net::NetBackend;
+use policy::policy_error;
+use poll::Wait;
+use process::Rseq;
+use procfs::Segment;
 
 use std::ffi::CString;
+use std::fmt::Debug;
+use std::hash::Hash;
 use std::marker::PhantomData;
+use std::task::Waker;
+use std::time::Instant;
 
-use goblin::elf::{program_header::PT_LOAD, Elf};
+use goblin::elf::{
+    program_header::{PT_LOAD, PT_PHDR},
+    Elf,
+};
 
 use riscv_vm::{
     error::MachineError,
-    hart::{Execute, Hart, X32, X64, Xlen},
-    machine::{Kernel, StepResult},
-    memory::{Memory, Memory32, Memory64},
+    hart::{Execute, Hart, Xlen, X32, X64},
+    machine::{Inspect, Kernel, Machine, StepResult, TerminationReason},
+    memory::Memory,
     riscv_inst::Reg,
 };
 use thiserror::Error;
@@ -17,14 +60,25 @@ use thiserror::Error;
 const PAGE_SIZE: u64 = 4096;
 /// Nominal stack reservation; brk may not grow into it.
 const STACK_RESERVE: u64 = 8 << 20;
+/// Kernel-internal, as in Linux: the syscall cannot complete yet. The
+/// dispatcher parks the guest on it (see [`StepResult::Yield`]) instead of
+/// returning it to the guest.
+const ERESTARTSYS: i32 = 512;
+/// Most guest bytes a read, write or datagram moves in one step.
+const CHUNK: u64 = 64 << 10;
 
 /// Per-width process layout and memory pairing for [`MockLinux`].
 pub trait KernelXlen: Execute {
     type Memory: Memory<Addr = <Self as Xlen>::U> + Default;
+    /// The width's syscall numbering.
+    type Sysno: Copy + Eq + Hash + Debug + Send + Sync + 'static;
 
     /// Initial stack top for a fresh process.
     const STACK_TOP: u64;
     /// Where anonymous mmap allocation starts...
+    ///
+    /// (Both are lowered to fit guest address spaces that end below them;
+    /// see [`Memory::max_addr`].)
     const MMAP_BASE: u64;
     /// ...and which way it moves. rv32 keeps the historical top-down layout
     /// inside its fixed 4 GiB; rv64 grows upward so the elastic arena only
@@ -33,14 +87,22 @@ pub trait KernelXlen: Execute {
 }
 
 impl KernelXlen for X32 {
-    type Memory = Memory32;
+    #[cfg(unix)]
+    type Memory = riscv_vm::memory::Memory32;
+    #[cfg(not(unix))]
+    type Memory = riscv_vm::memory::Paged32;
+    type Sysno = syscalls::riscv32::Sysno;
     const STACK_TOP: u64 = 0xCFFF_F000;
     const MMAP_BASE: u64 = 0xC000_0000;
     const MMAP_GROWS_DOWN: bool = true;
 }
 
 impl KernelXlen for X64 {
-    type Memory = Memory64;
+    #[cfg(target_os = "linux")]
+    type Memory = riscv_vm::memory::Memory64;
+    #[cfg(not(target_os = "linux"))]
+    type Memory = riscv_vm::memory::Paged64;
+    type Sysno = syscalls::riscv64::Sysno;
     // Stack lives just below 512 MiB; ELF + brk below it, mmap above,
     // so the arena grows with actual use.
     const STACK_TOP: u64 = 0x1FFF_F000;
@@ -49,10 +111,22 @@ impl KernelXlen for X64 {
 }
 
 #[derive(Error, Debug)]
-pub enum LinuxError {}
+pub enum LinuxError {
+    /// The syscall policy's [`Action::Kill`].
+    #[error("syscall {0} killed by policy")]
+    Killed(SyscallInfo),
+    /// The syscall policy's [`Action::Trap`]; see
+    /// [`MockLinux::complete_syscall`] and [`MockLinux::admit_syscall`].
+    #[error("syscall {0} trapped by policy")]
+    Trapped(SyscallInfo),
+}
 
+/// The mock kernel for width `X`, over guest memory `M`: the width's own
+/// arena unless another backend is chosen (e.g. [`Paged32`]).
+///
+/// [`Paged32`]: riscv_vm::memory::Paged32
 #[derive(Debug)]
-pub struct MockLinux<X: KernelXlen> {
+pub struct MockLinux<X: KernelXlen, M = <X as KernelXlen>::Memory> {
     exit_code: Option<u64>,
     passthrough_stdio: bool,
     /// Current program break.
@@ -61,15 +135,72 @@ pub struct MockLinux<X: KernelXlen> {
     pub(crate) brk_floor: u64,
     /// brk may not reach this address (bottom of mmap region or stack).
     pub(crate) brk_limit: u64,
-    /// Next anonymous mmap allocation position.
-    pub(crate) mmap_cursor: u64,
+    /// mmap regions.
+    pub(crate) vmas: Vmas,
+    /// Source for `getrandom` and `AT_RANDOM`.
+    pub(crate) entropy: Entropy,
+    pub(crate) auxv: AuxvConfig,
+    pub(crate) fs: GuestFs,
+    pub(crate) fds: FdTable,
+    /// Absolute, normalized working directory.
+    pub(crate) cwd: String,
+    /// Permission bits cleared from created files.
+    pub(crate) umask: u32,
+    pub(crate) hostname: String,
+    /// CPUs the guest is told it has.
+    pub(crate) cpus: u32,
+    pub(crate) rlimits: RLimits,
+    pub(crate) limits: Limits,
+    /// A guest `RLIMIT_CPU` change the hart's fuel has yet to follow.
+    pub(crate) cpu_limit_changed: bool,
+    /// Bytes written to descriptors, against [`Limits::output_bytes`].
+    pub(crate) output_bytes: u64,
+    /// Why the guest ended, once it has.
+    pub(crate) termination: Option<TerminationReason>,
+    /// The most address space the guest has held.
+    pub(crate) peak_memory: u64,
+    /// When the kernel was created, for `sysinfo`'s uptime.
+    pub(crate) started: Instant,
+    /// `membarrier` commands registered for.
+    pub(crate) membarrier: u32,
+    pub(crate) rseq: Option<Rseq>,
+    /// The loaded ELF's segments.
+    pub(crate) image: Vec<Segment>,
+    /// Top of the stack reservation; 0 until an ELF is loaded.
+    pub(crate) stack_top: u64,
+    /// Where anonymous mmap allocation starts: `X::MMAP_BASE`, unless the
+    /// guest address space is too small for it.
+    pub(crate) mmap_base: u64,
+    /// The syscall the guest is parked on, if any.
+    pub(crate) wait: Option<Wait>,
+    /// Where sockets go; none means no networking.
+    pub(crate) net: Option<Box<dyn NetBackend>>,
+    pub(crate) policy: Option<SyscallPolicy<X::Sysno>>,
+    /// The syscall at pc already passed the policy (it parked, or the
+    /// embedder admitted it after a trap).
+    pub(crate) admitted: bool,
+    /// The syscall at pc, suspended by the policy for the embedder.
+    pub(crate) suspended: Option<SyscallInfo>,
+    pub(crate) tracer: Option<Box<dyn Tracer>>,
     _xlen: PhantomData<X>,
+    _mem: PhantomData<fn() -> M>,
 }
 
 pub type MockLinux32 = MockLinux<X32>;
 pub type MockLinux64 = MockLinux<X64>;
 
-impl<X: KernelXlen> Default for MockLinux<X> {
+// Whole machines move between threads (see `riscv_vm::sched`), and their
+// inspections are shared across them.
+const _: () = {
+    const fn send<T: Send>() {}
+    const fn sync<T: Sync>() {}
+    send::<Machine<MockLinux32>>();
+    send::<Machine<MockLinux64>>();
+    sync::<Inspect<'static, MockLinux32>>();
+    sync::<Inspect<'static, MockLinux64>>();
+};
+
+impl<X: KernelXlen, M: Memory> Default for MockLinux<X, M> {
     fn default() -> Self {
         Self::new(false)
     }
@@ -86,44 +217,156 @@ macro_rules! syscall_dispatch {
             [Reg::A0, Reg::A1, Reg::A2, Reg::A3, Reg::A4, Reg::A5, Reg::A7]
                 .map(|r| <$X as Xlen>::to_u64($hart.get_reg(r)));
 
-        let Some(call) = Sysno::new($a7 as usize) else {
-            tracing::error!("SYSCALL({}) unknown", $a7);
-            $hart.set_reg(Reg::A0, <$X as Xlen>::from_i64(-libc_riscv32::ENOSYS as i64));
-            return Ok(StepResult::Ok);
-        };
-        tracing::debug!("SYSCALL({}) -> {call:?}", $a7);
-
-        let ret: Result<u64, i32> = match call {
-            Sysno::ioctl => $self.ioctl($a0 as i32, $a1),
-            Sysno::write => $self.write($mem, $a0 as i32, $a1, $a2),
-            Sysno::writev => $self.writev($mem, $a0 as i32, $a1, $a2 as i32),
-            Sysno::readlinkat => $self.readlinkat($mem, $a0 as i32, $a1, $a2, $a3),
-            Sysno::exit | Sysno::exit_group => {
-                $self.exit_code = Some($a0);
-                return Ok(StepResult::Halt);
+        let sysno = Sysno::new($a7 as usize);
+        let args = [$a0, $a1, $a2, $a3, $a4, $a5];
+        let denied = match $self.policy_action(&*$mem, sysno, &args) {
+            Some(Action::Errno(e)) => Some(e),
+            Some(stop @ (Action::Kill | Action::Trap)) => {
+                let info = SyscallInfo {
+                    nr: $a7,
+                    name: sysno.map(|s| s.name()),
+                    args,
+                };
+                if stop == Action::Kill {
+                    $self.termination = Some(TerminationReason::PolicyViolation(info.to_string()));
+                }
+                return Err(MachineError::Kernel(policy_error(stop, info)));
+            }
+            Some(Action::Suspend) => {
+                $self.suspended = Some(SyscallInfo {
+                    nr: $a7,
+                    name: sysno.map(|s| s.name()),
+                    args,
+                });
+                return Ok(StepResult::Yield);
             }
-            Sysno::set_tid_address => $self.set_tid_address($mem, $a0),
-            Sysno::futex => $self.futex($mem, $a0, $a1 as u32, $a2 as u32, $a3, $a4, $a5 as u32),
-            Sysno::set_robust_list => $self.set_robust_list($mem, $a0, $a1),
-            Sysno::tgkill => $self.tgkill($a0 as i32, $a1 as i32, $a2 as i32),
-            Sysno::rt_sigaction => $self.rt_sigaction($mem, $a0, $a1, $a2, $a3),
-            Sysno::rt_sigprocmask => $self.rt_sigprocmask($mem, $a0 as u32, $a1, $a2, $a3),
-            Sysno::getpid => $self.getpid(),
-            Sysno::gettid => $self.gettid(),
-            Sysno::brk => $self.brk($mem, $a0),
-            Sysno::mmap => $self.mmap($mem, $a0, $a1, $a2, $a3, $a4 as i32, $a5),
-            Sysno::mprotect => $self.mprotect($mem, $a0, $a1, $a2),
-            Sysno::riscv_hwprobe => $self.riscv_hwprobe($mem, $a0, $a1, $a2, $a3, $a4),
-            Sysno::getrlimit => $self.getrlimit($mem, $a0 as u32, $a1),
-            Sysno::getrandom => $self.getrandom($mem, $a0, $a1, $a2),
-            Sysno::statx => $self.statx($mem, $a0 as i32, $a1, $a2, $a3, $a4),
-            $($extra)*
-            _ => {
-                tracing::error!("SYSCALL({call}) unimplemented");
+            _ => None,
+        };
+
+        let ret: Result<u64, i32> = match (denied, sysno) {
+            (Some(e), _) => Err(e),
+            (None, None) => {
+                tracing::error!("SYSCALL({}) unknown", $a7);
                 Err(libc_riscv32::ENOSYS)
             }
+            (None, Some(call)) => {
+                tracing::debug!("SYSCALL({}) -> {call:?}", $a7);
+                match call {
+                    Sysno::ioctl => $self.ioctl($a0 as i32, $a1),
+                    Sysno::openat => $self.openat($mem, $a0 as i32, $a1, $a2 as u32, $a3 as u32),
+                    Sysno::close => $self.close($a0 as i32),
+                    Sysno::read => $self.read($mem, $a0 as i32, $a1, $a2),
+                    Sysno::write => $self.write($mem, $a0 as i32, $a1, $a2),
+                    Sysno::writev => $self.writev($mem, $a0 as i32, $a1, $a2 as i32),
+                    Sysno::pipe2 => $self.pipe2($mem, $a0, $a1 as u32),
+                    Sysno::dup => $self.dup($a0 as i32),
+                    Sysno::dup3 => $self.dup3($a0 as i32, $a1 as i32, $a2 as u32),
+                    Sysno::fcntl => $self.fcntl($a0 as i32, $a1 as u32, $a2),
+                    Sysno::eventfd2 => $self.eventfd2($a0 as u32, $a1 as u32),
+                    Sysno::epoll_create1 => $self.epoll_create1($a0 as u32),
+                    Sysno::epoll_ctl => $self.epoll_ctl($mem, $a0 as i32, $a1 as i32, $a2 as i32, $a3),
+                    Sysno::epoll_pwait => $self.epoll_pwait($mem, $a0 as i32, $a1, $a2 as i32, $a3 as i32),
+                    Sysno::epoll_pwait2 => $self.epoll_pwait2($mem, $a0 as i32, $a1, $a2 as i32, $a3),
+                    Sysno::socket => $self.socket($a0 as u32, $a1 as u32, $a2 as u32),
+                    Sysno::bind => $self.bind($mem, $a0 as i32, $a1, $a2),
+                    Sysno::listen => $self.listen($a0 as i32, $a1 as i32),
+                    Sysno::accept => $self.accept4($mem, $a0 as i32, $a1, $a2, 0),
+                    Sysno::accept4 => $self.accept4($mem, $a0 as i32, $a1, $a2, $a3 as u32),
+                    Sysno::connect => $self.connect($mem, $a0 as i32, $a1, $a2),
+                    Sysno::getsockname => $self.getsockname($mem, $a0 as i32, $a1, $a2),
+                    Sysno::getpeername => $self.getpeername($mem, $a0 as i32, $a1, $a2),
+                    Sysno::sendto => $self.sendto($mem, $a0 as i32, $a1, $a2, $a3 as u32, $a4, $a5),
+                    Sysno::recvfrom => $self.recvfrom($mem, $a0 as i32, $a1, $a2, $a3 as u32, $a4, $a5),
+                    Sysno::sendmsg => $self.sendmsg($mem, $a0 as i32, $a1, $a2 as u32),
+                    Sysno::recvmsg => $self.recvmsg($mem, $a0 as i32, $a1, $a2 as u32),
+                    Sysno::shutdown => $self.shutdown($a0 as i32, $a1 as u32),
+                    Sysno::setsockopt => $self.setsockopt($mem, $a0 as i32, $a1 as u32, $a2 as u32, $a3, $a4),
+                    Sysno::getsockopt => $self.getsockopt($mem, $a0 as i32, $a1 as u32, $a2 as u32, $a3, $a4),
+                    Sysno::readlinkat => $self.readlinkat($mem, $a0 as i32, $a1, $a2, $a3),
+                    Sysno::exit | Sysno::exit_group => {
+                        $self.trace(&*$mem, $a7, Some(call.name()), &args, None);
+                        $self.exit_code = Some($a0);
+                        // The status is the low byte, as `wait` reports it.
+                        let status = $a0 as i32 & 0xff;
+                        $self.termination = Some(if call == Sysno::exit {
+                            TerminationReason::Exited(status)
+                        } else {
+                            TerminationReason::ExitGroup(status)
+                        });
+                        return Ok(StepResult::Halt);
+                    }
+                    Sysno::set_tid_address => $self.set_tid_address($mem, $a0),
+                    Sysno::futex => $self.futex($mem, $a0, $a1 as u32, $a2 as u32, $a3, $a4, $a5 as u32),
+                    Sysno::set_robust_list => $self.set_robust_list($mem, $a0, $a1),
+                    Sysno::tgkill => $self.tgkill($a0 as i32, $a1 as i32, $a2 as i32),
+                    Sysno::rt_sigaction => $self.rt_sigaction($mem, $a0, $a1, $a2, $a3),
+                    Sysno::rt_sigprocmask => $self.rt_sigprocmask($mem, $a0 as u32, $a1, $a2, $a3),
+                    Sysno::getpid => $self.getpid(),
+                    Sysno::gettid => $self.gettid(),
+                    Sysno::brk => $self.brk($mem, $a0),
+                    // Mapping calls may replace code the hart has decoded.
+                    Sysno::mmap => $self
+                        .mmap($mem, $a0, $a1, $a2, $a3, $a4 as i32, $a5)
+                        .inspect(|&addr| $hart.invalidate_code(addr, $a1)),
+                    Sysno::munmap => $self
+                        .munmap($mem, $a0, $a1)
+                        .inspect(|_| $hart.invalidate_code($a0, $a1)),
+                    Sysno::mremap => $self
+                        .mremap($mem, $a0, $a1, $a2, $a3, $a4)
+                        .inspect(|&addr| {
+                            $hart.invalidate_code($a0, $a1);
+                            $hart.invalidate_code(addr, $a2);
+                        }),
+                    Sysno::mprotect => $self.mprotect($mem, $a0, $a1, $a2),
+                    Sysno::msync => $self.msync($mem, $a0, $a1, $a2),
+                    Sysno::riscv_hwprobe => $self.riscv_hwprobe($mem, $a0, $a1, $a2, $a3, $a4),
+                    Sysno::getrlimit => $self.getrlimit($mem, $a0 as u32, $a1),
+                    Sysno::setrlimit => $self.setrlimit($mem, $a0 as u32, $a1),
+                    Sysno::prlimit64 => $self.prlimit64($mem, $a0 as i32, $a1 as u32, $a2, $a3),
+                    Sysno::uname => $self.uname($mem, $a0),
+                    Sysno::getcwd => $self.getcwd($mem, $a0, $a1),
+                    Sysno::chdir => $self.chdir($mem, $a0),
+                    Sysno::fchdir => $self.fchdir($a0 as i32),
+                    Sysno::umask => $self.umask($a0 as u32),
+                    Sysno::getuid => $self.getuid(),
+                    Sysno::geteuid => $self.geteuid(),
+                    Sysno::getgid => $self.getgid(),
+                    Sysno::getegid => $self.getegid(),
+                    Sysno::getresuid => $self.getresuid($mem, $a0, $a1, $a2),
+                    Sysno::getresgid => $self.getresgid($mem, $a0, $a1, $a2),
+                    Sysno::sched_getaffinity => $self.sched_getaffinity($mem, $a0 as i32, $a1, $a2),
+                    Sysno::sched_setaffinity => $self.sched_setaffinity($mem, $a0 as i32, $a1, $a2),
+                    Sysno::sched_yield => $self.sched_yield(),
+                    Sysno::sysinfo => $self.sysinfo($mem, $a0),
+                    Sysno::madvise => $self
+                        .madvise($mem, $a0, $a1, $a2)
+                        .inspect(|_| $hart.invalidate_code($a0, $a1)),
+                    Sysno::riscv_flush_icache => $self.riscv_flush_icache($hart, $a2),
+                    Sysno::membarrier => $self.membarrier($a0 as u32, $a1 as u32, $a2 as i32),
+                    Sysno::rseq => $self.rseq($mem, $a0, $a1 as u32, $a2 as u32, $a3 as u32),
+                    Sysno::getrandom => $self.getrandom($mem, $a0, $a1, $a2),
+                    Sysno::statx => $self.statx($mem, $a0 as i32, $a1, $a2, $a3, $a4),
+                    $($extra)*
+                    _ => {
+                        tracing::error!("SYSCALL({call}) unimplemented");
+                        Err(libc_riscv32::ENOSYS)
+                    }
+                }
+            }
         };
 
+        if ret == Err(ERESTARTSYS) {
+            $self.admitted = true;
+            return Ok(StepResult::Yield);
+        }
+        $self.trace(&*$mem, $a7, sysno.map(|s| s.name()), &args, Some(ret));
+        $self.wait = None;
+        if $self.termination.is_some() {
+            return Ok(StepResult::Halt);
+        }
+        if std::mem::take(&mut $self.cpu_limit_changed) {
+            $self.refuel($hart);
+        }
         let ret = ret.unwrap_or_else(|e| (-(e as i64)) as u64);
         $hart.set_reg(Reg::A0, <$X as Xlen>::from_u64(ret));
 
@@ -131,42 +374,112 @@ macro_rules! syscall_dispatch {
     }};
 }
 
-impl Kernel for MockLinux<X32> {
+impl<M: Memory<Addr = u32>> Kernel for MockLinux<X32, M> {
     type Xlen = X32;
     type Error = LinuxError;
-    type Memory = Memory32;
+    type Memory = M;
 
     fn syscall(
         &mut self,
         hart: &mut Hart<X32>,
-        mem: &mut Memory32,
+        mem: &mut M,
     ) -> Result<StepResult, MachineError<Self::Error>> {
         use syscalls::riscv32::Sysno;
         syscall_dispatch!(self, hart, mem, X32, [a0, a1, a2, a3, a4, a5, a7], {
             Sysno::ppoll_time64 => self.ppoll(mem, a0, a1, a2, a3, a4),
+            Sysno::pselect6_time64 => self.pselect6(mem, a0 as i32, a1, a2, a3, a4, a5),
+            Sysno::lseek => self.llseek(mem, a0 as i32, a1, a2, a3, a4 as u32),
             Sysno::futex_time64 => self.futex(mem, a0, a1 as u32, a2 as u32, a3, a4, a5 as u32),
         })
     }
+
+    fn out_of_fuel(
+        &mut self,
+        hart: &mut Hart<X32>,
+        _mem: &mut M,
+    ) -> Result<StepResult, MachineError<Self::Error>> {
+        self.fuel_exhausted(hart);
+        Ok(StepResult::Halt)
+    }
+
+    fn termination(&self) -> Option<TerminationReason> {
+        self.termination.clone()
+    }
+
+    fn blocked_until(&self) -> Option<Instant> {
+        MockLinux::blocked_until(self)
+    }
+
+    fn wake_on(&mut self, waker: &Waker) -> bool {
+        MockLinux::wake_on(self, waker)
+    }
+
+    fn peak_memory(&self) -> Option<u64> {
+        Some(self.peak_memory)
+    }
 }
 
-impl Kernel for MockLinux<X64> {
+impl<M: Memory<Addr = u64>> Kernel for MockLinux<X64, M> {
     type Xlen = X64;
     type Error = LinuxError;
-    type Memory = Memory64;
+    type Memory = M;
 
     fn syscall(
         &mut self,
         hart: &mut Hart<X64>,
-        mem: &mut Memory64,
+        mem: &mut M,
     ) -> Result<StepResult, MachineError<Self::Error>> {
         use syscalls::riscv64::Sysno;
         syscall_dispatch!(self, hart, mem, X64, [a0, a1, a2, a3, a4, a5, a7], {
             Sysno::ppoll => self.ppoll(mem, a0, a1, a2, a3, a4),
+            Sysno::pselect6 => self.pselect6(mem, a0 as i32, a1, a2, a3, a4, a5),
+            Sysno::lseek => self.lseek(a0 as i32, a1, a2 as u32),
+            Sysno::fstat => self.fstat(mem, a0 as i32, a1),
+            Sysno::fstatat => self.newfstatat(mem, a0 as i32, a1, a2, a3 as u32),
         })
     }
+
+    fn out_of_fuel(
+        &mut self,
+        hart: &mut Hart<X64>,
+        _mem: &mut M,
+    ) -> Result<StepResult, MachineError<Self::Error>> {
+        self.fuel_exhausted(hart);
+        Ok(StepResult::Halt)
+    }
+
+    fn termination(&self) -> Option<TerminationReason> {
+        self.termination.clone()
+    }
+
+    fn blocked_until(&self) -> Option<Instant> {
+        MockLinux::blocked_until(self)
+    }
+
+    fn wake_on(&mut self, waker: &Waker) -> bool {
+        MockLinux::wake_on(self, waker)
+    }
+
+    fn peak_memory(&self) -> Option<u64> {
+        Some(self.peak_memory)
+    }
+}
+
+/// `PROT_*` bits for an ELF segment's `PF_*` flags.
+fn segment_prot(p_flags: u32) -> u32 {
+    use goblin::elf::program_header::{PF_R, PF_W, PF_X};
+
+    [
+        (PF_R, libc_riscv32::PROT_READ),
+        (PF_W, libc_riscv32::PROT_WRITE),
+        (PF_X, libc_riscv32::PROT_EXEC),
+    ]
+    .into_iter()
+    .filter(|&(flag, _)| p_flags & flag != 0)
+    .fold(0, |prot, (_, bit)| prot | bit)
 }
 
-impl<X: KernelXlen> MockLinux<X> {
+impl<X: KernelXlen, M: Memory> MockLinux<X, M> {
     pub fn new(passthrough_stdio: bool) -> Self {
         Self {
             exit_code: None,
@@ -178,24 +491,83 @@ impl<X: KernelXlen> MockLinux<X> {
             } else {
                 X::STACK_TOP - STACK_RESERVE
             },
-            mmap_cursor: X::MMAP_BASE,
+            vmas: Vmas::default(),
+            entropy: Entropy::default(),
+            auxv: AuxvConfig::new(X::BITS),
+            fs: GuestFs::new(),
+            fds: FdTable::with_stdio(),
+            cwd: "/".to_string(),
+            umask: 0o022,
+            hostname: "localhost".to_string(),
+            cpus: 1,
+            rlimits: default_rlimits(),
+            limits: Limits::default(),
+            cpu_limit_changed: false,
+            output_bytes: 0,
+            termination: None,
+            peak_memory: 0,
+            started: Instant::now(),
+            membarrier: 0,
+            rseq: None,
+            image: Vec::new(),
+            stack_top: 0,
+            mmap_base: X::MMAP_BASE,
+            wait: None,
+            net: None,
+            policy: None,
+            admitted: false,
+            suspended: None,
+            tracer: None,
             _xlen: PhantomData,
+            _mem: PhantomData,
         }
     }
 
+    /// Replace the entropy source (host randomness by default), e.g. with
+    /// [`Entropy::seeded`] for reproducible runs.
+    pub fn with_entropy(mut self, entropy: Entropy) -> Self {
+        self.entropy = entropy;
+        self
+    }
+
+    /// Replace the auxv settings (identity, platform, `AT_EXECFN`, ...).
+    pub fn with_auxv(mut self, auxv: AuxvConfig) -> Self {
+        self.auxv = auxv;
+        self
+    }
+
+    /// Give the guest a network (see [`net`]).
+    pub fn with_net(mut self, net: impl NetBackend + 'static) -> Self {
+        self.net = Some(Box::new(net));
+        self
+    }
+
+    pub fn auxv(&self) -> &AuxvConfig {
+        &self.auxv
+    }
+
     pub fn exit_code(&self) -> Option<u64> {
         self.exit_code
     }
 
+    /// Why the guest ended, once it has; see also [`Machine::termination`],
+    /// which adds the ends the hart sees (faults, `ebreak`).
+    ///
+    /// [`Machine::termination`]: riscv_vm::machine::Machine::termination
+    pub fn termination(&self) -> Option<&TerminationReason> {
+        self.termination.as_ref()
+    }
+
     pub fn load_static_elf<'a>(
         &mut self,
         hart: &mut Hart<X>,
-        mem: &mut X::Memory,
+        mem: &mut M,
         bytes: &'a [u8],
         args: &[&str],
         env: &[&str],
     ) -> Elf<'a> {
         let elf = Elf::parse(bytes).expect("Failed to parse ELF");
+        self.fit_layout(mem.max_addr());
 
         // Load main program segments
         let mut brk = 0u64;
@@ -205,7 +577,8 @@ impl<X: KernelXlen> MockLinux<X> {
                 let end = vaddr
                     .checked_add(ph.p_memsz)
                     .expect("ELF segment address overflow");
-                mem.grow_to(end).expect("guest memory cap too small for ELF");
+                mem.grow_to(end)
+                    .expect("guest memory cap too small for ELF");
                 let file = ph
                     .p_offset
                     .checked_add(ph.p_filesz)
@@ -214,30 +587,39 @@ impl<X: KernelXlen> MockLinux<X> {
                 mem.copy_to(vaddr, file).expect("Failed to copy segment");
                 // BSS is already zero: fresh arena pages are demand-zero.
                 brk = brk.max(end);
+                self.image.push(Segment {
+                    start: vaddr & !(PAGE_SIZE - 1),
+                    end: end.next_multiple_of(PAGE_SIZE),
+                    prot: segment_prot(ph.p_flags),
+                    offset: ph.p_offset & !(PAGE_SIZE - 1),
+                });
             }
         }
 
+        // Program headers, for static TLS setup: PT_PHDR when the linker
+        // emitted one, else wherever the headers fall inside a loaded
+        // segment.
+        let phoff = elf.header.e_phoff;
+        let phdr = elf
+            .program_headers
+            .iter()
+            .find(|ph| ph.p_type == PT_PHDR)
+            .map(|ph| ph.p_vaddr)
+            .or_else(|| {
+                elf.program_headers
+                    .iter()
+                    .filter(|ph| ph.p_type == PT_LOAD)
+                    .find(|ph| (ph.p_offset..ph.p_offset + ph.p_filesz).contains(&phoff))
+                    .map(|ph| ph.p_vaddr + (phoff - ph.p_offset))
+            });
+        if phdr.is_none() {
+            tracing::debug!("ELF program headers are not mapped; omitting AT_PHDR");
+        }
+
         // PC
         hart.pc = X::from_u64(elf.entry);
 
-        // Align and set brk
-        self.brk = (brk + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
-        self.brk_floor = self.brk;
-        // Images that sit above the mmap region / stack reservation (e.g.
-        // bare-metal tests linked at 0x8000_0000) simply get no brk heap:
-        // growth is capped at the floor, so brk() always fails cleanly.
-        let ceiling = if X::MMAP_GROWS_DOWN {
-            X::MMAP_BASE
-        } else {
-            X::STACK_TOP - STACK_RESERVE
-        };
-        if self.brk > ceiling {
-            tracing::debug!(
-                "ELF image (brk {:#x}) is above the layout ceiling {ceiling:#x}; brk disabled",
-                self.brk
-            );
-            self.brk_limit = self.brk;
-        }
+        self.init_brk(brk);
         // Global pointer is at __DATA_BEGIN__
         // TODO: Do we actually need to set this? Or does libc initialize it on its own?
         let data_begin = elf
@@ -250,8 +632,9 @@ impl<X: KernelXlen> MockLinux<X> {
 
         // Setup Stack.
         let align = std::mem::size_of::<X::U>() as u64;
-        let mut sp = X::STACK_TOP.min(mem.max_addr() - PAGE_SIZE);
-        mem.grow_to(sp + PAGE_SIZE).expect("guest memory cap too small for stack");
+        let mut sp = self.stack_top - PAGE_SIZE;
+        mem.grow_to(self.stack_top)
+            .expect("guest memory cap too small for stack");
         let mut stack_init: Vec<X::U> = vec![];
 
         // String data goes high-to-low; the pointer arrays must stay in
@@ -259,7 +642,8 @@ impl<X: KernelXlen> MockLinux<X> {
         let mut place = |strings: &[&str], what: &str| -> Vec<u64> {
             let mut ptrs = vec![0u64; strings.len()];
             for (i, &st) in strings.iter().enumerate().rev() {
-                let bytes = CString::new(st).unwrap_or_else(|_| panic!("{what} contains null byte"));
+                let bytes =
+                    CString::new(st).unwrap_or_else(|_| panic!("{what} contains null byte"));
                 let bytes = bytes.to_bytes_with_nul();
                 sp = (sp - bytes.len() as u64) & !(align - 1);
                 mem.copy_to(sp, bytes)
@@ -268,9 +652,24 @@ impl<X: KernelXlen> MockLinux<X> {
             }
             ptrs
         };
+        // Loader-owned strings sit above argv/envp, as on Linux.
+        let mut info = vec![self.auxv.execfn.as_str()];
+        info.extend(self.auxv.platform.as_deref());
+        let info = place(&info, "auxv string");
+        let (execfn, platform) = (info[0], info.get(1).copied());
         let argv = place(args, "argument");
         let envp = place(env, "environment variable");
 
+        // 16 random bytes for the libc stack protector / pointer guard.
+        let mut random = [0u8; 16];
+        self.entropy
+            .fill(&mut random)
+            .expect("Failed to gather entropy for AT_RANDOM");
+        sp = (sp - random.len() as u64) & !(align - 1);
+        mem.copy_to(sp, &random)
+            .expect("Failed to copy AT_RANDOM bytes to stack");
+        let at_random = sp;
+
         stack_init.push(X::from_u64(args.len() as u64)); // argc
         stack_init.extend(argv.into_iter().map(X::from_u64));
         stack_init.push(X::from_u64(0)); // argv NULL terminator
@@ -281,14 +680,36 @@ impl<X: KernelXlen> MockLinux<X> {
         macro_rules! set_AT {
             ($at:expr, $val:expr) => {
                 stack_init.push(X::from_u64($at as u64));
-                stack_init.push(X::from_u64($val));
+                stack_init.push(X::from_u64($val as u64));
             };
         }
-        // TODO: Fill in the rest of the aux vector
+        let creds = self.auxv.credentials;
+        set_AT!(
+            libc_riscv32::AT_HWCAP,
+            self.auxv.hwcap.unwrap_or(X::EXTENSIONS as u64)
+        );
         set_AT!(libc_riscv32::AT_PAGESZ, PAGE_SIZE);
         set_AT!(libc_riscv32::AT_CLKTCK, 100);
-        set_AT!(libc_riscv32::AT_BASE, 0); // TODO
+        if let Some(phdr) = phdr {
+            set_AT!(libc_riscv32::AT_PHDR, phdr);
+            set_AT!(libc_riscv32::AT_PHENT, elf.header.e_phentsize);
+            set_AT!(libc_riscv32::AT_PHNUM, elf.header.e_phnum);
+        }
+        // Static images only: there is no interpreter base.
+        set_AT!(libc_riscv32::AT_BASE, 0);
+        set_AT!(libc_riscv32::AT_FLAGS, 0);
         set_AT!(libc_riscv32::AT_ENTRY, elf.entry);
+        set_AT!(libc_riscv32::AT_UID, creds.uid);
+        set_AT!(libc_riscv32::AT_EUID, creds.euid);
+        set_AT!(libc_riscv32::AT_GID, creds.gid);
+        set_AT!(libc_riscv32::AT_EGID, creds.egid);
+        set_AT!(libc_riscv32::AT_SECURE, self.auxv.secure as u64);
+        set_AT!(libc_riscv32::AT_RANDOM, at_random);
+        set_AT!(libc_riscv32::AT_EXECFN, execfn);
+        if let Some(platform) = platform {
+            set_AT!(libc_riscv32::AT_PLATFORM, platform);
+        }
+        set_AT!(libc_riscv32::AT_MINSIGSTKSZ, self.auxv.minsigstksz);
         set_AT!(libc_riscv32::AT_NULL, 0);
 
         // Set up stack. The psABI requires a 16-byte-aligned sp at entry.
@@ -301,7 +722,13 @@ impl<X: KernelXlen> MockLinux<X> {
         hart.set_reg(Reg::Sp, X::from_u64(sp));
 
         tracing::debug!("Stack at {:#x}, GP at {:#x}", sp, data_begin);
-        tracing::debug!("Loaded ELF. Start at {:08x}, brk={:08x}", elf.entry, self.brk);
+        tracing::debug!(
+            "Loaded ELF. Start at {:08x}, brk={:08x}",
+            elf.entry,
+            self.brk
+        );
+        self.refuel(hart);
+        self.note_peak_memory();
 
         elf
     }
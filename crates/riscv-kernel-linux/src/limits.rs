@@ -0,0 +1,189 @@
+//! Embedder-imposed resource limits.
+//!
+//! Each cap in [`Limits`] but the output cap becomes the soft and hard
+//! `rlimit` of its resource, so the guest reads it back through
+//! `getrlimit`/`prlimit64` and may lower it but never raise it, even as
+//! root. Enforcement follows the current soft limit: memory and descriptors
+//! fail the syscall that would exceed them (`ENOMEM`, `EMFILE`), while file
+//! size and instructions kill the guest: a write at or past `RLIMIT_FSIZE`
+//! in a file, or running past its own `RLIMIT_CPU`, with `SIGXFSZ` or
+//! `SIGXCPU` as Linux does, and past the embedder's instruction cap with
+//! [`TerminationReason::FuelExhausted`]. The output cap has no `rlimit`:
+//! past it, writes fail with `EFBIG` and the guest is killed by `SIGXFSZ`.
+use riscv_vm::{hart::Hart, machine::TerminationReason, memory::Memory};
+
+use crate::{fd::MAX_FDS, impls::RLimit, KernelXlen, MockLinux, STACK_RESERVE};
+
+/// Nominal guest speed: how instructions convert to `RLIMIT_CPU` seconds.
+pub const INSTRUCTIONS_PER_SECOND: u64 = 1_000_000_000;
+
+/// Caps on what one guest may use; `None` is unlimited.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Limits {
+    /// Bytes of address space: the image, heap, mappings and stack
+    /// (`RLIMIT_AS`).
+    pub memory: Option<u64>,
+    /// Open descriptors (`RLIMIT_NOFILE`); the table holds at most 1024.
+    pub open_files: Option<u64>,
+    /// Threads and processes (`RLIMIT_NPROC`). Reported only: the guest
+    /// cannot create either.
+    pub processes: Option<u64>,
+    /// Largest offset a write may reach in a file (`RLIMIT_FSIZE`).
+    pub file_size: Option<u64>,
+    /// Bytes written to any descriptor, in total. The embedder's alone:
+    /// the guest neither sees nor changes it.
+    pub output_bytes: Option<u64>,
+    /// Instructions retired (`RLIMIT_CPU`, rounded up to whole seconds at
+    /// [`INSTRUCTIONS_PER_SECOND`]).
+    pub instructions: Option<u64>,
+}
+
+impl Limits {
+    /// The highest hard limit `resource` may have.
+    pub(crate) fn ceiling(&self, resource: u32) -> u64 {
+        use libc_riscv32::*;
+
+        let cap = match resource {
+            RLIMIT_AS => self.memory,
+            RLIMIT_NOFILE => Some(
+                self.open_files
+                    .map_or(MAX_FDS as u64, |n| n.min(MAX_FDS as u64)),
+            ),
+            RLIMIT_NPROC => self.processes,
+            RLIMIT_FSIZE => self.file_size,
+            RLIMIT_CPU => self
+                .instructions
+                .map(|n| n.div_ceil(INSTRUCTIONS_PER_SECOND)),
+            _ => None,
+        };
+        cap.unwrap_or(RLIM64_INFINITY)
+    }
+}
+
+impl<X: KernelXlen, M: Memory> MockLinux<X, M> {
+    /// Cap the guest's resources (nothing but the descriptor table's size
+    /// by default).
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        use libc_riscv32::*;
+
+        self.limits = limits;
+        for resource in [
+            RLIMIT_AS,
+            RLIMIT_NOFILE,
+            RLIMIT_NPROC,
+            RLIMIT_FSIZE,
+            RLIMIT_CPU,
+        ] {
+            let ceiling = limits.ceiling(resource);
+            self.rlimits[resource as usize] = RLimit {
+                rlim_cur: ceiling,
+                rlim_max: ceiling,
+            };
+        }
+        self.fds.set_limit(limits.ceiling(RLIMIT_NOFILE));
+        self
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    fn soft_limit(&self, resource: u32) -> u64 {
+        self.rlimits[resource as usize].rlim_cur
+    }
+
+    /// Bytes of address space in use: the image, heap, mappings and stack.
+    pub(crate) fn address_space(&self) -> u64 {
+        let image: u64 = self.image.iter().map(|seg| seg.end - seg.start).sum();
+        let stack = if self.stack_top != 0 {
+            STACK_RESERVE
+        } else {
+            0
+        };
+        image + self.mapped_bytes() + stack
+    }
+
+    /// `ENOMEM` unless `extra` more bytes fit under `RLIMIT_AS`.
+    pub(crate) fn reserve_memory(&self, extra: u64) -> Result<(), i32> {
+        let limit = self.soft_limit(libc_riscv32::RLIMIT_AS);
+        match self.address_space().checked_add(extra) {
+            Some(total) if total <= limit => Ok(()),
+            _ => Err(libc_riscv32::ENOMEM),
+        }
+    }
+
+    /// The memory the guest is told it has.
+    pub(crate) fn memory_total(&self, max_addr: u64) -> u64 {
+        max_addr.min(self.soft_limit(libc_riscv32::RLIMIT_AS))
+    }
+
+    /// How much of a `len`-byte write fits under the output cap. With no
+    /// room left the write fails with `EFBIG` and the guest is killed by
+    /// `SIGXFSZ`.
+    pub(crate) fn output_allowance(&mut self, len: usize) -> Result<usize, i32> {
+        let left = self
+            .limits
+            .output_bytes
+            .map_or(u64::MAX, |cap| cap.saturating_sub(self.output_bytes));
+        Self::allowance(&mut self.termination, len, left)
+    }
+
+    /// How much of a `len`-byte write at `offset` in a file fits under
+    /// `RLIMIT_FSIZE`, failing as [`Self::output_allowance`] does.
+    pub(crate) fn file_allowance(&mut self, offset: u64, len: usize) -> Result<usize, i32> {
+        let left = self
+            .soft_limit(libc_riscv32::RLIMIT_FSIZE)
+            .saturating_sub(offset);
+        Self::allowance(&mut self.termination, len, left)
+    }
+
+    fn allowance(
+        termination: &mut Option<TerminationReason>,
+        len: usize,
+        left: u64,
+    ) -> Result<usize, i32> {
+        if len > 0 && left == 0 {
+            *termination = Some(TerminationReason::Signaled(libc_riscv32::SIGXFSZ));
+            return Err(libc_riscv32::EFBIG);
+        }
+        Ok(len.min(usize::try_from(left).unwrap_or(usize::MAX)))
+    }
+
+    /// Instructions the guest may retire in all, under `RLIMIT_CPU` and
+    /// the embedder's exact cap.
+    fn instruction_budget(&self) -> Option<u64> {
+        let soft = self.soft_limit(libc_riscv32::RLIMIT_CPU);
+        let by_rlimit = (soft != libc_riscv32::RLIM64_INFINITY)
+            .then(|| soft.saturating_mul(INSTRUCTIONS_PER_SECOND));
+        [by_rlimit, self.limits.instructions]
+            .into_iter()
+            .flatten()
+            .min()
+    }
+
+    /// Give the hart the fuel left in the instruction budget.
+    pub(crate) fn refuel(&self, hart: &mut Hart<X>) {
+        hart.fuel = self
+            .instruction_budget()
+            .map(|budget| budget.saturating_sub(hart.inst_count));
+    }
+
+    /// The hart ran out of fuel: within the embedder's cap that can only
+    /// be the guest's own soft `RLIMIT_CPU`, which sends `SIGXCPU`.
+    pub(crate) fn fuel_exhausted(&mut self, hart: &Hart<X>) {
+        let within_cap = self.limits.instructions.is_none_or(|n| hart.inst_count < n);
+        let by_rlimit = self
+            .instruction_budget()
+            .is_some_and(|budget| hart.inst_count >= budget);
+        self.termination = Some(if within_cap && by_rlimit {
+            TerminationReason::Signaled(libc_riscv32::SIGXCPU)
+        } else {
+            TerminationReason::FuelExhausted
+        });
+    }
+
+    /// Fold the current address space into the peak.
+    pub(crate) fn note_peak_memory(&mut self) {
+        self.peak_memory = self.peak_memory.max(self.address_space());
+    }
+}
@@ -0,0 +1,794 @@
+//! Guest address-space management: the program break and the mmap VMA
+//! tree.
+//!
+//! Mappings are tracked as non-overlapping page-aligned VMAs keyed by start
+//! address. Placement is first-fit over the holes of the mmap window (from
+//! the top on rv32's grows-down layout, from the bottom on rv64's), so
+//! unmapped regions are reused and long-running allocating guests settle
+//! into a steady footprint instead of marching through the address space.
+//! Unmapped pages are handed back to the host through [`Memory::discard`].
+//!
+//! File mappings copy the file into the arena. Shared ones remember their
+//! inode and are written back on `msync` and `munmap`; `read`/`write` on the
+//! file sync the bytes they touch, so a guest sees one coherent file either
+//! way. There is no `fork`, so a `MAP_SHARED|MAP_ANONYMOUS` region is only
+//! ever shared with itself and behaves like a private one; the VMA keeps its
+//! flags for when there is something to share it with.
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use riscv_vm::{error::MemoryError, hart::Hart, memory::Memory};
+
+use crate::{
+    fd::{lock, Node},
+    fs::Inode,
+    procfs::Device,
+    KernelXlen, MockLinux, PAGE_SIZE, STACK_RESERVE,
+};
+
+const fn page_align_up(v: u64) -> Option<u64> {
+    match v.checked_add(PAGE_SIZE - 1) {
+        Some(v) => Some(v & !(PAGE_SIZE - 1)),
+        None => None,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Vma {
+    /// Exclusive.
+    pub(crate) end: u64,
+    pub(crate) prot: u32,
+    /// `MAP_*` flags the region was created with.
+    pub(crate) flags: u32,
+    /// The file behind a file mapping.
+    pub(crate) backing: Option<Backing>,
+}
+
+impl Vma {
+    /// The file this VMA writes through to, if it is a shared file mapping.
+    fn shared_file(&self) -> Option<&Backing> {
+        self.backing
+            .as_ref()
+            .filter(|_| self.flags & libc_riscv32::MAP_TYPE != libc_riscv32::MAP_PRIVATE)
+    }
+
+    /// This VMA (starting at `start`) as seen from `addr`: the file offset
+    /// moves along with the start.
+    fn rebased(&self, start: u64, addr: u64) -> Vma {
+        let mut vma = self.clone();
+        if let Some(backing) = &mut vma.backing {
+            backing.offset += addr - start;
+        }
+        vma
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Backing {
+    pub(crate) inode: Arc<Mutex<Inode>>,
+    /// The path the file was opened by.
+    pub(crate) path: String,
+    /// File offset of the VMA's first byte.
+    pub(crate) offset: u64,
+}
+
+impl Backing {
+    /// Copy file bytes `[offset, offset + len)` to guest `addr`. Bytes past
+    /// the end of the file are left alone (zero in a fresh mapping).
+    fn load<M: Memory>(
+        &self,
+        mem: &mut M,
+        addr: u64,
+        offset: u64,
+        len: u64,
+    ) -> Result<(), MemoryError> {
+        let inode = lock(&self.inode);
+        let eof = inode.data.len() as u64;
+        if offset < eof {
+            let end = offset.saturating_add(len).min(eof);
+            mem.copy_to(addr, &inode.data[offset as usize..end as usize])?;
+        }
+        Ok(())
+    }
+
+    /// Write guest `[addr, addr + len)` back to file bytes at `offset`,
+    /// never extending the file.
+    fn store<M: Memory>(
+        &self,
+        mem: &M,
+        addr: u64,
+        offset: u64,
+        len: u64,
+    ) -> Result<(), MemoryError> {
+        let mut inode = lock(&self.inode);
+        let eof = inode.data.len() as u64;
+        if offset < eof {
+            let end = offset.saturating_add(len).min(eof);
+            let bytes = mem.slice::<u8>(addr, end - offset)?;
+            inode.data[offset as usize..end as usize].copy_from_slice(&bytes);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Vmas {
+    map: BTreeMap<u64, Vma>,
+}
+
+impl Vmas {
+    /// The VMA containing `addr`.
+    pub(crate) fn get(&self, addr: u64) -> Option<(u64, &Vma)> {
+        self.map
+            .range(..=addr)
+            .next_back()
+            .filter(|(_, vma)| addr < vma.end)
+            .map(|(&start, vma)| (start, vma))
+    }
+
+    pub(crate) fn overlaps(&self, start: u64, end: u64) -> bool {
+        self.map
+            .range(..end)
+            .next_back()
+            .is_some_and(|(_, vma)| vma.end > start)
+    }
+
+    /// Whether every page of `[start, end)` is mapped.
+    pub(crate) fn covers(&self, start: u64, end: u64) -> bool {
+        let mut cur = start;
+        while cur < end {
+            match self.get(cur) {
+                Some((_, vma)) => cur = vma.end,
+                None => return false,
+            }
+        }
+        true
+    }
+
+    /// Make `at` a VMA boundary, splitting the VMA straddling it.
+    fn split(&mut self, at: u64) {
+        let Some((start, vma)) = self.get(at) else {
+            return;
+        };
+        if start != at {
+            let tail = vma.rebased(start, at);
+            self.map.get_mut(&start).unwrap().end = at;
+            self.map.insert(at, tail);
+        }
+    }
+
+    /// Remove `[start, end)`, returning the removed pieces.
+    pub(crate) fn remove(&mut self, start: u64, end: u64) -> Vec<(u64, Vma)> {
+        self.split(start);
+        self.split(end);
+        let keys: Vec<u64> = self.map.range(start..end).map(|(&k, _)| k).collect();
+        keys.into_iter()
+            .map(|k| (k, self.map.remove(&k).unwrap()))
+            .collect()
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (u64, &Vma)> {
+        self.map.iter().map(|(&start, vma)| (start, vma))
+    }
+
+    /// Bytes mapped across all VMAs.
+    pub(crate) fn size(&self) -> u64 {
+        self.map.iter().map(|(&start, vma)| vma.end - start).sum()
+    }
+
+    /// Bytes of `[start, end)` inside some VMA.
+    pub(crate) fn mapped_in(&self, start: u64, end: u64) -> u64 {
+        self.within(start, end)
+            .map(|(s, vma)| vma.end.min(end) - s.max(start))
+            .sum()
+    }
+
+    /// The VMAs intersecting `[start, end)`.
+    fn within(&self, start: u64, end: u64) -> impl Iterator<Item = (u64, &Vma)> {
+        let first = self.get(start).map_or(start, |(s, _)| s);
+        self.map.range(first..end).map(|(&s, vma)| (s, vma))
+    }
+
+    pub(crate) fn insert(&mut self, start: u64, vma: Vma) {
+        debug_assert!(!self.overlaps(start, vma.end));
+        self.map.insert(start, vma);
+    }
+
+    /// Apply `f` to every VMA piece inside `[start, end)`.
+    pub(crate) fn update(&mut self, start: u64, end: u64, mut f: impl FnMut(&mut Vma)) {
+        self.split(start);
+        self.split(end);
+        for (_, vma) in self.map.range_mut(start..end) {
+            f(vma);
+        }
+    }
+
+    /// First hole of `len` bytes within `[lo, hi)`: the highest one when
+    /// `top_down`, else the lowest.
+    pub(crate) fn find_free(&self, len: u64, lo: u64, hi: u64, top_down: bool) -> Option<u64> {
+        if hi < lo || hi - lo < len {
+            return None;
+        }
+        // Holes between consecutive VMAs, clamped to the window.
+        let mut bounds = vec![lo];
+        for (&start, vma) in self.map.range(..hi) {
+            if vma.end <= lo {
+                continue;
+            }
+            bounds.push(start.clamp(lo, hi));
+            bounds.push(vma.end.clamp(lo, hi));
+        }
+        bounds.push(hi);
+        let mut holes = bounds
+            .chunks_exact(2)
+            .map(|h| (h[0], h[1]))
+            .filter(|&(s, e)| e >= s && e - s >= len);
+        if top_down {
+            holes.next_back().map(|(_, e)| e - len)
+        } else {
+            holes.map(|(s, _)| s).next()
+        }
+    }
+}
+
+impl<X: KernelXlen, M: Memory> MockLinux<X, M> {
+    /// The mmap placement window `[lo, hi)` for non-fixed mappings.
+    fn mmap_window(&self, mem: &M) -> (u64, u64) {
+        if X::MMAP_GROWS_DOWN {
+            // The grows-down region bottoms out at the program break.
+            (page_align_up(self.brk).unwrap_or(u64::MAX), self.mmap_base)
+        } else {
+            (self.mmap_base, mem.max_addr())
+        }
+    }
+
+    /// Place the stack and the mmap region for a guest address space ending
+    /// at `max_addr`: where the width puts them, or as high as fits. A
+    /// grows-down mmap region starts below the stack reservation.
+    pub(crate) fn fit_layout(&mut self, max_addr: u64) {
+        self.stack_top = X::STACK_TOP.min(max_addr - PAGE_SIZE) + PAGE_SIZE;
+        self.mmap_base = if X::MMAP_GROWS_DOWN {
+            X::MMAP_BASE.min(self.stack_top.saturating_sub(STACK_RESERVE + PAGE_SIZE))
+        } else {
+            X::MMAP_BASE.min(max_addr)
+        };
+    }
+
+    /// Initial brk bounds for a freshly loaded image ending at `image_end`.
+    pub(crate) fn init_brk(&mut self, image_end: u64) {
+        // Align and set brk
+        self.brk = (image_end + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+        self.brk_floor = self.brk;
+        // Images that sit above the mmap region / stack reservation (e.g.
+        // bare-metal tests linked at 0x8000_0000) simply get no brk heap:
+        // growth is capped at the floor, so brk() always fails cleanly.
+        let ceiling = if X::MMAP_GROWS_DOWN {
+            self.mmap_base
+        } else {
+            (self.stack_top - PAGE_SIZE).saturating_sub(STACK_RESERVE)
+        };
+        self.brk_limit = ceiling;
+        if self.brk > ceiling {
+            tracing::debug!(
+                "ELF image (brk {:#x}) is above the layout ceiling {ceiling:#x}; brk disabled",
+                self.brk
+            );
+            self.brk_limit = self.brk;
+        }
+    }
+
+    pub(crate) fn brk(&mut self, mem: &mut M, addr: u64) -> Result<u64, i32> {
+        // TODO: OOM detection/handling
+        let old_brk = self.brk;
+        let new_brk = addr;
+        tracing::trace!("brk: new_brk={:#x} cur_brk={old_brk:#x}", addr);
+
+        // brk(0) is used to query the current break
+        // brk returns the old program break on failure
+        if new_brk == 0 {
+            return Ok(old_brk);
+        }
+
+        // brk may not grow into a mapping or the stack reservation (rv64
+        // layout), nor shrink below the loaded image.
+        if new_brk >= self.brk_limit || new_brk < self.brk_floor {
+            return Ok(old_brk);
+        }
+        if new_brk > old_brk
+            && (self
+                .vmas
+                .overlaps(old_brk, page_align_up(new_brk).ok_or(libc_riscv32::ENOMEM)?)
+                || self.reserve_memory(new_brk - old_brk).is_err())
+        {
+            return Ok(old_brk);
+        }
+
+        if mem.grow_to(new_brk).is_err() {
+            return Ok(old_brk);
+        }
+
+        self.brk = new_brk;
+        // Zero memory
+        let base = new_brk.min(old_brk);
+        let size = new_brk.abs_diff(old_brk);
+        mem.memset(base, 0, size).map_err(|_| {
+            tracing::warn!("brk: failed to zero memory");
+            libc_riscv32::ENOMEM
+        })?;
+
+        self.note_peak_memory();
+        // brk returns the new program break on success
+        Ok(new_brk)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn mmap(
+        &mut self,
+        mem: &mut M,
+        addr: u64,
+        len: u64,
+        prot: u64,
+        flags: u64,
+        fd: i32,
+        offset: u64,
+    ) -> Result<u64, i32> {
+        use libc_riscv32::{MAP_ANONYMOUS, MAP_FIXED, MAP_FIXED_NOREPLACE, MAP_TYPE};
+
+        tracing::trace!(
+            "mmap: addr={addr:#x} len={len:#x} prot={prot:#x} flags={flags:#x} fd={fd} offset={offset:#x}"
+        );
+        let (prot, flags) = (prot as u32, flags as u32);
+
+        let Some(size) = page_align_up(len) else {
+            return Err(libc_riscv32::ENOMEM);
+        };
+        if size == 0 || !offset.is_multiple_of(PAGE_SIZE) {
+            return Err(libc_riscv32::EINVAL);
+        }
+        if !matches!(
+            flags & MAP_TYPE,
+            libc_riscv32::MAP_SHARED
+                | libc_riscv32::MAP_PRIVATE
+                | libc_riscv32::MAP_SHARED_VALIDATE
+        ) {
+            return Err(libc_riscv32::EINVAL);
+        }
+        let backing = if flags & MAP_ANONYMOUS == 0 {
+            self.map_file(fd, prot, flags, offset)?
+        } else {
+            None
+        };
+
+        let fixed = flags & (MAP_FIXED | MAP_FIXED_NOREPLACE) != 0;
+        let (map_addr, replaced) = if fixed {
+            if !addr.is_multiple_of(PAGE_SIZE) {
+                return Err(libc_riscv32::EINVAL);
+            }
+            let end = addr.checked_add(size).ok_or(libc_riscv32::ENOMEM)?;
+            if end > mem.max_addr() {
+                return Err(libc_riscv32::ENOMEM);
+            }
+            if flags & MAP_FIXED_NOREPLACE != 0 && self.vmas.overlaps(addr, end) {
+                return Err(libc_riscv32::EEXIST);
+            }
+            (addr, self.vmas.mapped_in(addr, end))
+        } else {
+            (self.place(mem, addr, size)?, 0)
+        };
+        let end = map_addr + size;
+        self.reserve_memory(size - replaced)?;
+        // MAP_FIXED replaces whatever was there, but only once the mapping
+        // is sure to fit: a failed one leaves it in place.
+        if replaced != 0 {
+            self.munmap(mem, map_addr, size)?;
+        }
+
+        if mem.grow_to(end).is_err() {
+            tracing::warn!("mmap: out of guest address space");
+            return Err(libc_riscv32::ENOMEM);
+        }
+        // Fresh anonymous memory reads as zero, whatever was there before,
+        // as does the tail of a file mapping past end of file.
+        mem.discard(map_addr, size).map_err(|_| {
+            tracing::warn!("mmap: failed to zero memory");
+            libc_riscv32::ENOMEM
+        })?;
+        if let Some(backing) = &backing {
+            backing
+                .load(mem, map_addr, backing.offset, size)
+                .map_err(|_| libc_riscv32::ENOMEM)?;
+        }
+        self.vmas.insert(
+            map_addr,
+            Vma {
+                end,
+                prot,
+                flags: flags & !(MAP_FIXED | MAP_FIXED_NOREPLACE),
+                backing,
+            },
+        );
+
+        self.note_peak_memory();
+        tracing::debug!("mmap: returning region at {map_addr:#x} of size {size:#x}");
+
+        Ok(map_addr)
+    }
+
+    /// Check that `fd` can back a mapping with `prot` and `flags`. Mappings
+    /// of `/dev/zero` are anonymous (`None`).
+    fn map_file(
+        &self,
+        fd: i32,
+        prot: u32,
+        flags: u32,
+        offset: u64,
+    ) -> Result<Option<Backing>, i32> {
+        let fd = self.fds.get(fd)?.clone();
+        let file = fd.lock();
+        let (path, inode) = match &file.node {
+            Node::Inode { path, inode } => (path, inode),
+            Node::Device(Device::Zero) => return Ok(None),
+            _ => return Err(libc_riscv32::ENODEV),
+        };
+        if lock(inode).is_dir() {
+            return Err(libc_riscv32::ENODEV);
+        }
+        let shared = flags & libc_riscv32::MAP_TYPE != libc_riscv32::MAP_PRIVATE;
+        if !file.readable() || (shared && prot & libc_riscv32::PROT_WRITE != 0 && !file.writable())
+        {
+            return Err(libc_riscv32::EACCES);
+        }
+        Ok(Some(Backing {
+            inode: inode.clone(),
+            path: path.clone(),
+            offset,
+        }))
+    }
+
+    /// Choose an address for a non-fixed mapping: the hint if that range is
+    /// free and inside the mmap window, else first fit.
+    fn place(&self, mem: &M, hint: u64, size: u64) -> Result<u64, i32> {
+        let (lo, hi) = self.mmap_window(mem);
+        let hint = hint & !(PAGE_SIZE - 1);
+        if hint != 0
+            && hint >= lo
+            && hint
+                .checked_add(size)
+                .is_some_and(|end| end <= hi && !self.vmas.overlaps(hint, end))
+        {
+            return Ok(hint);
+        }
+        self.vmas
+            .find_free(size, lo, hi, X::MMAP_GROWS_DOWN)
+            .ok_or_else(|| {
+                tracing::warn!("mmap: no hole of {size:#x} bytes in [{lo:#x}, {hi:#x})");
+                libc_riscv32::ENOMEM
+            })
+    }
+
+    pub(crate) fn munmap(&mut self, mem: &mut M, addr: u64, len: u64) -> Result<u64, i32> {
+        if !addr.is_multiple_of(PAGE_SIZE) || len == 0 {
+            return Err(libc_riscv32::EINVAL);
+        }
+        let end = page_align_up(len)
+            .and_then(|size| addr.checked_add(size))
+            .filter(|&end| end <= mem.max_addr())
+            .ok_or(libc_riscv32::EINVAL)?;
+        // Unmapping a range with no mappings in it is not an error.
+        for (start, vma) in self.vmas.remove(addr, end) {
+            if let Some(backing) = vma.shared_file() {
+                backing
+                    .store(mem, start, backing.offset, vma.end - start)
+                    .map_err(|_| libc_riscv32::EINVAL)?;
+            }
+            mem.discard(start, vma.end - start)
+                .map_err(|_| libc_riscv32::EINVAL)?;
+        }
+        Ok(0)
+    }
+
+    pub(crate) fn msync(&mut self, mem: &M, addr: u64, len: u64, flags: u64) -> Result<u64, i32> {
+        use libc_riscv32::{MS_ASYNC, MS_INVALIDATE, MS_SYNC};
+
+        let flags = flags as u32;
+        if !addr.is_multiple_of(PAGE_SIZE)
+            || flags & !(MS_ASYNC | MS_INVALIDATE | MS_SYNC) != 0
+            || (flags & MS_ASYNC != 0 && flags & MS_SYNC != 0)
+        {
+            return Err(libc_riscv32::EINVAL);
+        }
+        let end = page_align_up(len)
+            .and_then(|size| addr.checked_add(size))
+            .ok_or(libc_riscv32::ENOMEM)?;
+        if !self.vmas.covers(addr, end) {
+            return Err(libc_riscv32::ENOMEM);
+        }
+        // Write-back is synchronous whatever the flags say, and with no page
+        // cache there is nothing to invalidate.
+        for (start, vma) in self.vmas.within(addr, end) {
+            let Some(backing) = vma.shared_file() else {
+                continue;
+            };
+            let (lo, hi) = (start.max(addr), vma.end.min(end));
+            backing
+                .store(mem, lo, backing.offset + (lo - start), hi - lo)
+                .map_err(|_| libc_riscv32::ENOMEM)?;
+        }
+        Ok(0)
+    }
+
+    /// Shared mappings of `inode` overlapping file bytes `[lo, hi)`, as
+    /// `(backing, guest address, file offset, length)` pieces.
+    fn shared_pieces<'a>(
+        &'a self,
+        inode: &'a Arc<Mutex<Inode>>,
+        lo: u64,
+        hi: u64,
+    ) -> impl Iterator<Item = (&'a Backing, u64, u64, u64)> + 'a {
+        self.vmas.map.iter().filter_map(move |(&start, vma)| {
+            let backing = vma.shared_file().filter(|b| Arc::ptr_eq(&b.inode, inode))?;
+            let file_end = backing.offset + (vma.end - start);
+            let (from, to) = (backing.offset.max(lo), file_end.min(hi));
+            (from < to).then(|| (backing, start + (from - backing.offset), from, to - from))
+        })
+    }
+
+    /// Push guest stores in shared mappings of `inode` to file bytes
+    /// `[lo, hi)` before they are read through a descriptor.
+    pub(crate) fn flush_shared(&self, mem: &M, inode: &Arc<Mutex<Inode>>, lo: u64, hi: u64) {
+        for (backing, addr, offset, len) in self.shared_pieces(inode, lo, hi) {
+            if backing.store(mem, addr, offset, len).is_err() {
+                tracing::warn!("failed to flush shared mapping at {addr:#x}");
+            }
+        }
+    }
+
+    /// Refresh shared mappings of `inode` after file bytes `[lo, hi)` were
+    /// written through a descriptor.
+    pub(crate) fn reload_shared(&self, mem: &mut M, inode: &Arc<Mutex<Inode>>, lo: u64, hi: u64) {
+        let pieces: Vec<_> = self
+            .shared_pieces(inode, lo, hi)
+            .map(|(backing, addr, offset, len)| (backing.clone(), addr, offset, len))
+            .collect();
+        for (backing, addr, offset, len) in pieces {
+            if backing.load(mem, addr, offset, len).is_err() {
+                tracing::warn!("failed to refresh shared mapping at {addr:#x}");
+            }
+        }
+    }
+
+    pub(crate) fn mremap(
+        &mut self,
+        mem: &mut M,
+        old_addr: u64,
+        old_size: u64,
+        new_size: u64,
+        flags: u64,
+        new_addr: u64,
+    ) -> Result<u64, i32> {
+        use libc_riscv32::{MREMAP_DONTUNMAP, MREMAP_FIXED, MREMAP_MAYMOVE};
+
+        let flags = flags as u32;
+        if flags & !(MREMAP_MAYMOVE | MREMAP_FIXED | MREMAP_DONTUNMAP) != 0
+            || (flags & MREMAP_FIXED != 0 && flags & MREMAP_MAYMOVE == 0)
+            || !old_addr.is_multiple_of(PAGE_SIZE)
+        {
+            return Err(libc_riscv32::EINVAL);
+        }
+        if flags & MREMAP_DONTUNMAP != 0 {
+            return Err(libc_riscv32::ENOSYS);
+        }
+        let (Some(old_size), Some(new_size)) = (page_align_up(old_size), page_align_up(new_size))
+        else {
+            return Err(libc_riscv32::ENOMEM);
+        };
+        if old_size == 0 || new_size == 0 {
+            return Err(libc_riscv32::EINVAL);
+        }
+        let old_end = old_addr.checked_add(old_size).ok_or(libc_riscv32::EFAULT)?;
+        // The old range must lie within a single mapping.
+        let vma = match self.vmas.get(old_addr) {
+            Some((start, vma)) if vma.end >= old_end => vma.rebased(start, old_addr),
+            _ => return Err(libc_riscv32::EFAULT),
+        };
+        if new_size > old_size {
+            self.reserve_memory(new_size - old_size)?;
+        }
+
+        if flags & MREMAP_FIXED != 0 {
+            if !new_addr.is_multiple_of(PAGE_SIZE) {
+                return Err(libc_riscv32::EINVAL);
+            }
+            let new_end = new_addr.checked_add(new_size).ok_or(libc_riscv32::EINVAL)?;
+            if new_addr < old_end && old_addr < new_end {
+                return Err(libc_riscv32::EINVAL);
+            }
+            if new_end > mem.max_addr() {
+                return Err(libc_riscv32::ENOMEM);
+            }
+            self.munmap(mem, new_addr, new_size)?;
+            return self.move_mapping(mem, old_addr, old_size, new_addr, new_size, vma);
+        }
+
+        if new_size <= old_size {
+            if new_size < old_size {
+                self.munmap(mem, old_addr + new_size, old_size - new_size)?;
+            }
+            return Ok(old_addr);
+        }
+
+        // Grow in place when the pages after the mapping are free. Mappings
+        // inside the mmap window stay inside it (on rv32 the stack sits just
+        // above it).
+        let (_, hi) = self.mmap_window(mem);
+        let limit = if old_end <= hi { hi } else { mem.max_addr() };
+        let grown_end = old_addr.checked_add(new_size).ok_or(libc_riscv32::ENOMEM)?;
+        if grown_end <= limit
+            && !self.vmas.overlaps(old_end, grown_end)
+            && mem.grow_to(grown_end).is_ok()
+        {
+            mem.discard(old_end, grown_end - old_end)
+                .map_err(|_| libc_riscv32::ENOMEM)?;
+            if let Some(backing) = &vma.backing {
+                backing
+                    .load(mem, old_end, backing.offset + old_size, new_size - old_size)
+                    .map_err(|_| libc_riscv32::ENOMEM)?;
+            }
+            self.vmas.remove(old_addr, old_end);
+            self.vmas.insert(
+                old_addr,
+                Vma {
+                    end: grown_end,
+                    ..vma
+                },
+            );
+            self.note_peak_memory();
+            return Ok(old_addr);
+        }
+
+        if flags & MREMAP_MAYMOVE == 0 {
+            return Err(libc_riscv32::ENOMEM);
+        }
+        let new_addr = self.place(mem, 0, new_size)?;
+        self.move_mapping(mem, old_addr, old_size, new_addr, new_size, vma)
+    }
+
+    /// Move `[old_addr, +old_size)` to a free `[new_addr, +new_size)`,
+    /// carrying over the contents and attributes.
+    fn move_mapping(
+        &mut self,
+        mem: &mut M,
+        old_addr: u64,
+        old_size: u64,
+        new_addr: u64,
+        new_size: u64,
+        vma: Vma,
+    ) -> Result<u64, i32> {
+        let new_end = new_addr + new_size;
+        mem.grow_to(new_end).map_err(|_| libc_riscv32::ENOMEM)?;
+        mem.discard(new_addr, new_size)
+            .map_err(|_| libc_riscv32::ENOMEM)?;
+        mem.copy_within(old_addr, new_addr, old_size.min(new_size))
+            .map_err(|_| libc_riscv32::EFAULT)?;
+        if let Some(backing) = vma.backing.as_ref().filter(|_| new_size > old_size) {
+            backing
+                .load(
+                    mem,
+                    new_addr + old_size,
+                    backing.offset + old_size,
+                    new_size - old_size,
+                )
+                .map_err(|_| libc_riscv32::ENOMEM)?;
+        }
+        self.munmap(mem, old_addr, old_size)?;
+        self.vmas.insert(
+            new_addr,
+            Vma {
+                end: new_end,
+                ..vma
+            },
+        );
+        self.note_peak_memory();
+        Ok(new_addr)
+    }
+
+    /// Guest memory in use by the heap and mmap regions.
+    pub(crate) fn mapped_bytes(&self) -> u64 {
+        (self.brk - self.brk_floor) + self.vmas.size()
+    }
+
+    pub(crate) fn madvise(
+        &mut self,
+        mem: &mut M,
+        addr: u64,
+        len: u64,
+        advice: u64,
+    ) -> Result<u64, i32> {
+        use libc_riscv32::*;
+
+        if !addr.is_multiple_of(PAGE_SIZE) {
+            return Err(EINVAL);
+        }
+        let end = page_align_up(len)
+            .and_then(|size| addr.checked_add(size))
+            .ok_or(EINVAL)?;
+        match advice as u32 {
+            MADV_DONTNEED | MADV_DONTNEED_LOCKED | MADV_FREE => {}
+            // Hints about paging, merging, and fork have nothing to act on.
+            MADV_NORMAL | MADV_RANDOM | MADV_SEQUENTIAL | MADV_WILLNEED | MADV_DONTFORK
+            | MADV_DOFORK | MADV_MERGEABLE | MADV_UNMERGEABLE | MADV_HUGEPAGE | MADV_NOHUGEPAGE
+            | MADV_DONTDUMP | MADV_DODUMP | MADV_WIPEONFORK | MADV_KEEPONFORK | MADV_COLD
+            | MADV_PAGEOUT | MADV_POPULATE_READ | MADV_POPULATE_WRITE => return Ok(0),
+            _ => return Err(EINVAL),
+        }
+        // Dropped private pages read back as zero (anonymous) or as the
+        // file (file mappings); shared ones keep their contents. `MADV_FREE`
+        // may drop lazily, so dropping now is as good as any time.
+        let pieces: Vec<_> = self
+            .vmas
+            .within(addr, end)
+            .filter(|(_, vma)| vma.flags & MAP_TYPE == MAP_PRIVATE)
+            .map(|(start, vma)| {
+                (
+                    start.max(addr),
+                    vma.end.min(end),
+                    vma.rebased(start, start.max(addr)),
+                )
+            })
+            .collect();
+        for (lo, hi, vma) in pieces {
+            mem.discard(lo, hi - lo).map_err(|_| ENOMEM)?;
+            if let Some(backing) = &vma.backing {
+                backing
+                    .load(mem, lo, backing.offset, hi - lo)
+                    .map_err(|_| ENOMEM)?;
+            }
+        }
+        // The heap is private anonymous memory too, though not a VMA.
+        let (lo, hi) = (
+            addr.max(self.brk_floor),
+            end.min(page_align_up(self.brk).unwrap_or(u64::MAX)),
+        );
+        if lo < hi {
+            mem.discard(lo, hi - lo).map_err(|_| ENOMEM)?;
+        }
+        Ok(0)
+    }
+
+    /// The range is only a hint, here as on Linux: everything is flushed.
+    pub(crate) fn riscv_flush_icache(
+        &mut self,
+        hart: &mut Hart<X>,
+        flags: u64,
+    ) -> Result<u64, i32> {
+        if flags & !(libc_riscv32::SYS_RISCV_FLUSH_ICACHE_LOCAL as u64) != 0 {
+            return Err(libc_riscv32::EINVAL);
+        }
+        hart.flush_icache();
+        Ok(0)
+    }
+
+    pub(crate) fn mprotect(
+        &mut self,
+        _mem: &M,
+        addr: u64,
+        len: u64,
+        prot: u64,
+    ) -> Result<u64, i32> {
+        if !addr.is_multiple_of(PAGE_SIZE) {
+            return Err(libc_riscv32::EINVAL);
+        }
+        let end = page_align_up(len)
+            .and_then(|size| addr.checked_add(size))
+            .ok_or(libc_riscv32::ENOMEM)?;
+        // Protections are recorded, not enforced: the arena is flat RW. The
+        // image, heap, and stack are not VMAs, so only mmap regions track
+        // their protection.
+        if self.vmas.covers(addr, end) {
+            self.vmas.update(addr, end, |vma| vma.prot = prot as u32);
+        }
+        Ok(0)
+    }
+}
@@ -0,0 +1,294 @@
+//! TCP passthrough to the host's network, limited to the addresses the
+//! embedder allows.
+//!
+//! A guest may only `connect` to an address added with
+//! [`HostTcp::allow_connect`] and only `listen` on one added with
+//! [`HostTcp::allow_listen`]; anything else fails with `EACCES`. Datagram
+//! sockets are not supported. Connecting blocks the host for up to
+//! [`HostTcp::with_connect_timeout`]; everything after that is non-blocking.
+use std::{
+    collections::{HashSet, VecDeque},
+    io::{self, Read, Write},
+    net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream},
+    time::Duration,
+};
+
+use super::{NetBackend, Shutdown, Socket, SocketType};
+
+/// A host TCP network, closed until addresses are allowed.
+#[derive(Debug, Clone)]
+pub struct HostTcp {
+    connect: HashSet<SocketAddr>,
+    listen: HashSet<SocketAddr>,
+    connect_timeout: Duration,
+}
+
+impl Default for HostTcp {
+    fn default() -> Self {
+        Self {
+            connect: HashSet::new(),
+            listen: HashSet::new(),
+            connect_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl HostTcp {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Let guests connect to `addr`.
+    pub fn allow_connect(mut self, addr: SocketAddr) -> Self {
+        self.connect.insert(addr);
+        self
+    }
+
+    /// Let guests bind and listen on `addr`.
+    pub fn allow_listen(mut self, addr: SocketAddr) -> Self {
+        self.listen.insert(addr);
+        self
+    }
+
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+}
+
+impl NetBackend for HostTcp {
+    fn socket(&mut self, ty: SocketType) -> Result<Box<dyn Socket>, i32> {
+        if ty != SocketType::Stream {
+            return Err(libc_riscv32::EPROTONOSUPPORT);
+        }
+        Ok(Box::new(HostSocket {
+            policy: self.clone(),
+            state: State::Fresh,
+            bound: None,
+            nodelay: false,
+        }))
+    }
+}
+
+#[derive(Debug)]
+enum State {
+    Fresh,
+    Listening {
+        listener: TcpListener,
+        /// Connections accepted from the host eagerly, so readiness can be
+        /// polled without consuming them.
+        pending: VecDeque<(TcpStream, SocketAddr)>,
+    },
+    Connected {
+        stream: TcpStream,
+        /// A `shutdown(SHUT_RD)` the host socket cannot express on its own.
+        read_shut: bool,
+    },
+}
+
+#[derive(Debug)]
+struct HostSocket {
+    policy: HostTcp,
+    state: State,
+    /// The address passed to `bind`, opened on `listen`.
+    bound: Option<SocketAddr>,
+    nodelay: bool,
+}
+
+fn errno(e: io::Error) -> i32 {
+    use io::ErrorKind::*;
+
+    match e.kind() {
+        WouldBlock => libc_riscv32::EAGAIN,
+        Interrupted => libc_riscv32::EINTR,
+        ConnectionRefused => libc_riscv32::ECONNREFUSED,
+        ConnectionReset => libc_riscv32::ECONNRESET,
+        ConnectionAborted => libc_riscv32::ECONNABORTED,
+        NotConnected => libc_riscv32::ENOTCONN,
+        AddrInUse => libc_riscv32::EADDRINUSE,
+        AddrNotAvailable => libc_riscv32::EADDRNOTAVAIL,
+        BrokenPipe => libc_riscv32::EPIPE,
+        TimedOut => libc_riscv32::ETIMEDOUT,
+        PermissionDenied => libc_riscv32::EACCES,
+        InvalidInput => libc_riscv32::EINVAL,
+        _ => libc_riscv32::EIO,
+    }
+}
+
+fn connected(stream: TcpStream, nodelay: bool) -> Result<State, i32> {
+    stream.set_nonblocking(true).map_err(errno)?;
+    stream.set_nodelay(nodelay).map_err(errno)?;
+    Ok(State::Connected {
+        stream,
+        read_shut: false,
+    })
+}
+
+impl HostSocket {
+    fn stream(&self) -> Result<&TcpStream, i32> {
+        match &self.state {
+            State::Connected { stream, .. } => Ok(stream),
+            _ => Err(libc_riscv32::ENOTCONN),
+        }
+    }
+
+    /// Move every connection the host has queued into `pending`.
+    fn accept_all(&mut self) {
+        if let State::Listening { listener, pending } = &mut self.state {
+            while let Ok(conn) = listener.accept() {
+                pending.push_back(conn);
+            }
+        }
+    }
+}
+
+impl Socket for HostSocket {
+    fn bind(&mut self, addr: SocketAddr) -> Result<(), i32> {
+        if !matches!(self.state, State::Fresh) || self.bound.is_some() {
+            return Err(libc_riscv32::EINVAL);
+        }
+        if !self.policy.listen.contains(&addr) {
+            return Err(libc_riscv32::EACCES);
+        }
+        self.bound = Some(addr);
+        Ok(())
+    }
+
+    fn listen(&mut self, _backlog: u32) -> Result<(), i32> {
+        match self.state {
+            State::Fresh => {}
+            State::Listening { .. } => return Ok(()),
+            State::Connected { .. } => return Err(libc_riscv32::EINVAL),
+        }
+        // Listening needs an allowed address; there is no autobind.
+        let addr = self.bound.ok_or(libc_riscv32::EACCES)?;
+        let listener = TcpListener::bind(addr).map_err(errno)?;
+        listener.set_nonblocking(true).map_err(errno)?;
+        self.state = State::Listening {
+            listener,
+            pending: VecDeque::new(),
+        };
+        Ok(())
+    }
+
+    fn accept(&mut self) -> Result<(Box<dyn Socket>, SocketAddr), i32> {
+        if !matches!(self.state, State::Listening { .. }) {
+            return Err(libc_riscv32::EINVAL);
+        }
+        self.accept_all();
+        let State::Listening { pending, .. } = &mut self.state else {
+            unreachable!();
+        };
+        let (stream, peer) = pending.pop_front().ok_or(libc_riscv32::EAGAIN)?;
+        let sock = HostSocket {
+            policy: self.policy.clone(),
+            state: connected(stream, self.nodelay)?,
+            bound: None,
+            nodelay: self.nodelay,
+        };
+        Ok((Box::new(sock), peer))
+    }
+
+    fn connect(&mut self, addr: SocketAddr) -> Result<(), i32> {
+        match self.state {
+            State::Fresh => {}
+            State::Listening { .. } => return Err(libc_riscv32::EINVAL),
+            State::Connected { .. } => return Err(libc_riscv32::EISCONN),
+        }
+        if !self.policy.connect.contains(&addr) {
+            return Err(libc_riscv32::EACCES);
+        }
+        let stream =
+            TcpStream::connect_timeout(&addr, self.policy.connect_timeout).map_err(errno)?;
+        self.state = connected(stream, self.nodelay)?;
+        Ok(())
+    }
+
+    fn send(&mut self, buf: &[u8], _to: Option<SocketAddr>) -> Result<usize, i32> {
+        let mut stream = self.stream()?;
+        stream.write(buf).map_err(errno)
+    }
+
+    fn recv(&mut self, buf: &mut [u8], peek: bool) -> Result<(usize, Option<SocketAddr>), i32> {
+        if let State::Connected {
+            read_shut: true, ..
+        } = self.state
+        {
+            return Ok((0, None));
+        }
+        let mut stream = self.stream()?;
+        let n = if peek {
+            stream.peek(buf)
+        } else {
+            stream.read(buf)
+        };
+        Ok((n.map_err(errno)?, None))
+    }
+
+    fn shutdown(&mut self, how: Shutdown) -> Result<(), i32> {
+        let State::Connected { stream, read_shut } = &mut self.state else {
+            return Err(libc_riscv32::ENOTCONN);
+        };
+        if how != Shutdown::Write {
+            *read_shut = true;
+        }
+        let how = match how {
+            Shutdown::Read => std::net::Shutdown::Read,
+            Shutdown::Write => std::net::Shutdown::Write,
+            Shutdown::Both => std::net::Shutdown::Both,
+        };
+        stream.shutdown(how).map_err(errno)
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr, i32> {
+        match &self.state {
+            State::Fresh => Ok(self
+                .bound
+                .unwrap_or(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0))),
+            State::Listening { listener, .. } => listener.local_addr().map_err(errno),
+            State::Connected { stream, .. } => stream.local_addr().map_err(errno),
+        }
+    }
+
+    fn peer_addr(&self) -> Result<SocketAddr, i32> {
+        self.stream()?.peer_addr().map_err(errno)
+    }
+
+    fn poll(&mut self) -> u32 {
+        use libc_riscv32::{POLLERR, POLLIN, POLLOUT};
+
+        self.accept_all();
+        let mask = match &self.state {
+            State::Fresh => 0,
+            State::Listening { pending, .. } => {
+                if pending.is_empty() {
+                    0
+                } else {
+                    POLLIN
+                }
+            }
+            State::Connected { stream, read_shut } => {
+                // A non-blocking peek tells data and EOF from neither.
+                let readable = *read_shut
+                    || !matches!(stream.peek(&mut [0]), Err(e) if e.kind() == io::ErrorKind::WouldBlock);
+                let mut mask = if readable { POLLIN } else { 0 };
+                match stream.take_error() {
+                    Ok(None) => mask |= POLLOUT,
+                    _ => mask |= POLLERR,
+                }
+                mask
+            }
+        };
+        mask as u32
+    }
+
+    fn set_option(&mut self, level: u32, name: u32, value: &[u8]) -> Result<(), i32> {
+        if (level, name) == (libc_riscv32::IPPROTO_TCP, libc_riscv32::TCP_NODELAY) {
+            self.nodelay = value.iter().any(|&b| b != 0);
+            if let State::Connected { stream, .. } = &self.state {
+                stream.set_nodelay(self.nodelay).map_err(errno)?;
+            }
+        }
+        Ok(())
+    }
+}
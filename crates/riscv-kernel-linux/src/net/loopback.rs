@@ -0,0 +1,576 @@
+//! An in-process network. Every [`Loopback`] clone is the same network, so
+//! guests given clones can reach each other, and the embedder can connect
+//! to guest listeners ([`Loopback::connect`]) or listen for guests
+//! ([`Loopback::listen`]).
+//!
+//! Ports are the only addressing: any IP reaches the socket bound to the
+//! port, and addresses are reported back as they were bound.
+//!
+//! Kernels parked on the network register a waker with it
+//! ([`NetBackend::wake_on`]), woken by the next send, receive, connection
+//! or shutdown anywhere on it.
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::{Arc, Mutex, Weak},
+    task::Waker,
+};
+
+use super::{NetBackend, Shutdown, Socket, SocketType};
+use crate::fd::lock;
+
+/// Bytes a stream buffers in each direction.
+const STREAM_CAPACITY: usize = 256 << 10;
+/// Datagrams a socket queues before dropping new arrivals.
+const INBOX_CAPACITY: usize = 256;
+/// Linux's default ephemeral port range.
+const EPHEMERAL: std::ops::RangeInclusive<u16> = 32768..=60999;
+
+#[derive(Debug, Default)]
+struct Net {
+    ports: HashMap<(SocketType, u16), Weak<Port>>,
+    next_ephemeral: u16,
+    wakers: Arc<Wakers>,
+}
+
+/// Wakers of the kernels parked on a network, each woken once, on the next
+/// change to any socket's readiness.
+#[derive(Debug, Default)]
+struct Wakers(Mutex<Vec<Waker>>);
+
+impl Wakers {
+    fn add(&self, waker: &Waker) {
+        let mut wakers = lock(&self.0);
+        if !wakers.iter().any(|w| w.will_wake(waker)) {
+            wakers.push(waker.clone());
+        }
+    }
+
+    fn wake(&self) {
+        for waker in std::mem::take(&mut *lock(&self.0)) {
+            waker.wake();
+        }
+    }
+}
+
+impl Net {
+    fn port(&self, ty: SocketType, port: u16) -> Option<Arc<Port>> {
+        self.ports.get(&(ty, port)).and_then(Weak::upgrade)
+    }
+
+    /// Reserve `addr` (an ephemeral port if its port is 0).
+    fn bind(&mut self, ty: SocketType, mut addr: SocketAddr) -> Result<Arc<Port>, i32> {
+        if addr.port() == 0 {
+            let span = EPHEMERAL.end() - EPHEMERAL.start() + 1;
+            let free = (0..span)
+                .map(|i| EPHEMERAL.start() + (self.next_ephemeral.wrapping_add(i) % span))
+                .find(|&p| self.port(ty, p).is_none())
+                .ok_or(libc_riscv32::EADDRINUSE)?;
+            self.next_ephemeral = free - EPHEMERAL.start() + 1;
+            addr.set_port(free);
+        } else if self.port(ty, addr.port()).is_some() {
+            return Err(libc_riscv32::EADDRINUSE);
+        }
+        let port = Arc::new(Port {
+            addr,
+            queue: Mutex::new(Queue::Bound),
+            wakers: self.wakers.clone(),
+        });
+        self.ports.insert((ty, addr.port()), Arc::downgrade(&port));
+        Ok(port)
+    }
+}
+
+/// A bound port, alive as long as its socket is.
+#[derive(Debug)]
+struct Port {
+    addr: SocketAddr,
+    queue: Mutex<Queue>,
+    wakers: Arc<Wakers>,
+}
+
+#[derive(Debug)]
+enum Queue {
+    Bound,
+    /// Connections waiting for `accept`, with room for `backlog`.
+    Listening {
+        pending: VecDeque<Conn>,
+        backlog: usize,
+    },
+    /// Received datagrams and their senders.
+    Inbox(VecDeque<(SocketAddr, Vec<u8>)>),
+}
+
+/// Both directions of a stream connection.
+#[derive(Debug)]
+struct Channel {
+    /// `bufs[side]` carries bytes written by `side`.
+    bufs: [VecDeque<u8>; 2],
+    /// `side` will write no more (shutdown or closed).
+    write_shut: [bool; 2],
+    /// `side` will read no more.
+    read_shut: [bool; 2],
+    addrs: [SocketAddr; 2],
+    wakers: Arc<Wakers>,
+}
+
+/// One side of a stream connection.
+#[derive(Debug)]
+struct Conn {
+    chan: Arc<Mutex<Channel>>,
+    side: usize,
+}
+
+impl Conn {
+    fn pair(net: &Mutex<Net>, a: SocketAddr, b: SocketAddr) -> (Conn, Conn) {
+        let chan = Arc::new(Mutex::new(Channel {
+            bufs: Default::default(),
+            write_shut: [false; 2],
+            read_shut: [false; 2],
+            addrs: [a, b],
+            wakers: lock(net).wakers.clone(),
+        }));
+        (
+            Conn {
+                chan: chan.clone(),
+                side: 0,
+            },
+            Conn { chan, side: 1 },
+        )
+    }
+
+    fn local(&self) -> SocketAddr {
+        lock(&self.chan).addrs[self.side]
+    }
+
+    fn peer(&self) -> SocketAddr {
+        lock(&self.chan).addrs[1 - self.side]
+    }
+
+    fn send(&self, buf: &[u8]) -> Result<usize, i32> {
+        let mut chan = lock(&self.chan);
+        if chan.write_shut[self.side] || chan.read_shut[1 - self.side] {
+            return Err(libc_riscv32::EPIPE);
+        }
+        let room = STREAM_CAPACITY - chan.bufs[self.side].len();
+        if room == 0 {
+            return Err(libc_riscv32::EAGAIN);
+        }
+        let n = buf.len().min(room);
+        chan.bufs[self.side].extend(&buf[..n]);
+        let wakers = chan.wakers.clone();
+        drop(chan);
+        wakers.wake();
+        Ok(n)
+    }
+
+    fn recv(&self, buf: &mut [u8], peek: bool) -> Result<usize, i32> {
+        let mut chan = lock(&self.chan);
+        let other = 1 - self.side;
+        if chan.read_shut[self.side] {
+            return Ok(0);
+        }
+        if chan.bufs[other].is_empty() {
+            return if chan.write_shut[other] {
+                Ok(0)
+            } else {
+                Err(libc_riscv32::EAGAIN)
+            };
+        }
+        let n = buf.len().min(chan.bufs[other].len());
+        for (dst, src) in buf.iter_mut().zip(chan.bufs[other].iter()) {
+            *dst = *src;
+        }
+        if !peek {
+            chan.bufs[other].drain(..n);
+            let wakers = chan.wakers.clone();
+            drop(chan);
+            wakers.wake();
+        }
+        Ok(n)
+    }
+
+    fn shutdown(&self, how: Shutdown) {
+        let mut chan = lock(&self.chan);
+        if how != Shutdown::Write {
+            chan.read_shut[self.side] = true;
+        }
+        if how != Shutdown::Read {
+            chan.write_shut[self.side] = true;
+        }
+        let wakers = chan.wakers.clone();
+        drop(chan);
+        wakers.wake();
+    }
+
+    fn poll(&self) -> u32 {
+        use libc_riscv32::{POLLERR, POLLHUP, POLLIN, POLLOUT};
+
+        let chan = lock(&self.chan);
+        let other = 1 - self.side;
+        let mut mask = 0;
+        if !chan.bufs[other].is_empty() || chan.write_shut[other] || chan.read_shut[self.side] {
+            mask |= POLLIN;
+        }
+        if chan.read_shut[other] {
+            mask |= POLLERR;
+        } else if chan.bufs[self.side].len() < STREAM_CAPACITY {
+            mask |= POLLOUT;
+        }
+        if chan.write_shut[other] && chan.write_shut[self.side] {
+            mask |= POLLHUP;
+        }
+        mask as u32
+    }
+}
+
+impl Drop for Conn {
+    fn drop(&mut self) {
+        self.shutdown(Shutdown::Both);
+    }
+}
+
+#[derive(Debug)]
+enum State {
+    Fresh,
+    Bound(Arc<Port>),
+    Listening(Arc<Port>),
+    Connected(Conn),
+}
+
+#[derive(Debug)]
+struct LoopbackSocket {
+    net: Arc<Mutex<Net>>,
+    ty: SocketType,
+    state: State,
+    /// Default destination of a connected datagram socket.
+    peer: Option<SocketAddr>,
+    /// The local port of a connected stream, held until it closes.
+    reserved: Option<Arc<Port>>,
+}
+
+impl LoopbackSocket {
+    fn port(&self) -> Option<&Arc<Port>> {
+        match &self.state {
+            State::Bound(port) | State::Listening(port) => Some(port),
+            _ => None,
+        }
+    }
+
+    /// The datagram port, binding an ephemeral one on first use.
+    fn autobind(&mut self) -> Result<Arc<Port>, i32> {
+        if let Some(port) = self.port() {
+            return Ok(port.clone());
+        }
+        let any = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+        let port = lock(&self.net).bind(self.ty, any)?;
+        *lock(&port.queue) = Queue::Inbox(VecDeque::new());
+        self.state = State::Bound(port.clone());
+        Ok(port)
+    }
+}
+
+impl Socket for LoopbackSocket {
+    fn bind(&mut self, addr: SocketAddr) -> Result<(), i32> {
+        if !matches!(self.state, State::Fresh) {
+            return Err(libc_riscv32::EINVAL);
+        }
+        let port = lock(&self.net).bind(self.ty, addr)?;
+        if self.ty == SocketType::Datagram {
+            *lock(&port.queue) = Queue::Inbox(VecDeque::new());
+        }
+        self.state = State::Bound(port);
+        Ok(())
+    }
+
+    fn listen(&mut self, backlog: u32) -> Result<(), i32> {
+        if self.ty != SocketType::Stream {
+            return Err(libc_riscv32::EOPNOTSUPP);
+        }
+        let port = match &self.state {
+            State::Bound(port) | State::Listening(port) => port.clone(),
+            State::Fresh => {
+                let any = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0);
+                lock(&self.net).bind(self.ty, any)?
+            }
+            State::Connected(_) => return Err(libc_riscv32::EINVAL),
+        };
+        let backlog = backlog.clamp(1, 4096) as usize;
+        let mut queue = lock(&port.queue);
+        match &mut *queue {
+            Queue::Listening { backlog: b, .. } => *b = backlog,
+            q => {
+                *q = Queue::Listening {
+                    pending: VecDeque::new(),
+                    backlog,
+                }
+            }
+        }
+        drop(queue);
+        self.state = State::Listening(port);
+        Ok(())
+    }
+
+    fn accept(&mut self) -> Result<(Box<dyn Socket>, SocketAddr), i32> {
+        let State::Listening(port) = &self.state else {
+            return Err(libc_riscv32::EINVAL);
+        };
+        let Queue::Listening { pending, .. } = &mut *lock(&port.queue) else {
+            return Err(libc_riscv32::EINVAL);
+        };
+        let conn = pending.pop_front().ok_or(libc_riscv32::EAGAIN)?;
+        let peer = conn.peer();
+        let sock = LoopbackSocket {
+            net: self.net.clone(),
+            ty: SocketType::Stream,
+            state: State::Connected(conn),
+            peer: None,
+            reserved: None,
+        };
+        Ok((Box::new(sock), peer))
+    }
+
+    fn connect(&mut self, addr: SocketAddr) -> Result<(), i32> {
+        if self.ty == SocketType::Datagram {
+            self.peer = Some(addr);
+            return Ok(());
+        }
+        let port = match &self.state {
+            State::Fresh => {
+                let any = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+                lock(&self.net).bind(self.ty, any)?
+            }
+            State::Bound(port) => port.clone(),
+            State::Listening(_) => return Err(libc_riscv32::EINVAL),
+            State::Connected(_) => return Err(libc_riscv32::EISCONN),
+        };
+        let (client, server) = Conn::pair(&self.net, port.addr, addr);
+        connect_to(&self.net, addr.port(), server)?;
+        self.state = State::Connected(client);
+        self.reserved = Some(port);
+        Ok(())
+    }
+
+    fn send(&mut self, buf: &[u8], to: Option<SocketAddr>) -> Result<usize, i32> {
+        match (&self.state, self.ty) {
+            (State::Connected(conn), _) => conn.send(buf),
+            (_, SocketType::Datagram) => {
+                let to = to.or(self.peer).ok_or(libc_riscv32::EDESTADDRREQ)?;
+                let from = self.autobind()?.addr;
+                let target = lock(&self.net).port(SocketType::Datagram, to.port());
+                // Datagrams to nobody, or to a full inbox, are dropped.
+                if let Some(target) = target {
+                    if let Queue::Inbox(inbox) = &mut *lock(&target.queue) {
+                        if inbox.len() < INBOX_CAPACITY {
+                            inbox.push_back((from, buf.to_vec()));
+                        }
+                    }
+                    target.wakers.wake();
+                }
+                Ok(buf.len())
+            }
+            _ => Err(libc_riscv32::ENOTCONN),
+        }
+    }
+
+    fn recv(&mut self, buf: &mut [u8], peek: bool) -> Result<(usize, Option<SocketAddr>), i32> {
+        match &self.state {
+            State::Connected(conn) => Ok((conn.recv(buf, peek)?, None)),
+            State::Bound(port) if self.ty == SocketType::Datagram => {
+                let Queue::Inbox(inbox) = &mut *lock(&port.queue) else {
+                    return Err(libc_riscv32::EINVAL);
+                };
+                let (from, data) = inbox.front().ok_or(libc_riscv32::EAGAIN)?;
+                let (from, n) = (*from, buf.len().min(data.len()));
+                buf[..n].copy_from_slice(&data[..n]);
+                if !peek {
+                    inbox.pop_front();
+                }
+                Ok((n, Some(from)))
+            }
+            // An unbound datagram socket has nothing to receive yet.
+            State::Fresh if self.ty == SocketType::Datagram => Err(libc_riscv32::EAGAIN),
+            _ => Err(libc_riscv32::ENOTCONN),
+        }
+    }
+
+    fn shutdown(&mut self, how: Shutdown) -> Result<(), i32> {
+        match &self.state {
+            State::Connected(conn) => {
+                conn.shutdown(how);
+                Ok(())
+            }
+            _ => Err(libc_riscv32::ENOTCONN),
+        }
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr, i32> {
+        Ok(match &self.state {
+            State::Fresh => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+            State::Bound(port) | State::Listening(port) => port.addr,
+            State::Connected(conn) => conn.local(),
+        })
+    }
+
+    fn peer_addr(&self) -> Result<SocketAddr, i32> {
+        match &self.state {
+            State::Connected(conn) => Ok(conn.peer()),
+            _ => self.peer.ok_or(libc_riscv32::ENOTCONN),
+        }
+    }
+
+    fn poll(&mut self) -> u32 {
+        use libc_riscv32::{POLLIN, POLLOUT};
+
+        let ready_in = match &self.state {
+            State::Connected(conn) => return conn.poll(),
+            State::Listening(port) | State::Bound(port) => match &*lock(&port.queue) {
+                Queue::Listening { pending, .. } => !pending.is_empty(),
+                Queue::Inbox(inbox) => !inbox.is_empty(),
+                Queue::Bound => false,
+            },
+            State::Fresh => false,
+        };
+        let mut mask = 0;
+        if ready_in {
+            mask |= POLLIN;
+        }
+        if self.ty == SocketType::Datagram {
+            mask |= POLLOUT;
+        }
+        mask as u32
+    }
+}
+
+/// Queue `server` on the listener at `port`.
+fn connect_to(net: &Mutex<Net>, port: u16, server: Conn) -> Result<(), i32> {
+    let listener = lock(net)
+        .port(SocketType::Stream, port)
+        .ok_or(libc_riscv32::ECONNREFUSED)?;
+    let mut queue = lock(&listener.queue);
+    match &mut *queue {
+        Queue::Listening { pending, backlog } if pending.len() < *backlog => {
+            pending.push_back(server);
+            drop(queue);
+            listener.wakers.wake();
+            Ok(())
+        }
+        _ => Err(libc_riscv32::ECONNREFUSED),
+    }
+}
+
+fn io_error(errno: i32) -> io::Error {
+    match errno {
+        libc_riscv32::EAGAIN => io::ErrorKind::WouldBlock.into(),
+        libc_riscv32::EPIPE => io::ErrorKind::BrokenPipe.into(),
+        libc_riscv32::ECONNREFUSED => io::ErrorKind::ConnectionRefused.into(),
+        libc_riscv32::EADDRINUSE => io::ErrorKind::AddrInUse.into(),
+        _ => io::Error::other(format!("errno {errno}")),
+    }
+}
+
+/// The in-process network.
+#[derive(Debug, Clone, Default)]
+pub struct Loopback {
+    net: Arc<Mutex<Net>>,
+}
+
+impl Loopback {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Connect the embedder to a guest listening on `port`.
+    pub fn connect(&self, port: u16) -> io::Result<LoopbackStream> {
+        let local = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+        let peer = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port);
+        let (host, guest) = Conn::pair(&self.net, local, peer);
+        connect_to(&self.net, port, guest).map_err(io_error)?;
+        Ok(LoopbackStream(host))
+    }
+
+    /// Listen on `port` for guest connections.
+    pub fn listen(&self, port: u16) -> io::Result<LoopbackListener> {
+        let any = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port);
+        let port = lock(&self.net)
+            .bind(SocketType::Stream, any)
+            .map_err(io_error)?;
+        *lock(&port.queue) = Queue::Listening {
+            pending: VecDeque::new(),
+            backlog: 128,
+        };
+        Ok(LoopbackListener(port))
+    }
+}
+
+impl NetBackend for Loopback {
+    fn socket(&mut self, ty: SocketType) -> Result<Box<dyn Socket>, i32> {
+        Ok(Box::new(LoopbackSocket {
+            net: self.net.clone(),
+            ty,
+            state: State::Fresh,
+            peer: None,
+            reserved: None,
+        }))
+    }
+
+    fn wake_on(&mut self, waker: &Waker) -> bool {
+        lock(&self.net).wakers.add(waker);
+        true
+    }
+}
+
+/// The embedder's end of a loopback listener. Accepting never blocks.
+#[derive(Debug)]
+pub struct LoopbackListener(Arc<Port>);
+
+impl LoopbackListener {
+    pub fn local_addr(&self) -> SocketAddr {
+        self.0.addr
+    }
+
+    /// Take a pending guest connection ([`io::ErrorKind::WouldBlock`] if
+    /// there is none).
+    pub fn accept(&self) -> io::Result<LoopbackStream> {
+        match &mut *lock(&self.0.queue) {
+            Queue::Listening { pending, .. } => pending
+                .pop_front()
+                .map(LoopbackStream)
+                .ok_or_else(|| io::ErrorKind::WouldBlock.into()),
+            _ => unreachable!("host listeners are created listening"),
+        }
+    }
+}
+
+/// The embedder's end of a loopback connection. Reads and writes never
+/// block ([`io::ErrorKind::WouldBlock`]); dropping it closes the connection.
+#[derive(Debug)]
+pub struct LoopbackStream(Conn);
+
+impl LoopbackStream {
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.0.peer()
+    }
+
+    /// Stop sending: the guest reads EOF once it has drained the stream.
+    pub fn shutdown_write(&self) {
+        self.0.shutdown(Shutdown::Write);
+    }
+}
+
+impl io::Read for LoopbackStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.recv(buf, false).map_err(io_error)
+    }
+}
+
+impl io::Write for LoopbackStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.send(buf).map_err(io_error)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
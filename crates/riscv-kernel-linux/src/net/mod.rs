@@ -0,0 +1,83 @@
+//! Guest networking.
+//!
+//! The kernel owns socket descriptors and the syscall ABI; the network
+//! itself is whatever [`NetBackend`] the embedder installs with
+//! [`MockLinux::with_net`](crate::MockLinux::with_net). Without one, guests
+//! get `EAFNOSUPPORT` from `socket`.
+//!
+//! Two backends ship here: [`Loopback`], an in-process network joining
+//! guests (and the embedder) that share it, and [`HostTcp`], which passes
+//! TCP through to the host for an allowlisted set of addresses only.
+//!
+//! Backend errors are Linux errno values (`libc_riscv32::E*`). Nothing here
+//! may block: operations that would wait return `EAGAIN`, and the kernel
+//! parks blocking guests until [`Socket::poll`] says otherwise.
+use std::{fmt::Debug, net::SocketAddr, task::Waker};
+
+mod host;
+mod loopback;
+
+pub use host::HostTcp;
+pub use loopback::{Loopback, LoopbackListener, LoopbackStream};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SocketType {
+    Stream,
+    Datagram,
+}
+
+/// The directions `shutdown` closes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shutdown {
+    Read,
+    Write,
+    Both,
+}
+
+/// A network stack guest sockets are routed to.
+pub trait NetBackend: Debug + Send {
+    fn socket(&mut self, ty: SocketType) -> Result<Box<dyn Socket>, i32>;
+
+    /// Have `waker` woken on the next change to any of the network's
+    /// sockets' readiness, if the backend can tell (it returns whether);
+    /// see [`riscv_vm::machine::Kernel::wake_on`].
+    fn wake_on(&mut self, _waker: &Waker) -> bool {
+        false
+    }
+}
+
+/// One socket in a [`NetBackend`]. Dropping it closes it.
+pub trait Socket: Debug + Send {
+    fn bind(&mut self, addr: SocketAddr) -> Result<(), i32>;
+
+    fn listen(&mut self, backlog: u32) -> Result<(), i32>;
+
+    /// Take a pending connection and its peer address, or `EAGAIN`.
+    fn accept(&mut self) -> Result<(Box<dyn Socket>, SocketAddr), i32>;
+
+    fn connect(&mut self, addr: SocketAddr) -> Result<(), i32>;
+
+    /// Send to the connected peer, or to `to` (datagram sockets). `EAGAIN`
+    /// when there is no room.
+    fn send(&mut self, buf: &[u8], to: Option<SocketAddr>) -> Result<usize, i32>;
+
+    /// Receive into `buf`, with the sender's address for datagrams. `EAGAIN`
+    /// when nothing is queued; `Ok(0)` at end of stream.
+    fn recv(&mut self, buf: &mut [u8], peek: bool) -> Result<(usize, Option<SocketAddr>), i32>;
+
+    fn shutdown(&mut self, how: Shutdown) -> Result<(), i32>;
+
+    fn local_addr(&self) -> Result<SocketAddr, i32>;
+
+    fn peer_addr(&self) -> Result<SocketAddr, i32>;
+
+    /// Current `POLLIN`/`POLLOUT`/`POLLHUP`/`POLLERR` readiness.
+    fn poll(&mut self) -> u32;
+
+    /// Apply a `setsockopt`. The kernel records every option the guest sets
+    /// and answers `getsockopt` itself, so backends only act on those they
+    /// care about.
+    fn set_option(&mut self, _level: u32, _name: u32, _value: &[u8]) -> Result<(), i32> {
+        Ok(())
+    }
+}
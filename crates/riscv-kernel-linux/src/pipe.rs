@@ -0,0 +1,225 @@
+//! Anonymous pipes, between guest descriptors or between the guest and the
+//! embedder.
+//!
+//! A pipe is a bounded byte queue plus a count of open ends on each side.
+//! Readers see EOF once every write end is closed; writers get `EPIPE` once
+//! every read end is. A blocking read of an empty pipe, or write to a full
+//! one, parks the guest on the syscall (see [`crate::ERESTARTSYS`]) until
+//! the other side makes progress: the embedder through a [`PipeWriter`] or
+//! [`PipeReader`], or the guest itself from another descriptor.
+use std::{
+    collections::VecDeque,
+    io,
+    sync::{Arc, Mutex},
+};
+
+use riscv_vm::memory::Memory;
+
+use crate::{
+    fd::{lock, Fd, Node, OpenFile},
+    poll::Readiness,
+    KernelXlen, MockLinux,
+};
+
+/// Linux's default pipe capacity (16 pages).
+const PIPE_CAPACITY: usize = 16 * 4096;
+
+#[derive(Debug, Default)]
+struct Pipe {
+    buf: VecDeque<u8>,
+    readers: usize,
+    writers: usize,
+    /// Bumped on every state change, for edge-triggered epoll.
+    seq: u64,
+}
+
+/// One open end of a pipe. Ends are counted, so cloning one opens another.
+#[derive(Debug)]
+pub(crate) struct PipeEnd {
+    pipe: Arc<Mutex<Pipe>>,
+    write: bool,
+}
+
+impl PipeEnd {
+    fn new(pipe: Arc<Mutex<Pipe>>, write: bool) -> Self {
+        let mut state = lock(&pipe);
+        if write {
+            state.writers += 1;
+        } else {
+            state.readers += 1;
+        }
+        state.seq += 1;
+        drop(state);
+        Self { pipe, write }
+    }
+
+    pub(crate) fn is_write(&self) -> bool {
+        self.write
+    }
+
+    /// Read into `buf`: `Ok(0)` at EOF, `EAGAIN` while empty.
+    pub(crate) fn read(&self, buf: &mut [u8]) -> Result<usize, i32> {
+        let mut state = lock(&self.pipe);
+        if state.buf.is_empty() && state.writers > 0 && !buf.is_empty() {
+            return Err(libc_riscv32::EAGAIN);
+        }
+        let n = buf.len().min(state.buf.len());
+        for (dst, src) in buf.iter_mut().zip(state.buf.drain(..n)) {
+            *dst = src;
+        }
+        state.seq += 1;
+        Ok(n)
+    }
+
+    /// Queue as much of `bytes` as fits, or `EAGAIN` if none does. Writes
+    /// of up to `PIPE_BUF` bytes are all-or-nothing.
+    pub(crate) fn write(&self, bytes: &[u8]) -> Result<usize, i32> {
+        let mut state = lock(&self.pipe);
+        if state.readers == 0 {
+            return Err(libc_riscv32::EPIPE);
+        }
+        let room = PIPE_CAPACITY - state.buf.len();
+        if room == 0 || (bytes.len() <= libc_riscv32::PIPE_BUF && room < bytes.len()) {
+            return Err(libc_riscv32::EAGAIN);
+        }
+        let n = bytes.len().min(room);
+        state.buf.extend(&bytes[..n]);
+        state.seq += 1;
+        Ok(n)
+    }
+
+    pub(crate) fn poll(&self) -> Readiness {
+        use libc_riscv32::{POLLERR, POLLHUP, POLLIN, POLLOUT};
+
+        let state = lock(&self.pipe);
+        let mut mask = 0;
+        if self.write {
+            if state.readers == 0 {
+                mask |= POLLERR;
+            } else if PIPE_CAPACITY - state.buf.len() >= libc_riscv32::PIPE_BUF {
+                mask |= POLLOUT;
+            }
+        } else {
+            if !state.buf.is_empty() {
+                mask |= POLLIN;
+            }
+            if state.writers == 0 {
+                mask |= POLLHUP;
+            }
+        }
+        Readiness {
+            mask: mask as u32,
+            seq: state.seq,
+        }
+    }
+}
+
+impl Clone for PipeEnd {
+    fn clone(&self) -> Self {
+        Self::new(self.pipe.clone(), self.write)
+    }
+}
+
+impl Drop for PipeEnd {
+    fn drop(&mut self) {
+        let mut state = lock(&self.pipe);
+        if self.write {
+            state.writers -= 1;
+        } else {
+            state.readers -= 1;
+        }
+        state.seq += 1;
+    }
+}
+
+fn pipe() -> (PipeEnd, PipeEnd) {
+    let pipe = Arc::new(Mutex::new(Pipe::default()));
+    (PipeEnd::new(pipe.clone(), false), PipeEnd::new(pipe, true))
+}
+
+fn io_error(errno: i32) -> io::Error {
+    match errno {
+        libc_riscv32::EAGAIN => io::ErrorKind::WouldBlock.into(),
+        libc_riscv32::EPIPE => io::ErrorKind::BrokenPipe.into(),
+        _ => io::Error::from_raw_os_error(errno),
+    }
+}
+
+/// The embedder's write end of a pipe the guest reads from. Writes never
+/// block: a full pipe reports [`io::ErrorKind::WouldBlock`]. Dropping it
+/// gives the guest EOF.
+#[derive(Debug)]
+pub struct PipeWriter(PipeEnd);
+
+impl io::Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf).map_err(io_error)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The embedder's read end of a pipe the guest writes to. Reads never
+/// block: an empty pipe reports [`io::ErrorKind::WouldBlock`], and `Ok(0)`
+/// means the guest closed its end.
+#[derive(Debug)]
+pub struct PipeReader(PipeEnd);
+
+impl io::Read for PipeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf).map_err(io_error)
+    }
+}
+
+impl<X: KernelXlen, M: Memory> MockLinux<X, M> {
+    /// Open a pipe the embedder feeds, returning the guest's read
+    /// descriptor and the host's write end.
+    pub fn pipe_from_host(&mut self) -> io::Result<(i32, PipeWriter)> {
+        let (read, write) = pipe();
+        let fd = self.install_pipe_end(read, 0).map_err(io_error)?;
+        Ok((fd, PipeWriter(write)))
+    }
+
+    /// Open a pipe the embedder drains, returning the guest's write
+    /// descriptor and the host's read end.
+    pub fn pipe_to_host(&mut self) -> io::Result<(i32, PipeReader)> {
+        let (read, write) = pipe();
+        let fd = self.install_pipe_end(write, 0).map_err(io_error)?;
+        Ok((fd, PipeReader(read)))
+    }
+
+    fn install_pipe_end(&mut self, end: PipeEnd, flags: u32) -> Result<i32, i32> {
+        let access = if end.is_write() {
+            libc_riscv32::O_WRONLY
+        } else {
+            libc_riscv32::O_RDONLY
+        };
+        let cloexec = flags & libc_riscv32::O_CLOEXEC != 0;
+        let file = OpenFile::new(Node::Pipe(end), access | (flags & libc_riscv32::O_NONBLOCK));
+        self.fds.insert(Fd::new(file, cloexec))
+    }
+
+    pub(crate) fn pipe2(&mut self, mem: &mut M, fds: u64, flags: u32) -> Result<u64, i32> {
+        use libc_riscv32::{O_CLOEXEC, O_NONBLOCK};
+
+        if flags & !(O_CLOEXEC | O_NONBLOCK) != 0 {
+            return Err(libc_riscv32::EINVAL);
+        }
+        mem.check_range(riscv_vm::error::MemoryAccess::Store, fds, 8)
+            .map_err(|_| libc_riscv32::EFAULT)?;
+        let (read, write) = pipe();
+        let read = self.install_pipe_end(read, flags)?;
+        let write = match self.install_pipe_end(write, flags) {
+            Ok(fd) => fd,
+            Err(e) => {
+                self.fds.remove(read)?;
+                return Err(e);
+            }
+        };
+        mem.copy_to(fds, &[read, write])
+            .map_err(|_| libc_riscv32::EFAULT)?;
+        Ok(0)
+    }
+}
@@ -0,0 +1,248 @@
+//! Seccomp-style syscall policy.
+//!
+//! A [`SyscallPolicy`] maps each `Sysno` to rules, checked in the order
+//! they were added; the first rule whose argument predicates all hold picks
+//! the [`Action`], and calls no rule matches (including numbers the kernel
+//! does not know) get the default. Install one with
+//! [`MockLinux::with_policy`].
+//!
+//! [`Action::Kill`] and [`Action::Trap`] stop the machine with a
+//! [`LinuxError`], leaving pc on the ecall. A trapped call can then be
+//! finished by the embedder ([`MockLinux::complete_syscall`]) or let through
+//! ([`MockLinux::admit_syscall`]) before resuming.
+//!
+//! [`Action::Suspend`] instead parks the machine on the call, as a syscall
+//! that cannot complete yet does: the run returns normally, with the call
+//! described by [`MockLinux::suspended_syscall`], and is finished or let
+//! through the same way. [`crate::run_async`] drives a machine this way,
+//! awaiting a host future for each suspended call.
+use std::{collections::HashMap, fmt, hash::Hash};
+
+use riscv_vm::hart::Hart;
+use riscv_vm::memory::Memory;
+use riscv_vm::riscv_inst::Reg;
+
+use crate::{fs::normalize, KernelXlen, LinuxError, MockLinux};
+
+/// What happens to a syscall.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Allow,
+    /// Fail with this errno (e.g. `libc_riscv32::EPERM`) without running.
+    Errno(i32),
+    /// Stop the machine with [`LinuxError::Killed`].
+    Kill,
+    /// Stop the machine with [`LinuxError::Trapped`] for the embedder to
+    /// decide.
+    Trap,
+    /// Park the machine on the call ([`StepResult::Yield`]) for the
+    /// embedder to finish; see [`MockLinux::suspended_syscall`].
+    ///
+    /// [`StepResult::Yield`]: riscv_vm::machine::StepResult::Yield
+    Suspend,
+}
+
+/// A condition on a syscall's arguments (`a0`..`a5`, numbered from 0).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgMatch {
+    /// `args[arg] == value`.
+    Eq { arg: usize, value: u64 },
+    /// `args[arg] & mask == value`, e.g. to refuse `O_CREAT`.
+    Masked { arg: usize, mask: u64, value: u64 },
+    /// `args[arg]` points at a path that, resolved against the directory fd
+    /// in `args[dirfd]` (or the cwd when `dirfd` is `None`) and normalized,
+    /// is `prefix` or lies under it. Unreadable paths never match.
+    PathUnder {
+        arg: usize,
+        dirfd: Option<usize>,
+        prefix: String,
+    },
+}
+
+impl ArgMatch {
+    /// The path of an `*at` call (dirfd in `a0`, path in `a1`) is under
+    /// `prefix`.
+    pub fn path_at_under(prefix: impl Into<String>) -> Self {
+        ArgMatch::PathUnder {
+            arg: 1,
+            dirfd: Some(0),
+            prefix: prefix.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    when: Vec<ArgMatch>,
+    action: Action,
+}
+
+#[derive(Debug, Clone)]
+pub struct SyscallPolicy<S> {
+    default: Action,
+    rules: HashMap<S, Vec<Rule>>,
+}
+
+pub type SyscallPolicy32 = SyscallPolicy<syscalls::riscv32::Sysno>;
+pub type SyscallPolicy64 = SyscallPolicy<syscalls::riscv64::Sysno>;
+
+impl<S: Copy + Eq + Hash> SyscallPolicy<S> {
+    /// A policy applying `default` to every syscall without a rule.
+    pub fn new(default: Action) -> Self {
+        Self {
+            default,
+            rules: HashMap::new(),
+        }
+    }
+
+    /// `action` for every call to `sysno` not caught by an earlier rule.
+    pub fn rule(self, sysno: S, action: Action) -> Self {
+        self.rule_when(sysno, [], action)
+    }
+
+    /// `action` for calls to `sysno` whose arguments satisfy all of `when`.
+    pub fn rule_when(
+        mut self,
+        sysno: S,
+        when: impl IntoIterator<Item = ArgMatch>,
+        action: Action,
+    ) -> Self {
+        self.rules.entry(sysno).or_default().push(Rule {
+            when: when.into_iter().collect(),
+            action,
+        });
+        self
+    }
+
+    /// Shorthand for [`Action::Allow`] rules on each of `sysnos`.
+    pub fn allow(self, sysnos: impl IntoIterator<Item = S>) -> Self {
+        sysnos
+            .into_iter()
+            .fold(self, |policy, sysno| policy.rule(sysno, Action::Allow))
+    }
+}
+
+/// A syscall as the guest issued it, for policy errors and suspensions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyscallInfo {
+    pub nr: u64,
+    /// `None` for numbers the kernel does not know.
+    pub name: Option<&'static str>,
+    pub args: [u64; 6],
+}
+
+impl fmt::Display for SyscallInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name {
+            Some(name) => write!(f, "{name}")?,
+            None => write!(f, "syscall_{}", self.nr)?,
+        }
+        write!(f, "({:#x}", self.args[0])?;
+        for arg in &self.args[1..] {
+            write!(f, ", {arg:#x}")?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl<X: KernelXlen, M: Memory> MockLinux<X, M> {
+    /// Restrict the guest's syscalls.
+    pub fn with_policy(mut self, policy: SyscallPolicy<X::Sysno>) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    pub fn policy(&self) -> Option<&SyscallPolicy<X::Sysno>> {
+        self.policy.as_ref()
+    }
+
+    /// The action for a call, or `None` if it skips the policy.
+    pub(crate) fn policy_action(
+        &mut self,
+        mem: &M,
+        sysno: Option<X::Sysno>,
+        args: &[u64; 6],
+    ) -> Option<Action> {
+        // A call the policy already let through (and that is being retried
+        // after a park, or was admitted after a trap) is not checked again.
+        if std::mem::take(&mut self.admitted) {
+            return None;
+        }
+        let policy = self.policy.as_ref()?;
+        let rules = sysno.and_then(|sysno| policy.rules.get(&sysno));
+        let action = rules
+            .into_iter()
+            .flatten()
+            .find(|rule| rule.when.iter().all(|m| self.arg_matches(mem, args, m)))
+            .map_or(policy.default, |rule| rule.action);
+        Some(action)
+    }
+
+    fn arg_matches(&self, mem: &M, args: &[u64; 6], m: &ArgMatch) -> bool {
+        let arg = |i: usize| args.get(i).copied();
+        match *m {
+            ArgMatch::Eq { arg: i, value } => arg(i) == Some(value),
+            ArgMatch::Masked {
+                arg: i,
+                mask,
+                value,
+            } => arg(i).is_some_and(|a| a & mask == value),
+            ArgMatch::PathUnder {
+                arg: i,
+                dirfd,
+                ref prefix,
+            } => {
+                let Some(ptr) = arg(i) else {
+                    return false;
+                };
+                let dirfd = match dirfd.map(arg) {
+                    Some(Some(fd)) => fd as i32,
+                    Some(None) => return false,
+                    None => libc_riscv32::AT_FDCWD,
+                };
+                let Ok(path) = self.resolve_at(mem, dirfd, ptr) else {
+                    return false;
+                };
+                let prefix = normalize("/", prefix);
+                prefix == "/"
+                    || path
+                        .strip_prefix(&prefix)
+                        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            }
+        }
+    }
+
+    /// The syscall the machine is parked on by [`Action::Suspend`], until it
+    /// is completed or admitted.
+    pub fn suspended_syscall(&self) -> Option<SyscallInfo> {
+        self.suspended
+    }
+
+    /// Finish the syscall the hart is stopped on (after
+    /// [`LinuxError::Trapped`], or suspended) without running it: `ret` goes
+    /// to the guest (an `Err` as `-errno`) and pc moves past the ecall.
+    pub fn complete_syscall(&mut self, hart: &mut Hart<X>, ret: Result<u64, i32>) {
+        let ret = ret.unwrap_or_else(|e| (-(e as i64)) as u64);
+        hart.set_reg(Reg::A0, X::from_u64(ret));
+        // ecall has no compressed form.
+        hart.pc = X::from_u64(X::to_u64(hart.pc).wrapping_add(4));
+        hart.retire(1);
+        self.admitted = false;
+        self.suspended = None;
+    }
+
+    /// Let the syscall the hart is stopped on (after [`LinuxError::Trapped`],
+    /// or suspended) run when the machine resumes.
+    pub fn admit_syscall(&mut self) {
+        self.admitted = true;
+        self.suspended = None;
+    }
+}
+
+/// The error for a stopping action.
+pub(crate) fn policy_error(action: Action, info: SyscallInfo) -> LinuxError {
+    match action {
+        Action::Trap => LinuxError::Trapped(info),
+        _ => LinuxError::Killed(info),
+    }
+}
@@ -0,0 +1,255 @@
+//! Readiness and waiting: `ppoll`, `pselect6`, and the parking that lets a
+//! syscall block without blocking the host.
+//!
+//! A syscall that cannot complete returns [`ERESTARTSYS`]; the dispatcher
+//! turns that into [`StepResult::Yield`], leaving pc on the ecall, and the
+//! embedder resumes the machine when it likes, re-issuing the call. Calls
+//! with a timeout remember their deadline across those retries (see
+//! [`MockLinux::blocked_until`]).
+//!
+//! [`StepResult::Yield`]: riscv_vm::machine::StepResult::Yield
+use std::{
+    task::Waker,
+    time::{Duration, Instant},
+};
+
+use riscv_vm::memory::{Memory, Pod};
+
+use crate::{
+    fd::{lock, Node},
+    KernelXlen, MockLinux, ERESTARTSYS,
+};
+
+/// `poll`-style readiness of an open file.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Readiness {
+    /// `POLL*` bits (which are also the `EPOLL*` bits).
+    pub(crate) mask: u32,
+    /// Changes whenever readiness may have changed; edge-triggered epoll
+    /// reports an event only once per value.
+    pub(crate) seq: u64,
+}
+
+impl Node {
+    pub(crate) fn poll(&self) -> Readiness {
+        use libc_riscv32::{POLLIN, POLLOUT};
+
+        let mask = match self {
+            // The host terminal is treated as always ready.
+            Node::Stdin => POLLIN,
+            Node::Stdout | Node::Stderr => POLLOUT,
+            // Regular files never block.
+            Node::Inode { .. } | Node::Device(_) => POLLIN | POLLOUT,
+            Node::Pipe(pipe) => return pipe.poll(),
+            Node::EventFd(eventfd) => return lock(eventfd).poll(),
+            Node::Socket(sock) => return lock(sock).poll(),
+            Node::Epoll(epoll) => {
+                if lock(epoll).any_ready() {
+                    POLLIN
+                } else {
+                    0
+                }
+            }
+        };
+        Readiness {
+            mask: mask as u32,
+            seq: 0,
+        }
+    }
+}
+
+/// A syscall parked with [`ERESTARTSYS`], remembered across its retries.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Wait {
+    deadline: Option<Instant>,
+}
+
+/// Map a would-block `EAGAIN` on a blocking descriptor to a park.
+pub(crate) fn park_unless(nonblock: bool) -> impl Fn(i32) -> i32 {
+    move |e| {
+        if e == libc_riscv32::EAGAIN && !nonblock {
+            ERESTARTSYS
+        } else {
+            e
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct PollFd {
+    pub(crate) fd: i32,
+    pub(crate) events: i16,
+    pub(crate) revents: i16,
+}
+// Safety: integers only; any bit pattern valid, no padding.
+unsafe impl Pod for PollFd {}
+
+/// `struct timespec` with 64-bit fields: rv64's, and rv32's time64 one.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Timespec {
+    sec: i64,
+    nsec: i64,
+}
+// Safety: integers only; any bit pattern valid, no padding.
+unsafe impl Pod for Timespec {}
+
+/// Read an optional timeout; null means wait forever.
+pub(crate) fn read_timespec<M: Memory>(mem: &M, tsp: u64) -> Result<Option<Duration>, i32> {
+    if tsp == 0 {
+        return Ok(None);
+    }
+    let ts = mem
+        .slice::<Timespec>(tsp, 1)
+        .map_err(|_| libc_riscv32::EFAULT)?[0];
+    if ts.sec < 0 || !(0..1_000_000_000).contains(&ts.nsec) {
+        return Err(libc_riscv32::EINVAL);
+    }
+    Ok(Some(Duration::new(ts.sec as u64, ts.nsec as u32)))
+}
+
+impl<X: KernelXlen, M: Memory> MockLinux<X, M> {
+    /// When the guest is parked on a syscall with a timeout, the instant it
+    /// times out. An embedder with nothing to feed the guest can sleep until
+    /// then before resuming it.
+    pub fn blocked_until(&self) -> Option<Instant> {
+        self.wait.and_then(|w| w.deadline)
+    }
+
+    /// [`Kernel::wake_on`]: only the network reaches a guest parked in the
+    /// kernel from outside. A call the policy suspended waits on the
+    /// embedder instead, which no waker covers.
+    ///
+    /// [`Kernel::wake_on`]: riscv_vm::machine::Kernel::wake_on
+    pub(crate) fn wake_on(&mut self, waker: &Waker) -> bool {
+        if self.suspended.is_some() {
+            return false;
+        }
+        self.net.as_mut().is_none_or(|net| net.wake_on(waker))
+    }
+
+    /// Park the current syscall until `timeout` (from its first issue)
+    /// expires: `Err(ERESTARTSYS)` while time remains, `Ok(())` once it is up.
+    pub(crate) fn park(&mut self, timeout: Option<Duration>) -> Result<(), i32> {
+        let wait = *self.wait.get_or_insert_with(|| Wait {
+            deadline: timeout.and_then(|t| Instant::now().checked_add(t)),
+        });
+        match wait.deadline {
+            Some(deadline) if Instant::now() >= deadline => Ok(()),
+            _ => Err(ERESTARTSYS),
+        }
+    }
+
+    /// Current readiness of `fd`, or `None` if it is not open.
+    pub(crate) fn poll_fd(&self, fd: i32) -> Option<Readiness> {
+        let fd = self.fds.get(fd).ok()?;
+        let readiness = fd.lock().node.poll();
+        Some(readiness)
+    }
+
+    pub(crate) fn ppoll(
+        &mut self,
+        mem: &mut M,
+        fds: u64,
+        nfds: u64,
+        tsp: u64,
+        _sigmask: u64,
+        _sigsetsize: u64,
+    ) -> Result<u64, i32> {
+        use libc_riscv32::{POLLERR, POLLHUP, POLLNVAL};
+
+        if nfds > self.fds.limit() as u64 {
+            return Err(libc_riscv32::EINVAL);
+        }
+        let timeout = read_timespec(mem, tsp)?;
+        let mut pollfds = mem
+            .slice::<PollFd>(fds, nfds)
+            .map_err(|_| libc_riscv32::EFAULT)?
+            .to_vec();
+        let mut ready = 0;
+        for pollfd in &mut pollfds {
+            pollfd.revents = if pollfd.fd < 0 {
+                0
+            } else {
+                let mask = self.poll_fd(pollfd.fd).map_or(POLLNVAL, |r| r.mask as i16);
+                // Errors and hangups are reported whether asked for or not.
+                mask & (pollfd.events | POLLERR | POLLHUP | POLLNVAL)
+            };
+            if pollfd.revents != 0 {
+                ready += 1;
+            }
+        }
+        if ready == 0 {
+            self.park(timeout)?;
+        }
+        mem.copy_to(fds, &pollfds)
+            .map_err(|_| libc_riscv32::EFAULT)?;
+        Ok(ready)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn pselect6(
+        &mut self,
+        mem: &mut M,
+        nfds: i32,
+        readfds: u64,
+        writefds: u64,
+        exceptfds: u64,
+        tsp: u64,
+        _sig: u64,
+    ) -> Result<u64, i32> {
+        use libc_riscv32::{POLLERR, POLLHUP, POLLIN, POLLOUT, POLLPRI};
+
+        let nfds = usize::try_from(nfds)
+            .ok()
+            .filter(|&n| n <= crate::fd::MAX_FDS)
+            .ok_or(libc_riscv32::EINVAL)?;
+        let timeout = read_timespec(mem, tsp)?;
+        // fd_sets are arrays of longs: round up to whole words.
+        let word = X::BITS as usize / 8;
+        let len = nfds.div_ceil(word * 8) * word;
+        let wanted = [
+            (readfds, (POLLIN | POLLHUP | POLLERR) as u32),
+            (writefds, (POLLOUT | POLLERR) as u32),
+            (exceptfds, POLLPRI as u32),
+        ];
+        let mut sets = Vec::with_capacity(wanted.len());
+        for &(ptr, _) in &wanted {
+            sets.push(if ptr == 0 {
+                None
+            } else {
+                Some(
+                    mem.slice::<u8>(ptr, len as u64)
+                        .map_err(|_| libc_riscv32::EFAULT)?
+                        .to_vec(),
+                )
+            });
+        }
+
+        let mut results = vec![vec![0u8; len]; wanted.len()];
+        let mut ready = 0;
+        for fd in 0..nfds {
+            let (byte, bit) = (fd / 8, 1u8 << (fd % 8));
+            if !sets.iter().flatten().any(|set| set[byte] & bit != 0) {
+                continue;
+            }
+            let mask = self.poll_fd(fd as i32).ok_or(libc_riscv32::EBADF)?.mask;
+            for ((set, &(_, want)), result) in sets.iter().zip(&wanted).zip(&mut results) {
+                if set.as_ref().is_some_and(|s| s[byte] & bit != 0) && mask & want != 0 {
+                    result[byte] |= bit;
+                    ready += 1;
+                }
+            }
+        }
+        if ready == 0 {
+            self.park(timeout)?;
+        }
+        for (&(ptr, _), result) in wanted.iter().zip(&results) {
+            if ptr != 0 {
+                mem.copy_to(ptr, result).map_err(|_| libc_riscv32::EFAULT)?;
+            }
+        }
+        Ok(ready)
+    }
+}
@@ -0,0 +1,313 @@
+//! Process identity and scheduling: `uname`, the `get*id` calls, CPU
+//! affinity, `sysinfo`, `membarrier` and `rseq`.
+//!
+//! The guest runs on a single hart but may be told it has more CPUs
+//! ([`MockLinux::with_cpus`]) so runtimes size their thread pools as they
+//! would on the host; all of them are always online and idle.
+use riscv_vm::memory::{Memory, Pod};
+
+use crate::{Credentials, KernelXlen, MockLinux, PAGE_SIZE};
+
+/// The kernel release `uname` reports.
+const RELEASE: &str = "6.8.0";
+
+/// Every `membarrier` command, as reported by `MEMBARRIER_CMD_QUERY`.
+const MEMBARRIER_COMMANDS: u32 = libc_riscv32::MEMBARRIER_CMD_GLOBAL
+    | libc_riscv32::MEMBARRIER_CMD_GLOBAL_EXPEDITED
+    | libc_riscv32::MEMBARRIER_CMD_REGISTER_GLOBAL_EXPEDITED
+    | libc_riscv32::MEMBARRIER_CMD_PRIVATE_EXPEDITED
+    | libc_riscv32::MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED
+    | libc_riscv32::MEMBARRIER_CMD_PRIVATE_EXPEDITED_SYNC_CORE
+    | libc_riscv32::MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED_SYNC_CORE;
+
+/// `struct sysinfo` up to `mem_unit`. The `u16` `procs` and its padding
+/// share one word (little-endian, so `procs` is the low half), as does
+/// `mem_unit` on rv64; rv32 has 8 more bytes of `_f` after the struct.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SysInfo<U> {
+    uptime: U,
+    loads: [U; 3],
+    totalram: U,
+    freeram: U,
+    sharedram: U,
+    bufferram: U,
+    totalswap: U,
+    freeswap: U,
+    procs: U,
+    totalhigh: U,
+    freehigh: U,
+    mem_unit: U,
+}
+// Safety: width-sized integers only; any bit pattern valid, no padding.
+unsafe impl<U: Pod> Pod for SysInfo<U> {}
+
+/// A registered `struct rseq` area.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Rseq {
+    addr: u64,
+    len: u32,
+    sig: u32,
+}
+
+/// Copy `s` into a NUL-padded `utsname` field, truncating if need be.
+fn uts_field(field: &mut [u8], s: &str) {
+    let n = s.len().min(field.len() - 1);
+    field[..n].copy_from_slice(&s.as_bytes()[..n]);
+}
+
+impl<X: KernelXlen, M: Memory> MockLinux<X, M> {
+    /// The name `uname` reports (`localhost` by default).
+    pub fn with_hostname(mut self, hostname: impl Into<String>) -> Self {
+        self.hostname = hostname.into();
+        self
+    }
+
+    /// The guest's uid/gid, for the auxv and the `get*id` calls (root by
+    /// default).
+    pub fn with_credentials(mut self, credentials: Credentials) -> Self {
+        self.auxv.credentials = credentials;
+        self
+    }
+
+    /// How many CPUs the guest sees (1 by default).
+    pub fn with_cpus(mut self, cpus: u32) -> Self {
+        assert!(cpus >= 1, "a guest needs at least one CPU");
+        self.cpus = cpus.min(libc_riscv32::CPU_SETSIZE);
+        self
+    }
+
+    pub fn hostname(&self) -> &str {
+        &self.hostname
+    }
+
+    pub fn cpus(&self) -> u32 {
+        self.cpus
+    }
+
+    pub(crate) fn uname(&mut self, mem: &mut M, buf: u64) -> Result<u64, i32> {
+        const LEN: usize = libc_riscv32::UTSNAME_LENGTH;
+
+        let machine = format!("riscv{}", X::BITS);
+        let fields = [
+            "Linux",
+            self.hostname.as_str(),
+            RELEASE,
+            "#1 SMP PREEMPT_DYNAMIC",
+            machine.as_str(),
+            "(none)",
+        ];
+        let mut uts = [0u8; 6 * LEN];
+        for (field, s) in uts.chunks_mut(LEN).zip(fields) {
+            uts_field(field, s);
+        }
+        mem.copy_to(buf, &uts).map_err(|_| libc_riscv32::EFAULT)?;
+        Ok(0)
+    }
+
+    pub(crate) fn getuid(&mut self) -> Result<u64, i32> {
+        Ok(self.auxv.credentials.uid as u64)
+    }
+
+    pub(crate) fn geteuid(&mut self) -> Result<u64, i32> {
+        Ok(self.auxv.credentials.euid as u64)
+    }
+
+    pub(crate) fn getgid(&mut self) -> Result<u64, i32> {
+        Ok(self.auxv.credentials.gid as u64)
+    }
+
+    pub(crate) fn getegid(&mut self) -> Result<u64, i32> {
+        Ok(self.auxv.credentials.egid as u64)
+    }
+
+    /// `getresuid`/`getresgid`: the saved id is the effective one.
+    fn getres(&mut self, mem: &mut M, ids: [u32; 2], ptrs: [u64; 3]) -> Result<u64, i32> {
+        let [real, effective] = ids;
+        for (ptr, id) in ptrs.into_iter().zip([real, effective, effective]) {
+            mem.store_at::<u32>(ptr, id)
+                .map_err(|_| libc_riscv32::EFAULT)?;
+        }
+        Ok(0)
+    }
+
+    pub(crate) fn getresuid(
+        &mut self,
+        mem: &mut M,
+        ruid: u64,
+        euid: u64,
+        suid: u64,
+    ) -> Result<u64, i32> {
+        let creds = self.auxv.credentials;
+        self.getres(mem, [creds.uid, creds.euid], [ruid, euid, suid])
+    }
+
+    pub(crate) fn getresgid(
+        &mut self,
+        mem: &mut M,
+        rgid: u64,
+        egid: u64,
+        sgid: u64,
+    ) -> Result<u64, i32> {
+        let creds = self.auxv.credentials;
+        self.getres(mem, [creds.gid, creds.egid], [rgid, egid, sgid])
+    }
+
+    /// Whether `pid` names this process (0 is the caller).
+    pub(crate) fn is_self(&mut self, pid: i32) -> bool {
+        pid == 0 || Ok(pid as u64) == self.getpid()
+    }
+
+    /// Bytes in the kernel's cpu mask: whole `long`s covering every CPU.
+    fn cpumask_size(&self) -> u64 {
+        let word = X::BITS as u64;
+        (self.cpus as u64).div_ceil(word) * (word / 8)
+    }
+
+    pub(crate) fn sched_getaffinity(
+        &mut self,
+        mem: &mut M,
+        pid: i32,
+        len: u64,
+        mask: u64,
+    ) -> Result<u64, i32> {
+        if !self.is_self(pid) {
+            return Err(libc_riscv32::ESRCH);
+        }
+        if len < (self.cpus as u64).div_ceil(8) || !len.is_multiple_of(X::BITS as u64 / 8) {
+            return Err(libc_riscv32::EINVAL);
+        }
+        let size = self.cpumask_size().min(len);
+        let mut bits = vec![0u8; size as usize];
+        for cpu in 0..self.cpus as usize {
+            bits[cpu / 8] |= 1 << (cpu % 8);
+        }
+        mem.copy_to(mask, &bits).map_err(|_| libc_riscv32::EFAULT)?;
+        Ok(size)
+    }
+
+    pub(crate) fn sched_setaffinity(
+        &mut self,
+        mem: &M,
+        pid: i32,
+        len: u64,
+        mask: u64,
+    ) -> Result<u64, i32> {
+        if !self.is_self(pid) {
+            return Err(libc_riscv32::ESRCH);
+        }
+        let bits = mem
+            .slice::<u8>(mask, len.min(self.cpumask_size()))
+            .map_err(|_| libc_riscv32::EFAULT)?;
+        // Any online CPU will do: they all run on the one hart.
+        let online = (0..self.cpus as usize)
+            .any(|cpu| bits.get(cpu / 8).is_some_and(|b| b & (1 << (cpu % 8)) != 0));
+        if !online {
+            return Err(libc_riscv32::EINVAL);
+        }
+        Ok(0)
+    }
+
+    pub(crate) fn sched_yield(&mut self) -> Result<u64, i32> {
+        Ok(0)
+    }
+
+    pub(crate) fn sysinfo(&mut self, mem: &mut M, info: u64) -> Result<u64, i32> {
+        let total = self.memory_total(mem.max_addr());
+        let used = self.mapped_bytes().min(total);
+        let pages = |bytes: u64| X::from_u64(bytes / PAGE_SIZE);
+        let zero = X::from_u64(0);
+        let sysinfo = SysInfo {
+            uptime: X::from_u64(self.started.elapsed().as_secs()),
+            loads: [zero; 3],
+            totalram: pages(total),
+            freeram: pages(total - used),
+            sharedram: zero,
+            bufferram: zero,
+            totalswap: zero,
+            freeswap: zero,
+            procs: X::from_u64(1),
+            totalhigh: zero,
+            freehigh: zero,
+            // Counted in pages so rv32's 4 GiB fits.
+            mem_unit: X::from_u64(PAGE_SIZE),
+        };
+        mem.copy_to(info, &[sysinfo])
+            .map_err(|_| libc_riscv32::EFAULT)?;
+        // rv32's trailing `_f[8]`; rv64's is empty.
+        let size = std::mem::size_of::<SysInfo<X::U>>() as u64;
+        let pad = 20u64.saturating_sub(2 * (X::BITS as u64 / 8) + 4);
+        mem.memset(info + size, 0, pad)
+            .map_err(|_| libc_riscv32::EFAULT)?;
+        Ok(0)
+    }
+
+    pub(crate) fn membarrier(&mut self, cmd: u32, flags: u32, _cpu_id: i32) -> Result<u64, i32> {
+        use libc_riscv32::*;
+
+        if flags != 0 {
+            return Err(EINVAL);
+        }
+        // With one hart every barrier is already complete; only the
+        // registration rules are observable.
+        match cmd {
+            MEMBARRIER_CMD_QUERY => return Ok(MEMBARRIER_COMMANDS as u64),
+            MEMBARRIER_CMD_GLOBAL | MEMBARRIER_CMD_GLOBAL_EXPEDITED => {}
+            MEMBARRIER_CMD_REGISTER_GLOBAL_EXPEDITED
+            | MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED
+            | MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED_SYNC_CORE => self.membarrier |= cmd,
+            MEMBARRIER_CMD_PRIVATE_EXPEDITED | MEMBARRIER_CMD_PRIVATE_EXPEDITED_SYNC_CORE => {
+                // Each REGISTER_* command is the next bit up.
+                if self.membarrier & (cmd << 1) == 0 {
+                    return Err(EPERM);
+                }
+            }
+            _ => return Err(EINVAL),
+        }
+        Ok(0)
+    }
+
+    pub(crate) fn rseq(
+        &mut self,
+        mem: &mut M,
+        addr: u64,
+        len: u32,
+        flags: u32,
+        sig: u32,
+    ) -> Result<u64, i32> {
+        use libc_riscv32::{EBUSY, EFAULT, EINVAL, EPERM, ORIG_RSEQ_SIZE};
+
+        // `cpu_id_start` and `cpu_id`; with one hart the guest never
+        // migrates and is never preempted mid-sequence.
+        let set_cpu = |mem: &mut M, cpu: i32| mem.copy_to(addr, &[cpu, cpu]).map_err(|_| EFAULT);
+        let request = Rseq { addr, len, sig };
+        if flags == libc_riscv32::RSEQ_FLAG_UNREGISTER {
+            let current = self.rseq.ok_or(EINVAL)?;
+            if (current.addr, current.len) != (addr, len) {
+                return Err(EINVAL);
+            }
+            if current.sig != sig {
+                return Err(EPERM);
+            }
+            set_cpu(mem, libc_riscv32::RSEQ_CPU_ID_UNINITIALIZED)?;
+            self.rseq = None;
+            return Ok(0);
+        }
+        if flags != 0 {
+            return Err(EINVAL);
+        }
+        if let Some(current) = self.rseq {
+            return Err(match current {
+                _ if (current.addr, current.len) != (addr, len) => EINVAL,
+                _ if current.sig != sig => EPERM,
+                _ => EBUSY,
+            });
+        }
+        if len < ORIG_RSEQ_SIZE || !addr.is_multiple_of(ORIG_RSEQ_SIZE as u64) {
+            return Err(EINVAL);
+        }
+        set_cpu(mem, 0)?;
+        self.rseq = Some(request);
+        Ok(0)
+    }
+}
@@ -0,0 +1,280 @@
+//! Synthetic `/proc`, `/sys` and `/dev` entries.
+//!
+//! These paths are answered by the kernel ahead of the guest filesystem.
+//! Generated files (`/proc/self/maps`, `/proc/cpuinfo`, ...) are rendered
+//! from live kernel and memory state when opened, into a read-only inode
+//! that belongs to no directory, so reads, seeks, `fstat` and `mmap` work
+//! on them as on any regular file. The character devices are [`Device`]s.
+use std::{
+    fmt::Write,
+    sync::{Arc, Mutex},
+};
+
+use riscv_vm::memory::Memory;
+
+use crate::{fd::Node, fs::Inode, KernelXlen, MockLinux, PAGE_SIZE, STACK_RESERVE};
+
+/// A character device under `/dev`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Device {
+    /// Reads end of file; writes are discarded.
+    Null,
+    /// Reads zeros; writes are discarded.
+    Zero,
+    /// Both read from the kernel's entropy source.
+    Random,
+    Urandom,
+}
+
+impl Device {
+    /// `(major, minor)`, as on Linux.
+    pub(crate) fn rdev(self) -> (u32, u32) {
+        match self {
+            Device::Null => (1, 3),
+            Device::Zero => (1, 5),
+            Device::Random => (1, 8),
+            Device::Urandom => (1, 9),
+        }
+    }
+}
+
+/// A loaded ELF segment, for `/proc/self/maps`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Segment {
+    /// Page-aligned bounds.
+    pub(crate) start: u64,
+    pub(crate) end: u64,
+    pub(crate) prot: u32,
+    /// File offset of `start`.
+    pub(crate) offset: u64,
+}
+
+fn kb(bytes: u64) -> u64 {
+    bytes / 1024
+}
+
+/// `0-3`-style list of the first `n` CPUs.
+fn cpu_list(n: u32) -> String {
+    if n == 1 {
+        "0".to_string()
+    } else {
+        format!("0-{}", n - 1)
+    }
+}
+
+/// Hex mask of the first `n` CPUs, in comma-separated 32-bit groups.
+fn cpu_mask(n: u32) -> String {
+    let digits = n.div_ceil(4) as usize;
+    let mut hex: Vec<char> = (0..digits)
+        .map(|i| {
+            let bits = (n as usize - i * 4).min(4);
+            char::from_digit((1 << bits) - 1, 16).unwrap()
+        })
+        .collect();
+    hex.reverse();
+    let groups: Vec<String> = hex
+        .rchunks(8)
+        .rev()
+        .map(|group| group.iter().collect())
+        .collect();
+    groups.join(",")
+}
+
+fn perms(prot: u32, shared: bool) -> String {
+    let bit = |flag: u32, c: char| if prot & flag != 0 { c } else { '-' };
+    [
+        bit(libc_riscv32::PROT_READ, 'r'),
+        bit(libc_riscv32::PROT_WRITE, 'w'),
+        bit(libc_riscv32::PROT_EXEC, 'x'),
+        if shared { 's' } else { 'p' },
+    ]
+    .into_iter()
+    .collect()
+}
+
+impl<X: KernelXlen, M: Memory> MockLinux<X, M> {
+    /// The node for a synthetic path, or `None` if the filesystem should
+    /// answer. `path` is normalized.
+    pub(crate) fn special_node(&self, mem: &M, path: &str) -> Option<Node> {
+        let own = path
+            .strip_prefix("/proc/self/")
+            .or_else(|| path.strip_prefix("/proc/1/"));
+        let contents = match (path, own) {
+            ("/dev/null", _) => return Some(Node::Device(Device::Null)),
+            ("/dev/zero", _) => return Some(Node::Device(Device::Zero)),
+            ("/dev/random", _) => return Some(Node::Device(Device::Random)),
+            ("/dev/urandom", _) => return Some(Node::Device(Device::Urandom)),
+            (_, Some("maps")) => self.proc_maps(),
+            (_, Some("status")) => self.proc_status(),
+            ("/proc/cpuinfo", _) => self.proc_cpuinfo(),
+            ("/proc/meminfo", _) => self.proc_meminfo(mem),
+            ("/sys/devices/system/cpu/online" | "/sys/devices/system/cpu/possible", _) => {
+                format!("{}\n", cpu_list(self.cpus))
+            }
+            _ => return None,
+        };
+        Some(Node::Inode {
+            path: path.to_string(),
+            inode: Arc::new(Mutex::new(Inode {
+                ino: 0,
+                mode: libc_riscv32::S_IFREG | 0o444,
+                data: contents.into_bytes(),
+            })),
+        })
+    }
+
+    /// Where the image, heap, mmap regions, and stack are.
+    fn proc_maps(&self) -> String {
+        // (start, end, perms, offset, inode, name)
+        let mut lines: Vec<(u64, u64, String, u64, u64, String)> = Vec::new();
+        for seg in &self.image {
+            lines.push((
+                seg.start,
+                seg.end,
+                perms(seg.prot, false),
+                seg.offset,
+                0,
+                self.auxv.execfn.clone(),
+            ));
+        }
+        let heap_end = self.brk.next_multiple_of(PAGE_SIZE);
+        if heap_end > self.brk_floor {
+            let rw = libc_riscv32::PROT_READ | libc_riscv32::PROT_WRITE;
+            lines.push((
+                self.brk_floor,
+                heap_end,
+                perms(rw, false),
+                0,
+                0,
+                "[heap]".to_string(),
+            ));
+        }
+        for (start, vma) in self.vmas.iter() {
+            let shared = vma.flags & libc_riscv32::MAP_TYPE != libc_riscv32::MAP_PRIVATE;
+            let (offset, ino, name) = match &vma.backing {
+                Some(backing) => (
+                    backing.offset,
+                    crate::fd::lock(&backing.inode).ino,
+                    backing.path.clone(),
+                ),
+                None => (0, 0, String::new()),
+            };
+            lines.push((start, vma.end, perms(vma.prot, shared), offset, ino, name));
+        }
+        if self.stack_top != 0 {
+            let rw = libc_riscv32::PROT_READ | libc_riscv32::PROT_WRITE;
+            lines.push((
+                self.stack_top - STACK_RESERVE,
+                self.stack_top,
+                perms(rw, false),
+                0,
+                0,
+                "[stack]".to_string(),
+            ));
+        }
+        lines.sort_by_key(|line| line.0);
+
+        // Names start in a fixed column, as Linux pads them.
+        let column = 25 + X::BITS as usize / 8 * 6 - 1;
+        let mut out = String::new();
+        for (start, end, perms, offset, ino, name) in lines {
+            let mut line = format!("{start:08x}-{end:08x} {perms} {offset:08x} 00:00 {ino}");
+            if !name.is_empty() {
+                let pad = column.saturating_sub(line.len()).max(1);
+                line.push_str(&" ".repeat(pad));
+                line.push_str(&name);
+            }
+            out.push_str(&line);
+            out.push('\n');
+        }
+        out
+    }
+
+    fn proc_status(&self) -> String {
+        let creds = self.auxv.credentials;
+        let name = self.auxv.execfn.rsplit('/').next().unwrap_or_default();
+        let image: u64 = self.image.iter().map(|seg| seg.end - seg.start).sum();
+        let data = self.mapped_bytes();
+        let size = self.address_space();
+        let mut out = String::new();
+        // Writing to a String cannot fail.
+        let _ = write!(
+            out,
+            "Name:\t{name:.15}\n\
+             Umask:\t{umask:04o}\n\
+             State:\tR (running)\n\
+             Tgid:\t1\n\
+             Ngid:\t0\n\
+             Pid:\t1\n\
+             PPid:\t0\n\
+             TracerPid:\t0\n\
+             Uid:\t{uid}\t{euid}\t{euid}\t{euid}\n\
+             Gid:\t{gid}\t{egid}\t{egid}\t{egid}\n\
+             FDSize:\t{fdsize}\n\
+             VmPeak:\t{peak:8} kB\n\
+             VmSize:\t{size:8} kB\n\
+             VmRSS:\t{size:8} kB\n\
+             VmData:\t{data:8} kB\n\
+             VmStk:\t{stack:8} kB\n\
+             VmExe:\t{image:8} kB\n\
+             Threads:\t1\n\
+             Cpus_allowed:\t{mask}\n\
+             Cpus_allowed_list:\t{list}\n",
+            umask = self.umask,
+            uid = creds.uid,
+            euid = creds.euid,
+            gid = creds.gid,
+            egid = creds.egid,
+            fdsize = self.fds.limit(),
+            size = kb(size),
+            peak = kb(self.peak_memory.max(size)),
+            data = kb(data),
+            stack = kb(STACK_RESERVE),
+            image = kb(image),
+            mask = cpu_mask(self.cpus),
+            list = cpu_list(self.cpus),
+        );
+        out
+    }
+
+    fn proc_cpuinfo(&self) -> String {
+        let isa: String = "imafdqcbv"
+            .chars()
+            .filter(|&c| X::EXTENSIONS & (1 << (c as u8 - b'a')) != 0)
+            .collect();
+        let mmu = if X::BITS == 32 { "sv32" } else { "sv39" };
+        let mut out = String::new();
+        for cpu in 0..self.cpus {
+            let _ = write!(
+                out,
+                "processor\t: {cpu}\n\
+                 hart\t\t: {cpu}\n\
+                 isa\t\t: rv{bits}{isa}\n\
+                 mmu\t\t: {mmu}\n\
+                 mvendorid\t: 0x0\n\
+                 marchid\t\t: 0x0\n\
+                 mimpid\t\t: 0x0\n\n",
+                bits = X::BITS,
+            );
+        }
+        out
+    }
+
+    fn proc_meminfo(&self, mem: &M) -> String {
+        let total = self.memory_total(mem.max_addr());
+        let free = total - self.mapped_bytes().min(total);
+        let mut out = String::new();
+        for (label, bytes) in [
+            ("MemTotal:", total),
+            ("MemFree:", free),
+            ("MemAvailable:", free),
+            ("Buffers:", 0),
+            ("Cached:", 0),
+            ("SwapTotal:", 0),
+            ("SwapFree:", 0),
+        ] {
+            let _ = writeln!(out, "{label:<16}{:>8} kB", kb(bytes));
+        }
+        out
+    }
+}
@@ -0,0 +1,623 @@
+//! The socket syscalls: descriptors over [`net::Socket`]s from the
+//! embedder's [`NetBackend`](crate::net::NetBackend).
+//!
+//! Only `AF_INET`/`AF_INET6` stream and datagram sockets exist. The kernel
+//! keeps what the guest can ask about (type, protocol, listening, options
+//! set) and leaves the rest to the backend. Operations a blocking socket
+//! would wait on park the guest (see [`crate::poll`]).
+use std::{
+    collections::BTreeMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6},
+    sync::{Arc, Mutex},
+};
+
+use riscv_vm::memory::{Memory, Pod};
+
+use crate::{
+    fd::{lock, Fd, Node, OpenFile},
+    impls::IoVec,
+    net::{Shutdown, Socket, SocketType},
+    poll::{park_unless, Readiness},
+    KernelXlen, MockLinux, CHUNK,
+};
+
+/// `sizeof(struct sockaddr_storage)`.
+const SOCKADDR_MAX: u64 = 128;
+/// `UIO_MAXIOV`.
+const IOV_MAX: u64 = 1024;
+/// Reported for `SO_SNDBUF`/`SO_RCVBUF` until the guest sets them.
+const DEFAULT_BUF: i32 = 212992;
+
+#[derive(Debug)]
+pub(crate) struct SocketFile {
+    sock: Box<dyn Socket>,
+    domain: u16,
+    ty: SocketType,
+    protocol: u32,
+    listening: bool,
+    /// Options the guest set, by `(level, name)`.
+    options: BTreeMap<(u32, u32), Vec<u8>>,
+    /// Bumped on every guest operation and readiness change, for
+    /// edge-triggered epoll.
+    seq: u64,
+    last_mask: u32,
+}
+
+impl SocketFile {
+    fn new(sock: Box<dyn Socket>, domain: u16, ty: SocketType, protocol: u32) -> Self {
+        Self {
+            sock,
+            domain,
+            ty,
+            protocol,
+            listening: false,
+            options: BTreeMap::new(),
+            seq: 0,
+            last_mask: 0,
+        }
+    }
+
+    /// The backend socket, for a guest operation.
+    fn sock(&mut self) -> &mut dyn Socket {
+        self.seq += 1;
+        &mut *self.sock
+    }
+
+    pub(crate) fn poll(&mut self) -> Readiness {
+        let mask = self.sock.poll();
+        if mask != self.last_mask {
+            self.last_mask = mask;
+            self.seq += 1;
+        }
+        Readiness {
+            mask,
+            seq: self.seq,
+        }
+    }
+
+    /// `read` on a socket: `recv` without flags.
+    pub(crate) fn read(&mut self, buf: &mut [u8]) -> Result<usize, i32> {
+        Ok(self.sock().recv(buf, false)?.0)
+    }
+
+    /// `write` on a socket: `send` to the connected peer.
+    pub(crate) fn write(&mut self, bytes: &[u8]) -> Result<usize, i32> {
+        self.sock().send(bytes, None)
+    }
+
+    fn int_option(&self, level: u32, name: u32) -> Result<i32, i32> {
+        use libc_riscv32::*;
+
+        if let Some(value) = self.options.get(&(level, name)) {
+            let mut int = [0u8; 4];
+            let n = value.len().min(4);
+            int[..n].copy_from_slice(&value[..n]);
+            return Ok(i32::from_le_bytes(int));
+        }
+        Ok(match (level, name) {
+            (SOL_SOCKET, SO_SNDBUF | SO_RCVBUF) => DEFAULT_BUF,
+            (SOL_SOCKET, SO_REUSEADDR | SO_REUSEPORT | SO_KEEPALIVE) => 0,
+            (IPPROTO_TCP, TCP_NODELAY) if self.ty == SocketType::Stream => 0,
+            _ => return Err(ENOPROTOOPT),
+        })
+    }
+}
+
+/// `struct msghdr`. `namelen` and `flags` are 32-bit fields padded to a
+/// word; only their low halves mean anything.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct MsgHdr<U> {
+    name: U,
+    namelen: U,
+    iov: U,
+    iovlen: U,
+    control: U,
+    controllen: U,
+    flags: U,
+}
+// Safety: width-sized integers only; any bit pattern valid, no padding.
+unsafe impl<U: Pod> Pod for MsgHdr<U> {}
+
+/// Decode a guest `sockaddr_in`/`sockaddr_in6` for a `domain` socket.
+/// IPv4-mapped IPv6 addresses come back as IPv4.
+pub(crate) fn read_sockaddr<M: Memory>(
+    mem: &M,
+    domain: u16,
+    addr: u64,
+    len: u64,
+) -> Result<SocketAddr, i32> {
+    if !(2..=SOCKADDR_MAX).contains(&len) {
+        return Err(libc_riscv32::EINVAL);
+    }
+    let bytes = mem
+        .slice::<u8>(addr, len)
+        .map_err(|_| libc_riscv32::EFAULT)?;
+    let be16 = |at: usize| u16::from_be_bytes([bytes[at], bytes[at + 1]]);
+    let be32 = |at: usize| u32::from_be_bytes(bytes[at..at + 4].try_into().unwrap());
+    if u16::from_le_bytes([bytes[0], bytes[1]]) != domain {
+        return Err(libc_riscv32::EAFNOSUPPORT);
+    }
+    // Linux accepts the RFC 2133 `sockaddr_in6`, without `sin6_scope_id`.
+    let min = match domain {
+        libc_riscv32::AF_INET => 16,
+        libc_riscv32::AF_INET6 => 24,
+        _ => return Err(libc_riscv32::EINVAL),
+    };
+    if bytes.len() < min {
+        return Err(libc_riscv32::EINVAL);
+    }
+    let port = be16(2);
+    match domain {
+        libc_riscv32::AF_INET => Ok(SocketAddr::new(IpAddr::V4(Ipv4Addr::from(be32(4))), port)),
+        libc_riscv32::AF_INET6 => {
+            let ip = Ipv6Addr::from(<[u8; 16]>::try_from(&bytes[8..24]).unwrap());
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return Ok(SocketAddr::new(IpAddr::V4(ip), port));
+            }
+            let scope = bytes
+                .get(24..28)
+                .map_or(0, |b| u32::from_le_bytes(b.try_into().unwrap()));
+            Ok(SocketAddr::V6(SocketAddrV6::new(ip, port, be32(4), scope)))
+        }
+        _ => Err(libc_riscv32::EINVAL),
+    }
+}
+
+/// Encode `addr` as a `domain` socket address.
+fn encode_sockaddr(domain: u16, addr: SocketAddr) -> Vec<u8> {
+    let mut out = Vec::with_capacity(28);
+    out.extend(domain.to_le_bytes());
+    out.extend(addr.port().to_be_bytes());
+    if domain == libc_riscv32::AF_INET {
+        let ip = match addr.ip() {
+            IpAddr::V4(ip) => ip,
+            IpAddr::V6(ip) => ip.to_ipv4_mapped().unwrap_or(Ipv4Addr::UNSPECIFIED),
+        };
+        out.extend(ip.octets());
+        out.extend([0; 8]);
+    } else {
+        let (ip, flowinfo, scope) = match addr {
+            SocketAddr::V4(addr) => (addr.ip().to_ipv6_mapped(), 0, 0),
+            SocketAddr::V6(addr) => (*addr.ip(), addr.flowinfo(), addr.scope_id()),
+        };
+        out.extend(flowinfo.to_be_bytes());
+        out.extend(ip.octets());
+        out.extend(scope.to_le_bytes());
+    }
+    out
+}
+
+/// Store `value` to a guest buffer whose capacity is at `lenp`, truncating
+/// to fit and reporting the full length back through `lenp`.
+fn write_sized<M: Memory>(mem: &mut M, buf: u64, lenp: u64, value: &[u8]) -> Result<(), i32> {
+    let cap = mem.load_at::<u32>(lenp).map_err(|_| libc_riscv32::EFAULT)?;
+    if (cap as i32) < 0 {
+        return Err(libc_riscv32::EINVAL);
+    }
+    let n = value.len().min(cap as usize);
+    mem.copy_to(buf, &value[..n])
+        .map_err(|_| libc_riscv32::EFAULT)?;
+    mem.store_at::<u32>(lenp, value.len() as u32)
+        .map_err(|_| libc_riscv32::EFAULT)
+}
+
+/// Report `addr` through an optional `sockaddr`/`socklen_t` pair.
+fn write_sockaddr<M: Memory>(
+    mem: &mut M,
+    domain: u16,
+    addr: u64,
+    lenp: u64,
+    sa: SocketAddr,
+) -> Result<(), i32> {
+    if addr == 0 {
+        return Ok(());
+    }
+    write_sized(mem, addr, lenp, &encode_sockaddr(domain, sa))
+}
+
+fn shutdown_how(how: u32) -> Result<Shutdown, i32> {
+    match how {
+        libc_riscv32::SHUT_RD => Ok(Shutdown::Read),
+        libc_riscv32::SHUT_WR => Ok(Shutdown::Write),
+        libc_riscv32::SHUT_RDWR => Ok(Shutdown::Both),
+        _ => Err(libc_riscv32::EINVAL),
+    }
+}
+
+impl<X: KernelXlen, M: Memory> MockLinux<X, M> {
+    /// The socket behind `fd`, and whether the descriptor is non-blocking.
+    fn socket_file(&self, fd: i32) -> Result<(Arc<Mutex<SocketFile>>, bool), i32> {
+        let file = self.fds.get(fd)?.lock();
+        match &file.node {
+            Node::Socket(sock) => Ok((sock.clone(), file.nonblock())),
+            _ => Err(libc_riscv32::ENOTSOCK),
+        }
+    }
+
+    /// Whether `fd` is a datagram socket, whose sends go out whole.
+    fn datagram(&self, fd: i32) -> Result<bool, i32> {
+        let (sock, _) = self.socket_file(fd)?;
+        let ty = lock(&sock).ty;
+        Ok(ty == SocketType::Datagram)
+    }
+
+    fn install_socket(&mut self, sock: SocketFile, flags: u32) -> Result<u64, i32> {
+        use libc_riscv32::{SOCK_CLOEXEC, SOCK_NONBLOCK};
+
+        let file = OpenFile::new(
+            Node::Socket(Arc::new(Mutex::new(sock))),
+            libc_riscv32::O_RDWR | (flags & SOCK_NONBLOCK),
+        );
+        Ok(self.fds.insert(Fd::new(file, flags & SOCK_CLOEXEC != 0))? as u64)
+    }
+
+    pub(crate) fn socket(&mut self, domain: u32, ty: u32, protocol: u32) -> Result<u64, i32> {
+        use libc_riscv32::*;
+
+        let domain = match domain as u16 {
+            d @ (AF_INET | AF_INET6) if domain <= u16::MAX as u32 => d,
+            _ => return Err(EAFNOSUPPORT),
+        };
+        let flags = ty & !SOCK_TYPE_MASK;
+        if flags & !(SOCK_NONBLOCK | SOCK_CLOEXEC) != 0 {
+            return Err(EINVAL);
+        }
+        let (ty, protocol) = match (ty & SOCK_TYPE_MASK, protocol) {
+            (SOCK_STREAM, 0 | IPPROTO_TCP) => (SocketType::Stream, IPPROTO_TCP),
+            (SOCK_DGRAM, 0 | IPPROTO_UDP) => (SocketType::Datagram, IPPROTO_UDP),
+            (SOCK_STREAM | SOCK_DGRAM, _) => return Err(EPROTONOSUPPORT),
+            _ => return Err(EINVAL),
+        };
+        let net = self.net.as_mut().ok_or(EAFNOSUPPORT)?;
+        let sock = SocketFile::new(net.socket(ty)?, domain, ty, protocol);
+        self.install_socket(sock, flags)
+    }
+
+    pub(crate) fn bind(&mut self, mem: &M, fd: i32, addr: u64, len: u64) -> Result<u64, i32> {
+        let (sock, _) = self.socket_file(fd)?;
+        let mut sock = lock(&sock);
+        let addr = read_sockaddr(mem, sock.domain, addr, len)?;
+        sock.sock().bind(addr)?;
+        Ok(0)
+    }
+
+    pub(crate) fn listen(&mut self, fd: i32, backlog: i32) -> Result<u64, i32> {
+        let (sock, _) = self.socket_file(fd)?;
+        let mut sock = lock(&sock);
+        sock.sock().listen(backlog.max(0) as u32)?;
+        sock.listening = true;
+        Ok(0)
+    }
+
+    pub(crate) fn accept4(
+        &mut self,
+        mem: &mut M,
+        fd: i32,
+        addr: u64,
+        lenp: u64,
+        flags: u32,
+    ) -> Result<u64, i32> {
+        use libc_riscv32::{SOCK_CLOEXEC, SOCK_NONBLOCK};
+
+        if flags & !(SOCK_NONBLOCK | SOCK_CLOEXEC) != 0 {
+            return Err(libc_riscv32::EINVAL);
+        }
+        let (sock, nonblock) = self.socket_file(fd)?;
+        let mut sock = lock(&sock);
+        if !sock.listening {
+            return Err(libc_riscv32::EINVAL);
+        }
+        let (conn, peer) = sock.sock().accept().map_err(park_unless(nonblock))?;
+        let accepted = SocketFile::new(conn, sock.domain, sock.ty, sock.protocol);
+        let domain = sock.domain;
+        drop(sock);
+        let fd = self.install_socket(accepted, flags)?;
+        write_sockaddr(mem, domain, addr, lenp, peer)?;
+        Ok(fd)
+    }
+
+    pub(crate) fn connect(&mut self, mem: &M, fd: i32, addr: u64, len: u64) -> Result<u64, i32> {
+        let (sock, _) = self.socket_file(fd)?;
+        let mut sock = lock(&sock);
+        let addr = read_sockaddr(mem, sock.domain, addr, len)?;
+        // Backends connect synchronously, so there is no EINPROGRESS.
+        sock.sock().connect(addr)?;
+        Ok(0)
+    }
+
+    pub(crate) fn getsockname(
+        &mut self,
+        mem: &mut M,
+        fd: i32,
+        addr: u64,
+        lenp: u64,
+    ) -> Result<u64, i32> {
+        let (sock, _) = self.socket_file(fd)?;
+        let sock = lock(&sock);
+        let local = sock.sock.local_addr()?;
+        write_sized(mem, addr, lenp, &encode_sockaddr(sock.domain, local))?;
+        Ok(0)
+    }
+
+    pub(crate) fn getpeername(
+        &mut self,
+        mem: &mut M,
+        fd: i32,
+        addr: u64,
+        lenp: u64,
+    ) -> Result<u64, i32> {
+        let (sock, _) = self.socket_file(fd)?;
+        let sock = lock(&sock);
+        let peer = sock.sock.peer_addr()?;
+        write_sized(mem, addr, lenp, &encode_sockaddr(sock.domain, peer))?;
+        Ok(0)
+    }
+
+    /// Send already-fetched guest bytes, to `to` for unconnected datagrams.
+    fn send_bytes(
+        &mut self,
+        fd: i32,
+        bytes: &[u8],
+        to: Option<(u64, u64)>,
+        flags: u32,
+        mem: &M,
+    ) -> Result<u64, i32> {
+        let (sock, nonblock) = self.socket_file(fd)?;
+        let bytes = &bytes[..self.output_allowance(bytes.len())?];
+        let mut sock = lock(&sock);
+        let to = match to {
+            // Stream sockets ignore a destination, as TCP does.
+            Some((addr, len)) if addr != 0 && sock.ty == SocketType::Datagram => {
+                Some(read_sockaddr(mem, sock.domain, addr, len)?)
+            }
+            _ => None,
+        };
+        let nonblock = nonblock || flags & libc_riscv32::MSG_DONTWAIT != 0;
+        let n = sock.sock().send(bytes, to).map_err(park_unless(nonblock))?;
+        self.output_bytes += n as u64;
+        Ok(n as u64)
+    }
+
+    /// Receive up to `len` bytes, with the sender's address if known.
+    fn recv_bytes(
+        &mut self,
+        fd: i32,
+        len: u64,
+        flags: u32,
+    ) -> Result<(Vec<u8>, Option<SocketAddr>, u16), i32> {
+        let (sock, nonblock) = self.socket_file(fd)?;
+        let mut sock = lock(&sock);
+        let nonblock = nonblock || flags & libc_riscv32::MSG_DONTWAIT != 0;
+        let mut chunk = vec![0u8; len.min(CHUNK) as usize];
+        let peek = flags & libc_riscv32::MSG_PEEK != 0;
+        let (n, from) = sock
+            .sock()
+            .recv(&mut chunk, peek)
+            .map_err(park_unless(nonblock))?;
+        chunk.truncate(n);
+        Ok((chunk, from, sock.domain))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn sendto(
+        &mut self,
+        mem: &M,
+        fd: i32,
+        buf: u64,
+        len: u64,
+        flags: u32,
+        addr: u64,
+        addrlen: u64,
+    ) -> Result<u64, i32> {
+        if len > CHUNK && self.datagram(fd)? {
+            return Err(libc_riscv32::EMSGSIZE);
+        }
+        let to = Some((addr, addrlen));
+        self.chunked(mem, buf, len, |k, bytes| {
+            k.send_bytes(fd, bytes, to, flags, mem)
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn recvfrom(
+        &mut self,
+        mem: &mut M,
+        fd: i32,
+        buf: u64,
+        len: u64,
+        flags: u32,
+        addr: u64,
+        lenp: u64,
+    ) -> Result<u64, i32> {
+        let (bytes, from, domain) = self.recv_bytes(fd, len, flags)?;
+        mem.copy_to(buf, &bytes).map_err(|_| libc_riscv32::EFAULT)?;
+        if let Some(from) = from {
+            write_sockaddr(mem, domain, addr, lenp, from)?;
+        }
+        Ok(bytes.len() as u64)
+    }
+
+    /// Read a `msghdr`.
+    fn msghdr(&self, mem: &M, msg: u64) -> Result<MsgHdr<X::U>, i32> {
+        Ok(mem
+            .slice::<MsgHdr<X::U>>(msg, 1)
+            .map_err(|_| libc_riscv32::EFAULT)?[0])
+    }
+
+    fn msg_iovs(&self, mem: &M, hdr: &MsgHdr<X::U>) -> Result<Vec<IoVec<X::U>>, i32> {
+        let iovlen = X::to_u64(hdr.iovlen);
+        if iovlen > IOV_MAX {
+            return Err(libc_riscv32::EMSGSIZE);
+        }
+        Ok(mem
+            .slice::<IoVec<X::U>>(X::to_u64(hdr.iov), iovlen)
+            .map_err(|_| libc_riscv32::EFAULT)?
+            .to_vec())
+    }
+
+    pub(crate) fn sendmsg(&mut self, mem: &M, fd: i32, msg: u64, flags: u32) -> Result<u64, i32> {
+        let hdr = self.msghdr(mem, msg)?;
+        let iovs = self.msg_iovs(mem, &hdr)?;
+        let name = X::to_u64(hdr.name);
+        let namelen = X::to_u64(hdr.namelen) as u32 as u64;
+        let to = Some((name, namelen));
+        let mut len = 0u64;
+        for iov in &iovs {
+            len = len
+                .checked_add(X::to_u64(iov.len))
+                .ok_or(libc_riscv32::EINVAL)?;
+        }
+        if len <= CHUNK {
+            // One send, so a datagram stays one datagram.
+            let mut bytes = Vec::new();
+            for iov in iovs {
+                bytes.extend_from_slice(
+                    &mem.slice::<u8>(X::to_u64(iov.base), X::to_u64(iov.len))
+                        .map_err(|_| libc_riscv32::EFAULT)?,
+                );
+            }
+            return self.send_bytes(fd, &bytes, to, flags, mem);
+        }
+        if self.datagram(fd)? {
+            return Err(libc_riscv32::EMSGSIZE);
+        }
+        // A stream takes the iovs in turn, as `writev` does.
+        let mut total = 0;
+        for iov in iovs {
+            let len = X::to_u64(iov.len);
+            match self.chunked(mem, X::to_u64(iov.base), len, |k, bytes| {
+                k.send_bytes(fd, bytes, to, flags, mem)
+            }) {
+                Ok(sent) => {
+                    total += sent;
+                    if sent < len {
+                        break;
+                    }
+                }
+                Err(_) if total > 0 => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(total)
+    }
+
+    pub(crate) fn recvmsg(
+        &mut self,
+        mem: &mut M,
+        fd: i32,
+        msg: u64,
+        flags: u32,
+    ) -> Result<u64, i32> {
+        let hdr = self.msghdr(mem, msg)?;
+        let iovs = self.msg_iovs(mem, &hdr)?;
+        let total = iovs
+            .iter()
+            .fold(0u64, |sum, iov| sum.saturating_add(X::to_u64(iov.len)));
+        let (bytes, from, domain) = self.recv_bytes(fd, total, flags)?;
+
+        let mut rest = &bytes[..];
+        for iov in iovs {
+            let n = rest.len().min(X::to_u64(iov.len) as usize);
+            mem.copy_to(X::to_u64(iov.base), &rest[..n])
+                .map_err(|_| libc_riscv32::EFAULT)?;
+            rest = &rest[n..];
+        }
+        let word = X::BITS as u64 / 8;
+        let name = X::to_u64(hdr.name);
+        match from {
+            Some(from) if name != 0 => write_sockaddr(mem, domain, name, msg + word, from)?,
+            _ => mem
+                .store_at::<u32>(msg + word, 0)
+                .map_err(|_| libc_riscv32::EFAULT)?,
+        }
+        // No ancillary data, and no flags to report.
+        mem.store_at(msg + 5 * word, X::from_u64(0))
+            .map_err(|_| libc_riscv32::EFAULT)?;
+        mem.store_at::<u32>(msg + 6 * word, 0)
+            .map_err(|_| libc_riscv32::EFAULT)?;
+        Ok(bytes.len() as u64)
+    }
+
+    pub(crate) fn shutdown(&mut self, fd: i32, how: u32) -> Result<u64, i32> {
+        let how = shutdown_how(how)?;
+        let (sock, _) = self.socket_file(fd)?;
+        lock(&sock).sock().shutdown(how)?;
+        Ok(0)
+    }
+
+    pub(crate) fn setsockopt(
+        &mut self,
+        mem: &M,
+        fd: i32,
+        level: u32,
+        name: u32,
+        optval: u64,
+        optlen: u64,
+    ) -> Result<u64, i32> {
+        use libc_riscv32::*;
+
+        let (sock, _) = self.socket_file(fd)?;
+        if optlen > 4096 {
+            return Err(EINVAL);
+        }
+        let value = mem
+            .slice::<u8>(optval, optlen)
+            .map_err(|_| EFAULT)?
+            .to_vec();
+        let mut sock = lock(&sock);
+        // Read-only options.
+        if level == SOL_SOCKET
+            && matches!(
+                name,
+                SO_TYPE | SO_ERROR | SO_ACCEPTCONN | SO_PROTOCOL | SO_DOMAIN
+            )
+        {
+            return Err(ENOPROTOOPT);
+        }
+        // Integer options need a whole int.
+        if sock.int_option(level, name).is_ok() && value.len() < 4 {
+            return Err(EINVAL);
+        }
+        sock.sock().set_option(level, name, &value)?;
+        sock.options.insert((level, name), value);
+        Ok(0)
+    }
+
+    pub(crate) fn getsockopt(
+        &mut self,
+        mem: &mut M,
+        fd: i32,
+        level: u32,
+        name: u32,
+        optval: u64,
+        lenp: u64,
+    ) -> Result<u64, i32> {
+        use libc_riscv32::*;
+
+        let (sock, _) = self.socket_file(fd)?;
+        let sock = lock(&sock);
+        let int = match (level, name) {
+            (SOL_SOCKET, SO_TYPE) => Some(match sock.ty {
+                SocketType::Stream => SOCK_STREAM,
+                SocketType::Datagram => SOCK_DGRAM,
+            } as i32),
+            (SOL_SOCKET, SO_DOMAIN) => Some(sock.domain as i32),
+            (SOL_SOCKET, SO_PROTOCOL) => Some(sock.protocol as i32),
+            (SOL_SOCKET, SO_ACCEPTCONN) => Some(sock.listening as i32),
+            // Errors are reported by the call that hit them, so there is
+            // never one pending.
+            (SOL_SOCKET, SO_ERROR) => Some(0),
+            _ => None,
+        };
+        let value = match int {
+            Some(int) => int.to_le_bytes().to_vec(),
+            None => match sock.options.get(&(level, name)) {
+                Some(value) => value.clone(),
+                None => sock.int_option(level, name)?.to_le_bytes().to_vec(),
+            },
+        };
+        write_sized(mem, optval, lenp, &value)?;
+        Ok(0)
+    }
+}
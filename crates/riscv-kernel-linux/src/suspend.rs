@@ -0,0 +1,203 @@
+//! Driving a machine from async code: syscalls the policy suspends
+//! ([`Action::Suspend`]) are handed to a host future instead of blocking a
+//! thread on them.
+//!
+//! [`Action::Suspend`]: crate::Action::Suspend
+use std::{
+    future::{self, Future},
+    mem,
+    pin::Pin,
+    sync::{Condvar, Mutex, OnceLock},
+    task::{Context, Poll, Waker},
+    thread,
+    time::{Duration, Instant},
+};
+
+use riscv_vm::{
+    error::MachineError,
+    machine::{Kernel, Machine, MachineState},
+    memory::Memory,
+};
+
+use crate::{KernelXlen, LinuxError, MockLinux, SyscallInfo};
+
+/// Instructions run between returns to the executor.
+const QUANTUM: u64 = 100_000;
+/// The first retry of a call parked where no waker reaches it.
+const BACKOFF_MIN: Duration = Duration::from_micros(100);
+/// The longest such a call is left parked.
+const BACKOFF_MAX: Duration = Duration::from_millis(10);
+
+/// Run `machine` until it halts, giving the executor the thread back every
+/// so often. Each suspended syscall goes to `serve`, whose result the guest
+/// gets (an `Err` as `-errno`); `serve` may also read and write guest
+/// memory through the machine, e.g. to fill a `read`'s buffer.
+///
+/// A call parked inside the kernel (a `ppoll` waiting on a pipe, say) is
+/// retried once more with the task's waker registered with the kernel
+/// ([`Kernel::wake_on`]), then waits for that waker or the call's deadline
+/// ([`MockLinux::blocked_until`]); where the waker does not cover it, also
+/// for a backoff doubling while nothing changes.
+pub async fn run_async<X: KernelXlen, M: Memory>(
+    machine: &mut Machine<MockLinux<X, M>>,
+    mut serve: impl AsyncFnMut(&mut Machine<MockLinux<X, M>>, SyscallInfo) -> Result<u64, i32>,
+) -> Result<(), MachineError<LinuxError>>
+where
+    MockLinux<X, M>: Kernel<Xlen = X, Memory = M, Error = LinuxError>,
+{
+    // Whether the waker is registered, and all the kernel needs, since the
+    // call last blocked.
+    let mut registered = None;
+    let mut backoff = BACKOFF_MIN;
+    while machine.state.is_running() {
+        let before = machine.hart.inst_count;
+        machine.run_for(QUANTUM)?;
+        let ran = machine.hart.inst_count != before;
+        if ran {
+            registered = None;
+            backoff = BACKOFF_MIN;
+        }
+        if machine.state == MachineState::Blocked {
+            if let Some(call) = machine.kernel.suspended_syscall() {
+                let ret = serve(machine, call).await;
+                machine.kernel.complete_syscall(&mut machine.hart, ret);
+                continue;
+            }
+            if !ran {
+                match registered.take() {
+                    // Retry once more, in case what it waits for came before
+                    // the waker.
+                    None => {
+                        let kernel = &mut machine.kernel;
+                        let wake_on = |cx: &mut Context| Poll::Ready(kernel.wake_on(cx.waker()));
+                        registered = Some(future::poll_fn(wake_on).await);
+                    }
+                    Some(covered) => {
+                        let mut at = machine.kernel.blocked_until();
+                        if !covered {
+                            let retry = Instant::now() + backoff;
+                            at = Some(at.map_or(retry, |at| at.min(retry)));
+                            backoff = (backoff * 2).min(BACKOFF_MAX);
+                        }
+                        Sleep { at, id: None }.await;
+                    }
+                }
+                continue;
+            }
+        }
+        YieldNow(false).await;
+    }
+    Ok(())
+}
+
+/// Pending once, waking itself: back to the executor, to be polled again.
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+/// Pending until woken: by whatever the task's waker was handed to, or by
+/// the timer at `at`.
+struct Sleep {
+    at: Option<Instant>,
+    /// The timer entry, once polled.
+    id: Option<u64>,
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.id.is_some() {
+            return Poll::Ready(());
+        }
+        self.id = Some(self.at.map_or(0, |at| timer().add(at, cx.waker().clone())));
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some(id @ 1..) = self.id {
+            timer().cancel(id);
+        }
+    }
+}
+
+/// Wakes tasks at their instants, from one thread started on first use.
+#[derive(Default)]
+struct Timer {
+    due: Mutex<Due>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct Due {
+    entries: Vec<(u64, Instant, Waker)>,
+    last: u64,
+}
+
+fn timer() -> &'static Timer {
+    static TIMER: OnceLock<Timer> = OnceLock::new();
+    let mut started = false;
+    let shared = TIMER.get_or_init(|| {
+        started = true;
+        Timer::default()
+    });
+    if started {
+        thread::Builder::new()
+            .name("riscv-timer".into())
+            .spawn(|| timer().tick())
+            .expect("failed to spawn the timer thread");
+    }
+    shared
+}
+
+impl Timer {
+    /// Wake `waker` at `at`; returns the entry's id (never 0).
+    fn add(&self, at: Instant, waker: Waker) -> u64 {
+        let mut due = self.due.lock().unwrap();
+        due.last += 1;
+        let id = due.last;
+        due.entries.push((id, at, waker));
+        self.changed.notify_one();
+        id
+    }
+
+    fn cancel(&self, id: u64) {
+        self.due.lock().unwrap().entries.retain(|e| e.0 != id);
+    }
+
+    fn tick(&self) -> ! {
+        let mut due = self.due.lock().unwrap();
+        loop {
+            let now = Instant::now();
+            let (ready, waiting) = mem::take(&mut due.entries)
+                .into_iter()
+                .partition::<Vec<_>, _>(|e| e.1 <= now);
+            due.entries = waiting;
+            if !ready.is_empty() {
+                drop(due);
+                for (_, _, waker) in ready {
+                    waker.wake();
+                }
+                due = self.due.lock().unwrap();
+                continue;
+            }
+            due = match due.entries.iter().map(|e| e.1).min() {
+                Some(next) => self.changed.wait_timeout(due, next - now).unwrap().0,
+                None => self.changed.wait(due).unwrap(),
+            };
+        }
+    }
+}
@@ -0,0 +1,666 @@
+//! strace-style syscall tracing.
+//!
+//! With a [`Tracer`] installed ([`MockLinux::with_tracer`]), every syscall
+//! that completes is decoded into a [`SyscallEvent`]: arguments typed per
+//! syscall (strings and buffers read from guest memory, flag names, and
+//! `iovec`/`pollfd`/`stat`/`statx`/`sockaddr` contents) and the result.
+//! Arguments are decoded after the call, so output buffers show what the
+//! kernel wrote. Calls parked on a wait are reported once, when they finish.
+//!
+//! An event's `Display` is a line close to strace's; [`Strace`] writes
+//! those, and an `mpsc::Sender<SyscallEvent>` streams the events themselves.
+use std::{
+    fmt::{self, Debug},
+    io::Write,
+    sync::mpsc,
+};
+
+use riscv_vm::memory::Memory;
+
+use crate::{
+    fs::{Stat64, Statx},
+    impls::IoVec,
+    poll::PollFd,
+    socket::read_sockaddr,
+    KernelXlen, MockLinux,
+};
+
+/// Bytes of a string or buffer shown before eliding (strace's `-s 32`).
+const STRING_MAX: usize = 32;
+/// Array elements shown before eliding.
+const ARRAY_MAX: usize = 16;
+
+/// A decoded syscall argument.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgValue {
+    Int(i64),
+    UInt(u64),
+    Hex(u64),
+    /// A pointer not worth following; `NULL` when zero.
+    Ptr(u64),
+    Fd(i32),
+    /// Guest bytes, cut to a preview when `truncated`.
+    Str {
+        bytes: Vec<u8>,
+        truncated: bool,
+    },
+    /// A value rendered symbolically: flags, enum names, file modes.
+    Symbolic {
+        value: u64,
+        text: String,
+    },
+    Struct(Vec<(&'static str, ArgValue)>),
+    /// Elements, and whether more were elided.
+    Array(Vec<ArgValue>, bool),
+}
+
+/// One completed syscall.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyscallEvent {
+    pub nr: u64,
+    /// `None` for numbers the kernel does not know.
+    pub name: Option<&'static str>,
+    pub args: Vec<ArgValue>,
+    /// `None` for calls that do not return (`exit`, `exit_group`).
+    pub ret: Option<Result<u64, i32>>,
+}
+
+/// Receives syscall events.
+pub trait Tracer: Debug + Send {
+    fn syscall(&mut self, event: SyscallEvent);
+}
+
+/// Writes one strace-style line per syscall.
+#[derive(Debug)]
+pub struct Strace<W> {
+    out: W,
+}
+
+impl<W: Write> Strace<W> {
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+}
+
+impl<W: Write + Debug + Send> Tracer for Strace<W> {
+    fn syscall(&mut self, event: SyscallEvent) {
+        // Tracing must not disturb the guest; a broken sink loses lines.
+        let _ = writeln!(self.out, "{event}");
+    }
+}
+
+impl Tracer for mpsc::Sender<SyscallEvent> {
+    fn syscall(&mut self, event: SyscallEvent) {
+        let _ = self.send(event);
+    }
+}
+
+fn write_bytes(f: &mut fmt::Formatter<'_>, bytes: &[u8]) -> fmt::Result {
+    write!(f, "\"")?;
+    for &b in bytes {
+        match b {
+            b'"' => write!(f, "\\\"")?,
+            b'\\' => write!(f, "\\\\")?,
+            b'\n' => write!(f, "\\n")?,
+            b'\r' => write!(f, "\\r")?,
+            b'\t' => write!(f, "\\t")?,
+            0x20..=0x7e => write!(f, "{}", b as char)?,
+            _ => write!(f, "\\x{b:02x}")?,
+        }
+    }
+    write!(f, "\"")
+}
+
+impl fmt::Display for ArgValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgValue::Int(v) => write!(f, "{v}"),
+            ArgValue::UInt(v) => write!(f, "{v}"),
+            ArgValue::Hex(v) => write!(f, "{v:#x}"),
+            ArgValue::Ptr(0) => write!(f, "NULL"),
+            ArgValue::Ptr(v) => write!(f, "{v:#x}"),
+            ArgValue::Fd(libc_riscv32::AT_FDCWD) => write!(f, "AT_FDCWD"),
+            ArgValue::Fd(v) => write!(f, "{v}"),
+            ArgValue::Str { bytes, truncated } => {
+                write_bytes(f, bytes)?;
+                if *truncated {
+                    write!(f, "...")?;
+                }
+                Ok(())
+            }
+            ArgValue::Symbolic { text, .. } => write!(f, "{text}"),
+            ArgValue::Struct(fields) => {
+                write!(f, "{{")?;
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{name}={value}")?;
+                }
+                write!(f, "}}")
+            }
+            ArgValue::Array(items, more) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                if *more {
+                    write!(f, ", ...")?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+impl fmt::Display for SyscallEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name {
+            Some(name) => write!(f, "{name}(")?,
+            None => write!(f, "syscall_{}(", self.nr)?,
+        }
+        for (i, arg) in self.args.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{arg}")?;
+        }
+        write!(f, ") = ")?;
+        match self.ret {
+            None => write!(f, "?"),
+            Some(Ok(v)) if matches!(self.name, Some("mmap" | "mremap" | "brk")) => {
+                write!(f, "{v:#x}")
+            }
+            Some(Ok(v)) => write!(f, "{v}"),
+            Some(Err(e)) => match syscalls::Errno::new(e).name_and_description() {
+                Some((name, description)) => write!(f, "-1 {name} ({description})"),
+                None => write!(f, "-1 errno {e}"),
+            },
+        }
+    }
+}
+
+/// How to decode one argument.
+#[derive(Clone, Copy)]
+enum Kind {
+    Int,
+    UInt,
+    Hex,
+    Ptr,
+    Fd,
+    /// A NUL-terminated string.
+    Path,
+    /// Bytes read by the kernel, with the length in argument `n`.
+    InBuf(usize),
+    /// Bytes written by the kernel; the result is their length.
+    OutBuf,
+    /// An input `iovec` array, with the count in argument `n`.
+    Iov(usize),
+    /// Bit flags.
+    Flags(&'static [(u64, &'static str)]),
+    /// One of several values.
+    Enum(&'static [(u64, &'static str)]),
+    OpenFlags,
+    /// Permission bits, in octal.
+    Mode,
+    MmapFlags,
+    SockType,
+    FutexOp,
+    /// A `pollfd` array, with the count in argument `n`.
+    PollFds(usize),
+    /// Two descriptors written by the kernel.
+    FdPair,
+    /// A `sockaddr`, with the length in argument `n`.
+    Sockaddr(usize),
+    /// `struct stat`, written by the kernel.
+    Stat,
+    /// `struct statx`, written by the kernel.
+    Statx,
+}
+
+macro_rules! named {
+    ($($name:ident),* $(,)?) => {
+        &[$((libc_riscv32::$name as u64, stringify!($name))),*]
+    };
+}
+
+const OPEN_FLAGS: &[(u64, &str)] = named![
+    O_CREAT,
+    O_EXCL,
+    O_NOCTTY,
+    O_TRUNC,
+    O_APPEND,
+    O_NONBLOCK,
+    O_ASYNC,
+    O_DIRECT,
+    O_DIRECTORY,
+    O_NOFOLLOW,
+    O_NOATIME,
+    O_CLOEXEC,
+    O_PATH,
+];
+const PROT: &[(u64, &str)] = named![PROT_READ, PROT_WRITE, PROT_EXEC];
+const MAP_FLAGS: &[(u64, &str)] = named![
+    MAP_FIXED,
+    MAP_ANONYMOUS,
+    MAP_GROWSDOWN,
+    MAP_DENYWRITE,
+    MAP_EXECUTABLE,
+    MAP_LOCKED,
+    MAP_NORESERVE,
+    MAP_POPULATE,
+    MAP_NONBLOCK,
+    MAP_STACK,
+    MAP_HUGETLB,
+    MAP_SYNC,
+    MAP_FIXED_NOREPLACE,
+];
+const MAP_TYPES: &[(u64, &str)] = named![MAP_SHARED, MAP_PRIVATE, MAP_SHARED_VALIDATE];
+const MREMAP_FLAGS: &[(u64, &str)] = named![MREMAP_MAYMOVE, MREMAP_FIXED, MREMAP_DONTUNMAP];
+const MS_FLAGS: &[(u64, &str)] = named![MS_ASYNC, MS_INVALIDATE, MS_SYNC];
+const AT_FLAGS: &[(u64, &str)] = named![AT_SYMLINK_NOFOLLOW, AT_NO_AUTOMOUNT, AT_EMPTY_PATH];
+const CLOEXEC: &[(u64, &str)] = named![O_CLOEXEC];
+const PIPE_FLAGS: &[(u64, &str)] = named![O_NONBLOCK, O_CLOEXEC, O_DIRECT];
+const EFD_FLAGS: &[(u64, &str)] = named![EFD_SEMAPHORE, EFD_NONBLOCK, EFD_CLOEXEC];
+const SOCK_FLAGS: &[(u64, &str)] = named![SOCK_NONBLOCK, SOCK_CLOEXEC];
+const MSG_FLAGS: &[(u64, &str)] =
+    named![MSG_PEEK, MSG_TRUNC, MSG_DONTWAIT, MSG_WAITALL, MSG_NOSIGNAL];
+const POLL_EVENTS: &[(u64, &str)] = named![POLLIN, POLLPRI, POLLOUT, POLLERR, POLLHUP, POLLNVAL];
+const WHENCE: &[(u64, &str)] = named![SEEK_SET, SEEK_CUR, SEEK_END];
+const FCNTL: &[(u64, &str)] = named![F_DUPFD, F_GETFD, F_SETFD, F_GETFL, F_SETFL, F_DUPFD_CLOEXEC];
+const EPOLL_CTL: &[(u64, &str)] = named![EPOLL_CTL_ADD, EPOLL_CTL_DEL, EPOLL_CTL_MOD];
+const DOMAINS: &[(u64, &str)] = named![AF_UNIX, AF_INET, AF_INET6];
+const SOCK_TYPES: &[(u64, &str)] = named![SOCK_STREAM, SOCK_DGRAM];
+const SHUT: &[(u64, &str)] = named![SHUT_RD, SHUT_WR, SHUT_RDWR];
+const FUTEX_OPS: &[(u64, &str)] = named![
+    FUTEX_WAIT,
+    FUTEX_WAKE,
+    FUTEX_FD,
+    FUTEX_REQUEUE,
+    FUTEX_CMP_REQUEUE,
+    FUTEX_WAKE_OP,
+    FUTEX_LOCK_PI,
+    FUTEX_UNLOCK_PI,
+    FUTEX_TRYLOCK_PI,
+    FUTEX_WAIT_BITSET,
+    FUTEX_WAKE_BITSET,
+    FUTEX_WAIT_REQUEUE_PI,
+    FUTEX_CMP_REQUEUE_PI,
+    FUTEX_LOCK_PI2,
+];
+const FILE_TYPES: &[(u64, &str)] = named![S_IFSOCK, S_IFREG, S_IFDIR, S_IFCHR, S_IFIFO];
+const RLIMITS: &[(u64, &str)] = named![
+    RLIMIT_CPU,
+    RLIMIT_FSIZE,
+    RLIMIT_DATA,
+    RLIMIT_STACK,
+    RLIMIT_CORE,
+    RLIMIT_RSS,
+    RLIMIT_NPROC,
+    RLIMIT_NOFILE,
+    RLIMIT_MEMLOCK,
+    RLIMIT_AS,
+    RLIMIT_LOCKS,
+    RLIMIT_SIGPENDING,
+    RLIMIT_MSGQUEUE,
+    RLIMIT_NICE,
+    RLIMIT_RTPRIO,
+    RLIMIT_RTTIME,
+];
+const MADVICE: &[(u64, &str)] = named![
+    MADV_NORMAL,
+    MADV_RANDOM,
+    MADV_SEQUENTIAL,
+    MADV_WILLNEED,
+    MADV_DONTNEED,
+    MADV_FREE,
+    MADV_REMOVE,
+    MADV_DONTFORK,
+    MADV_DOFORK,
+    MADV_MERGEABLE,
+    MADV_UNMERGEABLE,
+    MADV_HUGEPAGE,
+    MADV_NOHUGEPAGE,
+    MADV_DONTDUMP,
+    MADV_DODUMP,
+    MADV_WIPEONFORK,
+    MADV_KEEPONFORK,
+    MADV_COLD,
+    MADV_PAGEOUT,
+    MADV_POPULATE_READ,
+    MADV_POPULATE_WRITE,
+    MADV_DONTNEED_LOCKED,
+];
+const MEMBARRIER_CMDS: &[(u64, &str)] = named![
+    MEMBARRIER_CMD_QUERY,
+    MEMBARRIER_CMD_GLOBAL,
+    MEMBARRIER_CMD_GLOBAL_EXPEDITED,
+    MEMBARRIER_CMD_REGISTER_GLOBAL_EXPEDITED,
+    MEMBARRIER_CMD_PRIVATE_EXPEDITED,
+    MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED,
+    MEMBARRIER_CMD_PRIVATE_EXPEDITED_SYNC_CORE,
+    MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED_SYNC_CORE,
+];
+const RSEQ_FLAGS: &[(u64, &str)] = named![RSEQ_FLAG_UNREGISTER];
+
+/// The argument kinds of a syscall, by name, or `None` to show it raw.
+fn signature(name: &str) -> Option<&'static [Kind]> {
+    use Kind::*;
+
+    Some(match name {
+        "read" => &[Fd, OutBuf, UInt],
+        "write" => &[Fd, InBuf(2), UInt],
+        "writev" => &[Fd, Iov(2), Int],
+        "openat" => &[Fd, Path, OpenFlags, Mode],
+        "close" | "dup" => &[Fd],
+        "dup3" => &[Fd, Fd, Flags(CLOEXEC)],
+        "pipe2" => &[FdPair, Flags(PIPE_FLAGS)],
+        "fcntl" => &[Fd, Enum(FCNTL), Hex],
+        "lseek" => &[Fd, Int, Enum(WHENCE)],
+        "readlinkat" => &[Fd, Path, OutBuf, UInt],
+        "fstat" => &[Fd, Stat],
+        "fstatat" | "newfstatat" => &[Fd, Path, Stat, Flags(AT_FLAGS)],
+        "statx" => &[Fd, Path, Flags(AT_FLAGS), Hex, Statx],
+        "ioctl" => &[Fd, Hex, Hex],
+        "brk" => &[Ptr],
+        "mmap" => &[Ptr, UInt, Flags(PROT), MmapFlags, Fd, Hex],
+        "munmap" => &[Ptr, UInt],
+        "mprotect" => &[Ptr, UInt, Flags(PROT)],
+        "mremap" => &[Ptr, UInt, UInt, Flags(MREMAP_FLAGS), Ptr],
+        "msync" => &[Ptr, UInt, Flags(MS_FLAGS)],
+        "ppoll" | "ppoll_time64" => &[PollFds(1), UInt, Ptr, Ptr, UInt],
+        "pselect6" | "pselect6_time64" => &[Int, Ptr, Ptr, Ptr, Ptr, Ptr],
+        "eventfd2" => &[UInt, Flags(EFD_FLAGS)],
+        "epoll_create1" => &[Flags(CLOEXEC)],
+        "epoll_ctl" => &[Fd, Enum(EPOLL_CTL), Fd, Ptr],
+        "epoll_pwait" => &[Fd, Ptr, Int, Int, Ptr],
+        "epoll_pwait2" => &[Fd, Ptr, Int, Ptr, Ptr],
+        "socket" => &[Enum(DOMAINS), SockType, Int],
+        "bind" | "connect" => &[Fd, Sockaddr(2), UInt],
+        "listen" => &[Fd, Int],
+        "accept" => &[Fd, Ptr, Ptr],
+        "accept4" => &[Fd, Ptr, Ptr, Flags(SOCK_FLAGS)],
+        "getsockname" | "getpeername" => &[Fd, Ptr, Ptr],
+        "sendto" => &[Fd, InBuf(2), UInt, Flags(MSG_FLAGS), Sockaddr(5), UInt],
+        "recvfrom" => &[Fd, OutBuf, UInt, Flags(MSG_FLAGS), Ptr, Ptr],
+        "sendmsg" | "recvmsg" => &[Fd, Ptr, Flags(MSG_FLAGS)],
+        "shutdown" => &[Fd, Enum(SHUT)],
+        "setsockopt" | "getsockopt" => &[Fd, Int, Int, Ptr, Ptr],
+        "futex" | "futex_time64" => &[Ptr, FutexOp, UInt, Ptr, Ptr, UInt],
+        "set_tid_address" => &[Ptr],
+        "set_robust_list" => &[Ptr, UInt],
+        "rt_sigaction" => &[Int, Ptr, Ptr, UInt],
+        "rt_sigprocmask" => &[Int, Ptr, Ptr, UInt],
+        "tgkill" => &[Int, Int, Int],
+        "getpid" | "gettid" => &[],
+        "getrlimit" | "setrlimit" => &[Enum(RLIMITS), Ptr],
+        "prlimit64" => &[Int, Enum(RLIMITS), Ptr, Ptr],
+        "uname" | "sysinfo" => &[Ptr],
+        "getcwd" => &[OutBuf, UInt],
+        "chdir" => &[Path],
+        "fchdir" => &[Fd],
+        "umask" => &[Mode],
+        "getuid" | "geteuid" | "getgid" | "getegid" | "sched_yield" => &[],
+        "getresuid" | "getresgid" => &[Ptr, Ptr, Ptr],
+        "sched_getaffinity" | "sched_setaffinity" => &[Int, UInt, Ptr],
+        "madvise" => &[Ptr, UInt, Enum(MADVICE)],
+        "membarrier" => &[Enum(MEMBARRIER_CMDS), Hex, Int],
+        "rseq" => &[Ptr, UInt, Flags(RSEQ_FLAGS), Hex],
+        "getrandom" => &[OutBuf, UInt, Hex],
+        "riscv_hwprobe" => &[Ptr, UInt, UInt, Ptr, Hex],
+        "exit" | "exit_group" => &[Int],
+        _ => return None,
+    })
+}
+
+/// `value` as `NAME|NAME|0x..`, or `zero` when it is 0.
+fn flag_names(value: u64, table: &[(u64, &str)], zero: &str) -> String {
+    let mut names: Vec<String> = Vec::new();
+    let mut rest = value;
+    for &(bit, name) in table {
+        if bit != 0 && rest & bit == bit {
+            names.push(name.to_string());
+            rest &= !bit;
+        }
+    }
+    if rest != 0 || names.is_empty() && value != 0 {
+        names.push(format!("{rest:#x}"));
+    }
+    if names.is_empty() {
+        zero.to_string()
+    } else {
+        names.join("|")
+    }
+}
+
+fn enum_name(value: u64, table: &[(u64, &str)]) -> String {
+    table
+        .iter()
+        .find(|&&(v, _)| v == value)
+        .map_or_else(|| value.to_string(), |&(_, name)| name.to_string())
+}
+
+fn symbolic(value: u64, text: String) -> ArgValue {
+    ArgValue::Symbolic { value, text }
+}
+
+/// `S_IFREG|0644`-style file mode.
+fn file_mode(mode: u32) -> ArgValue {
+    let kind = enum_name((mode & libc_riscv32::S_IFMT) as u64, FILE_TYPES);
+    symbolic(
+        mode as u64,
+        format!("{kind}|{:#o}", mode & !libc_riscv32::S_IFMT),
+    )
+}
+
+/// Sign-extend a width-truncated argument.
+fn signed<X: KernelXlen>(value: u64) -> i64 {
+    let shift = 64 - X::BITS;
+    ((value << shift) as i64) >> shift
+}
+
+fn preview<M: Memory>(mem: &M, addr: u64, len: u64) -> ArgValue {
+    let shown = len.min(STRING_MAX as u64);
+    match mem.slice::<u8>(addr, shown) {
+        Ok(bytes) => ArgValue::Str {
+            bytes: bytes.to_vec(),
+            truncated: shown < len,
+        },
+        Err(_) => ArgValue::Ptr(addr),
+    }
+}
+
+fn c_string<M: Memory>(mem: &M, addr: u64) -> ArgValue {
+    match mem.bytes_null_terminated(addr, Some(libc_riscv32::PATH_MAX as u64)) {
+        Ok(bytes) => ArgValue::Str {
+            bytes: bytes[..bytes.len().min(STRING_MAX)].to_vec(),
+            truncated: bytes.len() > STRING_MAX,
+        },
+        Err(_) => ArgValue::Ptr(addr),
+    }
+}
+
+fn decode<X: KernelXlen, M: Memory>(
+    mem: &M,
+    kind: Kind,
+    args: &[u64; 6],
+    i: usize,
+    ret: Option<Result<u64, i32>>,
+) -> ArgValue {
+    let v = args[i];
+    let ok = matches!(ret, Some(Ok(_)));
+    match kind {
+        Kind::Int => ArgValue::Int(signed::<X>(v)),
+        Kind::UInt => ArgValue::UInt(v),
+        Kind::Hex => ArgValue::Hex(v),
+        Kind::Ptr => ArgValue::Ptr(v),
+        Kind::Fd => ArgValue::Fd(v as i32),
+        Kind::Path => c_string(mem, v),
+        Kind::InBuf(n) => preview(mem, v, args[n]),
+        Kind::OutBuf => match ret {
+            Some(Ok(len)) => preview(mem, v, len),
+            _ => ArgValue::Ptr(v),
+        },
+        Kind::Iov(n) => {
+            let count = args[n];
+            let Ok(iovs) = mem.slice::<IoVec<X::U>>(v, count.min(ARRAY_MAX as u64)) else {
+                return ArgValue::Ptr(v);
+            };
+            let items = iovs
+                .iter()
+                .map(|iov| {
+                    let (base, len) = (X::to_u64(iov.base), X::to_u64(iov.len));
+                    ArgValue::Struct(vec![
+                        ("iov_base", preview(mem, base, len)),
+                        ("iov_len", ArgValue::UInt(len)),
+                    ])
+                })
+                .collect();
+            ArgValue::Array(items, count > ARRAY_MAX as u64)
+        }
+        Kind::Flags(table) => symbolic(v, flag_names(v, table, "0")),
+        Kind::Enum(table) => symbolic(v, enum_name(v, table)),
+        Kind::OpenFlags => {
+            let access = enum_name(
+                v & libc_riscv32::O_ACCMODE as u64,
+                named![O_RDONLY, O_WRONLY, O_RDWR],
+            );
+            let rest = v & !(libc_riscv32::O_ACCMODE as u64);
+            let text = if rest == 0 {
+                access
+            } else {
+                format!("{access}|{}", flag_names(rest, OPEN_FLAGS, "0"))
+            };
+            symbolic(v, text)
+        }
+        Kind::Mode => symbolic(v, format!("{v:#o}")),
+        Kind::MmapFlags => {
+            let ty = enum_name(v & libc_riscv32::MAP_TYPE as u64, MAP_TYPES);
+            let rest = v & !(libc_riscv32::MAP_TYPE as u64);
+            let text = if rest == 0 {
+                ty
+            } else {
+                format!("{ty}|{}", flag_names(rest, MAP_FLAGS, "0"))
+            };
+            symbolic(v, text)
+        }
+        Kind::SockType => {
+            let ty = enum_name(v & libc_riscv32::SOCK_TYPE_MASK as u64, SOCK_TYPES);
+            let rest = v & !(libc_riscv32::SOCK_TYPE_MASK as u64);
+            let text = if rest == 0 {
+                ty
+            } else {
+                format!("{ty}|{}", flag_names(rest, SOCK_FLAGS, "0"))
+            };
+            symbolic(v, text)
+        }
+        Kind::FutexOp => {
+            let op = v as u32;
+            let mut text = enum_name((op & libc_riscv32::FUTEX_CMD_MASK) as u64, FUTEX_OPS);
+            if op & libc_riscv32::FUTEX_PRIVATE_FLAG != 0 {
+                text.push_str("_PRIVATE");
+            }
+            if op & libc_riscv32::FUTEX_CLOCK_REALTIME != 0 {
+                text.push_str("|FUTEX_CLOCK_REALTIME");
+            }
+            symbolic(v, text)
+        }
+        Kind::PollFds(n) => {
+            let count = args[n];
+            let Ok(fds) = mem.slice::<PollFd>(v, count.min(ARRAY_MAX as u64)) else {
+                return ArgValue::Ptr(v);
+            };
+            let items = fds
+                .iter()
+                .map(|p| {
+                    let events = |e: i16| {
+                        symbolic(
+                            e as u16 as u64,
+                            flag_names(e as u16 as u64, POLL_EVENTS, "0"),
+                        )
+                    };
+                    let mut fields = vec![("fd", ArgValue::Fd(p.fd)), ("events", events(p.events))];
+                    if ok {
+                        fields.push(("revents", events(p.revents)));
+                    }
+                    ArgValue::Struct(fields)
+                })
+                .collect();
+            ArgValue::Array(items, count > ARRAY_MAX as u64)
+        }
+        Kind::FdPair => match mem.slice::<i32>(v, 2) {
+            Ok(fds) if ok => {
+                ArgValue::Array(vec![ArgValue::Fd(fds[0]), ArgValue::Fd(fds[1])], false)
+            }
+            _ => ArgValue::Ptr(v),
+        },
+        Kind::Sockaddr(n) => {
+            let Ok(family) = mem.load_at::<u16>(v) else {
+                return ArgValue::Ptr(v);
+            };
+            let family_name = symbolic(family as u64, enum_name(family as u64, DOMAINS));
+            match read_sockaddr(mem, family, v, args[n]) {
+                Ok(addr) => ArgValue::Struct(vec![
+                    ("sa_family", family_name),
+                    ("port", ArgValue::UInt(addr.port() as u64)),
+                    ("addr", symbolic(0, format!("\"{}\"", addr.ip()))),
+                ]),
+                Err(_) => ArgValue::Struct(vec![("sa_family", family_name)]),
+            }
+        }
+        Kind::Stat => match mem.slice::<Stat64>(v, 1) {
+            Ok(st) if ok => ArgValue::Struct(vec![
+                ("st_mode", file_mode(st[0].mode)),
+                ("st_size", ArgValue::Int(st[0].size)),
+            ]),
+            _ => ArgValue::Ptr(v),
+        },
+        Kind::Statx => match mem.slice::<Statx>(v, 1) {
+            Ok(st) if ok => ArgValue::Struct(vec![
+                ("stx_mask", ArgValue::Hex(st[0].mask as u64)),
+                ("stx_mode", file_mode(st[0].mode as u32)),
+                ("stx_size", ArgValue::UInt(st[0].size)),
+            ]),
+            _ => ArgValue::Ptr(v),
+        },
+    }
+}
+
+impl<X: KernelXlen, M: Memory> MockLinux<X, M> {
+    /// Report every completed syscall to `tracer`.
+    pub fn with_tracer(mut self, tracer: impl Tracer + 'static) -> Self {
+        self.tracer = Some(Box::new(tracer));
+        self
+    }
+
+    /// Decode and report a syscall, if tracing.
+    pub(crate) fn trace(
+        &mut self,
+        mem: &M,
+        nr: u64,
+        name: Option<&'static str>,
+        args: &[u64; 6],
+        ret: Option<Result<u64, i32>>,
+    ) {
+        let Some(tracer) = self.tracer.as_mut() else {
+            return;
+        };
+        let args = match name.and_then(signature) {
+            Some(kinds) => kinds
+                .iter()
+                .enumerate()
+                .map(|(i, &kind)| decode::<X, M>(mem, kind, args, i, ret))
+                .collect(),
+            None => args.iter().map(|&v| ArgValue::Hex(v)).collect(),
+        };
+        tracer.syscall(SyscallEvent {
+            nr,
+            name,
+            args,
+            ret,
+        });
+    }
+}
@@ -49,6 +49,8 @@ pub enum MemoryError {
     },
     #[error("Memory access ({access:?}) at {addr:#x} is outside the guest address space")]
     Fault { access: MemoryAccess, addr: u64 },
+    #[error("Memory access ({access:?}) at {addr:#x} has no contiguous host range")]
+    NoHostRange { access: MemoryAccess, addr: u64 },
     #[error("Memory access ({access:?}) of length {len} at {addr:#x} would overflow")]
     OverflowMemoryAccess {
         access: MemoryAccess,
@@ -70,6 +72,10 @@ pub enum MachineError<E: Error> {
     Memory(Box<MemoryError>),
     #[error("Kernel error: {0}")]
     Kernel(E),
+    /// The hart or the kernel panicked running the machine; only a
+    /// [`crate::sched::Scheduler`] catches that, and reports it so.
+    #[error("Panicked: {0}")]
+    Panicked(String),
 }
 
 impl<E: Error> From<HartError> for MachineError<E> {
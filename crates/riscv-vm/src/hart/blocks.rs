@@ -0,0 +1,445 @@
+//! Pre-decoded basic blocks, chained and threaded.
+//!
+//! A block is a straight-line run of instructions decoded once, starting at
+//! the pc it is keyed by and ending at the first control transfer, trap or
+//! `fence.i`, at a page boundary, or after [`BLOCK_MAX`] instructions.
+//!
+//! Each pre-decoded instruction carries its op's [`Handler`], which executes
+//! it and tail-calls the next instruction's, so a block runs as a chain of
+//! indirect jumps, each predicted on its own, with no fetch, decode or
+//! central `match`. Pairs of instructions compilers emit as idioms (`lui` +
+//! `addi`, `auipc` + `jalr`, ...) are fused when a block is built: the
+//! first's handler executes both and skips the second, which stays in place
+//! so counts and exits are as if they ran apart. Each block also links the
+//! blocks it was last left for, one for falling through and one for a taken
+//! transfer: run loops follow a link when its target starts at the new pc,
+//! and only look blocks up by pc (and link them) when it does not.
+//!
+//! Invalidation is per page: a store that hits a page holding cached code
+//! drops every block on it, `fence.i` drops everything, and kernels report
+//! the code they replace behind the hart's back ([`Hart::invalidate_code`]).
+//! The block doing the storing still runs to its end as decoded, which the
+//! ISA allows: only `fence.i` orders stores before instruction fetch, and it
+//! always ends its block. Dropped blocks stay in the arenas, unmatchable,
+//! until they are reset.
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+use std::cell::Cell;
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    hash::{BuildHasherDefault, Hasher},
+    ptr, slice,
+};
+
+use super::{Exec, Hart, Xlen};
+use crate::{error::MachineError, memory::MemView};
+
+/// Longest block, in instructions.
+pub(crate) const BLOCK_MAX: u32 = 64;
+/// Instructions (and blocks) past which the arenas start over rather than
+/// keep growing around dropped blocks.
+const INSTS_MAX: usize = 1 << 18;
+const BLOCKS_MAX: usize = INSTS_MAX / 4;
+/// Instructions (and blocks) per arena chunk: the arenas grow a chunk at a
+/// time as code is first run, up to the maximums.
+const INSTS_CHUNK: usize = 1 << 12;
+const BLOCKS_CHUNK: usize = 1 << 10;
+/// Entries in the direct-mapped lookup in front of the block map.
+const RECENT: usize = 1024;
+const PAGE_SHIFT: u32 = 12;
+/// A dropped block's pc: no pc matches it.
+const DEAD: u64 = u64::MAX;
+
+/// Executes the instruction at `at`, whose pc is `pc`, then tail-calls the
+/// next one's handler, until [`Thread::end`] or an exit.
+pub(crate) type Handler<X> =
+    unsafe fn(&mut Hart<X>, &mut Thread<X>, *const Decoded<X>, <X as Xlen>::U, MemView<'_>) -> Exec;
+
+/// One pre-decoded instruction: its op's handler, the raw encoding the
+/// op's fields are extracted from, and its length in bytes.
+pub(crate) struct Decoded<X: Xlen> {
+    pub(crate) run: Handler<X>,
+    pub(crate) inst: u32,
+    pub(crate) size: u8,
+}
+
+impl<X: Xlen> Clone for Decoded<X> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<X: Xlen> Copy for Decoded<X> {}
+
+/// What a run of handlers reads and reports besides the hart.
+pub(crate) struct Thread<X: Xlen> {
+    /// One past the last instruction to run.
+    pub(crate) end: *const Decoded<X>,
+    /// Where execution stopped: the next pc, or the pc of the instruction
+    /// at [`Self::at`] if it trapped or failed.
+    pub(crate) pc: X::U,
+    /// The instruction that trapped or failed.
+    pub(crate) at: *const Decoded<X>,
+    /// The error of an [`Exec::Error`] exit (never a kernel's).
+    pub(crate) err: Option<MachineError<Infallible>>,
+}
+
+impl<X: Xlen> Thread<X> {
+    pub(crate) fn new() -> Self {
+        Self {
+            end: ptr::null(),
+            pc: X::U::default(),
+            at: ptr::null(),
+            err: None,
+        }
+    }
+}
+
+/// A block: `len` instructions at `code` in the instruction arena, and its
+/// links. Blocks and instructions are in arenas whose chunks never
+/// reallocate, so pointers to them stay valid until the arenas are reset,
+/// which run loops only cause through a lookup.
+pub(crate) struct Block<X: Xlen> {
+    /// [`DEAD`] once dropped, which fails every check against a pc.
+    pub(crate) pc: u64,
+    /// The fallthrough pc, just past the last instruction.
+    pub(crate) end: u64,
+    pub(crate) code: *const Decoded<X>,
+    /// The first instruction's handler, so entry waits on one load.
+    pub(crate) entry: Handler<X>,
+    pub(crate) len: u32,
+    /// Successors, indexed by whether the block fell through. Only hints:
+    /// followed if the target starts at the pc. Each starts out as the
+    /// block itself, which is right for loops and otherwise just misses.
+    pub(crate) links: [*const Block<X>; 2],
+    /// Interpreted entries, counted towards compiling the block.
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    pub(crate) hits: Cell<u32>,
+    /// The block's compiled code, or null.
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    pub(crate) native: Cell<*const u8>,
+}
+
+/// Multiplicative hash for pcs (which SipHash would spend longer on than
+/// the block they find).
+#[derive(Default)]
+struct PcHasher(u64);
+
+impl Hasher for PcHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.write_u64(self.0 << 8 | b as u64);
+        }
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        let h = n.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        self.0 = h ^ (h >> 32);
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+type PcMap<V> = HashMap<u64, V, BuildHasherDefault<PcHasher>>;
+
+/// Items in chunks allocated as they fill. A chunk is never pushed past
+/// its capacity, so it never moves.
+struct Arena<T> {
+    chunks: Vec<Vec<T>>,
+    /// Items in all chunks.
+    len: usize,
+}
+
+impl<T> Arena<T> {
+    fn new() -> Self {
+        Self {
+            chunks: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// The chunk to push the next `n` items to, contiguously: the last, or
+    /// a new one of `size` if the last lacks the room.
+    fn room(&mut self, n: usize, size: usize) -> &mut Vec<T> {
+        match self.chunks.last() {
+            Some(last) if last.capacity() - last.len() >= n => {}
+            _ => self.chunks.push(Vec::with_capacity(size)),
+        }
+        self.chunks.last_mut().unwrap()
+    }
+
+    /// The item `ptr` points into, if it is one: `ptr` may be stale, from
+    /// before a reset. The newest chunk first, which holds most items.
+    #[inline]
+    fn find(&mut self, ptr: *const T) -> Option<*mut T> {
+        let in_chunk = |chunk: &mut Vec<T>| {
+            let offset = (ptr as usize).wrapping_sub(chunk.as_ptr() as usize);
+            let index = offset / size_of::<T>();
+            // SAFETY: in bounds.
+            (index < chunk.len()).then(|| unsafe { chunk.as_mut_ptr().add(index) })
+        };
+        let (last, rest) = self.chunks.split_last_mut()?;
+        in_chunk(last).or_else(|| rest.iter_mut().find_map(in_chunk))
+    }
+
+    /// Empty, keeping the first chunk.
+    fn clear(&mut self) {
+        self.chunks.truncate(1);
+        if let Some(first) = self.chunks.first_mut() {
+            first.clear();
+        }
+        self.len = 0;
+    }
+}
+
+pub(crate) struct BlockCache<X: Xlen> {
+    /// `(pc, block)` by pc, direct-mapped; `pc == DEAD` is empty.
+    recent: Box<[(u64, *const Block<X>); RECENT]>,
+    /// Every block's instructions.
+    insts: Arena<Decoded<X>>,
+    /// Every block; written through raw pointers only, so the ones run
+    /// loops hold stay valid.
+    blocks: Arena<Block<X>>,
+    /// Live blocks, by pc.
+    by_pc: PcMap<*mut Block<X>>,
+    /// Code pages, each with the pcs of the blocks on it.
+    pages: PcMap<Vec<u64>>,
+    /// Inclusive bounds of every code page since the last clear, `lo` from
+    /// 7 bytes below so a misaligned store straddling into the first page
+    /// is inside: stores outside them skip the page lookup.
+    lo: u64,
+    hi: u64,
+    /// Cleared, with the arenas left to reset on the next build (a run
+    /// loop may still be reading the block that cleared them).
+    flushed: bool,
+    /// Whether the blocks were decoded for a window with a hole, their
+    /// loads and stores minding it (see [`MemView::has_hole`]).
+    holes: bool,
+}
+
+// SAFETY: the only pointers are into the cache's own arenas, which move
+// with it. The blocks' cells are only touched by the run loops, through the
+// hart's `&mut`; shared borrows only read the code bounds.
+unsafe impl<X: Xlen> Send for BlockCache<X> {}
+unsafe impl<X: Xlen> Sync for BlockCache<X> {}
+
+impl<X: Xlen> BlockCache<X> {
+    pub(crate) fn new() -> Self {
+        Self {
+            recent: Box::new([(DEAD, ptr::null()); RECENT]),
+            insts: Arena::new(),
+            blocks: Arena::new(),
+            by_pc: PcMap::default(),
+            pages: PcMap::default(),
+            lo: u64::MAX,
+            hi: 0,
+            flushed: false,
+            holes: false,
+        }
+    }
+
+    /// Decode for `view`'s memory from now on, dropping the blocks decoded
+    /// for one whose window has a hole if `view`'s has none, or the other
+    /// way round.
+    pub(crate) fn decode_for(&mut self, view: MemView) {
+        if self.holes != view.has_hole() {
+            self.clear();
+            self.holes = view.has_hole();
+        }
+    }
+
+    pub(crate) fn holes(&self) -> bool {
+        self.holes
+    }
+
+    fn slot(pc: u64) -> usize {
+        (pc >> 1) as usize % RECENT
+    }
+
+    /// The live block at `pc`, if one is cached.
+    #[inline]
+    pub(crate) fn lookup(&mut self, pc: u64) -> Option<*const Block<X>> {
+        let (tag, block) = self.recent[Self::slot(pc)];
+        if tag == pc {
+            return Some(block);
+        }
+        let block = *self.by_pc.get(&pc)?;
+        self.recent[Self::slot(pc)] = (pc, block);
+        Some(block)
+    }
+
+    /// Decode and cache the block at `pc`. `decode` fetches and decodes the
+    /// instruction at an address, returning it with whether it ends a
+    /// block, or `None` if it cannot be fetched. `None` if not even the
+    /// first instruction can. `fuse` then rewrites the block's handlers for
+    /// the pairs it fuses.
+    pub(crate) fn build(
+        &mut self,
+        pc: u64,
+        mut decode: impl FnMut(u64) -> Option<(Decoded<X>, bool)>,
+        fuse: fn(&mut [Decoded<X>]),
+    ) -> Option<*const Block<X>> {
+        if self.flushed || self.insts.len >= INSTS_MAX || self.blocks.len >= BLOCKS_MAX {
+            self.reset();
+        }
+        let insts = self.insts.room(BLOCK_MAX as usize, INSTS_CHUNK);
+        let start = insts.len();
+        let mut at = pc;
+        while let Some((inst, ends)) = decode(at) {
+            // Within capacity: `room` left `BLOCK_MAX`.
+            insts.push(inst);
+            at = at.wrapping_add(inst.size as u64);
+            let len = (insts.len() - start) as u32;
+            if ends || len == BLOCK_MAX || at >> PAGE_SHIFT != pc >> PAGE_SHIFT {
+                break;
+            }
+        }
+        let len = (insts.len() - start) as u32;
+        if len == 0 {
+            return None;
+        }
+        // SAFETY: just pushed. Not through the `Vec`, whose slice would
+        // cover, and so invalidate, the code of the blocks before.
+        let code =
+            unsafe { slice::from_raw_parts_mut(insts.as_mut_ptr().add(start), len as usize) };
+        fuse(code);
+        let (entry, code) = (code[0].run, code.as_ptr());
+        self.insts.len += len as usize;
+
+        let blocks = self.blocks.room(1, BLOCKS_CHUNK);
+        // SAFETY: in bounds once pushed.
+        let block = unsafe { blocks.as_mut_ptr().add(blocks.len()) };
+        blocks.push(Block {
+            pc,
+            end: at,
+            code,
+            entry,
+            len,
+            links: [block; 2],
+            #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+            hits: Cell::new(0),
+            #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+            native: Cell::new(ptr::null()),
+        });
+        self.blocks.len += 1;
+        self.by_pc.insert(pc, block);
+        self.recent[Self::slot(pc)] = (pc, block);
+        // The last instruction may straddle into the next page.
+        let (first, last) = (pc >> PAGE_SHIFT, at.wrapping_sub(1) >> PAGE_SHIFT);
+        for page in first..=last {
+            self.pages.entry(page).or_default().push(pc);
+        }
+        self.lo = self.lo.min((first << PAGE_SHIFT).saturating_sub(7));
+        self.hi = self.hi.max(last << PAGE_SHIFT | ((1 << PAGE_SHIFT) - 1));
+        Some(block)
+    }
+
+    /// Link `to` as `from`'s successor the way it was reached. `from` may
+    /// be stale (the arenas reset since it was found): it is only written if
+    /// it is still in the arena and live, and then at worst to a wrong but
+    /// harmless hint.
+    pub(crate) fn link(&mut self, from: *const Block<X>, to: *const Block<X>) {
+        let Some(from) = self.blocks.find(from) else {
+            return;
+        };
+        // SAFETY: in the arena, and `to` is a live block.
+        unsafe {
+            if (*from).pc != DEAD {
+                (*from).links[((*to).pc == (*from).end) as usize] = to;
+            }
+        }
+    }
+
+    /// Whether a store at `addr` may reach a page holding cached code: a
+    /// bounds check for the store path, which invalidates when it passes.
+    #[inline(always)]
+    pub(crate) fn near_code(&self, addr: u64) -> bool {
+        addr.wrapping_sub(self.lo) <= self.hi.wrapping_sub(self.lo)
+    }
+
+    /// [`Self::near_code`]'s bounds, as the first address a store may
+    /// start at and the span past it, for code that checks them itself.
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    pub(crate) fn code_range(&self) -> (u64, u64) {
+        (self.lo, self.hi.wrapping_sub(self.lo))
+    }
+
+    /// Detach every live block's compiled code, counting towards compiling
+    /// it afresh, before the code is dropped.
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    pub(crate) fn forget_native(&mut self) {
+        for &block in self.by_pc.values() {
+            // SAFETY: live blocks are in the arena.
+            let block = unsafe { &*block };
+            block.native.set(ptr::null());
+            block.hits.set(0);
+        }
+    }
+
+    /// Drop every block on a page overlapping `[addr, addr + len)`.
+    pub(crate) fn invalidate(&mut self, addr: u64, len: u64) {
+        if len == 0 {
+            return;
+        }
+        let first = addr >> PAGE_SHIFT;
+        let last = addr.saturating_add(len - 1) >> PAGE_SHIFT;
+        if last - first < self.pages.len() as u64 {
+            for page in first..=last {
+                self.invalidate_page(page);
+            }
+        } else {
+            // Wider than the cached code: sweep its pages instead.
+            let hit: Vec<u64> = self
+                .pages
+                .keys()
+                .copied()
+                .filter(|page| (first..=last).contains(page))
+                .collect();
+            for page in hit {
+                self.invalidate_page(page);
+            }
+        }
+    }
+
+    fn invalidate_page(&mut self, page: u64) {
+        let Some(pcs) = self.pages.remove(&page) else {
+            return;
+        };
+        for pc in pcs {
+            // Blocks spanning two pages are listed on both.
+            let Some(block) = self.by_pc.remove(&pc) else {
+                continue;
+            };
+            // SAFETY: live blocks are in the arena.
+            unsafe { (*block).pc = DEAD };
+            let slot = &mut self.recent[Self::slot(pc)];
+            if slot.0 == pc {
+                slot.0 = DEAD;
+            }
+        }
+    }
+
+    /// Drop every block. The arenas are only marked for reset: the block
+    /// executing `fence.i` is still read once it returns.
+    pub(crate) fn clear(&mut self) {
+        for &block in self.by_pc.values() {
+            // SAFETY: live blocks are in the arena.
+            unsafe { (*block).pc = DEAD };
+        }
+        self.by_pc.clear();
+        self.recent.fill((DEAD, ptr::null()));
+        self.pages.clear();
+        self.lo = u64::MAX;
+        self.hi = 0;
+        self.flushed = true;
+    }
+
+    fn reset(&mut self) {
+        self.clear();
+        self.insts.clear();
+        self.blocks.clear();
+        self.flushed = false;
+    }
+}
@@ -8,7 +8,13 @@
 use riscv_inst::codegen::rv64imasc::Rv64IMASC;
 use riscv_inst::Reg;
 
-use super::{mem_fault, take_err, Exec, Execute, Hart, X64};
+use std::{convert::Infallible, ptr};
+
+use super::{
+    blocks::{Block, Decoded, Handler, Thread},
+    code_written, handlers, inst_size, mem_fault, misa_extensions, take_err, Exec, Execute, Hart,
+    X64,
+};
 use crate::{
     error::{HartError, MachineError, MemoryAccess, MemoryError},
     machine::{Kernel, StepResult},
@@ -16,49 +22,123 @@ use crate::{
 };
 
 impl Execute for X64 {
+    // Unprivileged only, as on rv32.
+    const EXTENSIONS: u32 = misa_extensions(b"imac");
+    const MISALIGNED_FAST: bool = true;
+
     fn run<K: Kernel<Xlen = Self>>(
         hart: &mut Hart<Self>,
         mem: &mut K::Memory,
         kernel: &mut K,
-    ) -> Result<(), MachineError<K::Error>> {
+    ) -> Result<StepResult, MachineError<K::Error>> {
         // Same discipline as rv32: pc, the instruction count, and the memory
         // view live in registers; pc and count are flushed to the hart before
         // kernel entry and on exit, and the view is re-snapshotted after any
         // kernel call (which may grow, and so move, the arena).
         let mut pc = hart.pc;
         let mut count = 0u64;
-        let mut err = None;
         let mut view = mem.view();
+        hart.blocks.decode_for(view);
+        let mut thread = Thread::new();
+        // Checked against `count`, so refreshed whenever count is flushed.
+        let mut budget = hart.fuel.unwrap_or(u64::MAX);
+        // The block at pc, or null to look it up: after kernel calls, which
+        // may have dropped it.
+        let mut block: *const Block<X64> = ptr::null();
         let result = loop {
-            let Ok(inst) = view.load::<u32>(pc) else {
-                break Err(mem_fault(MemoryAccess::Load, pc));
-            };
-            let op = Rv64IMASC::decode(inst);
-            match exec_op_at(hart, op, inst, &mut pc, view, &mut err) {
-                Exec::Next => count += 1,
-                Exec::Error => break Err(take_err(&mut err)),
+            if count >= budget {
+                hart.pc = pc;
+                hart.retire(count);
+                count = 0;
+                let res = kernel.out_of_fuel(hart, mem);
+                view = mem.view();
+                budget = hart.fuel.unwrap_or(u64::MAX);
+                block = ptr::null();
+                match res {
+                    Ok(StepResult::Ok) => continue,
+                    Ok(res) => break Ok(res),
+                    Err(e) => break Err(e),
+                }
+            }
+            if block.is_null() {
+                block = match find_block(hart, view, pc) {
+                    Some(block) => block,
+                    None => break Err(mem_fault(MemoryAccess::Load, pc)),
+                };
+            }
+            // SAFETY: blocks stay valid until a lookup resets the arenas.
+            let (code, len, entry) = unsafe { ((*block).code, (*block).len, (*block).entry) };
+            // Stop partway through when the fuel runs out first (but never
+            // before the first instruction: there is fuel for at least one).
+            let len = (len as u64).min(budget - count);
+            // Counted up front; trap and error exits give back what did not
+            // retire.
+            count += len;
+            thread.end = unsafe { code.add(len as usize) };
+            let exit = unsafe { entry(hart, &mut thread, code, pc, view) };
+            pc = thread.pc;
+            match exit {
+                Exec::Next => {
+                    // Follow whichever link starts at pc, or find the
+                    // successor and link it. Links are checked rather than
+                    // picked by pc, so the next block's address does not
+                    // wait on the pc: only the (predicted) check does.
+                    let [taken, fall] = unsafe { (*block).links };
+                    block = if unsafe { (*fall).pc } == pc {
+                        fall
+                    } else if unsafe { (*taken).pc } == pc {
+                        taken
+                    } else {
+                        match chain(hart, view, block, pc) {
+                            Some(block) => block,
+                            None => break Err(mem_fault(MemoryAccess::Load, pc)),
+                        }
+                    };
+                }
+                Exec::Error => {
+                    count -= unsafe { thread.end.offset_from(thread.at) } as u64;
+                    break Err(take_err(&mut thread.err));
+                }
+                Exec::Miss => {
+                    count -= unsafe { thread.end.offset_from(thread.at) } as u64;
+                    let mut next = pc;
+                    match serve_miss(hart, thread.at, &mut next, view, &mut thread.err) {
+                        Exec::Next => {
+                            count += 1;
+                            pc = next;
+                            // On from just past it: a block of its own.
+                            block = ptr::null();
+                        }
+                        _ => break Err(take_err(&mut thread.err)),
+                    }
+                }
                 trap => {
+                    count -= unsafe { thread.end.offset_from(thread.at) } as u64;
+                    // Read before the kernel runs: it may drop the block.
+                    let size = unsafe { (*thread.at).size };
                     hart.pc = pc;
-                    hart.inst_count += count;
+                    hart.retire(count);
                     count = 0;
                     let res = match trap {
                         Exec::Syscall => kernel.syscall(hart, mem),
                         _ => kernel.ebreak(hart, mem),
                     };
                     view = mem.view();
+                    budget = hart.fuel.unwrap_or(u64::MAX);
+                    block = ptr::null();
                     match res {
                         Ok(StepResult::Ok) => {
                             count += 1;
-                            pc = pc.wrapping_add(if inst & 0b11 == 0b11 { 4 } else { 2 });
+                            pc = pc.wrapping_add(size as _);
                         }
-                        Ok(StepResult::Halt) => break Ok(()),
+                        Ok(res) => break Ok(res),
                         Err(e) => break Err(e),
                     }
                 }
             }
         };
         hart.pc = pc;
-        hart.inst_count += count;
+        hart.retire(count);
         result
     }
 
@@ -67,28 +147,40 @@ impl Execute for X64 {
         mem: &mut K::Memory,
         kernel: &mut K,
     ) -> Result<StepResult, MachineError<K::Error>> {
-        let mut pc = hart.pc;
-        let Ok(inst) = mem.load::<u32>(pc) else {
-            return Err(mem_fault(MemoryAccess::Load, pc as u64));
+        if hart.fuel == Some(0) {
+            return kernel.out_of_fuel(hart, mem);
+        }
+        let pc = hart.pc;
+        let Ok(inst) = mem.view().fetch(pc) else {
+            return Err(mem_fault(MemoryAccess::Load, pc));
         };
-        let op = Rv64IMASC::decode(inst);
+        let (_, decoded) = decode_for(inst, mem.view().has_hole());
 
-        let mut err = None;
-        match exec_op_at(hart, op, inst, &mut pc, mem.view(), &mut err) {
+        // A one-instruction run of the handlers.
+        let mut thread = Thread::new();
+        thread.end = unsafe { (&raw const decoded).add(1) };
+        let exit = match unsafe { (decoded.run)(hart, &mut thread, &decoded, pc, mem.view()) } {
+            Exec::Miss => {
+                thread.pc = pc;
+                serve_miss(hart, &decoded, &mut thread.pc, mem.view(), &mut thread.err)
+            }
+            exit => exit,
+        };
+        match exit {
             Exec::Next => {
-                hart.pc = pc;
-                hart.inst_count += 1;
+                hart.pc = thread.pc;
+                hart.retire(1);
                 Ok(StepResult::Ok)
             }
-            Exec::Error => Err(take_err(&mut err)),
+            Exec::Error => Err(take_err(&mut thread.err)),
             trap => {
                 let res = match trap {
                     Exec::Syscall => kernel.syscall(hart, mem)?,
                     _ => kernel.ebreak(hart, mem)?,
                 };
                 if let StepResult::Ok = res {
-                    hart.inst_count += 1;
-                    hart.pc = pc.wrapping_add(if inst & 0b11 == 0b11 { 4 } else { 2 });
+                    hart.retire(1);
+                    hart.pc = pc.wrapping_add(decoded.size as _);
                 }
                 Ok(res)
             }
@@ -96,7 +188,134 @@ impl Execute for X64 {
     }
 }
 
-/// Execute an already-decoded instruction at `*pc`.
+/// Handlers by op discriminant (see [`decode`]), for windows without a
+/// hole and with one.
+const HANDLERS: [[Handler<X64>; Rv64IMASC::DISC_SLOW as usize + 1]; 2] = handlers![
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25,
+    26, 27, 28, 29, 30, 31, 32, 33, 34, 35, 36, 37, 38, 39, 40, 41, 42, 43, 44, 45, 46, 47, 48, 49,
+    50, 51, 52, 53, 54, 55, 56, 57, 58, 59, 60, 61, 62, 63, 64, 65, 66, 67, 68, 69, 70, 71, 72, 73,
+    74, 75, 76, 77, 78, 79, 80, 81, 82, 83, 84, 85, 86, 87, 88, 89, 90, 91, 92, 93, 94, 95, 96, 97,
+    98, 99, 100, 101, 102, 103, 104, 105, 106, 107, 108, 109, 110, 111, 112, 113, 114, 115, 116,
+    117, 118, 119, 120, 121, 122, 123, 124, 125, 126, 127, 128, 129, 130, 131, 132, 133, 134, 135,
+    136, 137, 138
+];
+
+/// The handler for ops of discriminant `D`: [`exec_op_at`] with the op a
+/// constant, so it keeps that op's arm only, then a tail call to the next
+/// instruction's handler. Where the call is not turned into a jump (debug
+/// builds), the recursion is at most a block deep.
+///
+/// # Safety
+///
+/// `at` points to a pre-decoded instruction of op `D` at `pc`, followed by
+/// the rest up to `thread.end`.
+unsafe fn handler<const D: u8, const HOLES: bool>(
+    hart: &mut Hart<X64>,
+    thread: &mut Thread<X64>,
+    at: *const Decoded<X64>,
+    pc: u64,
+    view: MemView,
+) -> Exec {
+    // SAFETY: every discriminant up to `DISC_SLOW` is a variant, and all
+    // the variants' payloads are zero-sized.
+    let op = unsafe { std::mem::transmute::<u8, Rv64IMASC>(D) };
+    let Decoded { inst, size, .. } = unsafe { *at };
+    let mut next = pc;
+    match exec_op_at::<false, HOLES>(hart, op, inst, size, &mut next, view, &mut thread.err) {
+        Exec::Next => {
+            let at = unsafe { at.add(1) };
+            if at == thread.end {
+                thread.pc = next;
+                return Exec::Next;
+            }
+            unsafe { ((*at).run)(hart, thread, at, next, view) }
+        }
+        exit => {
+            thread.pc = pc;
+            thread.at = at;
+            exit
+        }
+    }
+}
+
+/// The handler for a fused pair of ops `A` and `B` (see [`fuse`]): both in
+/// one step, then a tail call past the second. Only the second can fail.
+///
+/// # Safety
+///
+/// As for [`handler`], with `at` followed by the pair's second instruction
+/// (unless `thread.end` falls between them).
+unsafe fn pair<const A: u8, const B: u8>(
+    hart: &mut Hart<X64>,
+    thread: &mut Thread<X64>,
+    at: *const Decoded<X64>,
+    pc: u64,
+    view: MemView,
+) -> Exec {
+    let second = unsafe { at.add(1) };
+    if second == thread.end {
+        // Cut off between the two (out of fuel): just the first.
+        return unsafe { handler::<A, false>(hart, thread, at, pc, view) };
+    }
+    // SAFETY: as in `handler`.
+    let (a, b) = unsafe {
+        (
+            std::mem::transmute::<u8, Rv64IMASC>(A),
+            std::mem::transmute::<u8, Rv64IMASC>(B),
+        )
+    };
+    let (x, y) = unsafe { (*at, *second) };
+    let (Some(a), Some(b)) = (part(a, x.inst, pc), part(b, y.inst, 0)) else {
+        unreachable!("fused ops have parts")
+    };
+    let mut next = pc.wrapping_add(x.size as u64).wrapping_add(y.size as u64);
+    match (a, b) {
+        (Part::Upper { value, .. }, Part::Addi { rd, imm, word, .. }) => {
+            let res = match word {
+                true => (value as u32).wrapping_add_signed(imm) as i32 as u64,
+                false => value.wrapping_add_signed(imm as i64),
+            };
+            hart.set_reg(rd, res);
+        }
+        (Part::Upper { rd: upper, value }, Part::Jalr { rd, imm, .. }) => {
+            hart.set_reg(upper, value);
+            hart.set_reg(rd, next);
+            next = value.wrapping_add_signed(imm as i64) & !1;
+        }
+        (Part::Slli { rs1, shamt, .. }, Part::Srli { rd, .. }) => {
+            hart.set_reg(rd, hart.get_reg(rs1) << shamt >> shamt);
+        }
+        (Part::Slli { rs1, shamt, .. }, Part::Srai { rd, .. }) => {
+            hart.set_reg(rd, ((hart.get_reg(rs1) << shamt) as i64 >> shamt) as u64);
+        }
+        _ => unreachable!("`fuse` pairs only these"),
+    }
+    let at = unsafe { second.add(1) };
+    if at == thread.end {
+        thread.pc = next;
+        return Exec::Next;
+    }
+    unsafe { ((*at).run)(hart, thread, at, next, view) }
+}
+
+/// Redo the instruction at `at` (at `*pc`), which left its block with
+/// [`Exec::Miss`], serving the miss; as [`exec_op_at`] otherwise.
+#[cold]
+#[inline(never)]
+fn serve_miss(
+    hart: &mut Hart<X64>,
+    at: *const Decoded<X64>,
+    pc: &mut u64,
+    view: MemView,
+    err: &mut Option<MachineError<Infallible>>,
+) -> Exec {
+    let Decoded { inst, size, .. } = unsafe { *at };
+    exec_op_at::<true, true>(hart, decode(inst).0, inst, size, pc, view, err)
+}
+
+/// Execute an already-decoded instruction of `size` bytes at `*pc`. Only
+/// ever inlined: into a [`handler`], with `op` a constant, and into
+/// [`serve_miss`].
 ///
 /// On [`Exec::Next`], the next pc is written through `pc` (an out-parameter
 /// rather than an enum payload; see [`Exec`]). On [`Exec::Error`], the error
@@ -104,18 +323,18 @@ impl Execute for X64 {
 /// untouched. `hart.pc` is never written here: callers keep pc in a register
 /// and flush it at trap and exit boundaries.
 #[inline(always)]
-fn exec_op_at<E: std::error::Error>(
+fn exec_op_at<const SERVE: bool, const HOLES: bool>(
     hart: &mut Hart<X64>,
     op: Rv64IMASC,
     inst: u32,
+    size: u8,
     pc: &mut u64,
     view: MemView,
-    err: &mut Option<MachineError<E>>,
+    err: &mut Option<MachineError<Infallible>>,
 ) -> Exec {
     let pc_out = pc;
     let pc = *pc_out;
-    let pc_inc = if inst & 0b11 == 0b11 { 4 } else { 2 };
-    let mut next_pc = pc.wrapping_add(pc_inc);
+    let mut next_pc = pc.wrapping_add(size as _);
 
     // Error return: deposit the error in the caller's slot (see `Exec`).
     macro_rules! fail {
@@ -126,22 +345,38 @@ fn exec_op_at<E: std::error::Error>(
     }
 
     // Guest memory accessors: attach fault context (cold) at the call site
-    // so the hot path only carries a zero-sized error.
+    // so the hot path only carries a zero-sized error. A miss the memory
+    // serves leaves the block (see `Exec::Miss`), unless serving it here.
     macro_rules! load {
         ($t:ty, $addr:expr) => {{
             let a = $addr;
-            match view.load::<$t>(a) {
+            let v = match SERVE {
+                true => view.serve_load::<$t>(a),
+                false => view.load_in::<$t, HOLES>(a),
+            };
+            match v {
                 Ok(v) => v,
+                Err(_) if !SERVE && view.misses() => return Exec::Miss,
                 Err(_) => fail!(mem_fault(MemoryAccess::Load, a)),
             }
         }};
     }
     macro_rules! store {
         ($t:ty, $addr:expr, $val:expr) => {{
-            let a = $addr;
-            if view.store::<$t>(a, $val).is_err() {
+            let (a, v) = ($addr, $val);
+            let stored = match SERVE {
+                true => view.serve_store::<$t>(a, v),
+                false => view.store_in::<$t, HOLES>(a, v),
+            };
+            if stored.is_err() {
+                if !SERVE && view.misses() {
+                    return Exec::Miss;
+                }
                 fail!(mem_fault(MemoryAccess::Store, a));
             }
+            if hart.blocks.near_code(a) {
+                code_written(hart, a, size_of::<$t>() as u64);
+            }
         }};
     }
 
@@ -258,13 +493,9 @@ fn exec_op_at<E: std::error::Error>(
         }};
     }
 
-    // The sentinels dispatch through the same jump table as real ops.
-    // Slow diverts out of line: a dispatch back-edge here makes LLVM
-    // jump-thread the tree decode into the hot loop head (speculated
-    // compares and cmov-selected discriminants on every instruction).
     match op {
-        Rv64IMASC::Invalid => fail!(HartError::invalid(pc, inst).into()),
-        Rv64IMASC::Slow => return exec_slow(hart, inst, pc_out, view, err),
+        // `decode` resolves Slow, to an op or to Invalid.
+        Rv64IMASC::Invalid | Rv64IMASC::Slow => fail!(HartError::invalid(pc, inst).into()),
         // --- RV64I ---
         Rv64IMASC::Lui(lui) => imm_op!(|lui.imm| imm as i64),
         Rv64IMASC::Auipc(auipc) => imm_op!(|auipc.imm| pc.wrapping_add_signed(imm as i64)),
@@ -342,7 +573,8 @@ fn exec_op_at<E: std::error::Error>(
         Rv64IMASC::Or(or) => reg_reg_op!(|or.rs1, or.rs2| rs1 | rs2),
         Rv64IMASC::And(and) => reg_reg_op!(|and.rs1, and.rs2| rs1 & rs2),
         Rv64IMASC::Fence(_) => {}
-        Rv64IMASC::FenceI(_) => {}
+        // Ends its block, so the run loop looks up the next one afresh.
+        Rv64IMASC::FenceI(_) => hart.blocks.clear(),
         Rv64IMASC::Ecall(_) => return Exec::Syscall,
         Rv64IMASC::Ebreak(_) => return Exec::Ebreak,
         Rv64IMASC::Unimp(_) => fail!(HartError::illegal(pc, inst).into()),
@@ -703,22 +935,276 @@ fn exec_op_at<E: std::error::Error>(
     Exec::Next
 }
 
-/// Inlining this in the hot loop caused some LLVM codegen to blow-up,
-/// so this is marked as cold and un-inlinable.
-#[cold]
+/// Decode `inst` for its handler, resolving the slow path's ops now rather
+/// than on every execution.
+fn decode(inst: u32) -> (Rv64IMASC, Decoded<X64>) {
+    decode_for(inst, false)
+}
+
+/// [`decode`], with a handler minding the window's hole if `holes`.
+fn decode_for(inst: u32, holes: bool) -> (Rv64IMASC, Decoded<X64>) {
+    let op = match Rv64IMASC::decode(inst) {
+        Rv64IMASC::Slow => Rv64IMASC::parse_slow(inst).unwrap_or(Rv64IMASC::Invalid),
+        op => op,
+    };
+    // SAFETY: the enum is `repr(u8)`, so it starts with its discriminant.
+    let disc = unsafe { *(&raw const op).cast::<u8>() };
+    let run = HANDLERS[holes as usize][disc as usize];
+    let size = inst_size(inst);
+    (op, Decoded { run, inst, size })
+}
+
+/// The block at `pc`, decoded into the hart's cache if need be.
+#[inline(never)]
+fn find_block(hart: &mut Hart<X64>, view: MemView, pc: u64) -> Option<*const Block<X64>> {
+    if let Some(block) = hart.blocks.lookup(pc) {
+        return Some(block);
+    }
+    let holes = hart.blocks.holes();
+    hart.blocks.build(
+        pc,
+        |at| {
+            let (op, decoded) = decode_for(view.fetch(at).ok()?, holes);
+            Some((decoded, ends_block(op)))
+        },
+        fuse,
+    )
+}
+
+/// [`find_block`], linking the result from `from`, the block just left.
 #[inline(never)]
-fn exec_slow<E: std::error::Error>(
+fn chain(
     hart: &mut Hart<X64>,
-    inst: u32,
-    pc: &mut u64,
     view: MemView,
-    err: &mut Option<MachineError<E>>,
-) -> Exec {
-    match Rv64IMASC::parse_slow(inst) {
-        Some(op) => exec_op_at(hart, op, inst, pc, view, err),
-        None => {
-            *err = Some(HartError::invalid(*pc, inst).into());
-            Exec::Error
+    from: *const Block<X64>,
+    pc: u64,
+) -> Option<*const Block<X64>> {
+    let block = find_block(hart, view, pc)?;
+    hart.blocks.link(from, block);
+    Some(block)
+}
+
+/// An op as the fusable idioms see it, whatever its encoding.
+#[derive(Clone, Copy)]
+enum Part {
+    /// `lui`/`auipc`, with the value it writes.
+    Upper {
+        rd: Reg,
+        value: u64,
+    },
+    /// `addi`, or with `word`, `addiw`.
+    Addi {
+        rd: Reg,
+        rs1: Reg,
+        imm: i32,
+        word: bool,
+    },
+    Jalr {
+        rd: Reg,
+        rs1: Reg,
+        imm: i32,
+    },
+    Slli {
+        rd: Reg,
+        rs1: Reg,
+        shamt: u32,
+    },
+    Srli {
+        rd: Reg,
+        rs1: Reg,
+        shamt: u32,
+    },
+    Srai {
+        rd: Reg,
+        rs1: Reg,
+        shamt: u32,
+    },
+}
+
+/// `op` (encoded as `inst`, at `pc`) as a [`Part`], if it can be one.
+#[inline(always)]
+fn part(op: Rv64IMASC, inst: u32, pc: u64) -> Option<Part> {
+    use Rv64IMASC as Op;
+
+    let addi = |rd, rs1, imm, word| Part::Addi { rd, rs1, imm, word };
+    Some(match op {
+        Op::Lui(i) => Part::Upper {
+            rd: i.rd(inst),
+            value: i.imm(inst) as i64 as u64,
+        },
+        Op::CLui(i) => Part::Upper {
+            rd: i.rd(inst),
+            value: i.imm(inst) as i64 as u64,
+        },
+        Op::Auipc(i) => Part::Upper {
+            rd: i.rd(inst),
+            value: pc.wrapping_add_signed(i.imm(inst) as i64),
+        },
+        Op::Addi(i) => addi(i.rd(inst), i.rs1(inst), i.imm(inst), false),
+        Op::CAddi(i) => addi(i.rs1rd(inst), i.rs1rd(inst), i.imm(inst), false),
+        Op::Addiw(i) => addi(i.rd(inst), i.rs1(inst), i.imm(inst), true),
+        Op::CAddiw(i) => addi(i.rs1rd(inst), i.rs1rd(inst), i.imm(inst), true),
+        Op::Jalr(i) => Part::Jalr {
+            rd: i.rd(inst),
+            rs1: i.rs1(inst),
+            imm: i.imm(inst),
+        },
+        Op::Slli(i) => Part::Slli {
+            rd: i.rd(inst),
+            rs1: i.rs1(inst),
+            shamt: i.shamt(inst),
+        },
+        Op::CSlli(i) => Part::Slli {
+            rd: i.rs1rd(inst),
+            rs1: i.rs1rd(inst),
+            shamt: i.shamt(inst),
+        },
+        Op::Srli(i) => Part::Srli {
+            rd: i.rd(inst),
+            rs1: i.rs1(inst),
+            shamt: i.shamt(inst),
+        },
+        Op::CSrli(i) => Part::Srli {
+            rd: i.rs1rd(inst),
+            rs1: i.rs1rd(inst),
+            shamt: i.shamt(inst),
+        },
+        Op::Srai(i) => Part::Srai {
+            rd: i.rd(inst),
+            rs1: i.rs1(inst),
+            shamt: i.shamt(inst),
+        },
+        Op::CSrai(i) => Part::Srai {
+            rd: i.rs1rd(inst),
+            rs1: i.rs1rd(inst),
+            shamt: i.shamt(inst),
+        },
+        _ => return None,
+    })
+}
+
+/// Whether `a` then `b` is an idiom [`pair`] executes as one:
+/// - `lui`/`auipc` then `addi`/`addiw` into the same register (a constant
+///   or an address), or `jalr` through it (a far call);
+/// - a shift left then right by the same amount in place (zero- or
+///   sign-extension, as in `zext.w`).
+///
+/// Constant-pool loads and split products run no faster fused.
+fn fusable(a: Part, b: Part) -> bool {
+    match (a, b) {
+        // Not through x0: the second reads it as zero, not as the value.
+        (Part::Upper { rd, .. }, Part::Addi { rd: rd2, rs1, .. }) => {
+            rd != Reg::Zero && rd2 == rd && rs1 == rd
         }
+        (Part::Upper { rd, .. }, Part::Jalr { rs1, .. }) => rd != Reg::Zero && rs1 == rd,
+        (
+            Part::Slli { rd, shamt, .. },
+            Part::Srli {
+                rd: rd2,
+                rs1,
+                shamt: shamt2,
+            }
+            | Part::Srai {
+                rd: rd2,
+                rs1,
+                shamt: shamt2,
+            },
+        ) => rd != Reg::Zero && rd2 == rd && rs1 == rd && shamt2 == shamt,
+        _ => false,
     }
 }
+
+/// Fuse the idiom pairs in `insts`, a block being built (see [`fusable`]).
+fn fuse(insts: &mut [Decoded<X64>]) {
+    let mut i = 0;
+    while i + 1 < insts.len() {
+        let (a, b) = (decode(insts[i].inst).0, decode(insts[i + 1].inst).0);
+        let run = match (part(a, insts[i].inst, 0), part(b, insts[i + 1].inst, 0)) {
+            (Some(x), Some(y)) if fusable(x, y) => pair_handler(a, b),
+            _ => None,
+        };
+        match run {
+            Some(run) => {
+                insts[i].run = run;
+                i += 2;
+            }
+            None => i += 1,
+        }
+    }
+}
+
+/// [`pair`] for ops `a` and `b`.
+fn pair_handler(a: Rv64IMASC, b: Rv64IMASC) -> Option<Handler<X64>> {
+    use riscv_inst::codegen::rv64imasc::*;
+    use Rv64IMASC as Op;
+
+    const fn disc(op: Rv64IMASC) -> u8 {
+        // SAFETY: the enum is `repr(u8)`, with zero-sized payloads.
+        unsafe { std::mem::transmute::<Rv64IMASC, u8>(op) }
+    }
+    macro_rules! pairs {
+        ($(($a:ident, $b:ident)),* $(,)?) => {
+            $(
+                if (a, b) == (Op::$a($a), Op::$b($b)) {
+                    return Some(pair::<{ disc(Op::$a($a)) }, { disc(Op::$b($b)) }>);
+                }
+            )*
+        };
+    }
+    pairs![
+        (Lui, Addi),
+        (Lui, Addiw),
+        (Lui, CAddi),
+        (Lui, CAddiw),
+        (CLui, Addi),
+        (CLui, Addiw),
+        (CLui, CAddi),
+        (CLui, CAddiw),
+        (Auipc, Addi),
+        (Auipc, Jalr),
+        (Slli, Srli),
+        (Slli, Srai),
+        (Slli, CSrli),
+        (Slli, CSrai),
+        (CSlli, Srli),
+        (CSlli, Srai),
+        (CSlli, CSrli),
+        (CSlli, CSrai),
+    ];
+    None
+}
+
+/// Whether execution may leave straight-line order after `op`: control
+/// transfers, traps, `fence.i`, and anything that always fails.
+fn ends_block(op: Rv64IMASC) -> bool {
+    matches!(
+        op,
+        Rv64IMASC::Invalid
+            | Rv64IMASC::Jal(_)
+            | Rv64IMASC::Jalr(_)
+            | Rv64IMASC::Beq(_)
+            | Rv64IMASC::Bne(_)
+            | Rv64IMASC::Blt(_)
+            | Rv64IMASC::Bge(_)
+            | Rv64IMASC::Bltu(_)
+            | Rv64IMASC::Bgeu(_)
+            | Rv64IMASC::FenceI(_)
+            | Rv64IMASC::Ecall(_)
+            | Rv64IMASC::Ebreak(_)
+            | Rv64IMASC::Unimp(_)
+            | Rv64IMASC::Uret(_)
+            | Rv64IMASC::Sret(_)
+            | Rv64IMASC::Hret(_)
+            | Rv64IMASC::Dret(_)
+            | Rv64IMASC::SfenceVm(_)
+            | Rv64IMASC::SfenceVma(_)
+            | Rv64IMASC::Wfi(_)
+            | Rv64IMASC::CJ(_)
+            | Rv64IMASC::CBeqz(_)
+            | Rv64IMASC::CBnez(_)
+            | Rv64IMASC::CJr(_)
+            | Rv64IMASC::CJalr(_)
+            | Rv64IMASC::CEbreak(_)
+            | Rv64IMASC::CUnimp(_)
+    )
+}
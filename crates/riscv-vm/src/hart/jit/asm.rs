@@ -0,0 +1,364 @@
+//! A minimal x86-64 encoder: just the instruction forms the JIT emits, and
+//! the executable memory it emits them into.
+
+pub(super) const RAX: u8 = 0;
+pub(super) const RCX: u8 = 1;
+pub(super) const RDX: u8 = 2;
+pub(super) const RBX: u8 = 3;
+pub(super) const RBP: u8 = 5;
+pub(super) const RSI: u8 = 6;
+pub(super) const RDI: u8 = 7;
+pub(super) const R8: u8 = 8;
+pub(super) const R9: u8 = 9;
+pub(super) const R10: u8 = 10;
+pub(super) const R11: u8 = 11;
+pub(super) const R12: u8 = 12;
+pub(super) const R13: u8 = 13;
+pub(super) const R14: u8 = 14;
+pub(super) const R15: u8 = 15;
+
+/// Condition codes, as `jcc`/`setcc`/`cmovcc` encode them.
+#[derive(Clone, Copy)]
+pub(super) enum Cond {
+    B = 0x2,
+    Ae = 0x3,
+    E = 0x4,
+    Ne = 0x5,
+    Be = 0x6,
+    L = 0xc,
+    Ge = 0xd,
+}
+
+/// The group-1 ALU operations, by their `/digit`.
+#[derive(Clone, Copy)]
+pub(super) enum Alu {
+    Add = 0,
+    Or = 1,
+    And = 4,
+    Sub = 5,
+    Xor = 6,
+    Cmp = 7,
+}
+
+/// Shifts, by their `/digit`.
+#[derive(Clone, Copy)]
+pub(super) enum Shift {
+    Shl = 4,
+    Shr = 5,
+    Sar = 7,
+}
+
+/// A register or memory operand.
+#[derive(Clone, Copy)]
+pub(super) enum Rm {
+    Reg(u8),
+    /// `[base + disp]`; `base` is not `rsp` or `r12`.
+    Mem {
+        base: u8,
+        disp: i8,
+    },
+    /// `[base + index]`; `base` is not `rbp` or `r13`, `index` not `rsp`.
+    Indexed {
+        base: u8,
+        index: u8,
+    },
+}
+
+/// A forward jump's rel32, to patch once its target is known.
+pub(super) struct Label(usize);
+
+#[derive(Default)]
+pub(super) struct Asm {
+    pub(super) code: Vec<u8>,
+}
+
+impl Asm {
+    fn byte(&mut self, b: u8) {
+        self.code.push(b);
+    }
+
+    fn imm32(&mut self, imm: u32) {
+        self.code.extend_from_slice(&imm.to_le_bytes());
+    }
+
+    /// REX (if needed), `opcode`, then ModRM (and SIB, displacement) for
+    /// `reg` (a register or a `/digit`) and `rm`.
+    fn op(&mut self, wide: bool, opcode: &[u8], reg: u8, rm: Rm) {
+        let (b, x) = match rm {
+            Rm::Reg(r) | Rm::Mem { base: r, .. } => (r >> 3, 0),
+            Rm::Indexed { base, index } => (base >> 3, index >> 3),
+        };
+        let rex = 0x40 | (wide as u8) << 3 | (reg >> 3) << 2 | x << 1 | b;
+        if rex != 0x40 {
+            self.byte(rex);
+        }
+        self.code.extend_from_slice(opcode);
+        let reg = (reg & 7) << 3;
+        match rm {
+            Rm::Reg(r) => self.byte(0xc0 | reg | r & 7),
+            Rm::Mem { base, disp } => {
+                self.byte(0x40 | reg | base & 7);
+                self.byte(disp as u8);
+            }
+            Rm::Indexed { base, index } => {
+                self.byte(0x04 | reg);
+                self.byte((index & 7) << 3 | base & 7);
+            }
+        }
+    }
+
+    /// `mov r32, r/m32`
+    pub(super) fn mov(&mut self, dst: u8, src: Rm) {
+        self.op(false, &[0x8b], dst, src);
+    }
+
+    /// `mov r/m32, r32`
+    pub(super) fn mov_to(&mut self, dst: Rm, src: u8) {
+        self.op(false, &[0x89], src, dst);
+    }
+
+    /// `mov r64, r/m64`
+    pub(super) fn mov64(&mut self, dst: u8, src: Rm) {
+        self.op(true, &[0x8b], dst, src);
+    }
+
+    /// `mov r/m32, imm32`
+    pub(super) fn mov_imm(&mut self, dst: Rm, imm: u32) {
+        match dst {
+            Rm::Reg(r) => {
+                if r >= 8 {
+                    self.byte(0x41);
+                }
+                self.byte(0xb8 + (r & 7));
+            }
+            _ => self.op(false, &[0xc7], 0, dst),
+        }
+        self.imm32(imm);
+    }
+
+    /// `op r32, r/m32`
+    pub(super) fn alu(&mut self, op: Alu, dst: u8, src: Rm) {
+        self.op(false, &[op as u8 * 8 + 3], dst, src);
+    }
+
+    /// `op r64, r/m64`
+    pub(super) fn alu64(&mut self, op: Alu, dst: u8, src: Rm) {
+        self.op(true, &[op as u8 * 8 + 3], dst, src);
+    }
+
+    /// `op r/m32, imm`
+    pub(super) fn alu_imm(&mut self, op: Alu, dst: Rm, imm: i32) {
+        if let Ok(imm) = i8::try_from(imm) {
+            self.op(false, &[0x83], op as u8, dst);
+            self.byte(imm as u8);
+        } else {
+            self.op(false, &[0x81], op as u8, dst);
+            self.imm32(imm as u32);
+        }
+    }
+
+    /// `shift r/m32, imm8`
+    pub(super) fn shift_imm(&mut self, op: Shift, dst: Rm, amount: u8) {
+        self.op(false, &[0xc1], op as u8, dst);
+        self.byte(amount);
+    }
+
+    /// `shift r/m64, imm8`
+    pub(super) fn shift64_imm(&mut self, op: Shift, dst: Rm, amount: u8) {
+        self.op(true, &[0xc1], op as u8, dst);
+        self.byte(amount);
+    }
+
+    /// `shift r/m32, cl`
+    pub(super) fn shift_cl(&mut self, op: Shift, dst: Rm) {
+        self.op(false, &[0xd3], op as u8, dst);
+    }
+
+    /// `imul r32, r/m32`
+    pub(super) fn imul(&mut self, dst: u8, src: Rm) {
+        self.op(false, &[0x0f, 0xaf], dst, src);
+    }
+
+    /// `imul r64, r/m64`
+    pub(super) fn imul64(&mut self, dst: u8, src: Rm) {
+        self.op(true, &[0x0f, 0xaf], dst, src);
+    }
+
+    /// `movsxd r64, r/m32`
+    pub(super) fn movsxd(&mut self, dst: u8, src: Rm) {
+        self.op(true, &[0x63], dst, src);
+    }
+
+    /// Zero- or sign-extending load of a byte or halfword into `r32`.
+    pub(super) fn movx(&mut self, dst: u8, src: Rm, width: u8, signed: bool) {
+        let opcode = match (width, signed) {
+            (1, false) => 0xb6,
+            (2, false) => 0xb7,
+            (1, true) => 0xbe,
+            _ => 0xbf,
+        };
+        self.op(false, &[0x0f, opcode], dst, src);
+    }
+
+    /// `mov r/m{8,16,32}, r{8,16,32}`; `src`'s low byte must be addressable
+    /// without REX (`al`..`bl`).
+    pub(super) fn store(&mut self, dst: Rm, src: u8, width: u8) {
+        match width {
+            1 => self.op(false, &[0x88], src, dst),
+            2 => {
+                self.byte(0x66);
+                self.op(false, &[0x89], src, dst);
+            }
+            _ => self.op(false, &[0x89], src, dst),
+        }
+    }
+
+    /// `test r/m32, r32`
+    pub(super) fn test(&mut self, a: Rm, b: u8) {
+        self.op(false, &[0x85], b, a);
+    }
+
+    /// `setcc r8` (`al`..`bl`)
+    pub(super) fn setcc(&mut self, cond: Cond, dst: u8) {
+        self.op(false, &[0x0f, 0x90 + cond as u8], 0, Rm::Reg(dst));
+    }
+
+    /// `cmovcc r32, r/m32`
+    pub(super) fn cmov(&mut self, cond: Cond, dst: u8, src: Rm) {
+        self.op(false, &[0x0f, 0x40 + cond as u8], dst, src);
+    }
+
+    /// `div r/m32` (`signed`: `idiv`) of `edx:eax`.
+    pub(super) fn div(&mut self, src: Rm, signed: bool) {
+        self.op(false, &[0xf7], if signed { 7 } else { 6 }, src);
+    }
+
+    /// `cdq`
+    pub(super) fn cdq(&mut self) {
+        self.byte(0x99);
+    }
+
+    pub(super) fn push(&mut self, r: u8) {
+        if r >= 8 {
+            self.byte(0x41);
+        }
+        self.byte(0x50 + (r & 7));
+    }
+
+    pub(super) fn pop(&mut self, r: u8) {
+        if r >= 8 {
+            self.byte(0x41);
+        }
+        self.byte(0x58 + (r & 7));
+    }
+
+    pub(super) fn ret(&mut self) {
+        self.byte(0xc3);
+    }
+
+    /// `jcc rel32`, to a target bound later.
+    pub(super) fn jcc(&mut self, cond: Cond) -> Label {
+        self.code.extend_from_slice(&[0x0f, 0x80 + cond as u8]);
+        self.imm32(0);
+        Label(self.code.len())
+    }
+
+    /// `jmp rel32`, to a target bound later.
+    pub(super) fn jmp(&mut self) -> Label {
+        self.byte(0xe9);
+        self.imm32(0);
+        Label(self.code.len())
+    }
+
+    /// Point `label`'s jump here.
+    pub(super) fn bind(&mut self, label: Label) {
+        let rel = (self.code.len() - label.0) as u32;
+        self.code[label.0 - 4..label.0].copy_from_slice(&rel.to_le_bytes());
+    }
+}
+
+/// Executable memory that compiled blocks are copied into, end to end.
+/// Never writable and executable at once: the mapping is read-execute, and
+/// writable only while [`Code::add`] copies into it.
+pub(super) struct Code {
+    ptr: *mut u8,
+    len: usize,
+    used: usize,
+}
+
+// SAFETY: the mapping is owned, and only written through `&mut self`.
+unsafe impl Send for Code {}
+unsafe impl Sync for Code {}
+
+impl Code {
+    /// Map `len` bytes, or `None` if the host refuses executable memory.
+    pub(super) fn new(len: usize) -> Option<Self> {
+        // SAFETY: a fresh anonymous mapping, checked below.
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return None;
+        }
+        let code = Self {
+            ptr: ptr as *mut u8,
+            len,
+            used: 0,
+        };
+        code.protect(libc::PROT_READ | libc::PROT_EXEC)
+            .then_some(code)
+    }
+
+    /// Bytes mapped.
+    pub(super) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Copy `code` in, returning where it starts, or `None` when full.
+    pub(super) fn add(&mut self, code: &[u8]) -> Option<*const u8> {
+        if code.len() > self.len - self.used {
+            return None;
+        }
+        // The whole mapping, so it stays one mapping with one protection,
+        // which it held before: neither change can fail.
+        assert!(self.protect(libc::PROT_READ | libc::PROT_WRITE));
+        // SAFETY: in bounds, checked above, and writable; nothing runs the
+        // bytes past `used`, which are only now being written.
+        let at = unsafe {
+            let at = self.ptr.add(self.used);
+            at.copy_from_nonoverlapping(code.as_ptr(), code.len());
+            at
+        };
+        assert!(self.protect(libc::PROT_READ | libc::PROT_EXEC));
+        self.used += code.len();
+        Some(at)
+    }
+
+    /// Forget everything copied in. Callers drop every pointer `add`
+    /// returned first.
+    pub(super) fn clear(&mut self) {
+        self.used = 0;
+    }
+
+    /// Set the mapping's protection to `prot`, returning whether the host
+    /// allowed it.
+    fn protect(&self, prot: libc::c_int) -> bool {
+        // SAFETY: the mapping is ours; nothing runs it while it is
+        // writable, as only `add` makes it so.
+        unsafe { libc::mprotect(self.ptr as *mut libc::c_void, self.len, prot) == 0 }
+    }
+}
+
+impl Drop for Code {
+    fn drop(&mut self) {
+        // SAFETY: mapped in `new`, and nothing runs it any more.
+        unsafe { libc::munmap(self.ptr as *mut libc::c_void, self.len) };
+    }
+}
@@ -0,0 +1,988 @@
+//! The rv32 JIT backend ([`Backend::Jit`]): hot blocks compiled to x86-64.
+//!
+//! Blocks start out interpreted, as by the interpreter backend. A block
+//! entered [`Hart::jit_threshold`] times is compiled, if its memory window
+//! spans the whole 32-bit space (as [`Memory32`]'s does): guest addresses
+//! are then offsets from the arena base, in bounds by construction, so
+//! loads and stores compile to single host accesses.
+//!
+//! Compiled code covers the longest prefix of the block it can lower (see
+//! [`lower`]); whatever follows, from `ecall` to CSR access, is left to the
+//! interpreter, which also takes over at *side exits*: stores that may hit
+//! cached code leave the compiled block before storing, so the interpreter
+//! stores and invalidates, and where the window has a hole (an
+//! [`Mmio`]'s devices), loads and stores in it leave for the interpreter to
+//! serve. Within a block, the guest registers it uses most live in host
+//! registers, loaded on entry and written back on exit.
+//!
+//! Compiled blocks are attached to the cached blocks, and are dropped with
+//! them. The code buffer is mapped on the first compile, small; when it
+//! fills up, every block's code is dropped and it is replaced by one twice
+//! the size, up to [`CODE_SIZE`], then starts over.
+//!
+//! [`Backend::Jit`]: super::Backend::Jit
+//! [`Memory32`]: crate::memory::Memory32
+//! [`Mmio`]: crate::memory::Mmio
+
+mod asm;
+
+use std::ptr;
+
+use riscv_inst::{codegen::rv32imasc::Rv32IMASC, Reg};
+
+use self::asm::{Alu, Asm, Code, Cond, Rm, Shift, R15, RAX, RCX, RDI, RDX, RSI};
+use super::{
+    blocks::{Block, Thread},
+    exec32::{chain, decode, find_block, serve_miss},
+    mem_fault, take_err, Exec, Execute, Hart, X32,
+};
+use crate::{
+    error::{MachineError, MemoryAccess},
+    machine::{Kernel, StepResult},
+    memory::{MemView, Memory},
+};
+
+/// Bytes the code buffer is first mapped with.
+const CODE_MIN: usize = 64 << 10;
+/// Bytes of compiled code kept before starting over.
+const CODE_SIZE: usize = 16 << 20;
+/// Host registers guest registers are allocated to. The rest have fixed
+/// roles: `rax`, `rcx` and `rdx` are scratch, `rdi` points to the hart's
+/// registers, `rsi` to the memory arena, `r15` to the [`Ctx`].
+const HOST_REGS: [u8; 9] = [
+    asm::RBX,
+    asm::RBP,
+    asm::R8,
+    asm::R9,
+    asm::R10,
+    asm::R11,
+    asm::R12,
+    asm::R13,
+    asm::R14,
+];
+/// The callee-saved registers compiled code uses, saved on entry.
+const SAVED: [u8; 6] = [asm::RBX, asm::RBP, asm::R12, asm::R13, asm::R14, R15];
+
+/// What compiled code is passed. `repr(C)`: it addresses the fields.
+#[repr(C)]
+struct Ctx {
+    regs: *mut u32,
+    base: *mut u8,
+    /// [`BlockCache::near_code`]'s bounds, as `lo` and `hi - lo`.
+    ///
+    /// [`BlockCache::near_code`]: super::blocks::BlockCache::near_code
+    code_lo: u64,
+    code_span: u64,
+    /// The window's [`Hole`](crate::memory::Hole), as `lo` and `span`.
+    hole_lo: u64,
+    hole_span: u64,
+}
+
+const CTX_REGS: i8 = 0;
+const CTX_BASE: i8 = 8;
+const CTX_CODE_LO: i8 = 16;
+const CTX_CODE_SPAN: i8 = 24;
+const CTX_HOLE_LO: i8 = 32;
+const CTX_HOLE_SPAN: i8 = 40;
+
+/// A compiled block: returns the next pc, with the instructions it retired
+/// in the high half.
+type Native = unsafe extern "sysv64" fn(*mut Ctx) -> u64;
+
+/// A hart's compiled code.
+pub(crate) struct Jit {
+    /// Mapped on first use; `None` until then, or if the host refused.
+    code: Option<Code>,
+    mapped: bool,
+}
+
+impl Jit {
+    pub(crate) fn new() -> Self {
+        Self {
+            code: None,
+            mapped: false,
+        }
+    }
+}
+
+/// Whether `view` spans the whole 32-bit space (and the guard page a
+/// primitive's overhang lands in), so compiled code need not check bounds.
+fn spans_all(view: MemView) -> bool {
+    view.mapped > u32::MAX as u64
+}
+
+/// The JIT backend's run loop: the interpreter's, entering blocks' compiled
+/// code where they have it, and compiling hot ones.
+pub(crate) fn run<K: Kernel<Xlen = X32>>(
+    hart: &mut Hart<X32>,
+    mem: &mut K::Memory,
+    kernel: &mut K,
+) -> Result<StepResult, MachineError<K::Error>> {
+    if !spans_all(mem.view()) {
+        return X32::run(hart, mem, kernel);
+    }
+    let mut pc = hart.pc;
+    let mut count = 0u64;
+    let mut view = mem.view();
+    let mut thread = Thread::new();
+    hart.blocks.decode_for(view);
+    let mut ctx = Ctx {
+        regs: ptr::null_mut(),
+        base: view.ptr,
+        code_lo: 0,
+        code_span: 0,
+        hole_lo: 0,
+        hole_span: 0,
+    };
+    let mut budget = hart.fuel.unwrap_or(u64::MAX);
+    let mut block: *const Block<X32> = ptr::null();
+    let result = loop {
+        if count >= budget {
+            hart.pc = pc;
+            hart.retire(count);
+            count = 0;
+            let res = kernel.out_of_fuel(hart, mem);
+            view = mem.view();
+            budget = hart.fuel.unwrap_or(u64::MAX);
+            block = ptr::null();
+            match res {
+                Ok(StepResult::Ok) => continue,
+                Ok(res) => break Ok(res),
+                Err(e) => break Err(e),
+            }
+        }
+        if !spans_all(view) {
+            // The kernel swapped the window out from under compiled code.
+            hart.pc = pc;
+            hart.retire(count);
+            return X32::run(hart, mem, kernel);
+        }
+        if block.is_null() {
+            block = match find_block(hart, view, pc) {
+                Some(block) => block,
+                None => break Err(mem_fault(MemoryAccess::Load, pc as u64)),
+            };
+        }
+        // SAFETY: blocks stay valid until a lookup resets the arenas.
+        let (code, len, entry) = unsafe { ((*block).code, (*block).len, (*block).entry) };
+        let native = unsafe { (*block).native.get() };
+        let mut retired = 0;
+        if !native.is_null() && len as u64 <= budget - count {
+            ctx.regs = hart.regs.as_mut_ptr();
+            ctx.base = view.ptr;
+            (ctx.code_lo, ctx.code_span) = hart.blocks.code_range();
+            let hole = view.hole();
+            (ctx.hole_lo, ctx.hole_span) = (hole.lo, hole.span);
+            // SAFETY: compiled for this block, which is live; the context
+            // points at the hart's registers and a window it spans.
+            let out = unsafe { std::mem::transmute::<*const u8, Native>(native)(&mut ctx) };
+            retired = out >> 32;
+            if retired != 0 {
+                pc = out as u32;
+                count += retired;
+            }
+        }
+        // Not compiled, or left before its first instruction (a store near
+        // code, which only the interpreter makes): interpret it.
+        if retired == 0 {
+            let hits = unsafe { &(*block).hits };
+            hits.set(hits.get().saturating_add(1));
+            if hits.get() == hart.jit_threshold.max(1) {
+                compile(hart, block);
+            }
+            // The interpreter's block entry, exits and all.
+            let len = (len as u64).min(budget - count);
+            count += len;
+            thread.end = unsafe { code.add(len as usize) };
+            let exit = unsafe { entry(hart, &mut thread, code, pc, view) };
+            pc = thread.pc;
+            match exit {
+                Exec::Next => {}
+                Exec::Error => {
+                    count -= unsafe { thread.end.offset_from(thread.at) } as u64;
+                    break Err(take_err(&mut thread.err));
+                }
+                // In the hole, the only misses of a window spanning every
+                // address: as in the interpreter.
+                Exec::Miss => {
+                    count -= unsafe { thread.end.offset_from(thread.at) } as u64;
+                    let mut next = pc;
+                    match serve_miss(hart, thread.at, &mut next, view, &mut thread.err) {
+                        Exec::Next => {
+                            count += 1;
+                            pc = next;
+                            block = ptr::null();
+                            continue;
+                        }
+                        _ => break Err(take_err(&mut thread.err)),
+                    }
+                }
+                trap => {
+                    count -= unsafe { thread.end.offset_from(thread.at) } as u64;
+                    let size = unsafe { (*thread.at).size };
+                    hart.pc = pc;
+                    hart.retire(count);
+                    count = 0;
+                    let res = match trap {
+                        Exec::Syscall => kernel.syscall(hart, mem),
+                        _ => kernel.ebreak(hart, mem),
+                    };
+                    view = mem.view();
+                    budget = hart.fuel.unwrap_or(u64::MAX);
+                    block = ptr::null();
+                    match res {
+                        Ok(StepResult::Ok) => {
+                            count += 1;
+                            pc = pc.wrapping_add(size as _);
+                            continue;
+                        }
+                        Ok(res) => break Ok(res),
+                        Err(e) => break Err(e),
+                    }
+                }
+            }
+        }
+        // As in the interpreter.
+        let [taken, fall] = unsafe { (*block).links };
+        block = if unsafe { (*fall).pc } == pc as u64 {
+            fall
+        } else if unsafe { (*taken).pc } == pc as u64 {
+            taken
+        } else {
+            match chain(hart, view, block, pc) {
+                Some(block) => block,
+                None => break Err(mem_fault(MemoryAccess::Load, pc as u64)),
+            }
+        };
+    };
+    hart.pc = pc;
+    hart.retire(count);
+    result
+}
+
+/// Compile `block`, attaching the code to it, unless none of it can be.
+#[cold]
+#[inline(never)]
+fn compile(hart: &mut Hart<X32>, block: *const Block<X32>) {
+    // SAFETY: a live block, and its instructions.
+    let (pc, insts) = unsafe {
+        let block = &*block;
+        (
+            block.pc as u32,
+            std::slice::from_raw_parts(block.code, block.len as usize),
+        )
+    };
+    let mut ir = Vec::with_capacity(insts.len());
+    let mut at = pc;
+    for decoded in insts {
+        let Some(op) = lower(decode(decoded.inst).0, decoded.inst, at) else {
+            break;
+        };
+        ir.push((at, op));
+        at = at.wrapping_add(decoded.size as u32);
+    }
+    if ir.is_empty() {
+        return;
+    }
+    let asm = emit(&ir, at, hart.blocks.holes());
+
+    let jit = &mut hart.jit;
+    if !jit.mapped {
+        jit.mapped = true;
+        jit.code = Code::new(CODE_MIN);
+    }
+    let Some(code) = &mut jit.code else {
+        return;
+    };
+    let native = match code.add(&asm.code) {
+        Some(native) => native,
+        None => {
+            // Full: drop every block's code, then grow, or start over.
+            hart.blocks.forget_native();
+            match (code.len() < CODE_SIZE).then(|| Code::new(code.len() * 2)) {
+                Some(Some(bigger)) => *code = bigger,
+                _ => code.clear(),
+            }
+            let Some(native) = code.add(&asm.code) else {
+                return;
+            };
+            native
+        }
+    };
+    unsafe { (*block).native.set(native) };
+}
+
+/// An ALU source operand.
+#[derive(Clone, Copy)]
+enum Src {
+    Reg(Reg),
+    Imm(i32),
+}
+
+#[derive(Clone, Copy)]
+enum AluOp {
+    Add,
+    Sub,
+    And,
+    Or,
+    Xor,
+    Sll,
+    Srl,
+    Sra,
+    Slt,
+    Sltu,
+    Mul,
+    Mulh,
+    Mulhsu,
+    Mulhu,
+    Div,
+    Divu,
+    Rem,
+    Remu,
+}
+
+/// The instructions compiled code handles, with compressed ones expanded.
+#[derive(Clone, Copy)]
+enum Ir {
+    Alu {
+        op: AluOp,
+        rd: Reg,
+        rs1: Reg,
+        src: Src,
+    },
+    Li {
+        rd: Reg,
+        imm: u32,
+    },
+    Load {
+        rd: Reg,
+        rs1: Reg,
+        imm: i32,
+        width: u8,
+        signed: bool,
+    },
+    Store {
+        rs1: Reg,
+        rs2: Reg,
+        imm: i32,
+        width: u8,
+    },
+    /// Ends the block.
+    Branch {
+        cond: Cond,
+        rs1: Reg,
+        rs2: Reg,
+        target: u32,
+    },
+    /// Ends the block.
+    Jal {
+        rd: Reg,
+        target: u32,
+    },
+    /// Ends the block.
+    Jalr {
+        rd: Reg,
+        rs1: Reg,
+        imm: i32,
+    },
+    Nop,
+}
+
+/// Lower `op` (encoded as `inst`, at `pc`), or `None` if compiled code does
+/// not handle it.
+fn lower(op: Rv32IMASC, inst: u32, pc: u32) -> Option<Ir> {
+    use Rv32IMASC as Op;
+
+    let alu = |op, rd, rs1, src| Ir::Alu { op, rd, rs1, src };
+    let load = |rd, rs1, imm, width, signed| Ir::Load {
+        rd,
+        rs1,
+        imm,
+        width,
+        signed,
+    };
+    let store = |rs1, rs2, imm, width| Ir::Store {
+        rs1,
+        rs2,
+        imm,
+        width,
+    };
+    let branch = |cond, rs1, rs2, imm: i32| Ir::Branch {
+        cond,
+        rs1,
+        rs2,
+        target: pc.wrapping_add_signed(imm),
+    };
+    Some(match op {
+        // --- RV32I ---
+        Op::Lui(i) => Ir::Li {
+            rd: i.rd(inst),
+            imm: i.imm(inst) as u32,
+        },
+        Op::Auipc(i) => Ir::Li {
+            rd: i.rd(inst),
+            imm: pc.wrapping_add_signed(i.imm(inst)),
+        },
+        Op::Jal(i) => Ir::Jal {
+            rd: i.rd(inst),
+            target: pc.wrapping_add_signed(i.imm(inst)),
+        },
+        Op::Jalr(i) => Ir::Jalr {
+            rd: i.rd(inst),
+            rs1: i.rs1(inst),
+            imm: i.imm(inst),
+        },
+        Op::Beq(i) => branch(Cond::E, i.rs1(inst), i.rs2(inst), i.imm(inst)),
+        Op::Bne(i) => branch(Cond::Ne, i.rs1(inst), i.rs2(inst), i.imm(inst)),
+        Op::Blt(i) => branch(Cond::L, i.rs1(inst), i.rs2(inst), i.imm(inst)),
+        Op::Bge(i) => branch(Cond::Ge, i.rs1(inst), i.rs2(inst), i.imm(inst)),
+        Op::Bltu(i) => branch(Cond::B, i.rs1(inst), i.rs2(inst), i.imm(inst)),
+        Op::Bgeu(i) => branch(Cond::Ae, i.rs1(inst), i.rs2(inst), i.imm(inst)),
+        Op::Lb(i) => load(i.rd(inst), i.rs1(inst), i.imm(inst), 1, true),
+        Op::Lh(i) => load(i.rd(inst), i.rs1(inst), i.imm(inst), 2, true),
+        Op::Lw(i) => load(i.rd(inst), i.rs1(inst), i.imm(inst), 4, false),
+        Op::Lbu(i) => load(i.rd(inst), i.rs1(inst), i.imm(inst), 1, false),
+        Op::Lhu(i) => load(i.rd(inst), i.rs1(inst), i.imm(inst), 2, false),
+        Op::Sb(i) => store(i.rs1(inst), i.rs2(inst), i.imm(inst), 1),
+        Op::Sh(i) => store(i.rs1(inst), i.rs2(inst), i.imm(inst), 2),
+        Op::Sw(i) => store(i.rs1(inst), i.rs2(inst), i.imm(inst), 4),
+        Op::Addi(i) => alu(AluOp::Add, i.rd(inst), i.rs1(inst), Src::Imm(i.imm(inst))),
+        Op::Slti(i) => alu(AluOp::Slt, i.rd(inst), i.rs1(inst), Src::Imm(i.imm(inst))),
+        Op::Sltiu(i) => alu(AluOp::Sltu, i.rd(inst), i.rs1(inst), Src::Imm(i.imm(inst))),
+        Op::Xori(i) => alu(AluOp::Xor, i.rd(inst), i.rs1(inst), Src::Imm(i.imm(inst))),
+        Op::Ori(i) => alu(AluOp::Or, i.rd(inst), i.rs1(inst), Src::Imm(i.imm(inst))),
+        Op::Andi(i) => alu(AluOp::And, i.rd(inst), i.rs1(inst), Src::Imm(i.imm(inst))),
+        Op::Slli(i) => alu(
+            AluOp::Sll,
+            i.rd(inst),
+            i.rs1(inst),
+            Src::Imm(i.shamt(inst) as i32),
+        ),
+        Op::Srli(i) => alu(
+            AluOp::Srl,
+            i.rd(inst),
+            i.rs1(inst),
+            Src::Imm(i.shamt(inst) as i32),
+        ),
+        Op::Srai(i) => alu(
+            AluOp::Sra,
+            i.rd(inst),
+            i.rs1(inst),
+            Src::Imm(i.shamt(inst) as i32),
+        ),
+        Op::Add(i) => alu(AluOp::Add, i.rd(inst), i.rs1(inst), Src::Reg(i.rs2(inst))),
+        Op::Sub(i) => alu(AluOp::Sub, i.rd(inst), i.rs1(inst), Src::Reg(i.rs2(inst))),
+        Op::Sll(i) => alu(AluOp::Sll, i.rd(inst), i.rs1(inst), Src::Reg(i.rs2(inst))),
+        Op::Slt(i) => alu(AluOp::Slt, i.rd(inst), i.rs1(inst), Src::Reg(i.rs2(inst))),
+        Op::Sltu(i) => alu(AluOp::Sltu, i.rd(inst), i.rs1(inst), Src::Reg(i.rs2(inst))),
+        Op::Xor(i) => alu(AluOp::Xor, i.rd(inst), i.rs1(inst), Src::Reg(i.rs2(inst))),
+        Op::Srl(i) => alu(AluOp::Srl, i.rd(inst), i.rs1(inst), Src::Reg(i.rs2(inst))),
+        Op::Sra(i) => alu(AluOp::Sra, i.rd(inst), i.rs1(inst), Src::Reg(i.rs2(inst))),
+        Op::Or(i) => alu(AluOp::Or, i.rd(inst), i.rs1(inst), Src::Reg(i.rs2(inst))),
+        Op::And(i) => alu(AluOp::And, i.rd(inst), i.rs1(inst), Src::Reg(i.rs2(inst))),
+        Op::Fence(_) => Ir::Nop,
+
+        // --- M ---
+        Op::Mul(i) => alu(AluOp::Mul, i.rd(inst), i.rs1(inst), Src::Reg(i.rs2(inst))),
+        Op::Mulh(i) => alu(AluOp::Mulh, i.rd(inst), i.rs1(inst), Src::Reg(i.rs2(inst))),
+        Op::Mulhsu(i) => alu(
+            AluOp::Mulhsu,
+            i.rd(inst),
+            i.rs1(inst),
+            Src::Reg(i.rs2(inst)),
+        ),
+        Op::Mulhu(i) => alu(AluOp::Mulhu, i.rd(inst), i.rs1(inst), Src::Reg(i.rs2(inst))),
+        Op::Div(i) => alu(AluOp::Div, i.rd(inst), i.rs1(inst), Src::Reg(i.rs2(inst))),
+        Op::Divu(i) => alu(AluOp::Divu, i.rd(inst), i.rs1(inst), Src::Reg(i.rs2(inst))),
+        Op::Rem(i) => alu(AluOp::Rem, i.rd(inst), i.rs1(inst), Src::Reg(i.rs2(inst))),
+        Op::Remu(i) => alu(AluOp::Remu, i.rd(inst), i.rs1(inst), Src::Reg(i.rs2(inst))),
+
+        // --- C ---
+        Op::CAddi4spn(i) => alu(
+            AluOp::Add,
+            i.rd(inst),
+            Reg::Sp,
+            Src::Imm(i.imm(inst) as i32),
+        ),
+        Op::CLw(i) => load(i.rd(inst), i.rs1(inst), i.imm(inst) as i32, 4, false),
+        Op::CSw(i) => store(i.rs1(inst), i.rs2(inst), i.imm(inst) as i32, 4),
+        Op::CAddi(i) => {
+            let rd = i.rs1rd(inst);
+            alu(AluOp::Add, rd, rd, Src::Imm(i.imm(inst)))
+        }
+        Op::CAddi16sp(i) => alu(AluOp::Add, i.rs1rd(inst), Reg::Sp, Src::Imm(i.imm(inst))),
+        Op::CLwsp(i) => load(i.rd(inst), Reg::Sp, i.imm(inst) as i32, 4, false),
+        Op::CSwsp(i) => store(Reg::Sp, i.rs2(inst), i.imm(inst) as i32, 4),
+        Op::CNop(_) => Ir::Nop,
+        Op::CJal(i) => Ir::Jal {
+            rd: Reg::Ra,
+            target: pc.wrapping_add_signed(i.imm(inst)),
+        },
+        Op::CLi(i) => Ir::Li {
+            rd: i.rs1rd(inst),
+            imm: i.imm(inst) as u32,
+        },
+        Op::CLui(i) => Ir::Li {
+            rd: i.rd(inst),
+            imm: i.imm(inst) as u32,
+        },
+        Op::CSrli(i) => {
+            let rd = i.rs1rd(inst);
+            alu(AluOp::Srl, rd, rd, Src::Imm(i.shamt(inst) as i32))
+        }
+        Op::CSrai(i) => {
+            let rd = i.rs1rd(inst);
+            alu(AluOp::Sra, rd, rd, Src::Imm(i.shamt(inst) as i32))
+        }
+        Op::CAndi(i) => {
+            let rd = i.rs1rd(inst);
+            alu(AluOp::And, rd, rd, Src::Imm(i.imm(inst)))
+        }
+        Op::CSub(i) => {
+            let rd = i.rs1rd(inst);
+            alu(AluOp::Sub, rd, rd, Src::Reg(i.rs2(inst)))
+        }
+        Op::CXor(i) => {
+            let rd = i.rs1rd(inst);
+            alu(AluOp::Xor, rd, rd, Src::Reg(i.rs2(inst)))
+        }
+        Op::COr(i) => {
+            let rd = i.rs1rd(inst);
+            alu(AluOp::Or, rd, rd, Src::Reg(i.rs2(inst)))
+        }
+        Op::CAnd(i) => {
+            let rd = i.rs1rd(inst);
+            alu(AluOp::And, rd, rd, Src::Reg(i.rs2(inst)))
+        }
+        Op::CJ(i) => Ir::Jal {
+            rd: Reg::Zero,
+            target: pc.wrapping_add_signed(i.imm(inst)),
+        },
+        Op::CBeqz(i) => branch(Cond::E, i.rs1(inst), Reg::Zero, i.imm(inst)),
+        Op::CBnez(i) => branch(Cond::Ne, i.rs1(inst), Reg::Zero, i.imm(inst)),
+        Op::CSlli(i) => {
+            let rd = i.rs1rd(inst);
+            alu(AluOp::Sll, rd, rd, Src::Imm(i.shamt(inst) as i32))
+        }
+        Op::CJr(i) => Ir::Jalr {
+            rd: Reg::Zero,
+            rs1: i.rs1(inst),
+            imm: 0,
+        },
+        Op::CMv(i) => alu(AluOp::Add, i.rd(inst), Reg::Zero, Src::Reg(i.rs2(inst))),
+        Op::CJalr(i) => Ir::Jalr {
+            rd: Reg::Ra,
+            rs1: i.rs1(inst),
+            imm: 0,
+        },
+        Op::CAdd(i) => {
+            let rd = i.rs1rd(inst);
+            alu(AluOp::Add, rd, rd, Src::Reg(i.rs2(inst)))
+        }
+        _ => return None,
+    })
+}
+
+/// Guest register locations for one block.
+struct Regs {
+    /// The host register each guest register is allocated to, if any.
+    host: [Option<u8>; 32],
+    /// Allocated guest registers the block writes, to write back on exit.
+    written: Vec<(Reg, u8)>,
+}
+
+impl Regs {
+    /// Allocate host registers to the guest registers `ir` uses most.
+    fn allocate(ir: &[(u32, Ir)]) -> Self {
+        let mut uses = [0u32; 32];
+        let mut writes = [false; 32];
+        for &(_, op) in ir {
+            let (rd, srcs) = match op {
+                Ir::Alu { rd, rs1, src, .. } => match src {
+                    Src::Reg(rs2) => (Some(rd), [Some(rs1), Some(rs2)]),
+                    Src::Imm(_) => (Some(rd), [Some(rs1), None]),
+                },
+                Ir::Li { rd, .. } | Ir::Jal { rd, .. } => (Some(rd), [None, None]),
+                Ir::Load { rd, rs1, .. } | Ir::Jalr { rd, rs1, .. } => {
+                    (Some(rd), [Some(rs1), None])
+                }
+                Ir::Store { rs1, rs2, .. } | Ir::Branch { rs1, rs2, .. } => {
+                    (None, [Some(rs1), Some(rs2)])
+                }
+                Ir::Nop => (None, [None, None]),
+            };
+            for reg in srcs.into_iter().flatten().chain(rd) {
+                uses[reg as usize] += 1;
+            }
+            if let Some(rd) = rd {
+                writes[rd as usize] = true;
+            }
+        }
+        // x0 is never allocated: reads are zero, writes dropped.
+        let mut by_use: Vec<usize> = (1..32).filter(|&r| uses[r] > 0).collect();
+        by_use.sort_by_key(|&r| std::cmp::Reverse(uses[r]));
+        let mut regs = Regs {
+            host: [None; 32],
+            written: Vec::new(),
+        };
+        for (&guest, &host) in by_use.iter().zip(&HOST_REGS) {
+            regs.host[guest] = Some(host);
+            if writes[guest] {
+                // SAFETY: below 32.
+                regs.written
+                    .push((unsafe { Reg::from_u5(guest as u8) }, host));
+            }
+        }
+        regs
+    }
+
+    /// Where `reg` lives, or `None` for x0.
+    fn rm(&self, reg: Reg) -> Option<Rm> {
+        match reg {
+            Reg::Zero => None,
+            _ => Some(match self.host[reg as usize] {
+                Some(host) => Rm::Reg(host),
+                None => Rm::Mem {
+                    base: RDI,
+                    disp: reg as i8 * 4,
+                },
+            }),
+        }
+    }
+}
+
+/// Compiled code for `ir`, which ends at `end`, checking accesses against
+/// the window's hole if `holes`.
+fn emit(ir: &[(u32, Ir)], end: u32, holes: bool) -> Asm {
+    let regs = Regs::allocate(ir);
+    let mut a = Asm::default();
+
+    // Load `reg` into the scratch register `dst`.
+    let get = |a: &mut Asm, dst: u8, reg: Reg| match regs.rm(reg) {
+        Some(src) => a.mov(dst, src),
+        None => a.alu(Alu::Xor, dst, Rm::Reg(dst)),
+    };
+    // Write the scratch register `src` to `reg`.
+    let put = |a: &mut Asm, reg: Reg, src: u8| {
+        if let Some(dst) = regs.rm(reg) {
+            a.mov_to(dst, src);
+        }
+    };
+    let put_imm = |a: &mut Asm, reg: Reg, imm: u32| {
+        if let Some(dst) = regs.rm(reg) {
+            a.mov_imm(dst, imm);
+        }
+    };
+    // Leave with the next pc in `eax`, having retired `retired`.
+    let exit = |a: &mut Asm, retired: usize| {
+        for &(guest, host) in &regs.written {
+            a.mov_to(
+                Rm::Mem {
+                    base: RDI,
+                    disp: guest as i8 * 4,
+                },
+                host,
+            );
+        }
+        a.mov_imm(Rm::Reg(RDX), retired as u32);
+        a.shift64_imm(Shift::Shl, Rm::Reg(RDX), 32);
+        a.alu64(Alu::Or, RAX, Rm::Reg(RDX));
+        for &r in SAVED.iter().rev() {
+            a.pop(r);
+        }
+        a.ret();
+    };
+
+    for &r in &SAVED {
+        a.push(r);
+    }
+    a.mov64(R15, Rm::Reg(RDI));
+    a.mov64(
+        RDI,
+        Rm::Mem {
+            base: R15,
+            disp: CTX_REGS,
+        },
+    );
+    a.mov64(
+        RSI,
+        Rm::Mem {
+            base: R15,
+            disp: CTX_BASE,
+        },
+    );
+    for guest in 1..32 {
+        if let Some(host) = regs.host[guest] {
+            a.mov(
+                host,
+                Rm::Mem {
+                    base: RDI,
+                    disp: guest as i8 * 4,
+                },
+            );
+        }
+    }
+
+    let mut side_exits = Vec::new();
+    // Exit if the address in `rax` is less than `span` (or, with `Be`, no
+    // more) past `lo`, both context fields.
+    let mut exit_if_in = |a: &mut Asm, [lo, span]: [i8; 2], cond: Cond, pc: u32, i: usize| {
+        a.mov64(RDX, Rm::Reg(RAX));
+        a.alu64(
+            Alu::Sub,
+            RDX,
+            Rm::Mem {
+                base: R15,
+                disp: lo,
+            },
+        );
+        a.alu64(
+            Alu::Cmp,
+            RDX,
+            Rm::Mem {
+                base: R15,
+                disp: span,
+            },
+        );
+        side_exits.push((a.jcc(cond), pc, i));
+    };
+    for (i, &(pc, op)) in ir.iter().enumerate() {
+        match op {
+            Ir::Alu { op, rd, rs1, src } => {
+                emit_alu(&mut a, &regs, op, rs1, src);
+                put(&mut a, rd, RAX);
+            }
+            Ir::Li { rd, imm } => put_imm(&mut a, rd, imm),
+            Ir::Load {
+                rd,
+                rs1,
+                imm,
+                width,
+                signed,
+            } => {
+                get(&mut a, RAX, rs1);
+                if imm != 0 {
+                    a.alu_imm(Alu::Add, Rm::Reg(RAX), imm);
+                }
+                // In the hole: leave for the interpreter to serve.
+                if holes {
+                    exit_if_in(&mut a, [CTX_HOLE_LO, CTX_HOLE_SPAN], Cond::B, pc, i);
+                }
+                let src = Rm::Indexed {
+                    base: RSI,
+                    index: RAX,
+                };
+                match width {
+                    4 => a.mov(RCX, src),
+                    _ => a.movx(RCX, src, width, signed),
+                }
+                put(&mut a, rd, RCX);
+            }
+            Ir::Store {
+                rs1,
+                rs2,
+                imm,
+                width,
+            } => {
+                get(&mut a, RAX, rs1);
+                if imm != 0 {
+                    a.alu_imm(Alu::Add, Rm::Reg(RAX), imm);
+                }
+                // Near cached code (from 7 bytes below it, so a store
+                // straddling in counts), or in the hole: leave for the
+                // interpreter to store.
+                exit_if_in(&mut a, [CTX_CODE_LO, CTX_CODE_SPAN], Cond::Be, pc, i);
+                if holes {
+                    exit_if_in(&mut a, [CTX_HOLE_LO, CTX_HOLE_SPAN], Cond::B, pc, i);
+                }
+                get(&mut a, RCX, rs2);
+                let dst = Rm::Indexed {
+                    base: RSI,
+                    index: RAX,
+                };
+                a.store(dst, RCX, width);
+            }
+            Ir::Branch {
+                cond,
+                rs1,
+                rs2,
+                target,
+            } => {
+                get(&mut a, RAX, rs1);
+                match regs.rm(rs2) {
+                    Some(src) => a.alu(Alu::Cmp, RAX, src),
+                    None => a.test(Rm::Reg(RAX), RAX),
+                }
+                a.mov_imm(Rm::Reg(RAX), end);
+                a.mov_imm(Rm::Reg(RCX), target);
+                a.cmov(cond, RAX, Rm::Reg(RCX));
+            }
+            Ir::Jal { rd, target } => {
+                put_imm(&mut a, rd, end);
+                a.mov_imm(Rm::Reg(RAX), target);
+            }
+            Ir::Jalr { rd, rs1, imm } => {
+                // Read the target before linking: rs1 may be rd.
+                get(&mut a, RAX, rs1);
+                if imm != 0 {
+                    a.alu_imm(Alu::Add, Rm::Reg(RAX), imm);
+                }
+                a.alu_imm(Alu::And, Rm::Reg(RAX), !1);
+                put_imm(&mut a, rd, end);
+            }
+            Ir::Nop => {}
+        }
+    }
+    // Control transfers leave the next pc in eax; anything else falls
+    // through to `end`.
+    if !matches!(
+        ir.last(),
+        Some((_, Ir::Branch { .. } | Ir::Jal { .. } | Ir::Jalr { .. }))
+    ) {
+        a.mov_imm(Rm::Reg(RAX), end);
+    }
+    exit(&mut a, ir.len());
+    for (label, pc, retired) in side_exits {
+        a.bind(label);
+        a.mov_imm(Rm::Reg(RAX), pc);
+        exit(&mut a, retired);
+    }
+    a
+}
+
+/// `eax = rs1 op src`.
+fn emit_alu(a: &mut Asm, regs: &Regs, op: AluOp, rs1: Reg, src: Src) {
+    let get = |a: &mut Asm, dst: u8, reg: Reg| match regs.rm(reg) {
+        Some(src) => a.mov(dst, src),
+        None => a.alu(Alu::Xor, dst, Rm::Reg(dst)),
+    };
+    // The second operand as an immediate or in `ecx`.
+    let src_imm = match src {
+        Src::Imm(imm) => Some(imm),
+        Src::Reg(Reg::Zero) => Some(0),
+        Src::Reg(_) => None,
+    };
+    let load_src = |a: &mut Asm| match src {
+        Src::Reg(reg) => get(a, RCX, reg),
+        Src::Imm(imm) => a.mov_imm(Rm::Reg(RCX), imm as u32),
+    };
+    let x86 = match op {
+        AluOp::Add => Some(Alu::Add),
+        AluOp::Sub => Some(Alu::Sub),
+        AluOp::And => Some(Alu::And),
+        AluOp::Or => Some(Alu::Or),
+        AluOp::Xor => Some(Alu::Xor),
+        _ => None,
+    };
+    if let Some(x86) = x86 {
+        get(a, RAX, rs1);
+        match (src_imm, src) {
+            (Some(imm), _) => a.alu_imm(x86, Rm::Reg(RAX), imm),
+            (None, Src::Reg(reg)) => a.alu(x86, RAX, regs.rm(reg).unwrap()),
+            (None, Src::Imm(_)) => unreachable!(),
+        }
+        return;
+    }
+    match op {
+        AluOp::Sll | AluOp::Srl | AluOp::Sra => {
+            let shift = match op {
+                AluOp::Sll => Shift::Shl,
+                AluOp::Srl => Shift::Shr,
+                _ => Shift::Sar,
+            };
+            get(a, RAX, rs1);
+            match src_imm {
+                // x86 masks register counts to 5 bits, as rv32 does.
+                Some(imm) => a.shift_imm(shift, Rm::Reg(RAX), imm as u8 & 31),
+                None => {
+                    load_src(a);
+                    a.shift_cl(shift, Rm::Reg(RAX));
+                }
+            }
+        }
+        AluOp::Slt | AluOp::Sltu => {
+            // Cleared before the compare: xor sets flags.
+            a.alu(Alu::Xor, RDX, Rm::Reg(RDX));
+            get(a, RAX, rs1);
+            match (src_imm, src) {
+                (Some(imm), _) => a.alu_imm(Alu::Cmp, Rm::Reg(RAX), imm),
+                (None, Src::Reg(reg)) => a.alu(Alu::Cmp, RAX, regs.rm(reg).unwrap()),
+                (None, Src::Imm(_)) => unreachable!(),
+            }
+            let cond = if let AluOp::Slt = op {
+                Cond::L
+            } else {
+                Cond::B
+            };
+            a.setcc(cond, RDX);
+            a.mov(RAX, Rm::Reg(RDX));
+        }
+        AluOp::Mul => {
+            get(a, RAX, rs1);
+            load_src(a);
+            a.imul(RAX, Rm::Reg(RCX));
+        }
+        AluOp::Mulh | AluOp::Mulhsu | AluOp::Mulhu => {
+            // 32-bit moves zero-extend; the signed operands are widened.
+            get(a, RAX, rs1);
+            load_src(a);
+            if !matches!(op, AluOp::Mulhu) {
+                a.movsxd(RAX, Rm::Reg(RAX));
+            }
+            if let AluOp::Mulh = op {
+                a.movsxd(RCX, Rm::Reg(RCX));
+            }
+            a.imul64(RAX, Rm::Reg(RCX));
+            a.shift64_imm(Shift::Shr, Rm::Reg(RAX), 32);
+        }
+        AluOp::Divu | AluOp::Remu => {
+            get(a, RAX, rs1);
+            load_src(a);
+            a.test(Rm::Reg(RCX), RCX);
+            let by_zero = a.jcc(Cond::E);
+            a.alu(Alu::Xor, RDX, Rm::Reg(RDX));
+            a.div(Rm::Reg(RCX), false);
+            if let AluOp::Remu = op {
+                a.mov(RAX, Rm::Reg(RDX));
+            }
+            let done = a.jmp();
+            a.bind(by_zero);
+            // Division by zero: all ones, or the dividend (already in eax).
+            if let AluOp::Divu = op {
+                a.mov_imm(Rm::Reg(RAX), u32::MAX);
+            }
+            a.bind(done);
+        }
+        AluOp::Div | AluOp::Rem => {
+            get(a, RAX, rs1);
+            load_src(a);
+            a.test(Rm::Reg(RCX), RCX);
+            let by_zero = a.jcc(Cond::E);
+            a.alu_imm(Alu::Cmp, Rm::Reg(RCX), -1);
+            let divide = a.jcc(Cond::Ne);
+            a.alu_imm(Alu::Cmp, Rm::Reg(RAX), i32::MIN);
+            let overflow = a.jcc(Cond::E);
+            a.bind(divide);
+            a.cdq();
+            a.div(Rm::Reg(RCX), true);
+            if let AluOp::Rem = op {
+                a.mov(RAX, Rm::Reg(RDX));
+            }
+            let done = a.jmp();
+            a.bind(by_zero);
+            // Division by zero: -1, or the dividend (already in eax).
+            if let AluOp::Div = op {
+                a.mov_imm(Rm::Reg(RAX), u32::MAX);
+            }
+            let done_by_zero = a.jmp();
+            a.bind(overflow);
+            // i32::MIN / -1: the dividend (already in eax), remainder 0.
+            if let AluOp::Rem = op {
+                a.alu(Alu::Xor, RAX, Rm::Reg(RAX));
+            }
+            a.bind(done);
+            a.bind(done_by_zero);
+        }
+        _ => unreachable!(),
+    }
+}
@@ -6,13 +6,20 @@
 //! exec modules ([`exec32`]/[`exec64`]) -- the decoded enum types differ per
 //! ISA, so each base gets its own match, with arms grouped by extension.
 //! [`Execute`] wires the right exec into the width so `Machine` stays fully
-//! generic.
+//! generic. Run loops execute pre-decoded basic blocks ([`blocks`]) cached
+//! on the hart, threaded through per-op handlers and chained to each other.
+//! On x86_64 Linux hosts, rv32 harts can also compile hot blocks to native
+//! code (see [`Backend`]).
 
+mod blocks;
 mod exec32;
 mod exec64;
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+mod jit;
 
-use std::fmt;
+use std::{convert::Infallible, fmt};
 
+use self::blocks::BlockCache;
 use crate::{
     error::{MachineError, MemoryAccess, MemoryError},
     machine::{Kernel, StepResult},
@@ -99,14 +106,46 @@ pub struct Hart<X: Xlen> {
     regs: [X::U; 32],
     pub pc: X::U,
     pub inst_count: u64,
+    /// Instructions left before the kernel's [`Kernel::out_of_fuel`] runs;
+    /// `None` is unlimited.
+    pub fuel: Option<u64>,
     /// Atomic memory reservation set on this hart
     pub amo_rsv: Option<X::U>,
     csrs: [X::U; 4096],
+    blocks: BlockCache<X>,
+    /// How [`Hart::run`] executes.
+    pub backend: Backend,
+    /// Interpreted entries after which [`Backend::Jit`] compiles a block
+    /// (at least one).
+    pub jit_threshold: u32,
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    jit: jit::Jit,
+}
+
+/// How a hart runs guest code.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// Pre-decoded blocks, run through per-op handlers.
+    #[default]
+    Interpreter,
+    /// The interpreter, with hot blocks compiled to x86-64. Only rv32 harts
+    /// on x86_64 Linux hosts, with memories spanning the whole 32-bit space
+    /// (as [`crate::memory::Memory32`] does), compile; anything else runs
+    /// as [`Backend::Interpreter`].
+    Jit,
 }
 
 pub type Hart32 = Hart<X32>;
 pub type Hart64 = Hart<X64>;
 
+// Machines move between threads with their harts (see `crate::sched`), and
+// are inspected from several at once (see `Machine::inspect`).
+const _: () = {
+    const fn shareable<T: Send + Sync>() {}
+    shareable::<Hart32>();
+    shareable::<Hart64>();
+};
+
 use riscv_inst::Reg;
 
 impl<X: Xlen> Hart<X> {
@@ -115,8 +154,36 @@ impl<X: Xlen> Hart<X> {
             regs: [X::U::default(); 32],
             pc: X::U::default(),
             inst_count: 0,
+            fuel: None,
             amo_rsv: None,
             csrs: [X::U::default(); 4096],
+            blocks: BlockCache::new(),
+            backend: Backend::default(),
+            jit_threshold: 64,
+            #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+            jit: jit::Jit::new(),
+        }
+    }
+
+    /// Drop all pre-decoded code, as `fence.i` does. Embedders that write
+    /// guest code through the memory rather than the hart call this before
+    /// running it again.
+    pub fn flush_icache(&mut self) {
+        self.blocks.clear();
+    }
+
+    /// Drop pre-decoded code overlapping `[addr, addr + len)`, for kernels
+    /// that map, unmap or rewrite guest code.
+    pub fn invalidate_code(&mut self, addr: u64, len: u64) {
+        self.blocks.invalidate(addr, len);
+    }
+
+    /// Count `n` retired instructions against `inst_count` and the fuel.
+    #[inline(always)]
+    pub fn retire(&mut self, n: u64) {
+        self.inst_count += n;
+        if let Some(fuel) = &mut self.fuel {
+            *fuel = fuel.saturating_sub(n);
         }
     }
 
@@ -157,13 +224,18 @@ impl<X: Execute> Hart<X> {
         X::step(self, mem, kernel)
     }
 
-    /// Run until the kernel halts the machine or an error occurs.
+    /// Run until the kernel halts the machine (`Halt`), parks it on a
+    /// syscall (`Yield`), or an error occurs. Running out of fuel hands
+    /// control to the kernel, which decides which of these it is.
     pub fn run<K: Kernel<Xlen = X>>(
         &mut self,
         mem: &mut K::Memory,
         kernel: &mut K,
-    ) -> Result<(), MachineError<K::Error>> {
-        X::run(self, mem, kernel)
+    ) -> Result<StepResult, MachineError<K::Error>> {
+        match self.backend {
+            Backend::Interpreter => X::run(self, mem, kernel),
+            Backend::Jit => X::run_jit(self, mem, kernel),
+        }
     }
 }
 
@@ -173,14 +245,46 @@ impl<X: Xlen> Default for Hart<X> {
     }
 }
 
+/// `misa`-style mask of single-letter extensions: bit `n` is letter
+/// `'a' + n`. This is also the layout of Linux's `AT_HWCAP` on RISC-V.
+pub const fn misa_extensions(letters: &[u8]) -> u32 {
+    let mut mask = 0;
+    let mut i = 0;
+    while i < letters.len() {
+        assert!(letters[i].is_ascii_lowercase(), "extension letters are a-z");
+        mask |= 1 << (letters[i] - b'a');
+        i += 1;
+    }
+    mask
+}
+
 /// Instruction execution for a width: implemented per base ISA in the exec
 /// modules, dispatched statically through `Kernel::Xlen`.
 pub trait Execute: Xlen {
+    /// Single-letter extensions this implementation executes (see
+    /// [`misa_extensions`]), for kernels that report CPU capabilities.
+    const EXTENSIONS: u32;
+
+    /// Whether misaligned scalar loads/stores run at full speed (rather
+    /// than trapping or being emulated). Guests use this to pick between
+    /// word-at-a-time and byte-wise string/memcpy routines.
+    const MISALIGNED_FAST: bool;
+
     fn run<K: Kernel<Xlen = Self>>(
         hart: &mut Hart<Self>,
         mem: &mut K::Memory,
         kernel: &mut K,
-    ) -> Result<(), MachineError<K::Error>>;
+    ) -> Result<StepResult, MachineError<K::Error>>;
+
+    /// [`Self::run`] for [`Backend::Jit`]: the interpreter where there is
+    /// no compiler.
+    fn run_jit<K: Kernel<Xlen = Self>>(
+        hart: &mut Hart<Self>,
+        mem: &mut K::Memory,
+        kernel: &mut K,
+    ) -> Result<StepResult, MachineError<K::Error>> {
+        Self::run(hart, mem, kernel)
+    }
 
     fn step<K: Kernel<Xlen = Self>>(
         hart: &mut Hart<Self>,
@@ -211,17 +315,53 @@ pub(crate) enum Exec {
     Ebreak,
     /// Execution faulted; the error is in the caller's error slot.
     Error,
+    /// A memory access missed a window whose memory serves misses (see
+    /// [`crate::memory::MemView`]); redo the instruction with `serve_miss`.
+    /// pc is not advanced.
+    Miss,
+}
+
+/// An exec module's handler tables: its `handler` for each discriminant,
+/// for windows without a hole and with one.
+macro_rules! handlers {
+    ($($disc:literal),* $(,)?) => {
+        [[$(handler::<$disc, false>),*], [$(handler::<$disc, true>),*]]
+    };
+}
+pub(crate) use handlers;
+
+/// Length in bytes of the instruction whose low halfword is `inst`'s.
+#[inline(always)]
+pub(crate) fn inst_size(inst: u32) -> u8 {
+    if inst & 0b11 == 0b11 {
+        4
+    } else {
+        2
+    }
+}
+
+/// A store hit the code range: drop the blocks it may have rewritten.
+/// Cold: stores land on code pages only in self-modifying or JIT code, and
+/// in data sharing a page with it.
+#[cold]
+#[inline(never)]
+pub(crate) fn code_written<X: Xlen>(hart: &mut Hart<X>, addr: u64, len: u64) {
+    hart.blocks.invalidate(addr, len);
 }
 
-/// Take the error deposited by an [`Exec::Error`] return. Cold: only ever
-/// reached on the (terminal) error path.
+/// Take the error deposited by an [`Exec::Error`] return, as the kernel's
+/// error type (instructions never fail with a kernel error). Cold: only
+/// ever reached on the (terminal) error path.
 #[cold]
 #[inline(never)]
 pub(crate) fn take_err<E: std::error::Error>(
-    slot: &mut Option<MachineError<E>>,
+    slot: &mut Option<MachineError<Infallible>>,
 ) -> MachineError<E> {
     match slot.take() {
-        Some(e) => e,
+        Some(MachineError::Hart(e)) => MachineError::Hart(e),
+        Some(MachineError::Memory(e)) => MachineError::Memory(e),
+        Some(MachineError::Kernel(never)) => match never {},
+        Some(MachineError::Panicked(msg)) => MachineError::Panicked(msg),
         // exec_op_at fills the slot before returning Exec::Error.
         None => unreachable!("Exec::Error without a deposited error"),
     }
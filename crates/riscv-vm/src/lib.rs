@@ -2,5 +2,6 @@ pub mod error;
 pub mod hart;
 pub mod machine;
 pub mod memory;
+pub mod sched;
 
 pub use riscv_inst;
@@ -1,12 +1,13 @@
-use std::error::Error;
+use std::{error::Error, fmt, task::Waker, time::Instant};
 
 use crate::{
-    error::MachineError,
+    error::{MachineError, MemoryError},
     hart::{Execute, Hart, Xlen},
     memory::Memory,
 };
 
-/// The OS personality of a machine: gets control on ecall/ebreak.
+/// The OS personality of a machine: gets control on ecall/ebreak and when
+/// the hart runs out of fuel.
 ///
 /// `Xlen` fixes the register width and (through the `Addr` equality bound)
 /// makes it impossible to pair a hart with a memory of the wrong width.
@@ -28,22 +29,118 @@ pub trait Kernel {
     ) -> Result<StepResult, MachineError<Self::Error>> {
         Ok(StepResult::Halt)
     }
+
+    /// The hart's [`Hart::fuel`] ran out; pc is on the next instruction.
+    /// Returning `Ok` resumes execution, so refill the fuel first.
+    fn out_of_fuel(
+        &mut self,
+        _hart: &mut Hart<Self::Xlen>,
+        _mem: &mut Self::Memory,
+    ) -> Result<StepResult, MachineError<Self::Error>> {
+        Ok(StepResult::Halt)
+    }
+
+    /// Why the kernel ended the guest (on a `Halt` or a kernel error), if
+    /// it did. `None` leaves [`Machine`] to work it out, and makes a kernel
+    /// error resumable.
+    fn termination(&self) -> Option<TerminationReason> {
+        None
+    }
+
+    /// The most guest memory in use at any point, if the kernel tracks it.
+    fn peak_memory(&self) -> Option<u64> {
+        None
+    }
+
+    /// While the machine is blocked ([`StepResult::Yield`]), when the
+    /// parked call times out, if it waits with a timeout. Until then only
+    /// [`Kernel::wake_on`]'s waker can usefully have it retried.
+    fn blocked_until(&self) -> Option<Instant> {
+        None
+    }
+
+    /// While the machine is blocked, have `waker` woken once something
+    /// outside the machine (another machine, the embedder) may have let the
+    /// parked call go on. Returns whether that and [`Kernel::blocked_until`]
+    /// cover everything that can; if not, whoever runs the machine has to
+    /// retry the call now and then.
+    fn wake_on(&mut self, _waker: &Waker) -> bool {
+        false
+    }
 }
 
 pub enum StepResult {
     Ok,
     Halt,
+    /// The syscall cannot complete yet (e.g. it waits for I/O). pc stays on
+    /// the ecall, so resuming the machine re-issues it.
+    Yield,
+}
+
+/// Why a machine halted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TerminationReason {
+    /// The guest's thread exited (`exit`) with this status.
+    Exited(i32),
+    /// The guest's process exited (`exit_group`) with this status.
+    ExitGroup(i32),
+    /// The kernel killed the guest with this signal.
+    Signaled(i32),
+    /// The hart ran out of [`Hart::fuel`].
+    FuelExhausted,
+    /// The kernel's syscall policy killed the guest at this call.
+    PolicyViolation(String),
+    /// The instruction at `pc` faulted, on `addr` for a memory access.
+    Fault { pc: u64, addr: Option<u64> },
+    /// An `ebreak` at `pc` the kernel did not handle.
+    Ebreak { pc: u64 },
+}
+
+impl fmt::Display for TerminationReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TerminationReason::Exited(code) => write!(f, "exited with status {code}"),
+            TerminationReason::ExitGroup(code) => {
+                write!(f, "exit_group with status {code}")
+            }
+            TerminationReason::Signaled(signal) => write!(f, "killed by signal {signal}"),
+            TerminationReason::FuelExhausted => write!(f, "ran out of fuel"),
+            TerminationReason::PolicyViolation(call) => {
+                write!(f, "killed by syscall policy at {call}")
+            }
+            TerminationReason::Fault { pc, addr: None } => write!(f, "fault at pc {pc:#x}"),
+            TerminationReason::Fault {
+                pc,
+                addr: Some(addr),
+            } => write!(f, "fault at pc {pc:#x} accessing {addr:#x}"),
+            TerminationReason::Ebreak { pc } => write!(f, "unhandled ebreak at pc {pc:#x}"),
+        }
+    }
+}
+
+/// What a run amounted to; see [`Machine::summary`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunSummary {
+    /// Instructions retired.
+    pub instructions: u64,
+    /// See [`Kernel::peak_memory`].
+    pub peak_memory: Option<u64>,
+    /// `None` until the machine halts (and for halts no one explained).
+    pub termination: Option<TerminationReason>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MachineState {
     Running,
+    /// Parked on a syscall that yielded; `run`/`step` retry it.
+    Blocked,
     Halted,
 }
 
 impl MachineState {
+    /// Not yet halted (possibly blocked).
     pub fn is_running(self) -> bool {
-        self == MachineState::Running
+        self != MachineState::Halted
     }
 }
 
@@ -52,6 +149,7 @@ pub struct Machine<K: Kernel> {
     pub mem: K::Memory,
     pub kernel: K,
     pub state: MachineState,
+    termination: Option<TerminationReason>,
 }
 
 impl<K: Kernel> Machine<K>
@@ -70,25 +168,262 @@ impl<K: Kernel> Machine<K> {
             mem,
             kernel,
             state: MachineState::Running,
+            termination: None,
         }
     }
 
+    /// Execute one instruction. Errors that end the guest (see
+    /// [`Machine::run`]) halt the machine.
     pub fn step(&mut self) -> Result<(), MachineError<K::Error>> {
-        match self.hart.step(&mut self.mem, &mut self.kernel)? {
-            StepResult::Ok => Ok(()),
-            StepResult::Halt => {
-                self.state = MachineState::Halted;
-                Ok(())
-            }
-        }
+        let res = self.hart.step(&mut self.mem, &mut self.kernel);
+        self.state = match self.settle(res)? {
+            StepResult::Ok => MachineState::Running,
+            StepResult::Halt => MachineState::Halted,
+            StepResult::Yield => MachineState::Blocked,
+        };
+        Ok(())
     }
 
+    /// Run until the machine halts or blocks. A blocked machine resumes
+    /// (retrying its pending syscall) on the next call.
+    ///
+    /// Faults, and kernel errors the kernel gives a
+    /// [`Kernel::termination`] for, are still returned but also halt the
+    /// machine; see [`Machine::termination`].
     pub fn run(&mut self) -> Result<(), MachineError<K::Error>> {
-        if self.state == MachineState::Running {
-            self.hart.run(&mut self.mem, &mut self.kernel)?;
-            self.state = MachineState::Halted;
+        if self.state.is_running() {
+            let res = self.hart.run(&mut self.mem, &mut self.kernel);
+            self.state = match self.settle(res)? {
+                StepResult::Yield => MachineState::Blocked,
+                _ => MachineState::Halted,
+            };
         }
 
         Ok(())
     }
+
+    /// [`Machine::run`] for at most `budget` more instructions. If the
+    /// budget runs out first, the machine is left running, to resume on the
+    /// next call. The kernel's own fuel still applies within the budget.
+    pub fn run_for(&mut self, budget: u64) -> Result<(), MachineError<K::Error>> {
+        if self.state.is_running() && budget > 0 {
+            let mut slice = Slice::new(&mut self.kernel, &mut self.hart, budget);
+            let res = self.hart.run(&mut self.mem, &mut slice);
+            let preempted = slice.finish(&mut self.hart);
+            self.state = match self.settle(res)? {
+                StepResult::Yield if preempted => MachineState::Running,
+                StepResult::Yield => MachineState::Blocked,
+                _ => MachineState::Halted,
+            };
+        }
+
+        Ok(())
+    }
+
+    /// Record why the machine is halting, if it is.
+    fn settle(
+        &mut self,
+        res: Result<StepResult, MachineError<K::Error>>,
+    ) -> Result<StepResult, MachineError<K::Error>> {
+        let pc = <K::Xlen as Xlen>::to_u64(self.hart.pc);
+        let reason = match &res {
+            Ok(StepResult::Halt) => self.kernel.termination().or_else(|| {
+                if self.hart.fuel == Some(0) {
+                    Some(TerminationReason::FuelExhausted)
+                } else if self.at_ebreak() {
+                    Some(TerminationReason::Ebreak { pc })
+                } else {
+                    None
+                }
+            }),
+            Ok(_) => return res,
+            Err(MachineError::Hart(_)) => Some(TerminationReason::Fault { pc, addr: None }),
+            Err(MachineError::Memory(e)) => {
+                let addr = match **e {
+                    MemoryError::UnalignedMemoryAccess { addr, .. }
+                    | MemoryError::Fault { addr, .. }
+                    | MemoryError::NoHostRange { addr, .. }
+                    | MemoryError::OverflowMemoryAccess { addr, .. } => addr,
+                };
+                Some(TerminationReason::Fault {
+                    pc,
+                    addr: Some(addr),
+                })
+            }
+            Err(MachineError::Kernel(_)) => self.kernel.termination(),
+            Err(MachineError::Panicked(_)) => None,
+        };
+        if reason.is_some() {
+            self.state = MachineState::Halted;
+            self.termination = reason;
+        }
+        res
+    }
+
+    /// Whether pc is on an `ebreak` (a trap leaves pc on its instruction).
+    fn at_ebreak(&self) -> bool {
+        const EBREAK: u32 = 0x0010_0073;
+        const C_EBREAK: u16 = 0x9002;
+
+        // A compressed one may end just below a device, where the full
+        // word does not load.
+        match self.mem.load::<u16>(self.hart.pc) {
+            Ok(C_EBREAK) => true,
+            Ok(_) => matches!(self.mem.load::<u32>(self.hart.pc), Ok(EBREAK)),
+            Err(_) => false,
+        }
+    }
+
+    /// Why the machine halted, once it has.
+    pub fn termination(&self) -> Option<&TerminationReason> {
+        self.termination.as_ref()
+    }
+
+    /// A read-only view of the machine, which (unlike `&Machine`, whose
+    /// kernel need not be `Sync`) may be shared across threads while the
+    /// machine is paused.
+    pub fn inspect(&self) -> Inspect<'_, K> {
+        Inspect {
+            hart: &self.hart,
+            mem: &self.mem,
+            state: self.state,
+            termination: self.termination.as_ref(),
+        }
+    }
+
+    pub fn summary(&self) -> RunSummary {
+        RunSummary {
+            instructions: self.hart.inst_count,
+            peak_memory: self.kernel.peak_memory(),
+            termination: self.termination.clone(),
+        }
+    }
+}
+
+/// A machine without its kernel; see [`Machine::inspect`]. `Sync` whenever
+/// the memory is, as the arenas are.
+pub struct Inspect<'m, K: Kernel> {
+    pub hart: &'m Hart<K::Xlen>,
+    pub mem: &'m K::Memory,
+    pub state: MachineState,
+    pub termination: Option<&'m TerminationReason>,
+}
+
+impl<K: Kernel> Clone for Inspect<'_, K> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<K: Kernel> Copy for Inspect<'_, K> {}
+
+/// The kernel as [`Machine::run_for`] lends it to the hart: the hart's fuel
+/// is what is left of the budget, capped by the kernel's own fuel, which is
+/// swapped back in around every call into the kernel.
+struct Slice<'k, K: Kernel> {
+    kernel: &'k mut K,
+    /// The kernel's fuel, as of the last call into it.
+    fuel: Option<u64>,
+    /// Budget left, as of the last call into the kernel.
+    left: u64,
+    /// The hart's fuel as last set here.
+    given: u64,
+    /// The budget, rather than the kernel, ended the run.
+    preempted: bool,
+}
+
+impl<'k, K: Kernel> Slice<'k, K> {
+    fn new(kernel: &'k mut K, hart: &mut Hart<K::Xlen>, budget: u64) -> Self {
+        let mut slice = Self {
+            kernel,
+            fuel: hart.fuel,
+            left: budget,
+            given: 0,
+            preempted: false,
+        };
+        slice.give(hart);
+        slice
+    }
+
+    fn give(&mut self, hart: &mut Hart<K::Xlen>) {
+        self.given = self.left.min(self.fuel.unwrap_or(u64::MAX));
+        hart.fuel = Some(self.given);
+    }
+
+    /// Charge what the hart retired since [`Slice::give`] to both budgets,
+    /// and hand the hart back the kernel's fuel.
+    fn take(&mut self, hart: &mut Hart<K::Xlen>) {
+        let used = self.given - hart.fuel.unwrap_or(0);
+        self.left -= used;
+        self.fuel = self.fuel.map(|fuel| fuel - used);
+        hart.fuel = self.fuel;
+    }
+
+    /// Settle up once the hart returns; whether the budget ran out.
+    fn finish(mut self, hart: &mut Hart<K::Xlen>) -> bool {
+        self.take(hart);
+        self.preempted
+    }
+
+    /// Run a kernel call with the kernel's fuel in the hart.
+    fn enter(
+        &mut self,
+        hart: &mut Hart<K::Xlen>,
+        call: impl FnOnce(&mut K, &mut Hart<K::Xlen>) -> Result<StepResult, MachineError<K::Error>>,
+    ) -> Result<StepResult, MachineError<K::Error>> {
+        self.take(hart);
+        let res = call(self.kernel, hart);
+        self.fuel = hart.fuel;
+        self.give(hart);
+        res
+    }
+}
+
+impl<K: Kernel> Kernel for Slice<'_, K> {
+    type Xlen = K::Xlen;
+    type Memory = K::Memory;
+    type Error = K::Error;
+
+    fn syscall(
+        &mut self,
+        hart: &mut Hart<Self::Xlen>,
+        mem: &mut Self::Memory,
+    ) -> Result<StepResult, MachineError<Self::Error>> {
+        self.enter(hart, |kernel, hart| kernel.syscall(hart, mem))
+    }
+
+    fn ebreak(
+        &mut self,
+        hart: &mut Hart<Self::Xlen>,
+        mem: &mut Self::Memory,
+    ) -> Result<StepResult, MachineError<Self::Error>> {
+        self.enter(hart, |kernel, hart| kernel.ebreak(hart, mem))
+    }
+
+    fn out_of_fuel(
+        &mut self,
+        hart: &mut Hart<Self::Xlen>,
+        mem: &mut Self::Memory,
+    ) -> Result<StepResult, MachineError<Self::Error>> {
+        self.take(hart);
+        if self.fuel != Some(0) {
+            // Stop where the budget ran out, as if the machine yielded;
+            // fuel left for the next budget.
+            self.preempted = true;
+            self.give(hart);
+            return Ok(StepResult::Yield);
+        }
+        let res = self.kernel.out_of_fuel(hart, mem);
+        self.fuel = hart.fuel;
+        self.give(hart);
+        res
+    }
+
+    fn termination(&self) -> Option<TerminationReason> {
+        self.kernel.termination()
+    }
+
+    fn peak_memory(&self) -> Option<u64> {
+        self.kernel.peak_memory()
+    }
 }
@@ -1,8 +1,22 @@
 mod _sealed {
     /// A trait to ensure that only Primitives can be loaded/stored.
-    pub trait Primitive {}
+    pub trait Primitive: Copy {
+        /// The value widened to a `u64`, as `as` widens it.
+        fn to_bits(self) -> u64;
+        /// The inverse of `to_bits`: the low bytes of `bits`.
+        fn from_bits(bits: u64) -> Self;
+    }
     macro_rules! impl_primitive {
-        ($($t:ty),*) => {$(impl Primitive for $t {})*}
+        ($($t:ty),*) => {$(impl Primitive for $t {
+            #[inline(always)]
+            fn to_bits(self) -> u64 {
+                self as u64
+            }
+            #[inline(always)]
+            fn from_bits(bits: u64) -> Self {
+                bits as $t
+            }
+        })*}
     }
     impl_primitive!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
 }
@@ -16,9 +30,9 @@ pub use _sealed::Primitive;
 /// enums, references) or padding makes [`Memory::slice`]/[`Memory::copy_to`]
 /// unsound.
 pub unsafe trait Pod: Copy {}
-unsafe impl<T: Primitive + Copy> Pod for T {}
+unsafe impl<T: Primitive> Pod for T {}
 
-use std::marker::PhantomData;
+use std::{any::Any, borrow::Cow, marker::PhantomData, ptr::NonNull};
 
 use crate::error::{MemoryAccess, MemoryError};
 
@@ -60,40 +74,214 @@ pub struct Fault;
 /// [`Memory::view`]'s `&mut self`. The phantom only ties `'m` to the
 /// type, hence `&'m ()`. Run loops release the borrow across kernel calls
 /// and re-take it after.
+///
+/// Accesses at or beyond `mapped`, or in the header's [`Hole`], are misses.
+/// For the arenas a miss is a fault; a memory without one flat window (see
+/// [`Paged`], [`Mmio`]) serves them instead, through a [`Header`] that
+/// `view` writes just below `ptr`. The view stays two words, passed in
+/// registers to every handler, and `load` and `store` still only fault: a
+/// handler that faults checks [`MemView::misses`] and leaves its block, and
+/// the run loop redoes the instruction out of line with
+/// `serve_load`/`serve_store`, so no handler keeps state live across a call.
 #[derive(Clone, Copy)]
 pub struct MemView<'m> {
-    ptr: *mut u8,
-    /// Exclusive access limit; accesses at or beyond it fault.
-    mapped: u64,
+    pub(crate) ptr: *mut u8,
+    /// Exclusive limit of the flat window; accesses at or beyond it miss.
+    pub(crate) mapped: u64,
     _mem: PhantomData<&'m ()>,
 }
 
 impl MemView<'_> {
     #[inline(always)]
     pub fn load<T: Primitive>(self, addr: u64) -> Result<T, Fault> {
-        if addr >= self.mapped {
+        self.load_in::<T, true>(addr)
+    }
+
+    #[inline(always)]
+    pub fn store<T: Primitive>(self, addr: u64, val: T) -> Result<(), Fault> {
+        self.store_in::<T, true>(addr, val)
+    }
+
+    /// `load`, minding the hole only if `HOLES`: handlers decoded for a
+    /// view without one skip the check (see [`MemView::has_hole`]).
+    #[inline(always)]
+    pub(crate) fn load_in<T: Primitive, const HOLES: bool>(self, addr: u64) -> Result<T, Fault> {
+        if addr >= self.mapped || (HOLES && self.hole().covers(addr)) {
             return Err(Fault);
         }
-        // Safety: addr < mapped, and a primitive's overhang past `mapped`
-        // lands in the guard page.
-        Ok(unsafe { (self.ptr.add(addr as usize) as *const T).read_unaligned() })
+        Ok(unsafe { self.flat_load(addr) })
     }
 
+    /// `store`, minding the hole only if `HOLES`, as [`MemView::load_in`].
     #[inline(always)]
-    pub fn store<T: Primitive>(self, addr: u64, val: T) -> Result<(), Fault> {
-        if addr >= self.mapped {
+    pub(crate) fn store_in<T: Primitive, const HOLES: bool>(
+        self,
+        addr: u64,
+        val: T,
+    ) -> Result<(), Fault> {
+        if addr >= self.mapped || (HOLES && self.hole().covers(addr)) {
             return Err(Fault);
         }
-        // Safety: see `load`.
-        unsafe { (self.ptr.add(addr as usize) as *mut T).write_unaligned(val) };
+        unsafe { self.flat_store(addr, val) };
         Ok(())
     }
+
+    /// `load`, hole or not.
+    ///
+    /// # Safety
+    /// `addr` must be below `mapped`: a primitive's overhang past it lands
+    /// in the guard page.
+    #[inline(always)]
+    pub(crate) unsafe fn flat_load<T: Primitive>(self, addr: u64) -> T {
+        unsafe { (self.ptr.add(addr as usize) as *const T).read_unaligned() }
+    }
+
+    /// `store`, hole or not.
+    ///
+    /// # Safety
+    /// As for [`MemView::flat_load`].
+    #[inline(always)]
+    pub(crate) unsafe fn flat_store<T: Primitive>(self, addr: u64, val: T) {
+        unsafe { (self.ptr.add(addr as usize) as *mut T).write_unaligned(val) }
+    }
+
+    /// The hole in the window: accesses in it miss although below `mapped`.
+    #[inline(always)]
+    pub(crate) fn hole(self) -> Hole {
+        // Safety: every `view` writes the header below its base.
+        unsafe { (*self.ptr.cast::<Header>().sub(1)).hole }
+    }
+
+    /// Whether the window has a hole. Fixed for a memory's life, so code
+    /// decoded for one view holds for the next.
+    #[inline(always)]
+    pub(crate) fn has_hole(self) -> bool {
+        self.hole().span != 0
+    }
+
+    /// Whether the memory serves misses, rather than faulting them.
+    #[inline(always)]
+    pub(crate) fn misses(self) -> bool {
+        // Safety: as in `hole`.
+        unsafe { (*self.ptr.cast::<Header>().sub(1)).miss.is_some() }
+    }
+
+    /// `load`, with a miss served where the memory serves it.
+    pub(crate) fn serve_load<T: Primitive>(self, addr: u64) -> Result<T, Fault> {
+        if let Ok(v) = self.load(addr) {
+            return Ok(v);
+        }
+        match unsafe { self.miss() } {
+            Some(miss) => miss.load(addr, size_of::<T>()).map(T::from_bits),
+            None => Err(Fault),
+        }
+    }
+
+    /// `store`, with a miss served where the memory serves it.
+    pub(crate) fn serve_store<T: Primitive>(self, addr: u64, val: T) -> Result<(), Fault> {
+        if self.store(addr, val).is_ok() {
+            return Ok(());
+        }
+        match unsafe { self.miss() } {
+            Some(miss) => miss.store(addr, size_of::<T>(), val.to_bits()),
+            None => Err(Fault),
+        }
+    }
+
+    /// An instruction fetch: `serve_load`, but from memory only, never a
+    /// device.
+    pub(crate) fn fetch(self, addr: u64) -> Result<u32, Fault> {
+        if let Ok(v) = self.load(addr) {
+            return Ok(v);
+        }
+        match unsafe { self.miss() } {
+            Some(miss) => miss.fetch(addr).map(|bits| bits as u32),
+            None => Err(Fault),
+        }
+    }
+
+    /// The miss handler `view` installed, if any.
+    ///
+    /// # Safety
+    /// The returned reference aliases nothing only while no other miss is
+    /// being served, which holds as views are neither `Send` nor reentrant.
+    unsafe fn miss<'a>(self) -> Option<&'a mut dyn Miss> {
+        // Safety: every `view` writes the header below its base, and the
+        // view's borrow keeps the handler alive and otherwise unused.
+        unsafe {
+            (*self.ptr.cast::<Header>().sub(1))
+                .miss
+                .map(|mut m| m.as_mut())
+        }
+    }
+}
+
+/// Serves the accesses a [`MemView`] misses, `size` bytes at `addr` (a
+/// primitive's width, so at most 8, carried in a `u64`).
+pub(crate) trait Miss {
+    fn load(&mut self, addr: u64, size: usize) -> Result<u64, Fault>;
+    fn store(&mut self, addr: u64, size: usize, val: u64) -> Result<(), Fault>;
+
+    /// A 4-byte instruction fetch, which only memory serves.
+    fn fetch(&mut self, addr: u64) -> Result<u64, Fault> {
+        self.load(addr, 4)
+    }
+}
+
+/// What sits just below a view's base: the hole in its window, and where
+/// its misses go, or `None` for a plain fault. The arenas map a page in
+/// front of the window for it.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct Header {
+    hole: Hole,
+    miss: Option<NonNull<dyn Miss>>,
+}
+
+impl Header {
+    /// A flat window's: no hole, and misses fault.
+    const FLAT: Self = Self {
+        hole: Hole::NONE,
+        miss: None,
+    };
+}
+
+/// Guest addresses `[lo, lo + span)`, which miss although below the view's
+/// `mapped` (an [`Mmio`]'s devices).
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct Hole {
+    pub(crate) lo: u64,
+    pub(crate) span: u64,
+}
+
+impl Hole {
+    const NONE: Self = Self { lo: 0, span: 0 };
+
+    #[inline(always)]
+    fn covers(self, addr: u64) -> bool {
+        addr.wrapping_sub(self.lo) < self.span
+    }
+}
+
+/// Write `header` below the window at `base`.
+///
+/// # Safety
+/// `base` must have room for a [`Header`] below it, aligned for one.
+#[inline(always)]
+unsafe fn install(base: *mut u8, header: Header) {
+    unsafe { base.cast::<Header>().sub(1).write(header) }
 }
 
 /// A guest physical memory arena.
 ///
 /// `Addr` is the guest address width. The hot `load`/`store` paths take it
 /// exactly (no conversions); the bulk helpers used by kernels take `u64`.
+///
+/// `&self` methods only read guest memory; anything that writes it, or may
+/// move the arena, takes `&mut self`. That is what lets the arenas be
+/// `Sync`: shared borrows on several threads (see
+/// [`crate::machine::Machine::inspect`]) only ever read.
 pub trait Memory {
     type Addr: Copy + Into<u64>;
 
@@ -111,9 +299,17 @@ pub trait Memory {
     /// Make `[0, top)` accessible, growing the arena if needed.
     fn grow_to(&mut self, top: u64) -> Result<(), MemoryError>;
 
-    /// Host pointer to guest range `[addr, addr + len)`, if fully accessible.
+    /// Host pointer to guest range `[addr, addr + len)`, if fully accessible
+    /// and contiguous on the host ([`Paged`] only has single pages, and
+    /// [`Mmio`] devices none). Writing through it needs the memory's `&mut`
+    /// borrow, as `store` does.
     fn ptr_range(&self, access: MemoryAccess, addr: u64, len: u64) -> Result<*mut u8, MemoryError>;
 
+    /// Whether guest range `[addr, addr + len)` is fully accessible.
+    fn check_range(&self, access: MemoryAccess, addr: u64, len: u64) -> Result<(), MemoryError> {
+        self.ptr_range(access, addr, len).map(drop)
+    }
+
     // --- Bulk helpers (kernel-facing, cold relative to load/store) ---
 
     /// Load at a `u64` address, whatever the width of `Addr`.
@@ -130,8 +326,9 @@ pub trait Memory {
     }
 
     /// View guest memory as a slice of `T`. Errors if the guest address is
-    /// not aligned for `T` on the host.
-    fn slice<T: Pod>(&self, addr: u64, len: u64) -> Result<&[T], MemoryError> {
+    /// not aligned for `T` on the host. Borrowed from the arenas; backends
+    /// without a flat window may copy.
+    fn slice<T: Pod>(&self, addr: u64, len: u64) -> Result<Cow<'_, [T]>, MemoryError> {
         let bytes = len.checked_mul(std::mem::size_of::<T>() as u64).ok_or(
             MemoryError::OverflowMemoryAccess {
                 access: MemoryAccess::Load,
@@ -140,14 +337,16 @@ pub trait Memory {
             },
         )?;
         let ptr = self.ptr_range(MemoryAccess::Load, addr, bytes)?;
-        if ptr as usize % std::mem::align_of::<T>() != 0 {
+        if !(ptr as usize).is_multiple_of(std::mem::align_of::<T>()) {
             return Err(MemoryError::UnalignedMemoryAccess {
                 access: MemoryAccess::Load,
                 addr,
                 required: std::mem::align_of::<T>() as u32,
             });
         }
-        Ok(unsafe { std::slice::from_raw_parts(ptr as *const T, len as usize) })
+        Ok(Cow::Borrowed(unsafe {
+            std::slice::from_raw_parts(ptr as *const T, len as usize)
+        }))
     }
 
     fn copy_to<T: Pod>(&mut self, dst: u64, src: &[T]) -> Result<(), MemoryError> {
@@ -164,9 +363,27 @@ pub trait Memory {
         Ok(())
     }
 
+    /// `memmove` within guest memory.
+    fn copy_within(&mut self, src: u64, dst: u64, len: u64) -> Result<(), MemoryError> {
+        let from = self.ptr_range(MemoryAccess::Load, src, len)?;
+        let to = self.ptr_range(MemoryAccess::Store, dst, len)?;
+        unsafe { std::ptr::copy(from, to, len as usize) };
+        Ok(())
+    }
+
+    /// Zero `[addr, addr + len)`, releasing whatever host memory backs it
+    /// where the arena can (e.g. on guest `munmap`).
+    fn discard(&mut self, addr: u64, len: u64) -> Result<(), MemoryError> {
+        self.memset(addr, 0, len)
+    }
+
     /// Bytes starting at `addr` up to (not including) the first NUL, bounded
     /// by `max_len` when given.
-    fn bytes_null_terminated(&self, addr: u64, max_len: Option<u64>) -> Result<&[u8], MemoryError> {
+    fn bytes_null_terminated(
+        &self,
+        addr: u64,
+        max_len: Option<u64>,
+    ) -> Result<Cow<'_, [u8]>, MemoryError> {
         let max_addr = max_len
             .map_or(Some(self.max_addr()), |m| addr.checked_add(m))
             .ok_or_else(|| MemoryError::OverflowMemoryAccess {
@@ -193,17 +410,32 @@ pub trait Memory {
 
 /// rv32 arena: the full 32-bit address space (+1 guard page) is mapped up
 /// front, so any `u32` address is in-bounds by construction and the hot
-/// paths compile to a single unchecked access.
+/// paths compile to a single unchecked access. [`Window32`] maps less.
+///
+/// The arenas are `mmap`s, so Unix hosts only; [`Paged`] runs anywhere.
+#[cfg(unix)]
 pub struct Memory32 {
     ptr: *mut u8,
 }
 
+// SAFETY: the arena owns its mapping outright: `ptr` is never handed out
+// past a borrow of the arena (a `MemView`, a slice, or a `ptr_range` pointer
+// whose writes need `&mut`), and is unmapped only on drop. So the arena moves
+// between threads as its bytes would, and, as `&self` methods only read,
+// shared borrows on several threads race with nothing.
+#[cfg(unix)]
+unsafe impl Send for Memory32 {}
+#[cfg(unix)]
+unsafe impl Sync for Memory32 {}
+
+#[cfg(unix)]
 pub const MEMORY32_SIZE: usize = const {
     // The scheme requires a host with a wider address space than the guest.
     assert!(std::mem::size_of::<usize>() > std::mem::size_of::<u32>());
     u32::MAX as usize + PAGE_SIZE
 };
 
+#[cfg(unix)]
 impl Memory for Memory32 {
     type Addr = u32;
 
@@ -223,6 +455,8 @@ impl Memory for Memory32 {
 
     #[inline(always)]
     fn view(&mut self) -> MemView<'_> {
+        // Safety: `map_window` leaves a header page below `ptr`.
+        unsafe { install(self.ptr, Header::FLAT) };
         // `mapped` is a constant the width of the guest space: after
         // inlining, every `u32`-derived address compares below it and the
         // view's limit check folds away, keeping rv32 accesses unchecked.
@@ -251,25 +485,134 @@ impl Memory for Memory32 {
         }
         Ok(unsafe { self.ptr.add(addr as usize) })
     }
+
+    fn discard(&mut self, addr: u64, len: u64) -> Result<(), MemoryError> {
+        let ptr = self.ptr_range(MemoryAccess::Store, addr, len)?;
+        discard_pages(ptr, len);
+        Ok(())
+    }
 }
 
+#[cfg(unix)]
 impl Memory32 {
     pub fn new() -> Self {
         Self {
-            ptr: map_anon(MEMORY32_SIZE),
+            ptr: map_window(MEMORY32_SIZE),
         }
     }
 }
 
+#[cfg(unix)]
 impl Default for Memory32 {
     fn default() -> Self {
         Self::new()
     }
 }
 
+#[cfg(unix)]
 impl Drop for Memory32 {
     fn drop(&mut self) {
-        unsafe { libc::munmap(self.ptr as *mut _, MEMORY32_SIZE) };
+        unmap_window(self.ptr, MEMORY32_SIZE);
+    }
+}
+
+/// rv32 arena over only the low part of the address space, for hosts that
+/// cap virtual memory or refuse to overcommit 4 GiB per guest. Accesses at
+/// or beyond its size fault, as they do past [`Memory64`]'s mapped top, and
+/// pay the same limit check.
+#[cfg(unix)]
+pub struct Window32 {
+    ptr: *mut u8,
+    /// Bytes mapped (excluding the guard page).
+    size: u64,
+}
+
+// SAFETY: as for `Memory32`.
+#[cfg(unix)]
+unsafe impl Send for Window32 {}
+#[cfg(unix)]
+unsafe impl Sync for Window32 {}
+
+#[cfg(unix)]
+impl Memory for Window32 {
+    type Addr = u32;
+
+    #[inline(always)]
+    fn load<T: Primitive>(&self, addr: u32) -> Result<T, Fault> {
+        if addr as u64 >= self.size {
+            return Err(Fault);
+        }
+        // Safety: addr < size, and a primitive's overhang past `size` lands
+        // in the guard page.
+        Ok(unsafe { (self.ptr.add(addr as usize) as *const T).read_unaligned() })
+    }
+
+    #[inline(always)]
+    fn store<T: Primitive>(&mut self, addr: u32, val: T) -> Result<(), Fault> {
+        if addr as u64 >= self.size {
+            return Err(Fault);
+        }
+        // Safety: see `load`.
+        unsafe { (self.ptr.add(addr as usize) as *mut T).write_unaligned(val) };
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn view(&mut self) -> MemView<'_> {
+        // Safety: `map_window` leaves a header page below `ptr`.
+        unsafe { install(self.ptr, Header::FLAT) };
+        MemView {
+            ptr: self.ptr,
+            mapped: self.size,
+            _mem: PhantomData,
+        }
+    }
+
+    fn max_addr(&self) -> u64 {
+        self.size
+    }
+
+    fn grow_to(&mut self, top: u64) -> Result<(), MemoryError> {
+        if top <= self.size {
+            Ok(())
+        } else {
+            Err(fault(MemoryAccess::Store, top))
+        }
+    }
+
+    fn ptr_range(&self, access: MemoryAccess, addr: u64, len: u64) -> Result<*mut u8, MemoryError> {
+        if addr.checked_add(len).is_none_or(|end| end > self.size) {
+            return Err(fault(access, addr));
+        }
+        Ok(unsafe { self.ptr.add(addr as usize) })
+    }
+
+    fn discard(&mut self, addr: u64, len: u64) -> Result<(), MemoryError> {
+        let ptr = self.ptr_range(MemoryAccess::Store, addr, len)?;
+        discard_pages(ptr, len);
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+impl Window32 {
+    /// Guest addresses `[0, size)`, `size` rounded up to a page (and at
+    /// most the full space).
+    pub fn new(size: u64) -> Self {
+        let size = size
+            .clamp(PAGE_SIZE as u64, 1 << 32)
+            .next_multiple_of(PAGE_SIZE as u64);
+        Self {
+            ptr: map_window(size as usize + PAGE_SIZE),
+            size,
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Window32 {
+    fn drop(&mut self) {
+        unmap_window(self.ptr, self.size as usize + PAGE_SIZE);
     }
 }
 
@@ -287,6 +630,9 @@ impl Drop for Memory32 {
 /// Every access is limit-checked against the mapped top. The check is a
 /// never-taken predictable branch (measured at parity with the unchecked
 /// rv32 path) and gives precise faults for wild pointers.
+///
+/// `mremap` is Linux's, and so is this arena.
+#[cfg(target_os = "linux")]
 pub struct Memory64 {
     ptr: *mut u8,
     /// Bytes currently mapped (excluding the guard page). Accesses at or
@@ -296,11 +642,34 @@ pub struct Memory64 {
     max: u64,
 }
 
+// SAFETY: as for `Memory32`. `grow_to`, which may move `ptr` and changes
+// `mapped`, takes `&mut self`, so no shared borrow sees either change.
+#[cfg(target_os = "linux")]
+unsafe impl Send for Memory64 {}
+#[cfg(target_os = "linux")]
+unsafe impl Sync for Memory64 {}
+
+// Machines move between threads with their arenas (see `crate::sched`).
+const _: () = {
+    const fn shareable<T: Send + Sync>() {}
+    #[cfg(unix)]
+    shareable::<Memory32>();
+    #[cfg(unix)]
+    shareable::<Window32>();
+    #[cfg(target_os = "linux")]
+    shareable::<Memory64>();
+    shareable::<Paged32>();
+    shareable::<Paged64>();
+    #[cfg(unix)]
+    shareable::<Mmio<Memory32>>();
+};
+
 /// Default initial window: 32 MiB (grown on demand).
 pub const MEMORY64_DEFAULT_INITIAL: u64 = 32 << 20;
 /// Default guest address-space cap: 64 GiB.
 pub const MEMORY64_DEFAULT_MAX: u64 = 64 << 30;
 
+#[cfg(target_os = "linux")]
 impl Memory for Memory64 {
     type Addr = u64;
 
@@ -326,6 +695,9 @@ impl Memory for Memory64 {
 
     #[inline(always)]
     fn view(&mut self) -> MemView<'_> {
+        // Safety: `map_window` leaves a header page below `ptr`, and
+        // `mremap` keeps it.
+        unsafe { install(self.ptr, Header::FLAT) };
         MemView {
             ptr: self.ptr,
             mapped: self.mapped,
@@ -348,16 +720,16 @@ impl Memory for Memory64 {
         let new_mapped = page_align_up(top.max(self.mapped * 2).min(self.max));
         let new_ptr = unsafe {
             libc::mremap(
-                self.ptr as *mut _,
-                self.mapped as usize + PAGE_SIZE,
-                new_mapped as usize + PAGE_SIZE,
+                self.ptr.sub(PAGE_SIZE) as *mut _,
+                self.mapped as usize + 2 * PAGE_SIZE,
+                new_mapped as usize + 2 * PAGE_SIZE,
                 libc::MREMAP_MAYMOVE,
             )
         };
         if new_ptr == libc::MAP_FAILED {
             return Err(fault(MemoryAccess::Store, top));
         }
-        self.ptr = new_ptr as *mut u8;
+        self.ptr = unsafe { (new_ptr as *mut u8).add(PAGE_SIZE) };
         self.mapped = new_mapped;
         Ok(())
     }
@@ -368,8 +740,15 @@ impl Memory for Memory64 {
         }
         Ok(unsafe { self.ptr.add(addr as usize) })
     }
+
+    fn discard(&mut self, addr: u64, len: u64) -> Result<(), MemoryError> {
+        let ptr = self.ptr_range(MemoryAccess::Store, addr, len)?;
+        discard_pages(ptr, len);
+        Ok(())
+    }
 }
 
+#[cfg(target_os = "linux")]
 impl Memory64 {
     pub fn new(initial: u64, max: u64) -> Self {
         const {
@@ -378,26 +757,809 @@ impl Memory64 {
         let max = page_align_up(max);
         let mapped = page_align_up(initial).min(max);
         Self {
-            ptr: map_anon(mapped as usize + PAGE_SIZE),
+            ptr: map_window(mapped as usize + PAGE_SIZE),
             mapped,
             max,
         }
     }
 }
 
+#[cfg(target_os = "linux")]
 impl Default for Memory64 {
     fn default() -> Self {
         Self::new(MEMORY64_DEFAULT_INITIAL, MEMORY64_DEFAULT_MAX)
     }
 }
 
+#[cfg(target_os = "linux")]
 impl Drop for Memory64 {
     fn drop(&mut self) {
-        unsafe { libc::munmap(self.ptr as *mut _, self.mapped as usize + PAGE_SIZE) };
+        unmap_window(self.ptr, self.mapped as usize + PAGE_SIZE);
     }
 }
 
-fn map_anon(len: usize) -> *mut u8 {
+/// Pages per directory of a [`Paged`] table: a directory spans 4 MiB.
+const DIR_PAGES: usize = 1024;
+const DIR_SPAN: u64 = (DIR_PAGES * PAGE_SIZE) as u64;
+
+type Page = [u8; PAGE_SIZE];
+type Dir = [Option<Box<Page>>; DIR_PAGES];
+
+/// What pages never written read as.
+static ZERO_PAGE: Page = [0; PAGE_SIZE];
+
+/// Portable memory: a two-level table of boxed 4 KiB pages, each allocated
+/// on its first write (pages never written read as zero). No `mmap`, no
+/// guard pages, no host-specific tricks: for hosts without them, or for
+/// running under Miri.
+///
+/// Slower than the arenas, which stay the default. There is no flat window,
+/// so every hart access misses and is served out of line (and the JIT falls
+/// back to the interpreter), and the bulk helpers copy ranges that cross
+/// pages.
+/// Otherwise it behaves as the arena of its width: `Paged<u32>` spans its
+/// whole size from the start, as [`Memory32`] does, and `Paged<u64>` raises
+/// its accessible top as [`Memory64`] grows, so the same accesses fault.
+pub struct Paged<A> {
+    /// The [`Header`] below the empty window `view` hands out.
+    header: Header,
+    table: Table,
+    _addr: PhantomData<A>,
+}
+
+pub type Paged32 = Paged<u32>;
+pub type Paged64 = Paged<u64>;
+
+// SAFETY: `header` only ever points at `table`, and is only followed
+// through a view, which holds the `&mut` borrow; the table itself is owned
+// boxes.
+unsafe impl<A> Send for Paged<A> {}
+unsafe impl<A> Sync for Paged<A> {}
+
+struct Table {
+    /// One directory per 4 MiB of guest space, allocated with its first
+    /// page.
+    dirs: Vec<Option<Box<Dir>>>,
+    /// Accessible top (exclusive); accesses at or beyond it fault.
+    top: u64,
+    /// Cap on `top`.
+    max: u64,
+}
+
+impl Table {
+    fn new(top: u64, max: u64) -> Self {
+        Self {
+            dirs: (0..max.div_ceil(DIR_SPAN)).map(|_| None).collect(),
+            top,
+            max,
+        }
+    }
+
+    /// The page holding `addr`, if it has been written.
+    fn page(&self, addr: u64) -> Option<&Page> {
+        let dir = self.dirs.get((addr / DIR_SPAN) as usize)?.as_deref()?;
+        dir[(addr / PAGE_SIZE as u64) as usize % DIR_PAGES].as_deref()
+    }
+
+    /// The page holding `addr` (below `max`), allocating it.
+    fn page_mut(&mut self, addr: u64) -> &mut Page {
+        let dir = self.dirs[(addr / DIR_SPAN) as usize]
+            .get_or_insert_with(|| Box::new([const { None }; DIR_PAGES]));
+        dir[(addr / PAGE_SIZE as u64) as usize % DIR_PAGES]
+            .get_or_insert_with(|| Box::new([0; PAGE_SIZE]))
+    }
+
+    fn check(&self, access: MemoryAccess, addr: u64, len: u64) -> Result<(), MemoryError> {
+        if addr.checked_add(len).is_none_or(|end| end > self.top) {
+            return Err(fault(access, addr));
+        }
+        Ok(())
+    }
+
+    /// Copy `len` bytes at `addr` out to `dst`. The range must lie below
+    /// `max`.
+    ///
+    /// # Safety
+    /// `dst` must be valid for `len` bytes of writes.
+    unsafe fn read(&self, addr: u64, dst: *mut u8, len: usize) {
+        let mut done = 0;
+        while done < len {
+            let at = addr + done as u64;
+            let off = at as usize % PAGE_SIZE;
+            let n = (PAGE_SIZE - off).min(len - done);
+            let page = self.page(at).unwrap_or(&ZERO_PAGE);
+            unsafe { std::ptr::copy_nonoverlapping(page[off..].as_ptr(), dst.add(done), n) };
+            done += n;
+        }
+    }
+
+    /// Copy `src` in at `addr`. The range must lie below `max`.
+    fn write(&mut self, addr: u64, src: &[u8]) {
+        let mut done = 0;
+        while done < src.len() {
+            let at = addr + done as u64;
+            let off = at as usize % PAGE_SIZE;
+            let n = (PAGE_SIZE - off).min(src.len() - done);
+            self.page_mut(at)[off..off + n].copy_from_slice(&src[done..done + n]);
+            done += n;
+        }
+    }
+
+    /// Set `len` bytes at `addr` to `val`, freeing whole pages zeroed. The
+    /// range must lie below `max`.
+    fn fill(&mut self, addr: u64, val: u8, len: u64) {
+        let end = addr + len;
+        let mut at = addr;
+        while at < end {
+            let off = at as usize % PAGE_SIZE;
+            let n = (end - at).min((PAGE_SIZE - off) as u64) as usize;
+            if val == 0 && n == PAGE_SIZE {
+                if let Some(Some(dir)) = self.dirs.get_mut((at / DIR_SPAN) as usize) {
+                    dir[(at / PAGE_SIZE as u64) as usize % DIR_PAGES] = None;
+                }
+            } else if val != 0 || self.page(at).is_some() {
+                self.page_mut(at)[off..off + n].fill(val);
+            }
+            at += n as u64;
+        }
+    }
+
+    /// A primitive's `size` bytes at `addr`. Its overhang past `max` (as
+    /// into the arenas' guard page) reads as zero.
+    fn bits(&self, addr: u64, size: usize) -> Result<u64, Fault> {
+        if addr >= self.top {
+            return Err(Fault);
+        }
+        let mut bytes = [0; 8];
+        let n = size.min((self.max - addr) as usize);
+        unsafe { self.read(addr, bytes.as_mut_ptr(), n) };
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    /// The store counterpart of `bits`, dropping the overhang past `max`.
+    fn set_bits(&mut self, addr: u64, size: usize, val: u64) -> Result<(), Fault> {
+        if addr >= self.top {
+            return Err(Fault);
+        }
+        let n = size.min((self.max - addr) as usize);
+        self.write(addr, &val.to_le_bytes()[..n]);
+        Ok(())
+    }
+}
+
+impl Miss for Table {
+    fn load(&mut self, addr: u64, size: usize) -> Result<u64, Fault> {
+        self.bits(addr, size)
+    }
+
+    fn store(&mut self, addr: u64, size: usize, val: u64) -> Result<(), Fault> {
+        self.set_bits(addr, size, val)
+    }
+}
+
+impl<A> Paged<A> {
+    fn from_table(table: Table) -> Self {
+        Self {
+            header: Header::FLAT,
+            table,
+            _addr: PhantomData,
+        }
+    }
+}
+
+impl Paged<u32> {
+    /// The full 32-bit space.
+    pub fn new() -> Self {
+        Self::with_size(1 << 32)
+    }
+
+    /// Guest addresses `[0, size)`, as [`Window32::new`].
+    pub fn with_size(size: u64) -> Self {
+        let size = size
+            .clamp(PAGE_SIZE as u64, 1 << 32)
+            .next_multiple_of(PAGE_SIZE as u64);
+        Self::from_table(Table::new(size, size))
+    }
+}
+
+impl Default for Paged<u32> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Paged<u64> {
+    /// `[0, initial)` accessible, growing up to `max`, as [`Memory64::new`].
+    pub fn new(initial: u64, max: u64) -> Self {
+        let max = page_align_up(max);
+        Self::from_table(Table::new(page_align_up(initial).min(max), max))
+    }
+}
+
+impl Default for Paged<u64> {
+    fn default() -> Self {
+        Self::new(MEMORY64_DEFAULT_INITIAL, MEMORY64_DEFAULT_MAX)
+    }
+}
+
+impl<A: Copy + Into<u64>> Memory for Paged<A> {
+    type Addr = A;
+
+    fn load<T: Primitive>(&self, addr: A) -> Result<T, Fault> {
+        self.table
+            .bits(addr.into(), size_of::<T>())
+            .map(T::from_bits)
+    }
+
+    fn store<T: Primitive>(&mut self, addr: A, val: T) -> Result<(), Fault> {
+        self.table
+            .set_bits(addr.into(), size_of::<T>(), val.to_bits())
+    }
+
+    fn view(&mut self) -> MemView<'_> {
+        self.header.miss = Some(NonNull::from(&mut self.table as &mut dyn Miss));
+        MemView {
+            ptr: (&raw mut self.header).wrapping_add(1).cast(),
+            mapped: 0,
+            _mem: PhantomData,
+        }
+    }
+
+    fn max_addr(&self) -> u64 {
+        self.table.max
+    }
+
+    fn grow_to(&mut self, top: u64) -> Result<(), MemoryError> {
+        if top <= self.table.top {
+            return Ok(());
+        }
+        if top > self.table.max {
+            return Err(fault(MemoryAccess::Store, top));
+        }
+        // As `Memory64` grows, so wild accesses fault alike.
+        self.table.top = page_align_up(top.max(self.table.top * 2).min(self.table.max));
+        Ok(())
+    }
+
+    /// Loads within one page only: the table has nothing contiguous to
+    /// offer past that, and nothing writable behind `&self`.
+    fn ptr_range(&self, access: MemoryAccess, addr: u64, len: u64) -> Result<*mut u8, MemoryError> {
+        self.table.check(access, addr, len)?;
+        let off = addr as usize % PAGE_SIZE;
+        if matches!(access, MemoryAccess::Store) || off as u64 + len > PAGE_SIZE as u64 {
+            return Err(MemoryError::NoHostRange { access, addr });
+        }
+        let page = self.table.page(addr).unwrap_or(&ZERO_PAGE);
+        Ok(page[off..].as_ptr() as *mut u8)
+    }
+
+    fn check_range(&self, access: MemoryAccess, addr: u64, len: u64) -> Result<(), MemoryError> {
+        self.table.check(access, addr, len)
+    }
+
+    fn load_at<T: Primitive>(&self, addr: u64) -> Result<T, MemoryError> {
+        let mut bytes = [0; 8];
+        let n = size_of::<T>();
+        self.table.check(MemoryAccess::Load, addr, n as u64)?;
+        unsafe { self.table.read(addr, bytes.as_mut_ptr(), n) };
+        Ok(T::from_bits(u64::from_le_bytes(bytes)))
+    }
+
+    fn store_at<T: Primitive>(&mut self, addr: u64, val: T) -> Result<(), MemoryError> {
+        let n = size_of::<T>();
+        self.table.check(MemoryAccess::Store, addr, n as u64)?;
+        self.table.write(addr, &val.to_bits().to_le_bytes()[..n]);
+        Ok(())
+    }
+
+    /// Borrowed when the range sits in one page at a host address aligned
+    /// for `T`; copied out otherwise.
+    fn slice<T: Pod>(&self, addr: u64, len: u64) -> Result<Cow<'_, [T]>, MemoryError> {
+        let bytes =
+            len.checked_mul(size_of::<T>() as u64)
+                .ok_or(MemoryError::OverflowMemoryAccess {
+                    access: MemoryAccess::Load,
+                    addr,
+                    len: len.min(u32::MAX as u64) as u32,
+                })?;
+        self.table.check(MemoryAccess::Load, addr, bytes)?;
+        let off = addr as usize % PAGE_SIZE;
+        if off as u64 + bytes <= PAGE_SIZE as u64 {
+            let page = self.table.page(addr).unwrap_or(&ZERO_PAGE);
+            let ptr = page[off..].as_ptr();
+            if (ptr as usize).is_multiple_of(align_of::<T>()) {
+                return Ok(Cow::Borrowed(unsafe {
+                    std::slice::from_raw_parts(ptr as *const T, len as usize)
+                }));
+            }
+        }
+        let mut out = Vec::<T>::with_capacity(len as usize);
+        // Safety: the capacity covers `bytes`, all of which `read` writes,
+        // and any bytes are a valid `T`.
+        unsafe {
+            self.table
+                .read(addr, out.as_mut_ptr() as *mut u8, bytes as usize);
+            out.set_len(len as usize);
+        }
+        Ok(Cow::Owned(out))
+    }
+
+    fn copy_to<T: Pod>(&mut self, dst: u64, src: &[T]) -> Result<(), MemoryError> {
+        let bytes = size_of_val(src);
+        self.table.check(MemoryAccess::Store, dst, bytes as u64)?;
+        // Safety: `Pod` types have no padding, so every byte is initialized.
+        let src = unsafe { std::slice::from_raw_parts(src.as_ptr() as *const u8, bytes) };
+        self.table.write(dst, src);
+        Ok(())
+    }
+
+    fn memset(&mut self, addr: u64, val: u8, len: u64) -> Result<(), MemoryError> {
+        self.table.check(MemoryAccess::Store, addr, len)?;
+        self.table.fill(addr, val, len);
+        Ok(())
+    }
+
+    /// A page at a time through a bounce buffer, back to front when the
+    /// destination starts inside the source.
+    fn copy_within(&mut self, src: u64, dst: u64, len: u64) -> Result<(), MemoryError> {
+        self.table.check(MemoryAccess::Load, src, len)?;
+        self.table.check(MemoryAccess::Store, dst, len)?;
+        let backward = dst > src && dst - src < len;
+        let mut page = [0; PAGE_SIZE];
+        let mut done = 0;
+        while done < len {
+            let n = (len - done).min(PAGE_SIZE as u64);
+            let off = if backward { len - done - n } else { done };
+            // Safety: `page` holds `n` bytes.
+            unsafe { self.table.read(src + off, page.as_mut_ptr(), n as usize) };
+            self.table.write(dst + off, &page[..n as usize]);
+            done += n;
+        }
+        Ok(())
+    }
+
+    fn discard(&mut self, addr: u64, len: u64) -> Result<(), MemoryError> {
+        self.memset(addr, 0, len)
+    }
+
+    fn bytes_null_terminated(
+        &self,
+        addr: u64,
+        max_len: Option<u64>,
+    ) -> Result<Cow<'_, [u8]>, MemoryError> {
+        let limit = max_len
+            .map_or(Some(self.table.max), |m| addr.checked_add(m))
+            .ok_or_else(|| MemoryError::OverflowMemoryAccess {
+                access: MemoryAccess::Load,
+                addr,
+                len: max_len.unwrap_or(u64::MAX).min(u32::MAX as u64) as u32,
+            })?;
+
+        // A page at a time: `top` is page-aligned, so checking a page's
+        // first byte checks it all.
+        let mut cur = addr;
+        while cur < limit {
+            self.table.check(MemoryAccess::Load, cur, 1)?;
+            let off = cur as usize % PAGE_SIZE;
+            let n = (limit - cur).min((PAGE_SIZE - off) as u64) as usize;
+            let page = self.table.page(cur).unwrap_or(&ZERO_PAGE);
+            match page[off..off + n].iter().position(|&b| b == 0) {
+                Some(at) => {
+                    cur += at as u64;
+                    break;
+                }
+                None => cur += n as u64,
+            }
+        }
+
+        self.slice(addr, cur - addr)
+    }
+}
+
+/// A memory-mapped device behind an [`Mmio`] region: guest accesses to the
+/// region, at `offset` from its base, `size` bytes wide (1, 2, 4 or 8; the
+/// value zero-extended in a `u64`, of which only the low `size` bytes
+/// count). `Err` faults the access, as an unmapped address does.
+///
+/// Only the hart's own loads and stores, and [`Memory::store`], reach a
+/// device. Instruction fetches fault on one, the kernel-facing bulk helpers
+/// refuse device ranges, and nothing behind `&self` reads one, so
+/// side-effecting reads (a UART's receive register) happen exactly when the
+/// guest asked for them.
+pub trait Device: Any + Send {
+    fn read(&mut self, offset: u64, size: usize) -> Result<u64, Fault>;
+    fn write(&mut self, offset: u64, size: usize, val: u64) -> Result<(), Fault>;
+}
+
+/// Guest RAM `M` with [`Device`]s mapped over address ranges, for UARTs,
+/// timers and accelerators in bare-metal guests.
+///
+/// RAM on either side of the devices stays on the hart's fast path: the
+/// view hands out `M`'s own window with a hole from the lowest device to
+/// the end of the highest, and the accesses in it miss (see [`MemView`]) to
+/// be routed to a device or, when none claims them, back to `M`. So keep
+/// the devices together, as the usual layouts do (a UART and interrupt
+/// controllers below RAM at `0x8000_0000`), and RAM costs what it did. A
+/// device shadows the RAM it overlaps; an access straddling a device's edge
+/// faults.
+pub struct Mmio<M> {
+    ram: M,
+    bus: Bus,
+}
+
+// SAFETY: the bus's pointers only describe `ram`'s window and are only
+// followed through a view, which holds the `&mut` borrow. Devices are
+// `Send`; the one `&self` method reaching one, `device`, requires the
+// device to be `Sync` as well.
+unsafe impl<M: Send> Send for Mmio<M> {}
+unsafe impl<M: Sync> Sync for Mmio<M> {}
+
+/// What an [`Mmio`] view's misses go to.
+struct Bus {
+    /// Disjoint, sorted by base.
+    regions: Vec<Region>,
+    /// `ram`'s window as its last view handed it out, and the header that
+    /// view wrote (which `view` overwrites).
+    ram: MemView<'static>,
+    ram_header: Header,
+}
+
+struct Region {
+    base: u64,
+    len: u64,
+    dev: Box<dyn Device>,
+}
+
+impl Region {
+    fn end(&self) -> u64 {
+        self.base + self.len
+    }
+}
+
+impl Bus {
+    /// The first region ending above `addr`.
+    fn index(&self, addr: u64) -> usize {
+        self.regions.partition_point(|r| r.end() <= addr)
+    }
+
+    /// Whether any device claims part of `[addr, addr + len)`.
+    fn claims(&self, addr: u64, len: u64) -> bool {
+        self.regions
+            .get(self.index(addr))
+            .is_some_and(|r| r.base < addr.saturating_add(len))
+    }
+
+    /// The device claiming an access of `size` bytes at `addr`, with the
+    /// access's offset into it. `Ok(None)` is RAM.
+    fn route(&mut self, addr: u64, size: usize) -> Result<Option<(&mut dyn Device, u64)>, Fault> {
+        let i = self.index(addr);
+        let end = addr.saturating_add(size as u64);
+        match self.regions.get_mut(i) {
+            Some(r) if r.base < end => {
+                if addr < r.base || end > r.end() {
+                    return Err(Fault);
+                }
+                Ok(Some((&mut *r.dev, addr - r.base)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// What views leave out of `ram`'s window: the devices, from 7 bytes
+    /// below the lowest (so a wide access straddling its edge misses too)
+    /// to the end of the highest, and whatever `ram`'s own view left out.
+    fn hole(&self) -> Hole {
+        let devices = match (self.regions.first(), self.regions.last()) {
+            (Some(lo), Some(hi)) => Some((lo.base.saturating_sub(7), hi.end())),
+            _ => None,
+        };
+        let Hole { lo, span } = self.ram_header.hole;
+        let bounds = match (devices, span) {
+            (None, 0) => return Hole::NONE,
+            (None, _) => (lo, lo + span),
+            (Some(bounds), 0) => bounds,
+            (Some((l, h)), _) => (l.min(lo), h.max(lo + span)),
+        };
+        Hole {
+            lo: bounds.0,
+            span: bounds.1 - bounds.0,
+        }
+    }
+
+    /// Whether `ram`'s own view reaches `addr` in its window.
+    fn flat(&self, addr: u64) -> bool {
+        addr < self.ram.mapped && !self.ram_header.hole.covers(addr)
+    }
+}
+
+impl Miss for Bus {
+    fn load(&mut self, addr: u64, size: usize) -> Result<u64, Fault> {
+        if let Some((dev, offset)) = self.route(addr, size)? {
+            return dev.read(offset, size);
+        }
+        if self.flat(addr) {
+            // Safety: below `ram`'s `mapped`.
+            return Ok(unsafe {
+                match size {
+                    1 => self.ram.flat_load::<u8>(addr).into(),
+                    2 => self.ram.flat_load::<u16>(addr).into(),
+                    4 => self.ram.flat_load::<u32>(addr).into(),
+                    _ => self.ram.flat_load::<u64>(addr),
+                }
+            });
+        }
+        // Safety: `ram`'s handler, alive and otherwise unused while its
+        // view is, as for any header.
+        match self.ram_header.miss {
+            Some(mut miss) => unsafe { miss.as_mut() }.load(addr, size),
+            None => Err(Fault),
+        }
+    }
+
+    fn store(&mut self, addr: u64, size: usize, val: u64) -> Result<(), Fault> {
+        if let Some((dev, offset)) = self.route(addr, size)? {
+            return dev.write(offset, size, val);
+        }
+        if self.flat(addr) {
+            // Safety: see `load`.
+            unsafe {
+                match size {
+                    1 => self.ram.flat_store(addr, val as u8),
+                    2 => self.ram.flat_store(addr, val as u16),
+                    4 => self.ram.flat_store(addr, val as u32),
+                    _ => self.ram.flat_store(addr, val),
+                }
+            }
+            return Ok(());
+        }
+        // Safety: see `load`.
+        match self.ram_header.miss {
+            Some(mut miss) => unsafe { miss.as_mut() }.store(addr, size, val),
+            None => Err(Fault),
+        }
+    }
+
+    /// Faults on a device: they hold no code. A compressed instruction may
+    /// end just below one, so only a full one needs its upper half in RAM.
+    fn fetch(&mut self, addr: u64) -> Result<u64, Fault> {
+        if self.route(addr, 2)?.is_some() {
+            return Err(Fault);
+        }
+        if self.flat(addr) {
+            // Safety: see `load`.
+            let half = unsafe { self.ram.flat_load::<u16>(addr) };
+            if half & 0b11 != 0b11 {
+                return Ok(half.into());
+            }
+            if self.route(addr, 4)?.is_some() {
+                return Err(Fault);
+            }
+            // Safety: see `load`.
+            return Ok(unsafe { self.ram.flat_load::<u32>(addr) }.into());
+        }
+        // Safety: see `load`.
+        match self.ram_header.miss {
+            Some(mut miss) => unsafe { miss.as_mut() }.fetch(addr),
+            None => Err(Fault),
+        }
+    }
+}
+
+impl<M: Memory> Mmio<M> {
+    /// `ram`, with no devices yet.
+    pub fn new(ram: M) -> Self {
+        Self {
+            ram,
+            bus: Bus {
+                regions: Vec::new(),
+                ram: MemView {
+                    ptr: std::ptr::null_mut(),
+                    mapped: 0,
+                    _mem: PhantomData,
+                },
+                ram_header: Header::FLAT,
+            },
+        }
+    }
+
+    /// Map `dev` over guest addresses `[base, base + len)`.
+    ///
+    /// # Panics
+    /// If the range is empty, overflows, or overlaps another device's.
+    pub fn with_device(mut self, base: u64, len: u64, dev: impl Device) -> Self {
+        assert!(
+            len > 0 && base.checked_add(len).is_some(),
+            "bad device range {base:#x}+{len:#x}"
+        );
+        assert!(
+            !self.bus.claims(base, len),
+            "device at {base:#x} overlaps another"
+        );
+        let at = self.bus.index(base);
+        self.bus.regions.insert(
+            at,
+            Region {
+                base,
+                len,
+                dev: Box::new(dev),
+            },
+        );
+        self
+    }
+
+    /// The device mapped at `base`, if it is a `D`. Shared, so only a
+    /// `Sync` one: a shared `Mmio` may be on several threads.
+    pub fn device<D: Device + Sync>(&self, base: u64) -> Option<&D> {
+        let r = self.bus.regions.iter().find(|r| r.base == base)?;
+        (&*r.dev as &dyn Any).downcast_ref()
+    }
+
+    pub fn device_mut<D: Device>(&mut self, base: u64) -> Option<&mut D> {
+        let r = self.bus.regions.iter_mut().find(|r| r.base == base)?;
+        (&mut *r.dev as &mut dyn Any).downcast_mut()
+    }
+
+    pub fn ram(&self) -> &M {
+        &self.ram
+    }
+
+    pub fn ram_mut(&mut self) -> &mut M {
+        &mut self.ram
+    }
+
+    /// Refuse ranges a device claims: they have no host memory.
+    fn ram_range(&self, access: MemoryAccess, addr: u64, len: u64) -> Result<(), MemoryError> {
+        if self.bus.claims(addr, len) {
+            return Err(MemoryError::NoHostRange { access, addr });
+        }
+        Ok(())
+    }
+}
+
+impl<M: Memory + Default> Default for Mmio<M> {
+    fn default() -> Self {
+        Self::new(M::default())
+    }
+}
+
+impl<M: Memory> Memory for Mmio<M> {
+    type Addr = M::Addr;
+
+    fn load<T: Primitive>(&self, addr: M::Addr) -> Result<T, Fault> {
+        if self.bus.claims(addr.into(), size_of::<T>() as u64) {
+            return Err(Fault);
+        }
+        self.ram.load(addr)
+    }
+
+    fn store<T: Primitive>(&mut self, addr: M::Addr, val: T) -> Result<(), Fault> {
+        match self.bus.route(addr.into(), size_of::<T>())? {
+            Some((dev, offset)) => dev.write(offset, size_of::<T>(), val.to_bits()),
+            None => self.ram.store(addr, val),
+        }
+    }
+
+    fn view(&mut self) -> MemView<'_> {
+        let ram = self.ram.view();
+        self.bus.ram = MemView {
+            ptr: ram.ptr,
+            mapped: ram.mapped,
+            _mem: PhantomData,
+        };
+        // Safety: every `view` writes the header below its base, so there
+        // is one to read, and room to write ours over it.
+        unsafe {
+            self.bus.ram_header = ram.ptr.cast::<Header>().sub(1).read();
+            let header = Header {
+                hole: self.bus.hole(),
+                miss: Some(NonNull::from(&mut self.bus as &mut dyn Miss)),
+            };
+            install(ram.ptr, header);
+        }
+        MemView {
+            ptr: ram.ptr,
+            mapped: ram.mapped,
+            _mem: PhantomData,
+        }
+    }
+
+    fn max_addr(&self) -> u64 {
+        self.ram.max_addr()
+    }
+
+    fn grow_to(&mut self, top: u64) -> Result<(), MemoryError> {
+        self.ram.grow_to(top)
+    }
+
+    fn ptr_range(&self, access: MemoryAccess, addr: u64, len: u64) -> Result<*mut u8, MemoryError> {
+        self.ram_range(access, addr, len)?;
+        self.ram.ptr_range(access, addr, len)
+    }
+
+    fn check_range(&self, access: MemoryAccess, addr: u64, len: u64) -> Result<(), MemoryError> {
+        self.ram_range(access, addr, len)?;
+        self.ram.check_range(access, addr, len)
+    }
+
+    fn load_at<T: Primitive>(&self, addr: u64) -> Result<T, MemoryError> {
+        self.ram_range(MemoryAccess::Load, addr, size_of::<T>() as u64)?;
+        self.ram.load_at(addr)
+    }
+
+    fn store_at<T: Primitive>(&mut self, addr: u64, val: T) -> Result<(), MemoryError> {
+        self.ram_range(MemoryAccess::Store, addr, size_of::<T>() as u64)?;
+        self.ram.store_at(addr, val)
+    }
+
+    fn slice<T: Pod>(&self, addr: u64, len: u64) -> Result<Cow<'_, [T]>, MemoryError> {
+        let bytes = len.saturating_mul(size_of::<T>() as u64);
+        self.ram_range(MemoryAccess::Load, addr, bytes)?;
+        self.ram.slice(addr, len)
+    }
+
+    fn copy_to<T: Pod>(&mut self, dst: u64, src: &[T]) -> Result<(), MemoryError> {
+        self.ram_range(MemoryAccess::Store, dst, size_of_val(src) as u64)?;
+        self.ram.copy_to(dst, src)
+    }
+
+    fn memset(&mut self, addr: u64, val: u8, len: u64) -> Result<(), MemoryError> {
+        self.ram_range(MemoryAccess::Store, addr, len)?;
+        self.ram.memset(addr, val, len)
+    }
+
+    fn copy_within(&mut self, src: u64, dst: u64, len: u64) -> Result<(), MemoryError> {
+        self.ram_range(MemoryAccess::Load, src, len)?;
+        self.ram_range(MemoryAccess::Store, dst, len)?;
+        self.ram.copy_within(src, dst, len)
+    }
+
+    fn discard(&mut self, addr: u64, len: u64) -> Result<(), MemoryError> {
+        self.ram_range(MemoryAccess::Store, addr, len)?;
+        self.ram.discard(addr, len)
+    }
+
+    /// Scans `ram`, then refuses the string (with its NUL) if a device
+    /// claims any of it.
+    fn bytes_null_terminated(
+        &self,
+        addr: u64,
+        max_len: Option<u64>,
+    ) -> Result<Cow<'_, [u8]>, MemoryError> {
+        let bytes = self.ram.bytes_null_terminated(addr, max_len)?;
+        self.ram_range(MemoryAccess::Load, addr, bytes.len() as u64 + 1)?;
+        Ok(bytes)
+    }
+}
+
+/// Zero a host range, handing whole pages back to the host: private
+/// anonymous pages read back as zero after `MADV_DONTNEED`. Partial pages at
+/// either end are cleared by hand.
+#[cfg(unix)]
+fn discard_pages(ptr: *mut u8, len: u64) {
+    let start = ptr as usize;
+    let end = start + len as usize;
+    let inner_start = start.next_multiple_of(PAGE_SIZE).min(end);
+    let inner_end = (end & !(PAGE_SIZE - 1)).max(inner_start);
+    unsafe {
+        std::ptr::write_bytes(ptr, 0, inner_start - start);
+        std::ptr::write_bytes(inner_end as *mut u8, 0, end - inner_end);
+        if inner_end > inner_start
+            && libc::madvise(
+                inner_start as *mut _,
+                inner_end - inner_start,
+                libc::MADV_DONTNEED,
+            ) != 0
+        {
+            std::ptr::write_bytes(inner_start as *mut u8, 0, inner_end - inner_start);
+        }
+    }
+}
+
+/// Map a window of `len` zeroed bytes, and a page below it for the view
+/// [`Header`]; returns the window's base.
+#[cfg(unix)]
+fn map_window(len: usize) -> *mut u8 {
+    let len = len + PAGE_SIZE;
     let ptr = unsafe {
         libc::mmap(
             std::ptr::null_mut(),
@@ -411,5 +1573,11 @@ fn map_anon(len: usize) -> *mut u8 {
     if ptr == libc::MAP_FAILED {
         panic!("failed to map {len:#x} bytes of guest memory");
     }
-    ptr as *mut u8
+    unsafe { (ptr as *mut u8).add(PAGE_SIZE) }
+}
+
+/// Unmap what `map_window(len)` returned.
+#[cfg(unix)]
+fn unmap_window(base: *mut u8, len: usize) {
+    unsafe { libc::munmap(base.sub(PAGE_SIZE) as *mut _, len + PAGE_SIZE) };
 }
@@ -0,0 +1,419 @@
+//! Running many independent machines on a pool of host threads.
+//!
+//! Each worker owns a queue of machines and runs them round-robin, one
+//! [`SchedulerConfig::quantum`] of instructions at a time (see
+//! [`Machine::run_for`]); a worker whose queue runs dry steals from the
+//! others. A machine leaves the pool when it halts or errors, through the
+//! completion callback it was spawned with.
+//!
+//! A machine blocked on a syscall is retried once more, with a waker
+//! registered with its kernel ([`Kernel::wake_on`]), then parked until the
+//! waker fires or the call's deadline passes ([`Kernel::blocked_until`]).
+//! Kernels whose waker does not cover everything that can end the call
+//! are also retried after a backoff, doubling while nothing changes.
+//!
+//! A panic in a machine's kernel or hart ends that machine alone, reported
+//! to its callback as [`MachineError::Panicked`]; one in a callback is
+//! held, and re-raised by [`Scheduler::join`].
+use std::{
+    any::Any,
+    collections::VecDeque,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, Weak,
+    },
+    task::{Wake, Waker},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    error::MachineError,
+    machine::{Kernel, Machine, MachineState},
+};
+
+/// The first retry of a parked machine whose kernel cannot wake it.
+const BACKOFF_MIN: Duration = Duration::from_micros(100);
+/// The longest such a machine is left parked.
+const BACKOFF_MAX: Duration = Duration::from_millis(10);
+
+/// Called once with a machine that left the pool, and the error that ended
+/// it, if one did.
+pub type Completion<K> =
+    Box<dyn FnOnce(Machine<K>, Result<(), MachineError<<K as Kernel>::Error>>) + Send>;
+
+/// How a [`Scheduler`] runs its machines.
+#[derive(Debug, Clone)]
+pub struct SchedulerConfig {
+    /// Host threads (at least one).
+    pub threads: usize,
+    /// Instructions a machine runs before the next one gets the thread.
+    pub quantum: u64,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            quantum: 100_000,
+        }
+    }
+}
+
+struct Job<K: Kernel> {
+    machine: Box<Machine<K>>,
+    done: Completion<K>,
+    /// The waker registered with the machine's kernel since it blocked,
+    /// and whether the kernel said it covers everything.
+    alarm: Option<(Arc<Alarm<K>>, bool)>,
+    /// How long to park the machine if its kernel cannot wake it.
+    backoff: Duration,
+}
+
+impl<K: Kernel> Job<K> {
+    fn new(machine: Machine<K>, done: Completion<K>) -> Self {
+        Self {
+            machine: Box::new(machine),
+            done,
+            alarm: None,
+            backoff: BACKOFF_MIN,
+        }
+    }
+}
+
+/// Parked machines, until their alarms ring or their times come.
+struct Parked<K: Kernel> {
+    jobs: Vec<(Job<K>, Option<Instant>)>,
+    /// The earliest of the times.
+    next: Option<Instant>,
+}
+
+/// The waker a parked machine's kernel is given: requeues the machine.
+struct Alarm<K: Kernel> {
+    rung: AtomicBool,
+    shared: Weak<Shared<K>>,
+}
+
+struct Shared<K: Kernel> {
+    queues: Vec<Mutex<VecDeque<Job<K>>>>,
+    quantum: u64,
+    /// Jobs sitting in the queues.
+    queued: AtomicUsize,
+    parked: Mutex<Parked<K>>,
+    /// An alarm rang since a worker last looked at the parked jobs.
+    rung: AtomicBool,
+    /// The first panic a completion callback raised.
+    panic: Mutex<Option<Box<dyn Any + Send>>>,
+    /// Machines spawned and not yet completed. Also taken around the
+    /// wakeups, so a worker between finding no work and sleeping sees them.
+    live: Mutex<usize>,
+    /// The pool is shutting down.
+    stopping: AtomicBool,
+    /// Signalled when a job is queued, an alarm rings, and on shutdown.
+    work: Condvar,
+    /// Signalled when a machine completes, and on shutdown.
+    idle: Condvar,
+}
+
+/// A pool of host threads running [`Machine`]s.
+///
+/// Dropping it without [`Scheduler::join`] abandons the machines still in
+/// it, uncompleted.
+pub struct Scheduler<K: Kernel> {
+    shared: Arc<Shared<K>>,
+    workers: Vec<JoinHandle<()>>,
+    /// The queue the next spawned machine goes on.
+    next: AtomicUsize,
+}
+
+impl<K> Scheduler<K>
+where
+    K: Kernel + Send + 'static,
+    K::Memory: Send,
+{
+    pub fn new(config: SchedulerConfig) -> Self {
+        let threads = config.threads.max(1);
+        let shared = Arc::new(Shared {
+            queues: (0..threads).map(|_| Mutex::default()).collect(),
+            quantum: config.quantum.max(1),
+            queued: AtomicUsize::new(0),
+            parked: Mutex::new(Parked {
+                jobs: Vec::new(),
+                next: None,
+            }),
+            rung: AtomicBool::new(false),
+            panic: Mutex::new(None),
+            live: Mutex::new(0),
+            stopping: AtomicBool::new(false),
+            work: Condvar::new(),
+            idle: Condvar::new(),
+        });
+        let workers = (0..threads)
+            .map(|me| {
+                let shared = shared.clone();
+                thread::Builder::new()
+                    .name(format!("riscv-sched-{me}"))
+                    .spawn(move || shared.work(me))
+                    .expect("failed to spawn scheduler thread")
+            })
+            .collect();
+        Self {
+            shared,
+            workers,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Add `machine` to the pool; `done` gets it back once it halts or
+    /// errors.
+    pub fn spawn(
+        &self,
+        machine: Machine<K>,
+        done: impl FnOnce(Machine<K>, Result<(), MachineError<K::Error>>) + Send + 'static,
+    ) {
+        *self.shared.live.lock().unwrap() += 1;
+        let queue = self.next.fetch_add(1, Ordering::Relaxed) % self.shared.queues.len();
+        self.shared.push(queue, Job::new(machine, Box::new(done)));
+    }
+
+    /// Machines spawned and not yet completed.
+    pub fn live(&self) -> usize {
+        *self.shared.live.lock().unwrap()
+    }
+
+    /// Wait for every machine to complete, then stop the threads. Raises
+    /// the first panic a completion callback raised, if one did.
+    pub fn join(mut self) {
+        let mut live = self.shared.live.lock().unwrap();
+        while *live > 0 && !self.shared.stopping.load(Ordering::SeqCst) {
+            live = self.shared.idle.wait(live).unwrap();
+        }
+        drop(live);
+        let panic = self
+            .stop()
+            .or_else(|| self.shared.panic.lock().unwrap().take());
+        if let Some(panic) = panic {
+            panic::resume_unwind(panic);
+        }
+    }
+}
+
+impl<K: Kernel> Scheduler<K> {
+    /// Stop the threads, returning the first panic one died of.
+    fn stop(&mut self) -> Option<Box<dyn Any + Send>> {
+        self.shared.stop();
+        self.workers
+            .drain(..)
+            .filter_map(|worker| worker.join().err())
+            .next()
+    }
+}
+
+impl<K: Kernel> Drop for Scheduler<K> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl<K: Kernel> Shared<K> {
+    fn stop(&self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        drop(self.live.lock());
+        self.work.notify_all();
+        self.idle.notify_all();
+    }
+}
+
+impl<K> Shared<K>
+where
+    K: Kernel + Send + 'static,
+    K::Memory: Send,
+{
+    fn push(&self, queue: usize, job: Job<K>) {
+        // Counted first, so a thief never takes it below zero.
+        self.queued.fetch_add(1, Ordering::SeqCst);
+        self.queues[queue].lock().unwrap().push_back(job);
+        drop(self.live.lock());
+        self.work.notify_one();
+    }
+
+    /// The next job from worker `me`'s queue, else one stolen from the back
+    /// of another's.
+    fn pop(&self, me: usize) -> Option<Job<K>> {
+        let n = self.queues.len();
+        let job = self.queues[me].lock().unwrap().pop_front().or_else(|| {
+            (1..n).find_map(|i| self.queues[(me + i) % n].lock().unwrap().pop_back())
+        })?;
+        self.queued.fetch_sub(1, Ordering::SeqCst);
+        Some(job)
+    }
+
+    /// Worker `me`'s loop: run a quantum of a job, then requeue, park or
+    /// complete it.
+    fn work(self: &Arc<Self>, me: usize) {
+        // Shuts the pool down if the worker itself panics, so `join` does
+        // not wait forever.
+        let _guard = PanicGuard(&**self);
+        while !self.stopping.load(Ordering::SeqCst) {
+            let next = self.unpark(me);
+            let Some(job) = self.pop(me) else {
+                let live = self.live.lock().unwrap();
+                if self.queued.load(Ordering::SeqCst) == 0
+                    && !self.rung.load(Ordering::SeqCst)
+                    && !self.stopping.load(Ordering::SeqCst)
+                {
+                    match next {
+                        Some(at) => {
+                            let timeout = at.saturating_duration_since(Instant::now());
+                            drop(self.work.wait_timeout(live, timeout).unwrap());
+                        }
+                        None => drop(self.work.wait(live).unwrap()),
+                    }
+                }
+                continue;
+            };
+            self.run(me, job);
+        }
+    }
+
+    /// Run a quantum of `job`, catching a panic in it.
+    fn run(self: &Arc<Self>, me: usize, mut job: Job<K>) {
+        let before = job.machine.hart.inst_count;
+        let ran = panic::catch_unwind(AssertUnwindSafe(|| {
+            let res = job.machine.run_for(self.quantum);
+            let parked = res.is_ok()
+                && job.machine.state == MachineState::Blocked
+                && job.machine.hart.inst_count == before;
+            let fresh = parked && job.alarm.is_none();
+            if fresh {
+                let alarm = Arc::new(Alarm {
+                    rung: AtomicBool::new(false),
+                    shared: Arc::downgrade(self),
+                });
+                let covered = job.machine.kernel.wake_on(&Waker::from(alarm.clone()));
+                job.alarm = Some((alarm, covered));
+            }
+            let blocked = parked.then(|| (fresh, job.machine.kernel.blocked_until()));
+            (res, blocked)
+        }));
+        let (res, blocked) = ran.unwrap_or_else(|panic| {
+            job.machine.state = MachineState::Halted;
+            (Err(MachineError::Panicked(message(&*panic))), None)
+        });
+
+        if res.is_err() || job.machine.state == MachineState::Halted {
+            let Job { machine, done, .. } = job;
+            if let Err(panic) = panic::catch_unwind(AssertUnwindSafe(|| done(*machine, res))) {
+                self.panic.lock().unwrap().get_or_insert(panic);
+            }
+            *self.live.lock().unwrap() -= 1;
+            self.idle.notify_all();
+            return;
+        }
+        match blocked {
+            // Still parked on its syscall, and the alarm just set: retry it
+            // once more, in case what it waits for came before the alarm.
+            Some((true, _)) => self.push(me, job),
+            Some((false, until)) => self.park(job, until),
+            None => {
+                job.alarm = None;
+                job.backoff = BACKOFF_MIN;
+                self.push(me, job);
+            }
+        }
+    }
+
+    /// Park `job` until its alarm rings or `until`, and, if its kernel's
+    /// waker does not cover everything, its backoff at most.
+    fn park(&self, mut job: Job<K>, until: Option<Instant>) {
+        let Some((alarm, covered)) = job.alarm.clone() else {
+            unreachable!("parked without an alarm");
+        };
+        let mut at = until;
+        if !covered {
+            let retry = Instant::now() + job.backoff;
+            at = Some(at.map_or(retry, |at| at.min(retry)));
+            job.backoff = (job.backoff * 2).min(BACKOFF_MAX);
+        }
+        let mut parked = self.parked.lock().unwrap();
+        parked.jobs.push((job, at));
+        parked.next = parked.next.into_iter().chain(at).min();
+        drop(parked);
+        // Rung before it was parked, and maybe looked for already.
+        if alarm.rung.load(Ordering::SeqCst) {
+            self.rung.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// Requeue on worker `me`'s queue the parked jobs whose alarms rang or
+    /// whose times came; returns the next time.
+    fn unpark(&self, me: usize) -> Option<Instant> {
+        let rung = self.rung.swap(false, Ordering::SeqCst);
+        let now = Instant::now();
+        let mut parked = self.parked.lock().unwrap();
+        if !rung && parked.next.is_none_or(|next| next > now) {
+            return parked.next;
+        }
+        let (ready, waiting): (Vec<_>, Vec<_>) = std::mem::take(&mut parked.jobs)
+            .into_iter()
+            .partition(|(job, at)| {
+                at.is_some_and(|at| at <= now)
+                    || job
+                        .alarm
+                        .as_ref()
+                        .is_some_and(|(alarm, _)| alarm.rung.load(Ordering::SeqCst))
+            });
+        parked.next = waiting.iter().filter_map(|&(_, at)| at).min();
+        parked.jobs = waiting;
+        let next = parked.next;
+        drop(parked);
+        for (mut job, _) in ready {
+            job.alarm = None;
+            self.push(me, job);
+        }
+        next
+    }
+}
+
+impl<K> Wake for Alarm<K>
+where
+    K: Kernel + Send + 'static,
+    K::Memory: Send,
+{
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        if self.rung.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        if let Some(shared) = self.shared.upgrade() {
+            shared.rung.store(true, Ordering::SeqCst);
+            drop(shared.live.lock());
+            shared.work.notify_one();
+        }
+    }
+}
+
+/// A panic's message, as the default hook prints it.
+fn message(panic: &(dyn Any + Send)) -> String {
+    match panic.downcast_ref::<&str>() {
+        Some(s) => s.to_string(),
+        None => match panic.downcast_ref::<String>() {
+            Some(s) => s.clone(),
+            None => "Box<dyn Any>".to_string(),
+        },
+    }
+}
+
+struct PanicGuard<'a, K: Kernel>(&'a Shared<K>);
+
+impl<K: Kernel> Drop for PanicGuard<'_, K> {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.0.stop();
+        }
+    }
+}
@@ -24,12 +24,19 @@ fn main() {
             _ => continue,
         };
 
-        tests.push(generate_test_for_artifact(&path, kernel));
+        tests.push(generate_test_for_artifact(&path, kernel.clone(), None));
+        if test_name.starts_with("rv32") {
+            tests.push(generate_test_for_artifact(
+                &path,
+                kernel,
+                Some(quote! { Backend::Jit }),
+            ));
+        }
     }
     let output = syn::parse_quote! {
         #![cfg(test)]
         use riscv_kernel_linux::{MockLinux32, MockLinux64};
-        use riscv_vm::machine::Machine;
+        use riscv_vm::{hart::Backend, machine::Machine};
 
         #(#tests)*
     };
@@ -39,16 +46,32 @@ fn main() {
     std::fs::write(output_file, prettyplease::unparse(&output)).unwrap();
 }
 
-fn generate_test_for_artifact(artifact: &Path, kernel: TokenStream) -> TokenStream {
-    let test_name = artifact
+/// A test running `artifact` to a zero exit, under `backend` if not the
+/// default. The tests run most code once, so JIT tests compile every block
+/// on first entry.
+fn generate_test_for_artifact(
+    artifact: &Path,
+    kernel: TokenStream,
+    backend: Option<TokenStream>,
+) -> TokenStream {
+    let mut test_name = artifact
         .file_name()
         .unwrap()
         .to_str()
         .unwrap()
         .replace("-", "_");
+    if backend.is_some() {
+        test_name.push_str("_jit");
+    }
     let test_name = Ident::new(&test_name, Span::call_site());
 
     let program_file = artifact.to_str().unwrap();
+    let backend = backend.map(|backend| {
+        quote! {
+            machine.hart.backend = #backend;
+            machine.hart.jit_threshold = 1;
+        }
+    });
 
     quote! {
         #[test]
@@ -56,6 +79,7 @@ fn generate_test_for_artifact(artifact: &Path, kernel: TokenStream) -> TokenStre
             let program = include_bytes!(#program_file);
 
             let mut machine = Machine::new(#kernel::default());
+            #backend
             machine.kernel.load_static_elf(&mut machine.hart, &mut machine.mem, program, &[], &[]);
             let res = machine.run();
             assert!(res.is_ok(), "Test failed: {}", res.unwrap_err());
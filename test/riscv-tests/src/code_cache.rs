@@ -0,0 +1,218 @@
+//! Every way guest code can change under the decoded-block cache: each
+//! guest calls a function, replaces it, and calls it again, which must run
+//! the new code.
+#![cfg(test)]
+
+use riscv_kernel_linux::MockLinux64;
+use riscv_vm::{
+    error::{HartError, MachineError},
+    machine::TerminationReason,
+    riscv_inst::Reg,
+};
+
+use crate::guest::*;
+
+const OPENAT: u32 = 56;
+const READ: u32 = 63;
+const MUNMAP: u32 = 215;
+const MREMAP: u32 = 216;
+const MMAP: u32 = 222;
+const RISCV_FLUSH_ICACHE: u32 = 259;
+
+const PAGE: u32 = 0x1000;
+const AT_FDCWD: u32 = -100i32 as u32;
+const PROT_RWX: u32 = 7;
+const MAP_PRIVATE: u32 = 0x02;
+const MAP_FIXED: u32 = 0x10;
+const MAP_ANON: u32 = 0x20;
+const MREMAP_MAYMOVE_FIXED: u32 = 3;
+
+/// `li a0, v; ret`
+fn function(v: i32) -> [u32; 2] {
+    [addi(Reg::A0, Reg::Zero, v), jalr(Reg::Zero, Reg::Ra, 0)]
+}
+
+/// A fresh anonymous read-write-execute page, into `save`.
+fn map_page(save: Reg) -> Vec<u32> {
+    let anon = MAP_PRIVATE | MAP_ANON;
+    syscall(MMAP, &[0, PAGE, PROT_RWX, anon, -1i32 as u32, 0], save)
+}
+
+/// Store [`function`]`(v)` at `at`.
+fn store_function(at: Reg, v: i32) -> Vec<u32> {
+    let mut text = Vec::new();
+    for (i, word) in function(v).into_iter().enumerate() {
+        text.extend(li(Reg::T0, word));
+        text.push(sw(Reg::T0, at, 4 * i as i32));
+    }
+    text
+}
+
+/// Call the function at `at`, its result into `save`.
+fn call_at(at: Reg, save: Reg) -> [u32; 2] {
+    [jalr(Reg::Ra, at, 0), mv(save, Reg::A0)]
+}
+
+/// `openat(AT_FDCWD, "/code")`, the path at [`DATA`], into `save`.
+fn open_code(save: Reg) -> Vec<u32> {
+    syscall(OPENAT, &[AT_FDCWD, DATA as u32, 0, 0], save)
+}
+
+/// A guest that maps a page to `s0` with `function(1)` on it, calls it into
+/// `s1`, runs `replace`, and calls it again into `s2`.
+fn guest(replace: Vec<u32>) -> Vec<u32> {
+    let mut text = map_page(Reg::S0);
+    text.extend(store_function(Reg::S0, 1));
+    text.extend(call_at(Reg::S0, Reg::S1));
+    text.extend(replace);
+    text.extend(call_at(Reg::S0, Reg::S2));
+    text.push(EBREAK);
+    text
+}
+
+/// Run `text` with `/code` holding `function(2)`, expecting the second
+/// call to have run it.
+fn run(text: &[u32]) {
+    let code: Vec<u8> = function(2).iter().flat_map(|w| w.to_le_bytes()).collect();
+    let mut kernel = MockLinux64::new(false);
+    kernel.fs_mut().add_file("/code", code);
+    let mut m = load64_with(kernel, text, b"/code\0");
+    m.run().unwrap();
+
+    assert!(
+        matches!(m.termination(), Some(TerminationReason::Ebreak { .. })),
+        "{:?}",
+        m.termination()
+    );
+    assert_eq!(m.hart.get_reg(Reg::S1), 1);
+    assert_eq!(m.hart.get_reg(Reg::S2), 2);
+}
+
+/// Read `function(2)` over the function, behind the hart's back.
+fn read_code() -> Vec<u32> {
+    let mut text = open_code(Reg::S3);
+    text.extend([mv(Reg::A0, Reg::S3), mv(Reg::A1, Reg::S0)]);
+    text.extend(li(Reg::A2, 8));
+    text.extend(call(READ));
+    text
+}
+
+#[test]
+fn more_blocks_than_a_chunk() {
+    const BLOCKS: usize = 3000;
+    let mut text = li(Reg::S1, 2).to_vec();
+    text.push(auipc(Reg::S2, 0));
+    for _ in 0..BLOCKS {
+        text.extend([addi(Reg::A0, Reg::A0, 1), beq(Reg::Zero, Reg::Zero, 4)]);
+    }
+    text.extend([
+        addi(Reg::S1, Reg::S1, -1),
+        beq(Reg::S1, Reg::Zero, 8),
+        jalr(Reg::Zero, Reg::S2, 4),
+        EBREAK,
+    ]);
+    let mut m = load64(&text, &[]);
+    m.run().unwrap();
+
+    assert_eq!(m.hart.get_reg(Reg::A0), 2 * BLOCKS as u64);
+}
+
+#[test]
+fn store_into_code() {
+    run(&guest(store_function(Reg::S0, 2)));
+}
+
+#[test]
+fn misaligned_store_into_code() {
+    // `slti a0, zero, 2; ret`, returning 1, at the start of the second of
+    // two pages below the image, so on the lowest code page; a word
+    // straddling in from the first rewrites its low half to `addi`'s, so it
+    // returns 2.
+    let low = BASE as u32 - 2 * PAGE;
+    let fixed = MAP_PRIVATE | MAP_FIXED | MAP_ANON;
+    let mut text = syscall(
+        MMAP,
+        &[low, 2 * PAGE, PROT_RWX, fixed, -1i32 as u32, 0],
+        Reg::A0,
+    );
+    text.extend(li(Reg::S0, low + PAGE));
+    let [_, ret] = function(2);
+    for (i, word) in [slti(Reg::A0, Reg::Zero, 2), ret].into_iter().enumerate() {
+        text.extend(li(Reg::T0, word));
+        text.push(sw(Reg::T0, Reg::S0, 4 * i as i32));
+    }
+    text.extend(call_at(Reg::S0, Reg::S1));
+    text.extend(li(Reg::T0, function(2)[0] << 16));
+    text.push(sw(Reg::T0, Reg::S0, -2));
+    text.extend(call_at(Reg::S0, Reg::S2));
+    text.push(EBREAK);
+    run(&text);
+}
+
+#[test]
+fn fence_i() {
+    let mut replace = read_code();
+    replace.push(FENCE_I);
+    run(&guest(replace));
+}
+
+#[test]
+fn riscv_flush_icache() {
+    let mut replace = read_code();
+    replace.extend([mv(Reg::A0, Reg::S0), addi(Reg::A1, Reg::S0, 8)]);
+    replace.extend(li(Reg::A2, 0));
+    replace.extend(call(RISCV_FLUSH_ICACHE));
+    run(&guest(replace));
+}
+
+#[test]
+fn mmap_over_code() {
+    let mut replace = open_code(Reg::S3);
+    replace.push(mv(Reg::A0, Reg::S0));
+    replace.extend(li(Reg::A1, PAGE));
+    replace.extend(li(Reg::A2, PROT_RWX));
+    replace.extend(li(Reg::A3, MAP_PRIVATE | MAP_FIXED));
+    replace.push(mv(Reg::A4, Reg::S3));
+    replace.extend(li(Reg::A5, 0));
+    replace.extend(call(MMAP));
+    run(&guest(replace));
+}
+
+#[test]
+fn mremap_onto_code() {
+    // `function(2)` on a second page, moved over the first.
+    let mut replace = map_page(Reg::S3);
+    replace.extend(store_function(Reg::S3, 2));
+    replace.push(mv(Reg::A0, Reg::S3));
+    replace.extend(li(Reg::A1, PAGE));
+    replace.extend(li(Reg::A2, PAGE));
+    replace.extend(li(Reg::A3, MREMAP_MAYMOVE_FIXED));
+    replace.push(mv(Reg::A4, Reg::S0));
+    replace.extend(call(MREMAP));
+    run(&guest(replace));
+}
+
+#[test]
+fn munmap_code() {
+    let mut text = map_page(Reg::S0);
+    text.extend(store_function(Reg::S0, 1));
+    text.extend(call_at(Reg::S0, Reg::S1));
+    text.push(mv(Reg::A0, Reg::S0));
+    text.extend(li(Reg::A1, PAGE));
+    text.extend(call(MUNMAP));
+    // Gone: it reads as zeros rather than running the cached block.
+    text.extend(call_at(Reg::S0, Reg::S2));
+    text.push(EBREAK);
+    let mut m = load64(&text, &[]);
+    let err = m.run().unwrap_err();
+
+    assert_eq!(m.hart.get_reg(Reg::S1), 1);
+    let addr = m.hart.get_reg(Reg::S0);
+    let MachineError::Hart(err) = err else {
+        panic!("{err:?}");
+    };
+    assert!(
+        matches!(*err, HartError::IllegalInst { addr: a, inst: 0 } if a == addr),
+        "{err:?}"
+    );
+}
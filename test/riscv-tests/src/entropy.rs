@@ -0,0 +1,64 @@
+//! Guest entropy from a pinned seed: `AT_RANDOM` and `getrandom` the same
+//! from run to run, and apart from any other seed's.
+#![cfg(test)]
+
+use riscv_kernel_linux::{Entropy, MockLinux64};
+use riscv_vm::{machine::TerminationReason, memory::Memory, riscv_inst::Reg};
+
+use crate::guest::*;
+
+const GETRANDOM: u32 = 278;
+
+const AT_RANDOM: u64 = 25;
+
+/// The `AT_RANDOM` blob and 32 bytes of `getrandom` a guest drawing from
+/// `entropy` sees.
+fn draw(entropy: Entropy) -> (Vec<u8>, Vec<u8>) {
+    let mut text = syscall(GETRANDOM, &[DATA as u32, 32, 0], Reg::S1);
+    text.push(EBREAK);
+    let kernel = MockLinux64::new(false).with_entropy(entropy);
+    let mut m = load64_with(kernel, &text, &[0; 32]);
+
+    // Past `argc`, `argv` and `envp` (each ending in 0) to the auxv pairs.
+    let word = |addr: u64| m.mem.load::<u64>(addr).unwrap();
+    let sp = m.hart.get_reg(Reg::Sp);
+    let mut p = sp + 8 * (word(sp) + 2);
+    while word(p) != 0 {
+        p += 8;
+    }
+    p += 8;
+    while word(p) != AT_RANDOM {
+        assert_ne!(word(p), 0, "no AT_RANDOM");
+        p += 16;
+    }
+    let at_random = m.mem.slice::<u8>(word(p + 8), 16).unwrap().to_vec();
+
+    m.run().unwrap();
+    assert!(
+        matches!(m.termination(), Some(TerminationReason::Ebreak { .. })),
+        "{:?}",
+        m.termination()
+    );
+    assert_eq!(m.hart.get_reg(Reg::S1), 32);
+    (at_random, m.mem.slice::<u8>(DATA, 32).unwrap().to_vec())
+}
+
+#[test]
+fn same_seed_same_bytes() {
+    let drawn = draw(Entropy::seeded(7));
+
+    assert_eq!(draw(Entropy::seeded(7)), drawn);
+    let (at_random, random) = drawn;
+    // One stream: `getrandom` carries on past `AT_RANDOM`.
+    assert_ne!(at_random[..], random[..16]);
+    assert_ne!(random, [0; 32]);
+}
+
+#[test]
+fn different_seeds_differ() {
+    let (at_random, random) = draw(Entropy::seeded(7));
+    let (other_at_random, other_random) = draw(Entropy::seeded(8));
+
+    assert_ne!(at_random, other_at_random);
+    assert_ne!(random, other_random);
+}
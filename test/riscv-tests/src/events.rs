@@ -0,0 +1,120 @@
+//! `eventfd` counters and `epoll` waits: what a guest's event loop sees of
+//! readiness, timeouts and the counter behind it.
+#![cfg(test)]
+
+use std::time::{Duration, Instant};
+
+use riscv_kernel_linux::MockLinux64;
+use riscv_vm::{
+    machine::{Machine, TerminationReason},
+    memory::Memory,
+    riscv_inst::Reg,
+};
+
+use crate::guest::*;
+
+const EVENTFD2: u32 = 19;
+const EPOLL_CREATE1: u32 = 20;
+const EPOLL_CTL: u32 = 21;
+const EPOLL_PWAIT: u32 = 22;
+const READ: u32 = 63;
+const WRITE: u32 = 64;
+
+const EAGAIN: u64 = 11;
+const EINVAL: u64 = 22;
+
+const EFD_SEMAPHORE: u32 = 1;
+const EFD_NONBLOCK: u32 = 0o4000;
+const EPOLL_CTL_ADD: u32 = 1;
+const EPOLLIN: u32 = 1;
+
+/// Run `text` to its `ebreak` with `words` at [`DATA`], retrying the
+/// calls it parks on.
+fn run(text: &[u32], words: &[u64]) -> Machine<MockLinux64> {
+    let mut text = text.to_vec();
+    text.push(EBREAK);
+    let data: Vec<u8> = words.iter().flat_map(|w| w.to_le_bytes()).collect();
+    let mut m = load64(&text, &data);
+    while m.state.is_running() {
+        m.run().unwrap();
+    }
+    assert!(
+        matches!(m.termination(), Some(TerminationReason::Ebreak { .. })),
+        "{:?}",
+        m.termination()
+    );
+    m
+}
+
+/// The `n`th word at [`DATA`].
+fn word(n: u32) -> u32 {
+    DATA as u32 + 8 * n
+}
+
+#[test]
+fn eventfd_counter() {
+    // 5, plus 2, read whole.
+    let mut text = syscall(EVENTFD2, &[5, EFD_NONBLOCK], Reg::S0);
+    text.extend(syscall(WRITE, &[3, word(0), 8], Reg::S1));
+    text.extend(syscall(READ, &[3, word(4), 8], Reg::S2));
+    text.extend(syscall(READ, &[3, word(4), 8], Reg::S3));
+    // Up to the maximum, one below `u64::MAX`, and no further.
+    text.extend(syscall(WRITE, &[3, word(1), 8], Reg::S4));
+    text.extend(syscall(WRITE, &[3, word(0), 8], Reg::S5));
+    text.extend(syscall(WRITE, &[3, word(2), 8], Reg::S6));
+    // A semaphore reads one at a time.
+    text.extend(syscall(
+        EVENTFD2,
+        &[2, EFD_SEMAPHORE | EFD_NONBLOCK],
+        Reg::S7,
+    ));
+    text.extend(syscall(READ, &[4, word(5), 8], Reg::S8));
+    text.extend(syscall(READ, &[4, word(6), 8], Reg::S9));
+    text.extend(syscall(READ, &[4, word(6), 8], Reg::S10));
+    let m = run(&text, &[2, u64::MAX - 1, u64::MAX, 0, 0, 0, 0]);
+
+    let reg = |r| m.hart.get_reg(r);
+    assert_eq!(reg(Reg::S0), 3);
+    assert_eq!(reg(Reg::S1), 8);
+    assert_eq!(reg(Reg::S2), 8);
+    assert_eq!(reg(Reg::S3), EAGAIN.wrapping_neg());
+    assert_eq!(reg(Reg::S4), 8);
+    assert_eq!(reg(Reg::S5), EAGAIN.wrapping_neg());
+    assert_eq!(reg(Reg::S6), EINVAL.wrapping_neg());
+    assert_eq!(reg(Reg::S7), 4);
+    assert_eq!(reg(Reg::S8), 8);
+    assert_eq!(reg(Reg::S9), 8);
+    assert_eq!(reg(Reg::S10), EAGAIN.wrapping_neg());
+    assert_eq!(*m.mem.slice::<u64>(DATA + 32, 3).unwrap(), [7, 1, 1]);
+}
+
+#[test]
+fn epoll_pwait_times_out_then_sees_ready_fd() {
+    // Word 0 is the `epoll_event` registered (two words), word 2 the one
+    // returned, word 4 the count written.
+    let mut text = syscall(EPOLL_CREATE1, &[0], Reg::S0);
+    text.extend(syscall(EVENTFD2, &[0, EFD_NONBLOCK], Reg::S1));
+    text.extend(syscall(EPOLL_CTL, &[3, EPOLL_CTL_ADD, 4, word(0)], Reg::S2));
+    text.extend(syscall(EPOLL_PWAIT, &[3, word(2), 1, 20, 0, 8], Reg::S3));
+    text.extend(syscall(WRITE, &[4, word(4), 8], Reg::S4));
+    // Forever, but ready now.
+    let forever = -1i32 as u32;
+    text.extend(syscall(
+        EPOLL_PWAIT,
+        &[3, word(2), 1, forever, 0, 8],
+        Reg::S5,
+    ));
+    let start = Instant::now();
+    let m = run(&text, &[EPOLLIN as u64, 0x1234, 0, 0, 1]);
+
+    assert!(start.elapsed() >= Duration::from_millis(20));
+    let reg = |r| m.hart.get_reg(r);
+    assert_eq!([reg(Reg::S0), reg(Reg::S1), reg(Reg::S2)], [3, 4, 0]);
+    assert_eq!(reg(Reg::S3), 0);
+    assert_eq!(reg(Reg::S4), 8);
+    assert_eq!(reg(Reg::S5), 1);
+    assert_eq!(
+        *m.mem.slice::<u64>(DATA + 16, 2).unwrap(),
+        [EPOLLIN as u64, 0x1234]
+    );
+}
@@ -0,0 +1,73 @@
+//! Fused pairs must not forward a value through x0: the second instruction
+//! reads x0 as zero whatever the first computed.
+#![cfg(test)]
+
+use riscv_vm::{machine::TerminationReason, memory::Memory, riscv_inst::Reg};
+
+use crate::guest::*;
+
+const MARKER: u32 = 0x1234_5678;
+
+/// Each candidate pair with x0 as the link, ending in a jump to an
+/// `ebreak` at 0x40.
+fn program() -> Vec<u32> {
+    let mut text = vec![lui(Reg::A1, 0xfedc_b000)];
+    text.extend([auipc(Reg::Zero, 0x1000), lw(Reg::A0, Reg::Zero, 8)]);
+    text.extend([lui(Reg::Zero, 0x5000), addi(Reg::Zero, Reg::Zero, 7)]);
+    text.push(add(Reg::A2, Reg::Zero, Reg::Zero));
+    text.extend([slli(Reg::Zero, Reg::A1, 16), srli(Reg::Zero, Reg::Zero, 16)]);
+    text.push(add(Reg::A3, Reg::Zero, Reg::Zero));
+    text.extend([auipc(Reg::Zero, 0x2000), jalr(Reg::Ra, Reg::Zero, 0x40)]);
+    text
+}
+
+/// Where the final `jalr` links to.
+fn link() -> u64 {
+    ENTRY + 4 * program().len() as u64
+}
+
+#[test]
+fn pairs_through_x0_rv32() {
+    let mut m = load32(&program(), &[]);
+    m.mem.copy_to(8, &MARKER.to_le_bytes()).unwrap();
+    m.mem.copy_to(0x40, &EBREAK.to_le_bytes()).unwrap();
+    m.run().unwrap();
+
+    assert_eq!(
+        m.termination(),
+        Some(&TerminationReason::Ebreak { pc: 0x40 })
+    );
+    assert_eq!(m.hart.get_reg(Reg::A0), MARKER);
+    assert_eq!(m.hart.get_reg(Reg::A2), 0);
+    assert_eq!(m.hart.get_reg(Reg::A3), 0);
+    assert_eq!(m.hart.get_reg(Reg::Ra) as u64, link());
+}
+
+#[test]
+fn pairs_through_x0_rv64() {
+    let mut text = program();
+    // rv64's zero-extension idiom, at its own shift.
+    let at = text.len() - 2;
+    text.splice(
+        at..at,
+        [
+            slli(Reg::Zero, Reg::A1, 32),
+            srli(Reg::Zero, Reg::Zero, 32),
+            add(Reg::A4, Reg::Zero, Reg::Zero),
+        ],
+    );
+    let mut m = load64(&text, &[]);
+    m.mem.copy_to(8u64, &MARKER.to_le_bytes()).unwrap();
+    m.mem.copy_to(0x40u64, &EBREAK.to_le_bytes()).unwrap();
+    m.run().unwrap();
+
+    assert_eq!(
+        m.termination(),
+        Some(&TerminationReason::Ebreak { pc: 0x40 })
+    );
+    assert_eq!(m.hart.get_reg(Reg::A0), MARKER as u64);
+    assert_eq!(m.hart.get_reg(Reg::A2), 0);
+    assert_eq!(m.hart.get_reg(Reg::A3), 0);
+    assert_eq!(m.hart.get_reg(Reg::A4), 0);
+    assert_eq!(m.hart.get_reg(Reg::Ra), ENTRY + 4 * text.len() as u64);
+}
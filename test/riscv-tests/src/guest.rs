@@ -0,0 +1,195 @@
+//! Hand-assembled guests: encoders for the instructions the tests use, and
+//! a static ELF around them for `MockLinux` to load.
+#![cfg(test)]
+
+use riscv_kernel_linux::{MockLinux32, MockLinux64};
+use riscv_vm::{machine::Machine, riscv_inst::Reg};
+
+/// Where the image is loaded; the code starts `0x100` in, as the linkers
+/// put it.
+pub const BASE: u64 = 0x10000;
+pub const ENTRY: u64 = BASE + 0x100;
+/// A page of zeroed data after the code, writable, for loads and stores.
+pub const DATA: u64 = BASE + 0x10000;
+
+fn r(f7: u32, rs2: Reg, rs1: Reg, f3: u32, rd: Reg, op: u32) -> u32 {
+    f7 << 25 | (rs2 as u32) << 20 | (rs1 as u32) << 15 | f3 << 12 | (rd as u32) << 7 | op
+}
+
+fn i(imm: i32, rs1: Reg, f3: u32, rd: Reg, op: u32) -> u32 {
+    (imm as u32 & 0xfff) << 20 | (rs1 as u32) << 15 | f3 << 12 | (rd as u32) << 7 | op
+}
+
+/// `lui rd, imm >> 12`: `imm` is the value loaded.
+pub fn lui(rd: Reg, imm: u32) -> u32 {
+    imm & !0xfff | (rd as u32) << 7 | 0x37
+}
+
+/// `auipc rd, imm >> 12`: `imm` is the offset added to pc.
+pub fn auipc(rd: Reg, imm: u32) -> u32 {
+    imm & !0xfff | (rd as u32) << 7 | 0x17
+}
+
+/// `lui` + `addi` loading `val` (sign-extended on rv64).
+pub fn li(rd: Reg, val: u32) -> [u32; 2] {
+    let hi = val.wrapping_add(0x800) & !0xfff;
+    [lui(rd, hi), addi(rd, rd, val.wrapping_sub(hi) as i32)]
+}
+
+pub fn addi(rd: Reg, rs1: Reg, imm: i32) -> u32 {
+    i(imm, rs1, 0, rd, 0x13)
+}
+
+pub fn slti(rd: Reg, rs1: Reg, imm: i32) -> u32 {
+    i(imm, rs1, 2, rd, 0x13)
+}
+
+pub fn slli(rd: Reg, rs1: Reg, shamt: u32) -> u32 {
+    i(shamt as i32, rs1, 1, rd, 0x13)
+}
+
+pub fn srli(rd: Reg, rs1: Reg, shamt: u32) -> u32 {
+    i(shamt as i32, rs1, 5, rd, 0x13)
+}
+
+/// `mv rd, rs`
+pub fn mv(rd: Reg, rs: Reg) -> u32 {
+    addi(rd, rs, 0)
+}
+
+pub fn add(rd: Reg, rs1: Reg, rs2: Reg) -> u32 {
+    r(0, rs2, rs1, 0, rd, 0x33)
+}
+
+pub fn lw(rd: Reg, rs1: Reg, imm: i32) -> u32 {
+    i(imm, rs1, 2, rd, 0x03)
+}
+
+pub fn sw(rs2: Reg, rs1: Reg, imm: i32) -> u32 {
+    let imm = imm as u32;
+    (imm >> 5 & 0x7f) << 25
+        | (rs2 as u32) << 20
+        | (rs1 as u32) << 15
+        | 2 << 12
+        | (imm & 0x1f) << 7
+        | 0x23
+}
+
+pub fn beq(rs1: Reg, rs2: Reg, imm: i32) -> u32 {
+    let imm = imm as u32;
+    (imm >> 12 & 1) << 31
+        | (imm >> 5 & 0x3f) << 25
+        | (rs2 as u32) << 20
+        | (rs1 as u32) << 15
+        | (imm >> 1 & 0xf) << 8
+        | (imm >> 11 & 1) << 7
+        | 0x63
+}
+
+pub fn jalr(rd: Reg, rs1: Reg, imm: i32) -> u32 {
+    i(imm, rs1, 0, rd, 0x67)
+}
+
+pub const ECALL: u32 = 0x0000_0073;
+pub const EBREAK: u32 = 0x0010_0073;
+pub const FENCE_I: u32 = 0x0000_100f;
+
+/// `li a7, nr; ecall`, with the arguments already in place.
+pub fn call(nr: u32) -> [u32; 3] {
+    let [a, b] = li(Reg::A7, nr);
+    [a, b, ECALL]
+}
+
+/// Syscall `nr` with `args` in `a0`.., its result then copied to `save`.
+pub fn syscall(nr: u32, args: &[u32], save: Reg) -> Vec<u32> {
+    const ARGS: [Reg; 6] = [Reg::A0, Reg::A1, Reg::A2, Reg::A3, Reg::A4, Reg::A5];
+    let mut text: Vec<u32> = ARGS
+        .iter()
+        .zip(args)
+        .flat_map(|(&r, &v)| li(r, v))
+        .collect();
+    text.extend(call(nr));
+    text.push(mv(save, Reg::A0));
+    text
+}
+
+/// A static ELF of `bits` loading `text` at [`ENTRY`] and `data` at
+/// [`DATA`], both in one read-write-execute segment with a zeroed page
+/// after the data.
+pub fn elf(bits: u32, text: &[u32], data: &[u8]) -> Vec<u8> {
+    let mut image = vec![0u8; (DATA - BASE) as usize];
+    let code: Vec<u8> = text.iter().flat_map(|w| w.to_le_bytes()).collect();
+    let at = (ENTRY - BASE) as usize;
+    image[at..at + code.len()].copy_from_slice(&code);
+    image.extend_from_slice(data);
+    let (filesz, memsz) = (image.len() as u64, image.len() as u64 + 0x1000);
+
+    let mut out = Vec::new();
+    let ident = |class: u8| {
+        [
+            0x7f, b'E', b'L', b'F', class, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        ]
+    };
+    if bits == 32 {
+        out.extend(ident(1));
+        out.extend(2u16.to_le_bytes()); // ET_EXEC
+        out.extend(243u16.to_le_bytes()); // EM_RISCV
+        out.extend(1u32.to_le_bytes());
+        out.extend((ENTRY as u32).to_le_bytes());
+        out.extend(52u32.to_le_bytes()); // e_phoff
+        out.extend(0u32.to_le_bytes());
+        out.extend(0u32.to_le_bytes());
+        for half in [52u16, 32, 1, 0, 0, 0] {
+            out.extend(half.to_le_bytes());
+        }
+        for word in [1, 0, BASE, BASE, filesz, memsz, 7, 0x1000] {
+            out.extend((word as u32).to_le_bytes());
+        }
+    } else {
+        out.extend(ident(2));
+        out.extend(2u16.to_le_bytes());
+        out.extend(243u16.to_le_bytes());
+        out.extend(1u32.to_le_bytes());
+        out.extend(ENTRY.to_le_bytes());
+        out.extend(64u64.to_le_bytes());
+        out.extend(0u64.to_le_bytes());
+        out.extend(0u32.to_le_bytes());
+        for half in [64u16, 56, 1, 0, 0, 0] {
+            out.extend(half.to_le_bytes());
+        }
+        out.extend(1u32.to_le_bytes()); // PT_LOAD
+        out.extend(7u32.to_le_bytes());
+        for word in [0, BASE, BASE, filesz, memsz, 0x1000] {
+            out.extend(word.to_le_bytes());
+        }
+    }
+    // The image's first page holds the headers, as linkers lay it out.
+    image[..out.len()].copy_from_slice(&out);
+    image
+}
+
+/// An rv32 machine with `text` and `data` loaded, ready to run.
+pub fn load32(text: &[u32], data: &[u8]) -> Machine<MockLinux32> {
+    load32_with(MockLinux32::new(false), text, data)
+}
+
+/// [`load32`] under `kernel`.
+pub fn load32_with(kernel: MockLinux32, text: &[u32], data: &[u8]) -> Machine<MockLinux32> {
+    let mut m = Machine::new(kernel);
+    m.kernel
+        .load_static_elf(&mut m.hart, &mut m.mem, &elf(32, text, data), &[], &[]);
+    m
+}
+
+/// The rv64 counterpart of [`load32`].
+pub fn load64(text: &[u32], data: &[u8]) -> Machine<MockLinux64> {
+    load64_with(MockLinux64::new(false), text, data)
+}
+
+/// [`load64`] under `kernel`.
+pub fn load64_with(kernel: MockLinux64, text: &[u32], data: &[u8]) -> Machine<MockLinux64> {
+    let mut m = Machine::new(kernel);
+    m.kernel
+        .load_static_elf(&mut m.hart, &mut m.mem, &elf(64, text, data), &[], &[]);
+    m
+}
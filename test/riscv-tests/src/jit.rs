@@ -0,0 +1,201 @@
+//! The JIT backend against the interpreter: each guest runs under both,
+//! every block compiled on its first entry and run compiled from its
+//! second, and must end in the same state.
+#![cfg(test)]
+
+use riscv_kernel_linux::MockLinux32;
+use riscv_vm::{
+    hart::Backend,
+    machine::{Machine, TerminationReason},
+    memory::Memory,
+    riscv_inst::Reg,
+};
+
+use crate::guest::*;
+
+const MMAP: u32 = 222;
+
+const PAGE: u32 = 0x1000;
+const PROT_RW: u32 = 3;
+const MAP_PRIVATE: u32 = 0x02;
+const MAP_FIXED: u32 = 0x10;
+const MAP_ANON: u32 = 0x20;
+
+/// `op rd, rs1, rs2` from the M extension, by its `funct3`.
+fn m_ext(f3: u32, rd: Reg, rs1: Reg, rs2: Reg) -> u32 {
+    1 << 25 | (rs2 as u32) << 20 | (rs1 as u32) << 15 | f3 << 12 | (rd as u32) << 7 | 0x33
+}
+
+/// `body` run `times` times, counting `s1` down, each pass entering it at
+/// the same pc: the first compiles its blocks, the rest run them compiled.
+fn repeat(times: u32, body: &[u32]) -> Vec<u32> {
+    let mut text = li(Reg::S1, times).to_vec();
+    // Branch to the body, so its first block starts there on every pass.
+    text.extend([auipc(Reg::S2, 0), beq(Reg::Zero, Reg::Zero, 4)]);
+    text.extend(body);
+    text.extend([
+        addi(Reg::S1, Reg::S1, -1),
+        beq(Reg::S1, Reg::Zero, 8),
+        jalr(Reg::Zero, Reg::S2, 8),
+        EBREAK,
+    ]);
+    text
+}
+
+/// Run `text` under both backends, expecting the same end, registers, pc,
+/// instruction count and data page; returns the interpreter's machine.
+fn differ(text: &[u32]) -> Machine<MockLinux32> {
+    let run = |backend| {
+        let mut m = load32(text, &[]);
+        m.hart.backend = backend;
+        m.hart.jit_threshold = 1;
+        let res = m.run();
+        (format!("{res:?}"), m)
+    };
+    let (interp_res, interp) = run(Backend::Interpreter);
+    let (jit_res, jit) = run(Backend::Jit);
+
+    assert_eq!(interp_res, jit_res);
+    assert_eq!(interp.termination(), jit.termination());
+    assert_eq!(interp.hart.pc, jit.hart.pc);
+    assert_eq!(interp.hart.inst_count, jit.hart.inst_count);
+    assert_eq!(
+        interp.hart.regs().collect::<Vec<_>>(),
+        jit.hart.regs().collect::<Vec<_>>()
+    );
+    let page = |m: &Machine<MockLinux32>| m.mem.slice::<u8>(DATA, PAGE as u64).unwrap().to_vec();
+    assert_eq!(page(&interp), page(&jit));
+    interp
+}
+
+fn ebreak(m: &Machine<MockLinux32>) {
+    assert!(
+        matches!(m.termination(), Some(TerminationReason::Ebreak { .. })),
+        "{:?}",
+        m.termination()
+    );
+}
+
+#[test]
+fn mul_div_edge_cases() {
+    const PAIRS: [(i32, i32); 8] = [
+        (i32::MIN, -1),
+        (i32::MIN, 0),
+        (7, 0),
+        (0, 0),
+        (-7, 2),
+        (7, -2),
+        (-1, -1),
+        (-1, 1),
+    ];
+    // Operands and results in registers compiled code keeps in host
+    // registers and in ones it does not, aliased every way.
+    const FORMS: [[Reg; 5]; 6] = [
+        // a, b, rd, rs1, rs2
+        [Reg::T0, Reg::T1, Reg::T2, Reg::T0, Reg::T1],
+        [Reg::T0, Reg::T1, Reg::T0, Reg::T0, Reg::T1],
+        [Reg::T0, Reg::T1, Reg::T1, Reg::T0, Reg::T1],
+        [Reg::A4, Reg::A5, Reg::A6, Reg::A4, Reg::A4],
+        [Reg::S6, Reg::S7, Reg::S8, Reg::S6, Reg::Zero],
+        [Reg::S6, Reg::S7, Reg::Zero, Reg::S6, Reg::S7],
+    ];
+    let mut body = li(Reg::S3, DATA as u32).to_vec();
+    for (a, b) in PAIRS {
+        for f3 in 0..8 {
+            for [ra, rb, rd, rs1, rs2] in FORMS {
+                body.extend(li(ra, a as u32));
+                body.extend(li(rb, b as u32));
+                body.push(m_ext(f3, rd, rs1, rs2));
+                body.extend([sw(rd, Reg::S3, 0), addi(Reg::S3, Reg::S3, 4)]);
+            }
+        }
+    }
+    ebreak(&differ(&repeat(2, &body)));
+}
+
+#[test]
+fn self_modifying_code() {
+    let anon = MAP_PRIVATE | MAP_ANON;
+    let mut text = syscall(MMAP, &[0, PAGE, 7, anon, -1i32 as u32, 0], Reg::S0);
+    // And one at `s6`, on the lowest code page, below the image: `slti a0,
+    // zero, 2` then `addi a0, zero, 2` each pass, its low half rewritten by
+    // a word straddling in from the page below.
+    let (low, fixed) = (BASE as u32 - 2 * PAGE, MAP_PRIVATE | MAP_FIXED | MAP_ANON);
+    text.extend(syscall(
+        MMAP,
+        &[low, 2 * PAGE, 7, fixed, -1i32 as u32, 0],
+        Reg::A0,
+    ));
+    text.extend(li(Reg::S6, low + PAGE));
+    text.extend(li(Reg::T4, addi(Reg::A0, Reg::Zero, 2)));
+    text.push(sw(Reg::T4, Reg::S6, 0));
+    text.extend(li(Reg::T4, addi(Reg::A0, Reg::Zero, 2) << 16));
+    text.extend(li(Reg::T5, slti(Reg::A0, Reg::Zero, 2) << 16));
+    // A function at `s0`, `li a0, 0; ret`, whose immediate each pass
+    // rewrites before calling it.
+    text.extend(li(Reg::T0, jalr(Reg::Zero, Reg::Ra, 0)));
+    text.extend([sw(Reg::T0, Reg::S0, 4), sw(Reg::T0, Reg::S6, 4)]);
+    text.extend(li(Reg::T3, addi(Reg::A1, Reg::A1, 16)));
+    let mut body = li(Reg::T0, addi(Reg::A0, Reg::Zero, 0)).to_vec();
+    body.extend([
+        slli(Reg::T1, Reg::S1, 20),
+        add(Reg::T0, Reg::T0, Reg::T1),
+        sw(Reg::T0, Reg::S0, 0),
+        FENCE_I,
+        jalr(Reg::Ra, Reg::S0, 0),
+        add(Reg::S4, Reg::S4, Reg::A0),
+        // A block storing into itself, past the store.
+        auipc(Reg::S5, 0),
+        sw(Reg::T3, Reg::S5, 12),
+        addi(Reg::A1, Reg::A1, 1),
+        addi(Reg::A1, Reg::A1, 1),
+    ]);
+    text.extend(repeat(4, &body));
+    // Then, with no fence flushing the blocks each pass, the stores run
+    // compiled. The first loop's exit falls through into the second.
+    text.pop();
+    text.extend(repeat(
+        4,
+        &[
+            sw(Reg::T5, Reg::S6, -2),
+            jalr(Reg::Ra, Reg::S6, 0),
+            add(Reg::S7, Reg::S7, Reg::A0),
+            sw(Reg::T4, Reg::S6, -2),
+            jalr(Reg::Ra, Reg::S6, 0),
+            add(Reg::S7, Reg::S7, Reg::A0),
+        ],
+    ));
+    let m = differ(&text);
+    ebreak(&m);
+    assert_eq!(m.hart.get_reg(Reg::S7), 12);
+}
+
+#[test]
+fn address_wrap() {
+    let fixed = MAP_PRIVATE | MAP_FIXED | MAP_ANON;
+    let top = 0u32.wrapping_sub(PAGE);
+    let mut text = syscall(MMAP, &[top, PAGE, PROT_RW, fixed, -1i32 as u32, 0], Reg::S0);
+    text.extend(li(Reg::T1, 0x1234_5678));
+    let mut body = li(Reg::S3, DATA as u32).to_vec();
+    body.extend([
+        // 0x10 - 0x14 and 0 - 4: the last word.
+        addi(Reg::T0, Reg::Zero, 0x10),
+        sw(Reg::T1, Reg::T0, -0x14),
+        lw(Reg::T2, Reg::Zero, -4),
+        // -16 + 12: the last word again.
+        addi(Reg::T0, Reg::Zero, -16),
+        lw(Reg::T4, Reg::T0, 12),
+        sw(Reg::T4, Reg::S3, 0),
+    ]);
+    body.extend(li(Reg::T5, 0xffff_f800));
+    // 0xffff_f800 + 0x7fc, the last word once more; then the page's first
+    // word, through both ends of it.
+    body.extend([
+        lw(Reg::T6, Reg::T5, 0x7fc),
+        sw(Reg::T6, Reg::S3, 4),
+        sw(Reg::T1, Reg::T5, -0x800),
+        lw(Reg::A3, Reg::S0, 0),
+    ]);
+    text.extend(repeat(2, &body));
+    ebreak(&differ(&text));
+}
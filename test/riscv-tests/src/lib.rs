@@ -1 +1,18 @@
+mod code_cache;
+mod entropy;
+mod events;
+mod fusion;
+mod guest;
 mod isa_tests;
+mod jit;
+mod memory;
+mod mm;
+mod pipes;
+mod policy;
+mod procfs;
+mod sched;
+mod suspend;
+mod syscalls;
+mod termination;
+mod threads;
+mod trace;
@@ -0,0 +1,248 @@
+//! Guest memory backends other than the full-space arena, as guests see
+//! them. The [`Paged32`] ones also run under Miri:
+//! `cargo +nightly miri test -p riscv-tests paged_`.
+#![cfg(test)]
+
+use riscv_kernel_linux::MockLinux;
+use riscv_vm::{
+    hart::{Backend, X32, X64},
+    machine::{Machine, TerminationReason},
+    memory::{Device, Fault, Memory, Memory32, Memory64, Mmio, Paged32, Window32, PAGE_SIZE},
+    riscv_inst::Reg,
+};
+
+use crate::guest::*;
+
+const PIPE2: u32 = 59;
+const WRITE: u32 = 64;
+
+const O_NONBLOCK: u32 = 0o4000;
+
+const WINDOW: u32 = 16 << 20;
+
+/// The usual layout: a UART below RAM.
+const UART: u32 = 0x1000_0000;
+const RAM: u32 = 0x8000_0000;
+const PASSES: u32 = 1000;
+
+/// Store to `addr`, load it back into `s0`, and stop.
+fn poke(addr: u32) -> Vec<u32> {
+    let mut text = li(Reg::T0, addr).to_vec();
+    text.extend(li(Reg::T1, 0x5a5a_5a5a));
+    text.extend([sw(Reg::T1, Reg::T0, 0), lw(Reg::S0, Reg::T0, 0), EBREAK]);
+    text
+}
+
+/// `text` run to its end on `mem`, whether it faults or not.
+fn run<M: Memory<Addr = u32>>(mem: M, text: &[u32]) -> Machine<MockLinux<X32, M>> {
+    let mut m = Machine::with_memory(MockLinux::new(false), mem);
+    m.kernel
+        .load_static_elf(&mut m.hart, &mut m.mem, &elf(32, text, &[]), &[], &[]);
+    let _ = m.run();
+    m
+}
+
+#[test]
+fn window_ends_at_its_size() {
+    let last = WINDOW - 4;
+    let m = run(Window32::new(WINDOW as u64), &poke(last));
+    assert!(
+        matches!(m.termination(), Some(TerminationReason::Ebreak { .. })),
+        "{:?}",
+        m.termination()
+    );
+    assert_eq!(m.hart.get_reg(Reg::S0), 0x5a5a_5a5a);
+    // The stack was placed inside it.
+    assert!((m.hart.get_reg(Reg::Sp) as u64) < m.mem.max_addr());
+
+    let m = run(Window32::new(WINDOW as u64), &poke(WINDOW));
+    let Some(&TerminationReason::Fault { addr, .. }) = m.termination() else {
+        panic!("{:?}", m.termination());
+    };
+    assert_eq!(addr, Some(WINDOW as u64));
+
+    // The full space has no such end.
+    let m = run(Memory32::new(), &poke(WINDOW));
+    assert_eq!(m.hart.get_reg(Reg::S0), 0x5a5a_5a5a);
+}
+
+#[test]
+fn paged_writes_huge_buffers_a_chunk_at_a_time() {
+    // A nonblocking pipe takes what fits of a 3.75 GiB write, without the
+    // whole buffer copied out first.
+    let mut text = syscall(PIPE2, &[DATA as u32, O_NONBLOCK], Reg::S0);
+    text.extend(li(Reg::T0, DATA as u32));
+    text.push(lw(Reg::A0, Reg::T0, 4));
+    text.extend(li(Reg::A1, DATA as u32 + 8));
+    text.extend(li(Reg::A2, 0xf000_0000));
+    text.extend(call(WRITE));
+    text.extend([mv(Reg::S1, Reg::A0), EBREAK]);
+    let m = run(Paged32::new(), &text);
+
+    assert!(
+        matches!(m.termination(), Some(TerminationReason::Ebreak { .. })),
+        "{:?}",
+        m.termination()
+    );
+    assert_eq!(m.hart.get_reg(Reg::S0), 0);
+    assert_eq!(m.hart.get_reg(Reg::S1), 16 * PAGE_SIZE as u32);
+}
+
+#[test]
+fn paged_copy_within_overlapping_pages() {
+    let len = 3 * PAGE_SIZE;
+    let pattern: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+    for (src, dst) in [(100, 5000), (5000, 100), (100, 100 + 4 * PAGE_SIZE)] {
+        let mut mem = Paged32::new();
+        let mut model = vec![0; 8 * PAGE_SIZE];
+        mem.copy_to(src as u64, &pattern).unwrap();
+        model[src..src + len].copy_from_slice(&pattern);
+
+        mem.copy_within(src as u64, dst as u64, len as u64).unwrap();
+        model.copy_within(src..src + len, dst);
+        assert_eq!(*mem.slice::<u8>(0, model.len() as u64).unwrap(), *model);
+    }
+}
+
+/// Reads as how many reads it has had; keeps its last store.
+#[derive(Default)]
+struct Counter {
+    reads: u32,
+    stored: u32,
+}
+
+impl Device for Counter {
+    fn read(&mut self, _offset: u64, _size: usize) -> Result<u64, Fault> {
+        self.reads += 1;
+        Ok(self.reads as u64)
+    }
+
+    fn write(&mut self, _offset: u64, _size: usize, val: u64) -> Result<(), Fault> {
+        self.stored = val as u32;
+        Ok(())
+    }
+}
+
+/// Sum [`PASSES`] reads of the [`Counter`] at [`UART`] into `s0`, storing
+/// each sum to RAM above it and just below it, and the last to it.
+fn polling() -> Vec<u32> {
+    let mut text = li(Reg::S1, PASSES).to_vec();
+    text.extend(li(Reg::T0, RAM));
+    text.extend(li(Reg::T1, UART));
+    text.extend([
+        lw(Reg::T2, Reg::T1, 0),
+        add(Reg::S0, Reg::S0, Reg::T2),
+        sw(Reg::S0, Reg::T0, 0),
+        sw(Reg::S0, Reg::T1, -4),
+        addi(Reg::S1, Reg::S1, -1),
+        beq(Reg::S1, Reg::Zero, 8),
+        beq(Reg::Zero, Reg::Zero, -24),
+        sw(Reg::S0, Reg::T1, 0),
+        EBREAK,
+    ]);
+    text
+}
+
+fn polled<M: Memory<Addr = u32>>(ram: M, backend: Backend) {
+    let mem = Mmio::new(ram).with_device(UART as u64, 8, Counter::default());
+    let mut m = Machine::with_memory(MockLinux::<X32, _>::new(false), mem);
+    m.hart.backend = backend;
+    m.hart.jit_threshold = 1;
+    m.kernel
+        .load_static_elf(&mut m.hart, &mut m.mem, &elf(32, &polling(), &[]), &[], &[]);
+    m.run().unwrap();
+
+    let sum = PASSES * (PASSES + 1) / 2;
+    assert_eq!(m.hart.get_reg(Reg::S0), sum);
+    assert_eq!(m.mem.load_at::<u32>(RAM as u64).unwrap(), sum);
+    assert_eq!(m.mem.load_at::<u32>(UART as u64 - 4).unwrap(), sum);
+    let uart = m.mem.device::<Counter>(UART as u64).unwrap();
+    assert_eq!((uart.reads, uart.stored), (PASSES, sum));
+}
+
+#[test]
+fn mmio_devices_between_ram() {
+    polled(Memory32::new(), Backend::Interpreter);
+    polled(
+        Window32::new(RAM as u64 + WINDOW as u64),
+        Backend::Interpreter,
+    );
+}
+
+#[test]
+fn mmio_devices_between_ram_compiled() {
+    polled(Memory32::new(), Backend::Jit);
+}
+
+#[test]
+fn paged_mmio_devices_between_ram() {
+    polled(Paged32::new(), Backend::Interpreter);
+}
+
+/// Where a jump to the [`Counter`] at [`UART`] faulted, and the reads it
+/// had by then.
+fn fetched<M: Memory>(end: Option<&TerminationReason>, mem: &Mmio<M>) -> (Option<u64>, u32) {
+    let Some(&TerminationReason::Fault { addr, .. }) = end else {
+        panic!("{end:?}");
+    };
+    (addr, mem.device::<Counter>(UART as u64).unwrap().reads)
+}
+
+#[test]
+fn mmio_devices_hold_no_code() {
+    let mut text = li(Reg::T1, UART).to_vec();
+    text.push(jalr(Reg::Zero, Reg::T1, 0));
+    let mem = Mmio::new(Memory32::new()).with_device(UART as u64, 8, Counter::default());
+    let m = run(mem, &text);
+    assert_eq!(fetched(m.termination(), &m.mem), (Some(UART as u64), 0));
+
+    let mem = Mmio::new(Memory64::default()).with_device(UART as u64, 8, Counter::default());
+    let mut m = Machine::with_memory(MockLinux::<X64, _>::new(false), mem);
+    m.kernel
+        .load_static_elf(&mut m.hart, &mut m.mem, &elf(64, &text, &[]), &[], &[]);
+    let _ = m.run();
+    assert_eq!(fetched(m.termination(), &m.mem), (Some(UART as u64), 0));
+}
+
+/// `c.li s0, 7`.
+const C_LI_S0_7: u16 = 0x441d;
+const C_EBREAK: u16 = 0x9002;
+
+/// A jump to `c.li s0, 7` and then `last`, the two bytes just below the
+/// [`Counter`] at [`UART`], run to its end or a step at a time.
+fn below_device(last: u16, stepped: bool) -> Machine<MockLinux<X32, Mmio<Memory32>>> {
+    let mut text = li(Reg::T1, UART - 4).to_vec();
+    text.push(jalr(Reg::Zero, Reg::T1, 0));
+    let mem = Mmio::new(Memory32::new()).with_device(UART as u64, 8, Counter::default());
+    let mut m = Machine::with_memory(MockLinux::<X32, _>::new(false), mem);
+    m.kernel
+        .load_static_elf(&mut m.hart, &mut m.mem, &elf(32, &text, &[]), &[], &[]);
+    let code = (last as u32) << 16 | C_LI_S0_7 as u32;
+    m.mem.store::<u32>(UART - 4, code).unwrap();
+    if stepped {
+        while m.state.is_running() && m.step().is_ok() {}
+    } else {
+        let _ = m.run();
+    }
+    m
+}
+
+#[test]
+fn mmio_compressed_code_ends_at_a_device() {
+    for stepped in [false, true] {
+        let m = below_device(C_EBREAK, stepped);
+        let ebreak = TerminationReason::Ebreak {
+            pc: UART as u64 - 2,
+        };
+        assert_eq!(m.termination(), Some(&ebreak), "stepped: {stepped}");
+        assert_eq!(m.hart.get_reg(Reg::S0), 7);
+        assert_eq!(m.mem.device::<Counter>(UART as u64).unwrap().reads, 0);
+
+        // The lower half of a full instruction, whose upper half is the
+        // device's.
+        let m = below_device(0x0073, stepped);
+        let straddled = Some(UART as u64 - 2);
+        assert_eq!(fetched(m.termination(), &m.mem), (straddled, 0));
+        assert_eq!(m.hart.get_reg(Reg::S0), 7);
+    }
+}
@@ -0,0 +1,283 @@
+//! The guest's address space as the VMA tree keeps it: mappings split,
+//! grown, moved and placed in holes, and shared with the files under them,
+//! seen through what the guest and the filesystem read back.
+#![cfg(test)]
+
+use riscv_kernel_linux::{Limits, MockLinux64};
+use riscv_vm::{
+    machine::{Machine, TerminationReason},
+    riscv_inst::Reg,
+};
+
+use crate::guest::*;
+
+const OPENAT: u32 = 56;
+const WRITE: u32 = 64;
+const MUNMAP: u32 = 215;
+const MREMAP: u32 = 216;
+const MMAP: u32 = 222;
+const MSYNC: u32 = 227;
+
+const ENOMEM: u64 = 12;
+const EEXIST: u64 = 17;
+
+const AT_FDCWD: u32 = -100i32 as u32;
+const O_RDWR: u32 = 2;
+const PAGE: u32 = 0x1000;
+const PROT_RW: u32 = 3;
+const MAP_SHARED: u32 = 0x01;
+const MAP_PRIVATE: u32 = 0x02;
+const MAP_FIXED: u32 = 0x10;
+const MAP_ANON: u32 = 0x20;
+const MAP_FIXED_NOREPLACE: u32 = 0x10_0000;
+const MREMAP_MAYMOVE: u32 = 1;
+const MS_SYNC: u32 = 4;
+
+/// `mmap(0, len)`, private and anonymous, into `save`.
+fn map(len: u32, save: Reg) -> Vec<u32> {
+    let anon = MAP_PRIVATE | MAP_ANON;
+    syscall(MMAP, &[0, len, PROT_RW, anon, -1i32 as u32, 0], save)
+}
+
+/// Syscall `nr` on `off` bytes past `base` and `args`, its result into
+/// `save`.
+fn at(nr: u32, base: Reg, off: u32, args: &[u32], save: Reg) -> Vec<u32> {
+    const ARGS: [Reg; 5] = [Reg::A1, Reg::A2, Reg::A3, Reg::A4, Reg::A5];
+    let mut text = li(Reg::T0, off).to_vec();
+    text.push(add(Reg::A0, base, Reg::T0));
+    text.extend(ARGS.iter().zip(args).flat_map(|(&r, &v)| li(r, v)));
+    text.extend(call(nr));
+    text.push(mv(save, Reg::A0));
+    text
+}
+
+/// `MAP_FIXED_NOREPLACE` of a page `off` bytes past `base`, into `save`.
+fn noreplace(base: Reg, off: u32, save: Reg) -> Vec<u32> {
+    let flags = MAP_PRIVATE | MAP_ANON | MAP_FIXED_NOREPLACE;
+    at(
+        MMAP,
+        base,
+        off,
+        &[PAGE, PROT_RW, flags, -1i32 as u32, 0],
+        save,
+    )
+}
+
+/// Store `v` `off` bytes past `base`.
+fn poke(base: Reg, off: u32, v: u32) -> Vec<u32> {
+    let mut text = li(Reg::T0, off).to_vec();
+    text.push(add(Reg::T0, base, Reg::T0));
+    text.extend(li(Reg::T1, v));
+    text.push(sw(Reg::T1, Reg::T0, 0));
+    text
+}
+
+/// Load the word `off` bytes past `base` into `save`.
+fn peek(base: Reg, off: u32, save: Reg) -> Vec<u32> {
+    let mut text = li(Reg::T0, off).to_vec();
+    text.extend([add(Reg::T0, base, Reg::T0), lw(save, Reg::T0, 0)]);
+    text
+}
+
+/// Run `text` to its `ebreak`.
+fn run(text: Vec<u32>) -> Machine<MockLinux64> {
+    run_with(MockLinux64::new(false), text, &[])
+}
+
+/// [`run`] under `kernel`, with `data` at [`DATA`].
+fn run_with(kernel: MockLinux64, mut text: Vec<u32>, data: &[u8]) -> Machine<MockLinux64> {
+    text.push(EBREAK);
+    let mut m = load64_with(kernel, &text, data);
+    m.run().unwrap();
+    assert!(
+        matches!(m.termination(), Some(TerminationReason::Ebreak { .. })),
+        "{:?}",
+        m.termination()
+    );
+    m
+}
+
+#[test]
+fn munmap_splits_a_mapping() {
+    let mut text = map(3 * PAGE, Reg::S1);
+    for page in 0..3 {
+        text.extend(poke(Reg::S1, page * PAGE, page + 1));
+    }
+    text.extend(at(MUNMAP, Reg::S1, PAGE, &[PAGE], Reg::S2));
+    // Only the middle page is free now.
+    text.extend(noreplace(Reg::S1, 0, Reg::S3));
+    text.extend(noreplace(Reg::S1, 2 * PAGE, Reg::S4));
+    text.extend(noreplace(Reg::S1, PAGE, Reg::S5));
+    text.extend(peek(Reg::S1, 0, Reg::S6));
+    text.extend(peek(Reg::S1, PAGE, Reg::S7));
+    text.extend(peek(Reg::S1, 2 * PAGE, Reg::S8));
+    let m = run(text);
+
+    let base = m.hart.get_reg(Reg::S1);
+    assert_eq!(m.hart.get_reg(Reg::S2), 0);
+    assert_eq!(m.hart.get_reg(Reg::S3), EEXIST.wrapping_neg());
+    assert_eq!(m.hart.get_reg(Reg::S4), EEXIST.wrapping_neg());
+    assert_eq!(m.hart.get_reg(Reg::S5), base + PAGE as u64);
+    // The ends keep their contents; the page mapped between reads zero.
+    assert_eq!(m.hart.get_reg(Reg::S6), 1);
+    assert_eq!(m.hart.get_reg(Reg::S7), 0);
+    assert_eq!(m.hart.get_reg(Reg::S8), 3);
+}
+
+#[test]
+fn map_fixed_noreplace_only_into_holes() {
+    let mut text = map(PAGE, Reg::S1);
+    text.extend(poke(Reg::S1, 0, 7));
+    text.extend(noreplace(Reg::S1, 0, Reg::S2));
+    text.extend(noreplace(Reg::S1, PAGE, Reg::S3));
+    // MAP_FIXED, by contrast, replaces the page.
+    let fixed = MAP_PRIVATE | MAP_ANON | MAP_FIXED;
+    text.extend(at(
+        MMAP,
+        Reg::S1,
+        0,
+        &[PAGE, PROT_RW, fixed, -1i32 as u32, 0],
+        Reg::S4,
+    ));
+    text.extend(peek(Reg::S1, 0, Reg::S5));
+    let m = run(text);
+
+    let base = m.hart.get_reg(Reg::S1);
+    assert_eq!(m.hart.get_reg(Reg::S2), EEXIST.wrapping_neg());
+    assert_eq!(m.hart.get_reg(Reg::S3), base + PAGE as u64);
+    assert_eq!(m.hart.get_reg(Reg::S4), base);
+    assert_eq!(m.hart.get_reg(Reg::S5), 0);
+}
+
+#[test]
+fn mremap_grows_in_place_or_moves() {
+    // Two pages mapped back to back (rv64 places upwards, lowest first).
+    let mut text = map(PAGE, Reg::S1);
+    text.extend(map(PAGE, Reg::S2));
+    text.extend(poke(Reg::S1, 0, 1));
+    text.extend(poke(Reg::S2, 0, 2));
+    // The first cannot grow into the second, and may not move.
+    text.extend(at(MREMAP, Reg::S1, 0, &[PAGE, 2 * PAGE, 0], Reg::S3));
+    // The second grows in place.
+    text.extend(at(MREMAP, Reg::S2, 0, &[PAGE, 2 * PAGE, 0], Reg::S4));
+    // The first moves, its contents with it.
+    let grow = [PAGE, 2 * PAGE, MREMAP_MAYMOVE];
+    text.extend(at(MREMAP, Reg::S1, 0, &grow, Reg::S5));
+    text.extend(peek(Reg::S5, 0, Reg::S6));
+    text.extend(peek(Reg::S5, PAGE, Reg::S7));
+    text.extend(peek(Reg::S2, 0, Reg::S8));
+    let m = run(text);
+
+    let (first, second) = (m.hart.get_reg(Reg::S1), m.hart.get_reg(Reg::S2));
+    assert_eq!(second, first + PAGE as u64);
+    assert_eq!(m.hart.get_reg(Reg::S3), ENOMEM.wrapping_neg());
+    assert_eq!(m.hart.get_reg(Reg::S4), second);
+    let moved = m.hart.get_reg(Reg::S5);
+    assert!(moved >= second + 2 * PAGE as u64, "{moved:#x}");
+    assert_eq!(m.hart.get_reg(Reg::S6), 1);
+    assert_eq!(m.hart.get_reg(Reg::S7), 0);
+    assert_eq!(m.hart.get_reg(Reg::S8), 2);
+}
+
+#[test]
+fn first_fit_reuses_a_freed_hole() {
+    let mut text = map(PAGE, Reg::S1);
+    text.extend(map(PAGE, Reg::S2));
+    text.extend(map(PAGE, Reg::S3));
+    text.extend(at(MUNMAP, Reg::S2, 0, &[PAGE], Reg::S4));
+    // Too big for the hole: past the last mapping.
+    text.extend(map(2 * PAGE, Reg::S5));
+    // Fits: in it.
+    text.extend(map(PAGE, Reg::S6));
+    let m = run(text);
+
+    let [first, second, third] = [Reg::S1, Reg::S2, Reg::S3].map(|r| m.hart.get_reg(r));
+    assert_eq!(second, first + PAGE as u64);
+    assert_eq!(third, second + PAGE as u64);
+    assert_eq!(m.hart.get_reg(Reg::S4), 0);
+    assert_eq!(m.hart.get_reg(Reg::S5), third + PAGE as u64);
+    assert_eq!(m.hart.get_reg(Reg::S6), second);
+}
+
+#[test]
+fn map_fixed_past_the_memory_limit_keeps_the_old_mapping() {
+    const MIB: u32 = 1 << 20;
+    let fixed = MAP_PRIVATE | MAP_ANON | MAP_FIXED;
+    let mut text = map(16 * MIB, Reg::S1);
+    text.extend(poke(Reg::S1, 0, 7));
+    // Over the limit: fails, the mapping under it untouched.
+    let args = [64 * MIB, PROT_RW, fixed, -1i32 as u32, 0];
+    text.extend(at(MMAP, Reg::S1, 0, &args, Reg::S2));
+    text.extend(peek(Reg::S1, 0, Reg::S3));
+    // As big as what it replaces: fits, though both would not.
+    let args = [16 * MIB, PROT_RW, fixed, -1i32 as u32, 0];
+    text.extend(at(MMAP, Reg::S1, 0, &args, Reg::S4));
+    text.extend(peek(Reg::S1, 0, Reg::S5));
+    let limits = Limits {
+        memory: Some(32 << 20),
+        ..Limits::default()
+    };
+    let m = run_with(MockLinux64::new(false).with_limits(limits), text, &[]);
+
+    let base = m.hart.get_reg(Reg::S1);
+    assert_eq!(m.hart.get_reg(Reg::S2), ENOMEM.wrapping_neg());
+    assert_eq!(m.hart.get_reg(Reg::S3), 7);
+    assert_eq!(m.hart.get_reg(Reg::S4), base);
+    assert_eq!(m.hart.get_reg(Reg::S5), 0);
+}
+
+/// Run `text` after mapping a page of `/f`, zeros, shared at `s2`; returns
+/// the file's first words after.
+fn shared(mut text: Vec<u32>) -> (Machine<MockLinux64>, [u32; 2]) {
+    let mut map = syscall(OPENAT, &[AT_FDCWD, DATA as u32, O_RDWR, 0], Reg::S1);
+    map.extend(syscall(
+        MMAP,
+        &[0, PAGE, PROT_RW, MAP_SHARED, 3, 0],
+        Reg::S2,
+    ));
+    text.splice(..0, map);
+    let mut kernel = MockLinux64::new(false);
+    kernel.fs_mut().add_file("/f", vec![0; PAGE as usize]);
+    // The path, then a word for `write` to take.
+    let mut data = b"/f\0\0\0\0\0\0".to_vec();
+    data.extend(0x2222_2222u32.to_le_bytes());
+    let m = run_with(kernel, text, &data);
+
+    assert_eq!(m.hart.get_reg(Reg::S1), 3);
+    let file = m.kernel.fs().read_file("/f").unwrap();
+    let word = |at: usize| u32::from_le_bytes(file[at..at + 4].try_into().unwrap());
+    (m, [word(0), word(4)])
+}
+
+#[test]
+fn shared_mapping_writes_back_on_msync() {
+    let mut text = poke(Reg::S2, 0, 0x1111_1111);
+    text.extend(at(MSYNC, Reg::S2, 0, &[PAGE, MS_SYNC], Reg::S3));
+    // Not synced: the file does not see it.
+    text.extend(poke(Reg::S2, 4, 0x3333_3333));
+    let (m, words) = shared(text);
+
+    assert_eq!(m.hart.get_reg(Reg::S3), 0);
+    assert_eq!(words, [0x1111_1111, 0]);
+}
+
+#[test]
+fn shared_mapping_writes_back_on_munmap() {
+    let mut text = poke(Reg::S2, 4, 0x1111_1111);
+    text.extend(at(MUNMAP, Reg::S2, 0, &[PAGE], Reg::S3));
+    let (m, words) = shared(text);
+
+    assert_eq!(m.hart.get_reg(Reg::S3), 0);
+    assert_eq!(words, [0, 0x1111_1111]);
+}
+
+#[test]
+fn file_writes_show_in_shared_mapping() {
+    let mut text = syscall(WRITE, &[3, DATA as u32 + 8, 4], Reg::S3);
+    text.extend(peek(Reg::S2, 0, Reg::S4));
+    let (m, words) = shared(text);
+
+    assert_eq!(m.hart.get_reg(Reg::S3), 4);
+    assert_eq!(m.hart.get_reg(Reg::S4), 0x2222_2222);
+    assert_eq!(words, [0x2222_2222, 0]);
+}
@@ -0,0 +1,98 @@
+//! Pipes between the guest's own descriptors: EOF once every write end is
+//! closed, however duplicated, and `EAGAIN` instead of parking when
+//! nonblocking.
+#![cfg(test)]
+
+use riscv_kernel_linux::MockLinux64;
+use riscv_vm::{
+    machine::{Machine, TerminationReason},
+    memory::Memory,
+    riscv_inst::Reg,
+};
+
+use crate::guest::*;
+
+const DUP3: u32 = 24;
+const FCNTL: u32 = 25;
+const CLOSE: u32 = 57;
+const PIPE2: u32 = 59;
+const READ: u32 = 63;
+const WRITE: u32 = 64;
+
+const EAGAIN: u64 = 11;
+
+const F_GETFL: u32 = 3;
+const F_SETFL: u32 = 4;
+const O_NONBLOCK: u32 = 0o4000;
+
+/// Where `pipe2` puts the two descriptors; the bytes written follow.
+const FDS: u32 = DATA as u32;
+const BYTES: u32 = FDS + 8;
+/// Where reads land.
+const BUF: u32 = FDS + 16;
+
+/// `pipe2(FDS, flags)`, then `text`, to its `ebreak`; the pipe is 3 and 4.
+fn run(flags: u32, text: &[u32]) -> Machine<MockLinux64> {
+    let mut all = syscall(PIPE2, &[FDS, flags], Reg::S0);
+    all.extend(text);
+    all.push(EBREAK);
+    let mut m = load64(&all, &[0, 0, 0, 0, 0, 0, 0, 0, b'h', b'i']);
+    m.run().unwrap();
+
+    assert!(
+        matches!(m.termination(), Some(TerminationReason::Ebreak { .. })),
+        "{:?}",
+        m.termination()
+    );
+    assert_eq!(m.hart.get_reg(Reg::S0), 0);
+    assert_eq!(*m.mem.slice::<i32>(DATA, 2).unwrap(), [3, 4]);
+    m
+}
+
+#[test]
+fn eof_once_every_write_end_is_closed() {
+    let mut text = syscall(WRITE, &[4, BYTES, 2], Reg::S1);
+    text.extend(syscall(DUP3, &[4, 10, 0], Reg::S2));
+    text.extend(syscall(CLOSE, &[4], Reg::A0));
+    text.extend(syscall(READ, &[3, BUF, 8], Reg::S3));
+    // Empty, but the duplicate still writes.
+    text.extend(syscall(READ, &[3, BUF, 8], Reg::S4));
+    text.extend(syscall(CLOSE, &[10], Reg::A0));
+    text.extend(syscall(READ, &[3, BUF, 8], Reg::S5));
+    let m = run(O_NONBLOCK, &text);
+
+    assert_eq!(m.hart.get_reg(Reg::S1), 2);
+    assert_eq!(m.hart.get_reg(Reg::S2), 10);
+    assert_eq!(m.hart.get_reg(Reg::S3), 2);
+    assert_eq!(*m.mem.slice::<u8>(BUF as u64, 2).unwrap(), *b"hi");
+    assert_eq!(m.hart.get_reg(Reg::S4), EAGAIN.wrapping_neg());
+    assert_eq!(m.hart.get_reg(Reg::S5), 0);
+}
+
+#[test]
+fn blocking_read_at_eof_returns() {
+    let mut text = syscall(WRITE, &[4, BYTES, 2], Reg::S1);
+    text.extend(syscall(CLOSE, &[4], Reg::A0));
+    text.extend(syscall(READ, &[3, BUF, 8], Reg::S2));
+    text.extend(syscall(READ, &[3, BUF, 8], Reg::S3));
+    let m = run(0, &text);
+
+    assert_eq!(m.hart.get_reg(Reg::S1), 2);
+    assert_eq!(m.hart.get_reg(Reg::S2), 2);
+    assert_eq!(m.hart.get_reg(Reg::S3), 0);
+}
+
+#[test]
+fn nonblocking_through_fcntl() {
+    let mut text = syscall(FCNTL, &[3, F_GETFL], Reg::S1);
+    text.extend(syscall(FCNTL, &[3, F_SETFL, O_NONBLOCK], Reg::S2));
+    text.extend(syscall(FCNTL, &[3, F_GETFL], Reg::S3));
+    // Would park the guest forever, blocking.
+    text.extend(syscall(READ, &[3, BUF, 8], Reg::S4));
+    let m = run(0, &text);
+
+    assert_eq!(m.hart.get_reg(Reg::S1), 0);
+    assert_eq!(m.hart.get_reg(Reg::S2), 0);
+    assert_eq!(m.hart.get_reg(Reg::S3), O_NONBLOCK as u64);
+    assert_eq!(m.hart.get_reg(Reg::S4), EAGAIN.wrapping_neg());
+}
@@ -0,0 +1,230 @@
+//! The syscall policy: each action as the guest and the embedder see it,
+//! rules in the order added, and the argument predicates, paths above all
+//! (the ones a sandbox leans on).
+#![cfg(test)]
+
+use riscv_kernel_linux::{
+    syscalls::riscv64::Sysno, Action, ArgMatch, LinuxError, MockLinux64, SyscallInfo,
+    SyscallPolicy64,
+};
+use riscv_vm::{
+    error::MachineError,
+    machine::{Machine, TerminationReason},
+    memory::Memory,
+    riscv_inst::Reg,
+};
+
+use crate::guest::*;
+
+const OPENAT: u32 = 56;
+const WRITE: u32 = 64;
+const GETPID: u32 = 172;
+
+const EPERM: i32 = 1;
+const EACCES: u64 = 13;
+
+const AT_FDCWD: u32 = -100i32 as u32;
+const O_CREAT: u32 = 0o100;
+const O_DIRECTORY: u32 = 0o200000;
+
+/// Paths for the guest to open, each in 16 bytes.
+const PATHS: [&str; 6] = [
+    "/tmp/a",
+    "/tmpfoo/a",
+    "/tmp/../etc/x",
+    "/tmp",
+    "a",
+    "../etc/x",
+];
+
+/// `text` then `ebreak`, under `policy`, with `/tmp/a`, `/tmpfoo/a` and
+/// `/etc/x` in the filesystem and [`PATHS`] at [`DATA`].
+fn load(policy: SyscallPolicy64, mut text: Vec<u32>) -> Machine<MockLinux64> {
+    text.push(EBREAK);
+    let mut kernel = MockLinux64::new(false).with_policy(policy);
+    for path in ["/tmp/a", "/tmpfoo/a", "/etc/x"] {
+        kernel.fs_mut().add_file(path, *b"x");
+    }
+    let data: Vec<u8> = PATHS
+        .iter()
+        .flat_map(|path| {
+            let mut slot = path.as_bytes().to_vec();
+            slot.resize(16, 0);
+            slot
+        })
+        .collect();
+    load64_with(kernel, &text, &data)
+}
+
+/// Where [`PATHS`]`[i]` is.
+fn path(i: usize) -> u32 {
+    DATA as u32 + 16 * i as u32
+}
+
+fn ebreak(m: &Machine<MockLinux64>) {
+    let end = m.termination();
+    assert!(
+        matches!(end, Some(TerminationReason::Ebreak { .. })),
+        "{end:?}"
+    );
+}
+
+/// The call a trap or kill stopped on.
+fn stopped(err: MachineError<LinuxError>) -> (bool, SyscallInfo) {
+    match err {
+        MachineError::Kernel(LinuxError::Killed(info)) => (true, info),
+        MachineError::Kernel(LinuxError::Trapped(info)) => (false, info),
+        err => panic!("{err:?}"),
+    }
+}
+
+#[test]
+fn kill_ends_the_guest_on_the_call() {
+    let policy = SyscallPolicy64::new(Action::Allow).rule(Sysno::getpid, Action::Kill);
+    let text = syscall(GETPID, &[], Reg::S1);
+    let mut m = load(policy, text);
+    let (killed, info) = stopped(m.run().unwrap_err());
+
+    assert!(killed);
+    assert_eq!((info.nr, info.name), (GETPID as u64, Some("getpid")));
+    assert_eq!(
+        m.termination(),
+        Some(&TerminationReason::PolicyViolation(info.to_string()))
+    );
+    assert!(!m.state.is_running());
+    // Left on the ecall, which never ran.
+    assert_eq!(m.mem.load::<u32>(m.hart.pc).unwrap(), ECALL);
+    assert_eq!(m.hart.get_reg(Reg::S1), 0);
+}
+
+#[test]
+fn trapped_calls_are_completed_or_admitted() {
+    let policy = SyscallPolicy64::new(Action::Allow).rule(Sysno::getpid, Action::Trap);
+    let mut text = syscall(GETPID, &[], Reg::S1);
+    text.extend(syscall(GETPID, &[], Reg::S2));
+    text.extend(syscall(GETPID, &[], Reg::S3));
+    let mut m = load(policy, text);
+
+    let (killed, info) = stopped(m.run().unwrap_err());
+    assert!(!killed);
+    assert_eq!(info.nr, GETPID as u64);
+    assert_eq!(m.termination(), None);
+    m.kernel.complete_syscall(&mut m.hart, Ok(42));
+    stopped(m.run().unwrap_err());
+    m.kernel.complete_syscall(&mut m.hart, Err(EPERM));
+    stopped(m.run().unwrap_err());
+    m.kernel.admit_syscall();
+    m.run().unwrap();
+
+    ebreak(&m);
+    assert_eq!(m.hart.get_reg(Reg::S1), 42);
+    assert_eq!(m.hart.get_reg(Reg::S2), (EPERM as u64).wrapping_neg());
+    // The real pid.
+    assert_eq!(m.hart.get_reg(Reg::S3), 1);
+}
+
+#[test]
+fn errno_from_the_first_matching_rule() {
+    let policy = SyscallPolicy64::new(Action::Kill)
+        .rule_when(
+            Sysno::write,
+            [ArgMatch::Eq { arg: 0, value: 1 }],
+            Action::Allow,
+        )
+        .rule(Sysno::write, Action::Errno(EPERM))
+        // Shadowed by the rule before.
+        .rule_when(
+            Sysno::write,
+            [ArgMatch::Eq { arg: 0, value: 2 }],
+            Action::Kill,
+        );
+    let mut text = syscall(WRITE, &[1, path(0), 1], Reg::S1);
+    text.extend(syscall(WRITE, &[2, path(0), 1], Reg::S2));
+    let mut m = load(policy, text);
+    m.run().unwrap();
+
+    ebreak(&m);
+    assert_eq!(m.hart.get_reg(Reg::S1), 1);
+    assert_eq!(m.hart.get_reg(Reg::S2), (EPERM as u64).wrapping_neg());
+}
+
+#[test]
+fn masked_flags() {
+    let no_create = ArgMatch::Masked {
+        arg: 2,
+        mask: O_CREAT as u64,
+        value: O_CREAT as u64,
+    };
+    let policy = SyscallPolicy64::new(Action::Allow).rule_when(
+        Sysno::openat,
+        [no_create],
+        Action::Errno(EACCES as i32),
+    );
+    let mut text = syscall(OPENAT, &[AT_FDCWD, path(0), O_CREAT, 0o644], Reg::S1);
+    text.extend(syscall(OPENAT, &[AT_FDCWD, path(0), 0, 0], Reg::S2));
+    let mut m = load(policy, text);
+    m.run().unwrap();
+
+    ebreak(&m);
+    assert_eq!(m.hart.get_reg(Reg::S1), EACCES.wrapping_neg());
+    assert_eq!(m.hart.get_reg(Reg::S2), 3);
+}
+
+/// `openat` allowed under `prefix` only, with `EACCES` elsewhere.
+fn under(prefix: &str) -> SyscallPolicy64 {
+    SyscallPolicy64::new(Action::Allow)
+        .rule_when(
+            Sysno::openat,
+            [ArgMatch::path_at_under(prefix)],
+            Action::Allow,
+        )
+        .rule(Sysno::openat, Action::Errno(EACCES as i32))
+}
+
+#[test]
+fn paths_under_a_directory() {
+    let open = |dirfd, path, flags, save| syscall(OPENAT, &[dirfd, path, flags, 0], save);
+    let mut text = open(AT_FDCWD, path(0), 0, Reg::S1);
+    // A sibling sharing the prefix's bytes, and a way out through `..`.
+    text.extend(open(AT_FDCWD, path(1), 0, Reg::S2));
+    text.extend(open(AT_FDCWD, path(2), 0, Reg::S3));
+    // The directory itself, then paths relative to it.
+    text.extend(open(AT_FDCWD, path(3), O_DIRECTORY, Reg::S4));
+    text.extend(open(4, path(4), 0, Reg::S5));
+    text.extend(open(4, path(5), 0, Reg::S6));
+    // Unreadable, past the end of guest memory: no match, so the
+    // catch-all rather than `EFAULT`.
+    text.extend(open(AT_FDCWD, -16i32 as u32, 0, Reg::S7));
+    let mut m = load(under("/tmp"), text);
+    m.run().unwrap();
+
+    ebreak(&m);
+    let denied = EACCES.wrapping_neg();
+    let regs = [
+        Reg::S1,
+        Reg::S2,
+        Reg::S3,
+        Reg::S4,
+        Reg::S5,
+        Reg::S6,
+        Reg::S7,
+    ];
+    assert_eq!(
+        regs.map(|r| m.hart.get_reg(r)),
+        [3, denied, denied, 4, 5, denied, denied]
+    );
+}
+
+#[test]
+fn everything_is_under_the_root() {
+    let mut text = Vec::new();
+    for (i, save) in [Reg::S1, Reg::S2, Reg::S3].into_iter().enumerate() {
+        text.extend(syscall(OPENAT, &[AT_FDCWD, path(i), 0, 0], save));
+    }
+    let mut m = load(under("/"), text);
+    m.run().unwrap();
+
+    ebreak(&m);
+    let regs = [Reg::S1, Reg::S2, Reg::S3];
+    assert_eq!(regs.map(|r| m.hart.get_reg(r)), [3, 4, 5]);
+}
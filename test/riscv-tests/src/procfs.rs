@@ -0,0 +1,133 @@
+//! The kernel's own files, read through the guest: `/proc/self` rendered
+//! from the live address space, and the `/dev` character devices.
+#![cfg(test)]
+
+use riscv_kernel_linux::MockLinux64;
+use riscv_vm::{
+    machine::{Machine, TerminationReason},
+    memory::Memory,
+    riscv_inst::Reg,
+};
+
+use crate::guest::*;
+
+const OPENAT: u32 = 56;
+const READ: u32 = 63;
+const WRITE: u32 = 64;
+const MMAP: u32 = 222;
+
+const AT_FDCWD: u32 = -100i32 as u32;
+const O_RDWR: u32 = 2;
+const PAGE: u64 = 0x1000;
+const PROT_RW: u32 = 3;
+const MAP_PRIVATE: u32 = 0x02;
+const MAP_ANON: u32 = 0x20;
+
+/// Paths for the guest to open, each in 32 bytes at [`DATA`].
+const PATHS: [&str; 4] = [
+    "/proc/self/maps",
+    "/proc/self/status",
+    "/dev/null",
+    "/dev/zero",
+];
+/// Where reads land, after the paths.
+const BUF: u64 = DATA + 0x100;
+const BUF_LEN: u32 = 0x2000;
+
+/// Where [`PATHS`]`[i]` is.
+fn path(i: usize) -> u32 {
+    DATA as u32 + 32 * i as u32
+}
+
+/// Run `text` to its `ebreak` with [`PATHS`] and a [`BUF`] of `0xff`s.
+fn run(text: &[u32]) -> Machine<MockLinux64> {
+    let mut text = text.to_vec();
+    text.push(EBREAK);
+    let mut data = vec![0xff; (BUF - DATA) as usize + BUF_LEN as usize];
+    for (i, path) in PATHS.iter().enumerate() {
+        let slot = &mut data[32 * i..][..32];
+        slot.fill(0);
+        slot[..path.len()].copy_from_slice(path.as_bytes());
+    }
+    let mut m = load64(&text, &data);
+    m.run().unwrap();
+    assert!(
+        matches!(m.termination(), Some(TerminationReason::Ebreak { .. })),
+        "{:?}",
+        m.termination()
+    );
+    m
+}
+
+/// The `len` bytes read into [`BUF`], as text.
+fn read_back(m: &Machine<MockLinux64>, len: u64) -> String {
+    String::from_utf8(m.mem.slice::<u8>(BUF, len).unwrap().to_vec()).unwrap()
+}
+
+#[test]
+fn maps_shows_the_live_address_space() {
+    let anon = MAP_PRIVATE | MAP_ANON;
+    let mut text = syscall(
+        MMAP,
+        &[0, PAGE as u32, PROT_RW, anon, -1i32 as u32, 0],
+        Reg::S1,
+    );
+    text.extend(syscall(OPENAT, &[AT_FDCWD, path(0), 0, 0], Reg::S2));
+    text.extend(syscall(READ, &[3, BUF as u32, BUF_LEN], Reg::S3));
+    let m = run(&text);
+
+    assert_eq!(m.hart.get_reg(Reg::S2), 3);
+    let maps = read_back(&m, m.hart.get_reg(Reg::S3));
+    // Padding collapsed.
+    let lines: Vec<String> = maps
+        .lines()
+        .map(|l| l.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect();
+    // The image, the stack under the mmap base, and the page just mapped.
+    let start = m.hart.get_reg(Reg::S1);
+    assert_eq!(
+        lines,
+        [
+            "00010000-00024000 rwxp 00000000 00:00 0 /tmp/main".to_string(),
+            "1f800000-20000000 rw-p 00000000 00:00 0 [stack]".to_string(),
+            format!("{start:08x}-{:08x} rw-p 00000000 00:00 0", start + PAGE),
+        ],
+        "{maps}"
+    );
+}
+
+#[test]
+fn status_names_the_process() {
+    let mut text = syscall(OPENAT, &[AT_FDCWD, path(1), 0, 0], Reg::S1);
+    text.extend(syscall(READ, &[3, BUF as u32, BUF_LEN], Reg::S2));
+    let m = run(&text);
+
+    assert_eq!(m.hart.get_reg(Reg::S1), 3);
+    let status = read_back(&m, m.hart.get_reg(Reg::S2));
+    let lines: Vec<&str> = status.lines().collect();
+    assert!(lines.contains(&"Name:\tmain"), "{status}");
+    assert!(lines.contains(&"Pid:\t1"), "{status}");
+    assert!(lines.contains(&"Threads:\t1"), "{status}");
+}
+
+#[test]
+fn null_and_zero() {
+    let mut text = syscall(OPENAT, &[AT_FDCWD, path(2), O_RDWR, 0], Reg::S1);
+    text.extend(syscall(READ, &[3, BUF as u32, 16], Reg::S2));
+    text.extend(syscall(WRITE, &[3, BUF as u32, 16], Reg::S3));
+    text.extend(syscall(OPENAT, &[AT_FDCWD, path(3), O_RDWR, 0], Reg::S4));
+    // Half the buffer, leaving the rest as it was.
+    text.extend(syscall(READ, &[4, BUF as u32, 8], Reg::S5));
+    text.extend(syscall(WRITE, &[4, BUF as u32, 16], Reg::S6));
+    let m = run(&text);
+
+    let reg = |r| m.hart.get_reg(r);
+    assert_eq!([reg(Reg::S1), reg(Reg::S4)], [3, 4]);
+    // End of file, and writes swallowed whole.
+    assert_eq!([reg(Reg::S2), reg(Reg::S3)], [0, 16]);
+    assert_eq!([reg(Reg::S5), reg(Reg::S6)], [8, 16]);
+    assert_eq!(
+        *m.mem.slice::<u8>(BUF, 16).unwrap(),
+        [[0; 8], [0xff; 8]].concat()
+    );
+}
@@ -0,0 +1,229 @@
+//! The scheduler with machines that block and machines that panic: parked
+//! machines wait for their deadline or their waker instead of retrying,
+//! and a panic takes down one machine, not the pool.
+#![cfg(test)]
+
+use std::{
+    io::Write,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    task::Waker,
+    thread,
+    time::{Duration, Instant},
+};
+
+use riscv_kernel_linux::{net::Loopback, MockLinux64};
+use riscv_vm::{
+    error::MachineError,
+    hart::{Hart, X64},
+    machine::{Kernel, Machine, StepResult, TerminationReason},
+    memory::Memory64,
+    riscv_inst::Reg,
+    sched::{Scheduler, SchedulerConfig},
+};
+
+use crate::guest::*;
+
+const READ: u32 = 63;
+const PPOLL: u32 = 73;
+const SOCKET: u32 = 198;
+const CONNECT: u32 = 203;
+const GETPID: u32 = 172;
+
+const PORT: u16 = 7000;
+
+/// [`MockLinux64`], counting the syscalls it is handed (retries included),
+/// and panicking on the first if `panics`.
+struct Counted {
+    inner: MockLinux64,
+    calls: Arc<AtomicUsize>,
+    panics: bool,
+}
+
+impl Kernel for Counted {
+    type Xlen = X64;
+    type Memory = Memory64;
+    type Error = <MockLinux64 as Kernel>::Error;
+
+    fn syscall(
+        &mut self,
+        hart: &mut Hart<X64>,
+        mem: &mut Memory64,
+    ) -> Result<StepResult, MachineError<Self::Error>> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        assert!(!self.panics, "the kernel panicked");
+        self.inner.syscall(hart, mem)
+    }
+
+    fn ebreak(
+        &mut self,
+        hart: &mut Hart<X64>,
+        mem: &mut Memory64,
+    ) -> Result<StepResult, MachineError<Self::Error>> {
+        self.inner.ebreak(hart, mem)
+    }
+
+    fn blocked_until(&self) -> Option<Instant> {
+        self.inner.blocked_until()
+    }
+
+    fn wake_on(&mut self, waker: &Waker) -> bool {
+        Kernel::wake_on(&mut self.inner, waker)
+    }
+}
+
+fn machine(inner: MockLinux64, text: &[u32], data: &[u8]) -> (Machine<Counted>, Arc<AtomicUsize>) {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let kernel = Counted {
+        inner,
+        calls: calls.clone(),
+        panics: false,
+    };
+    let mut m = Machine::new(kernel);
+    m.kernel
+        .inner
+        .load_static_elf(&mut m.hart, &mut m.mem, &elf(64, text, data), &[], &[]);
+    (m, calls)
+}
+
+/// Run `m` alone on a pool, returning how it ended.
+fn run(m: Machine<Counted>) -> Option<TerminationReason> {
+    let pool = Scheduler::new(SchedulerConfig {
+        threads: 2,
+        quantum: 1000,
+    });
+    let end = Arc::new(Mutex::new(None));
+    let out = end.clone();
+    pool.spawn(m, move |m, res| {
+        res.unwrap();
+        *out.lock().unwrap() = m.termination().cloned();
+    });
+    pool.join();
+    let end = end.lock().unwrap().take();
+    end
+}
+
+#[test]
+fn sleeps_until_the_deadline() {
+    // ppoll(NULL, 0, {0, 50ms}): nothing to wake it early.
+    let text = [
+        syscall(PPOLL, &[0, 0, DATA as u32, 0, 8], Reg::S1),
+        vec![EBREAK],
+    ]
+    .concat();
+    let data = [0u64.to_le_bytes(), 50_000_000u64.to_le_bytes()].concat();
+    let (m, calls) = machine(MockLinux64::new(false), &text, &data);
+    let start = Instant::now();
+    let end = run(m);
+
+    assert!(
+        matches!(end, Some(TerminationReason::Ebreak { .. })),
+        "{end:?}"
+    );
+    assert!(start.elapsed() >= Duration::from_millis(50));
+    // The first try, the retry after setting the alarm, and the one at the
+    // deadline.
+    assert!(calls.load(Ordering::SeqCst) <= 4, "{calls:?}");
+}
+
+#[test]
+fn woken_by_the_network() {
+    // Connect to the embedder and read a byte it sends later.
+    let mut text = syscall(SOCKET, &[2, 1, 0], Reg::S0);
+    text.extend([mv(Reg::A0, Reg::S0)]);
+    text.extend(li(Reg::A1, DATA as u32));
+    text.extend(li(Reg::A2, 16));
+    text.extend(call(CONNECT));
+    text.extend([mv(Reg::A0, Reg::S0)]);
+    text.extend(li(Reg::A1, DATA as u32 + 16));
+    text.extend(li(Reg::A2, 1));
+    text.extend(call(READ));
+    text.push(EBREAK);
+    let mut data = vec![0; 17];
+    data[..2].copy_from_slice(&2u16.to_le_bytes());
+    data[2..4].copy_from_slice(&PORT.to_be_bytes());
+    data[4..8].copy_from_slice(&[127, 0, 0, 1]);
+
+    let net = Loopback::new();
+    let listener = net.listen(PORT).unwrap();
+    let (m, calls) = machine(MockLinux64::new(false).with_net(net), &text, &data);
+    let sender = thread::spawn(move || {
+        let mut stream = loop {
+            match listener.accept() {
+                Ok(stream) => break stream,
+                Err(_) => thread::sleep(Duration::from_millis(1)),
+            }
+        };
+        thread::sleep(Duration::from_millis(50));
+        stream.write_all(b"x").unwrap();
+        // Held until the guest has read.
+        thread::sleep(Duration::from_millis(50));
+    });
+    let end = run(m);
+    sender.join().unwrap();
+
+    assert!(
+        matches!(end, Some(TerminationReason::Ebreak { .. })),
+        "{end:?}"
+    );
+    // socket, connect, and the read: tried, retried with the alarm set,
+    // and tried again once woken.
+    assert!(calls.load(Ordering::SeqCst) <= 6, "{calls:?}");
+}
+
+#[test]
+fn a_panicking_kernel_ends_its_machine_alone() {
+    let text = [call(GETPID).to_vec(), vec![EBREAK]].concat();
+    let pool = Scheduler::new(SchedulerConfig {
+        threads: 2,
+        quantum: 1000,
+    });
+    let ends = Arc::new(Mutex::new(Vec::new()));
+    for i in 0..8 {
+        let (mut m, _) = machine(MockLinux64::new(false), &text, &[]);
+        m.kernel.panics = i == 3;
+        let ends = ends.clone();
+        pool.spawn(m, move |_, res| {
+            let end = match res {
+                Ok(()) => "ok".to_string(),
+                Err(MachineError::Panicked(msg)) => msg,
+                Err(e) => panic!("{e}"),
+            };
+            ends.lock().unwrap().push(end);
+        });
+    }
+    pool.join();
+
+    let mut ends = ends.lock().unwrap().clone();
+    ends.sort();
+    assert_eq!(ends[..7], ["ok"; 7]);
+    assert_eq!(ends[7], "the kernel panicked");
+}
+
+#[test]
+fn a_panicking_callback_is_raised_by_join() {
+    let pool = Scheduler::new(SchedulerConfig {
+        threads: 2,
+        quantum: 1000,
+    });
+    let completed = Arc::new(AtomicUsize::new(0));
+    for i in 0..8 {
+        let (m, _) = machine(MockLinux64::new(false), &[EBREAK], &[]);
+        let completed = completed.clone();
+        pool.spawn(m, move |_, _| {
+            completed.fetch_add(1, Ordering::SeqCst);
+            assert_ne!(i, 3, "the callback panicked");
+        });
+    }
+    let joined = panic::catch_unwind(AssertUnwindSafe(|| pool.join()));
+
+    let panic = joined.unwrap_err();
+    assert_eq!(
+        panic.downcast_ref::<String>().unwrap(),
+        "assertion `left != right` failed: the callback panicked\n  left: 3\n right: 3"
+    );
+    assert_eq!(completed.load(Ordering::SeqCst), 8);
+}
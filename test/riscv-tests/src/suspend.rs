@@ -0,0 +1,172 @@
+//! `run_async`: suspended calls served by a host future, and calls parked
+//! inside the kernel waiting on the task's waker or their deadline instead
+//! of spinning the executor.
+#![cfg(test)]
+
+use std::{
+    future::{self, Future},
+    io::Write,
+    pin::pin,
+    sync::Arc,
+    task::{Context, Poll, Wake, Waker},
+    thread,
+    time::{Duration, Instant},
+};
+
+use riscv_kernel_linux::{
+    net::Loopback, run_async, syscalls::riscv64::Sysno, Action, MockLinux64, SyscallPolicy,
+};
+use riscv_vm::{
+    machine::{Machine, TerminationReason},
+    memory::Memory,
+    riscv_inst::Reg,
+};
+
+use crate::guest::*;
+
+const READ: u32 = 63;
+const PPOLL: u32 = 73;
+const SOCKET: u32 = 198;
+const CONNECT: u32 = 203;
+
+const EIO: i32 = 5;
+
+const PORT: u16 = 7001;
+
+/// Poll `future` to completion on this thread, parking between wakeups;
+/// returns its output and how many times it was polled.
+fn block_on<F: Future>(future: F) -> (F::Output, usize) {
+    struct Unpark(thread::Thread);
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Waker::from(Arc::new(Unpark(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+    let mut polls = 0;
+    loop {
+        polls += 1;
+        if let Poll::Ready(out) = future.as_mut().poll(&mut cx) {
+            return (out, polls);
+        }
+        thread::park();
+    }
+}
+
+/// Pending once, the waker woken from another thread after `after`.
+async fn later(after: Duration) {
+    let mut waited = false;
+    future::poll_fn(|cx| {
+        if waited {
+            return Poll::Ready(());
+        }
+        waited = true;
+        let waker = cx.waker().clone();
+        thread::spawn(move || {
+            thread::sleep(after);
+            waker.wake();
+        });
+        Poll::Pending
+    })
+    .await
+}
+
+fn ebreak(m: &Machine<MockLinux64>) {
+    let end = m.termination();
+    assert!(
+        matches!(end, Some(TerminationReason::Ebreak { .. })),
+        "{end:?}"
+    );
+}
+
+#[test]
+fn suspended_calls_are_served() {
+    let mut text = syscall(READ, &[0, DATA as u32, 8], Reg::S0);
+    text.extend(syscall(READ, &[0, DATA as u32 + 8, 8], Reg::S1));
+    text.push(EBREAK);
+    let policy = SyscallPolicy::new(Action::Allow).rule(Sysno::read, Action::Suspend);
+    let mut m = load64_with(MockLinux64::new(false).with_policy(policy), &text, &[0; 16]);
+
+    let mut calls = Vec::new();
+    let (res, _) = block_on(run_async(&mut m, async |m, call| {
+        calls.push(call.args[..3].to_vec());
+        later(Duration::from_millis(10)).await;
+        if calls.len() > 1 {
+            return Err(EIO);
+        }
+        m.mem.copy_to(call.args[1], b"host").map_err(|_| EIO)?;
+        Ok(4)
+    }));
+
+    res.unwrap();
+    ebreak(&m);
+    assert_eq!(calls, [[0, DATA, 8], [0, DATA + 8, 8]]);
+    assert_eq!(m.hart.get_reg(Reg::S0), 4);
+    assert_eq!(m.hart.get_reg(Reg::S1), -EIO as u64);
+    assert_eq!(*m.mem.slice::<u8>(DATA, 8).unwrap(), *b"host\0\0\0\0");
+}
+
+#[test]
+fn parked_calls_wait_for_their_deadline() {
+    // ppoll(NULL, 0, {0, 50ms}): nothing to wake it early.
+    let text = [
+        syscall(PPOLL, &[0, 0, DATA as u32, 0, 8], Reg::S1),
+        vec![EBREAK],
+    ]
+    .concat();
+    let data = [0u64.to_le_bytes(), 50_000_000u64.to_le_bytes()].concat();
+    let mut m = load64(&text, &data);
+    let start = Instant::now();
+    let (res, polls) = block_on(run_async(&mut m, async |_, _| unreachable!()));
+
+    res.unwrap();
+    ebreak(&m);
+    assert!(start.elapsed() >= Duration::from_millis(50));
+    assert_eq!(m.hart.get_reg(Reg::S1), 0);
+    assert!(polls <= 4, "{polls}");
+}
+
+#[test]
+fn parked_calls_are_woken_by_the_network() {
+    // Connect to the embedder and read a byte it sends later.
+    let mut text = syscall(SOCKET, &[2, 1, 0], Reg::S0);
+    text.extend([mv(Reg::A0, Reg::S0)]);
+    text.extend(li(Reg::A1, DATA as u32));
+    text.extend(li(Reg::A2, 16));
+    text.extend(call(CONNECT));
+    text.extend([mv(Reg::A0, Reg::S0)]);
+    text.extend(li(Reg::A1, DATA as u32 + 16));
+    text.extend(li(Reg::A2, 1));
+    text.extend(call(READ));
+    text.push(EBREAK);
+    let mut data = vec![0; 17];
+    data[..2].copy_from_slice(&2u16.to_le_bytes());
+    data[2..4].copy_from_slice(&PORT.to_be_bytes());
+    data[4..8].copy_from_slice(&[127, 0, 0, 1]);
+
+    let net = Loopback::new();
+    let listener = net.listen(PORT).unwrap();
+    let mut m = load64_with(MockLinux64::new(false).with_net(net), &text, &data);
+    let sender = thread::spawn(move || {
+        let mut stream = loop {
+            match listener.accept() {
+                Ok(stream) => break stream,
+                Err(_) => thread::sleep(Duration::from_millis(1)),
+            }
+        };
+        thread::sleep(Duration::from_millis(50));
+        stream.write_all(b"x").unwrap();
+        // Held until the guest has read.
+        thread::sleep(Duration::from_millis(50));
+    });
+    let (res, polls) = block_on(run_async(&mut m, async |_, _| unreachable!()));
+    sender.join().unwrap();
+
+    res.unwrap();
+    ebreak(&m);
+    assert_eq!(*m.mem.slice::<u8>(DATA + 16, 1).unwrap(), *b"x");
+    assert!(polls <= 4, "{polls}");
+}
@@ -0,0 +1,149 @@
+//! Syscalls fed hostile arguments: each must fail (or succeed) as Linux
+//! does, not panic the host.
+#![cfg(test)]
+
+use riscv_kernel_linux::{net::Loopback, Limits, MockLinux32, MockLinux64};
+use riscv_vm::{machine::TerminationReason, memory::Memory, riscv_inst::Reg};
+
+use crate::guest::*;
+
+const OPENAT: u32 = 56;
+const LSEEK: u32 = 62;
+const WRITE: u32 = 64;
+const SCHED_GETAFFINITY: u32 = 123;
+const PRLIMIT64: u32 = 261;
+const SOCKET: u32 = 198;
+const BIND: u32 = 200;
+const CONNECT: u32 = 203;
+
+const EINVAL: u32 = 22;
+const EFBIG: u64 = 27;
+const SIGXFSZ: i32 = 25;
+
+const AT_FDCWD: u32 = -100i32 as u32;
+const O_RDWR_CREAT: u32 = 0o102;
+const RLIMIT_FSIZE: u32 = 1;
+
+#[test]
+fn short_sockaddr() {
+    let (inet, inet6) = (DATA as u32, DATA as u32 + 16);
+    let mut text = Vec::new();
+    text.extend(syscall(SOCKET, &[2, 1, 0], Reg::S1)); // AF_INET, fd 3
+    text.extend(syscall(SOCKET, &[10, 1, 0], Reg::S2)); // AF_INET6, fd 4
+                                                        // Long enough for the family, not the port.
+    text.extend(syscall(CONNECT, &[3, inet, 3], Reg::S3));
+    text.extend(syscall(BIND, &[3, inet, 15], Reg::S4));
+    // `sockaddr_in6` without its address.
+    text.extend(syscall(CONNECT, &[4, inet6, 23], Reg::S5));
+    text.push(EBREAK);
+    let mut data = vec![0; 16 + 28];
+    data[0] = 2;
+    data[16] = 10;
+    let kernel = MockLinux32::new(false).with_net(Loopback::new());
+    let mut m = load32_with(kernel, &text, &data);
+    m.run().unwrap();
+
+    assert!(matches!(
+        m.termination(),
+        Some(TerminationReason::Ebreak { .. })
+    ));
+    assert_eq!(m.hart.get_reg(Reg::S1), 3);
+    assert_eq!(m.hart.get_reg(Reg::S2), 4);
+    for r in [Reg::S3, Reg::S4, Reg::S5] {
+        assert_eq!(m.hart.get_reg(r), EINVAL.wrapping_neg(), "{r:?}");
+    }
+}
+
+#[test]
+fn sched_getaffinity_huge_len() {
+    // 1 << 61 bytes, whose bit count overflows.
+    let mut text = vec![addi(Reg::A1, Reg::Zero, 1), slli(Reg::A1, Reg::A1, 61)];
+    text.extend(li(Reg::A0, 0));
+    text.extend(li(Reg::A2, DATA as u32));
+    text.extend(li(Reg::A7, SCHED_GETAFFINITY));
+    text.extend([ECALL, EBREAK]);
+    let mut m = load64(&text, &[]);
+    m.run().unwrap();
+
+    // The kernel's mask size, with the one CPU set.
+    assert_eq!(m.hart.get_reg(Reg::A0), 8);
+    assert_eq!(m.mem.slice::<u8>(DATA, 1).unwrap()[0], 1);
+}
+
+/// `openat(AT_FDCWD, "/f", O_RDWR | O_CREAT)`, the path at [`DATA`], into
+/// `save`.
+fn create(save: Reg) -> Vec<u32> {
+    syscall(OPENAT, &[AT_FDCWD, DATA as u32, O_RDWR_CREAT, 0o644], save)
+}
+
+#[test]
+fn file_size_limit_is_per_file() {
+    let buf = DATA as u32 + 8;
+    let mut text = create(Reg::S1);
+    text.extend(syscall(WRITE, &[3, buf, 60], Reg::S2));
+    // Short, up to the limit.
+    text.extend(syscall(WRITE, &[3, buf, 60], Reg::S3));
+    // Other descriptors, and other files, have no offset to limit.
+    text.extend(syscall(WRITE, &[1, buf, 200], Reg::S4));
+    // Killed, as at the limit.
+    text.extend(syscall(WRITE, &[3, buf, 1], Reg::S5));
+    text.push(EBREAK);
+    let limits = Limits {
+        file_size: Some(100),
+        ..Limits::default()
+    };
+    let mut m = load64_with(
+        MockLinux64::new(false).with_limits(limits),
+        &text,
+        b"/f\0\0\0\0\0\0",
+    );
+    m.run().unwrap();
+
+    assert_eq!(m.termination(), Some(&TerminationReason::Signaled(SIGXFSZ)));
+    assert_eq!(m.hart.get_reg(Reg::S1), 3);
+    assert_eq!(m.hart.get_reg(Reg::S2), 60);
+    assert_eq!(m.hart.get_reg(Reg::S3), 40);
+    assert_eq!(m.hart.get_reg(Reg::S4), 200);
+}
+
+#[test]
+fn output_cap_is_not_an_rlimit() {
+    let mut text = syscall(PRLIMIT64, &[0, RLIMIT_FSIZE, 0, DATA as u32], Reg::S1);
+    text.extend(syscall(WRITE, &[1, DATA as u32, 20], Reg::S2));
+    // Killed, as at the cap.
+    text.extend(syscall(WRITE, &[1, DATA as u32, 1], Reg::S3));
+    text.push(EBREAK);
+    let limits = Limits {
+        output_bytes: Some(10),
+        ..Limits::default()
+    };
+    let mut m = load64_with(MockLinux64::new(false).with_limits(limits), &text, &[]);
+    m.run().unwrap();
+
+    assert_eq!(m.termination(), Some(&TerminationReason::Signaled(SIGXFSZ)));
+    assert_eq!(m.hart.get_reg(Reg::S1), 0);
+    assert_eq!(*m.mem.slice::<u64>(DATA, 2).unwrap(), [u64::MAX; 2]);
+    assert_eq!(m.hart.get_reg(Reg::S2), 10);
+}
+
+#[test]
+fn seek_far_past_the_end() {
+    let mut text = create(Reg::S1);
+    // lseek(3, 1 << 40, SEEK_SET)
+    text.extend([addi(Reg::A1, Reg::Zero, 1), slli(Reg::A1, Reg::A1, 40)]);
+    text.extend(li(Reg::A0, 3));
+    text.extend(li(Reg::A2, 0));
+    text.extend(li(Reg::A7, LSEEK));
+    text.push(ECALL);
+    text.extend(syscall(WRITE, &[3, DATA as u32, 1], Reg::S2));
+    text.push(EBREAK);
+    let mut m = load64(&text, b"/f\0");
+    m.run().unwrap();
+
+    // No signal: the filesystem's limit, not the guest's.
+    assert!(matches!(
+        m.termination(),
+        Some(TerminationReason::Ebreak { .. })
+    ));
+    assert_eq!(m.hart.get_reg(Reg::S2), EFBIG.wrapping_neg());
+}
@@ -0,0 +1,89 @@
+//! Why a guest ended, as the machine reports it after `run`: the two ways
+//! of exiting, running out of instructions, and faulting.
+#![cfg(test)]
+
+use riscv_kernel_linux::{Limits, MockLinux64};
+use riscv_vm::{
+    machine::{Machine, RunSummary, TerminationReason},
+    riscv_inst::Reg,
+};
+
+use crate::guest::*;
+
+const EXIT: u32 = 93;
+const EXIT_GROUP: u32 = 94;
+
+/// Run `text` under `kernel` until it halts, and whether `run` failed.
+fn run(kernel: MockLinux64, text: &[u32]) -> (Machine<MockLinux64>, bool) {
+    let mut m = load64_with(kernel, text, &[]);
+    let failed = m.run().is_err();
+    assert!(!m.state.is_running());
+    (m, failed)
+}
+
+#[test]
+fn exit_and_exit_group() {
+    let (exit, _) = run(MockLinux64::new(false), &syscall(EXIT, &[3], Reg::A0));
+    let (group, _) = run(MockLinux64::new(false), &syscall(EXIT_GROUP, &[4], Reg::A0));
+
+    assert_eq!(exit.termination(), Some(&TerminationReason::Exited(3)));
+    assert_eq!(exit.kernel.exit_code(), Some(3));
+    assert_eq!(group.termination(), Some(&TerminationReason::ExitGroup(4)));
+    assert_eq!(group.kernel.exit_code(), Some(4));
+    assert_eq!(
+        group.summary(),
+        RunSummary {
+            // Loading `a0` and `a7`; the `ecall` never returns.
+            instructions: 4,
+            // The stack, and the image up to its (empty) data page.
+            peak_memory: Some((8 << 20) + (DATA - BASE) + 0x1000),
+            termination: Some(TerminationReason::ExitGroup(4)),
+        }
+    );
+}
+
+#[test]
+fn out_of_instructions() {
+    let limits = Limits {
+        instructions: Some(1000),
+        ..Limits::default()
+    };
+    // Spin forever.
+    let spin = [beq(Reg::Zero, Reg::Zero, 0)];
+    let (m, failed) = run(MockLinux64::new(false).with_limits(limits), &spin);
+
+    assert!(!failed);
+    assert_eq!(m.termination(), Some(&TerminationReason::FuelExhausted));
+    assert_eq!(m.summary().instructions, 1000);
+    assert_eq!(m.termination().unwrap().to_string(), "ran out of fuel");
+}
+
+#[test]
+fn faults() {
+    // A load past the end of memory, on the address.
+    let mut text = li(Reg::T0, -16i32 as u32).to_vec();
+    text.push(lw(Reg::T1, Reg::T0, 0));
+    let (m, failed) = run(MockLinux64::new(false), &text);
+
+    assert!(failed);
+    let load = TerminationReason::Fault {
+        pc: ENTRY + 8,
+        addr: Some(-16i64 as u64),
+    };
+    assert_eq!(m.termination(), Some(&load));
+    assert_eq!(
+        load.to_string(),
+        "fault at pc 0x10108 accessing 0xfffffffffffffff0"
+    );
+
+    // An illegal instruction, on none.
+    let (m, failed) = run(MockLinux64::new(false), &[0]);
+
+    assert!(failed);
+    let illegal = TerminationReason::Fault {
+        pc: ENTRY,
+        addr: None,
+    };
+    assert_eq!(m.termination(), Some(&illegal));
+    assert_eq!(m.summary().instructions, 0);
+}
@@ -0,0 +1,135 @@
+//! Machines across host threads: one moved between threads mid-run ends as
+//! if it had stayed on one, and a paused one reads the same from every
+//! thread through [`Machine::inspect`]. The [`Paged32`] ones need no
+//! `mmap`, so they also run under Miri:
+//! `cargo +nightly miri test -p riscv-tests paged_`.
+#![cfg(test)]
+
+use std::thread;
+
+use riscv_kernel_linux::MockLinux;
+use riscv_vm::{
+    hart::X32,
+    machine::Machine,
+    memory::{Memory, Memory32, Paged32},
+    riscv_inst::Reg,
+};
+
+use crate::guest::*;
+
+const PASSES: u32 = 1000;
+/// Instructions a pass of [`counting`] takes.
+const PASS: u64 = 6;
+
+/// Count `s1` down from [`PASSES`], summing it into `s0` and storing each
+/// sum after the last.
+fn counting() -> Vec<u32> {
+    let mut text = li(Reg::S1, PASSES).to_vec();
+    text.extend(li(Reg::S3, DATA as u32));
+    text.extend([
+        add(Reg::S0, Reg::S0, Reg::S1),
+        sw(Reg::S0, Reg::S3, 0),
+        addi(Reg::S3, Reg::S3, 4),
+        addi(Reg::S1, Reg::S1, -1),
+        beq(Reg::S1, Reg::Zero, 8),
+        beq(Reg::Zero, Reg::Zero, -20),
+        EBREAK,
+    ]);
+    text
+}
+
+fn machine<M>() -> Machine<MockLinux<X32, M>>
+where
+    M: Memory<Addr = u32> + Default,
+{
+    let mut m = Machine::new(MockLinux::<X32, M>::new(false));
+    let data = vec![0; 4 * PASSES as usize];
+    m.kernel.load_static_elf(
+        &mut m.hart,
+        &mut m.mem,
+        &elf(32, &counting(), &data),
+        &[],
+        &[],
+    );
+    m
+}
+
+/// Registers and the stored sums.
+fn state<M: Memory<Addr = u32>>(m: &Machine<MockLinux<X32, M>>) -> (Vec<u32>, Vec<u8>) {
+    let regs = m.hart.regs().map(|(_, v)| v).collect();
+    let sums = m.mem.slice::<u8>(DATA, 4 * PASSES as u64).unwrap().to_vec();
+    (regs, sums)
+}
+
+fn moved_between_threads<M>()
+where
+    M: Memory<Addr = u32> + Default + Send + 'static,
+{
+    let mut still = machine::<M>();
+    still.run().unwrap();
+
+    let mut moved = machine::<M>();
+    let mut hops = 0;
+    while moved.state.is_running() {
+        moved = thread::spawn(move || {
+            moved.run_for(PASS * 7).unwrap();
+            moved
+        })
+        .join()
+        .unwrap();
+        hops += 1;
+    }
+    assert!(hops > 100, "{hops}");
+    assert_eq!(moved.termination(), still.termination());
+    assert_eq!(moved.hart.inst_count, still.hart.inst_count);
+    assert_eq!(state(&moved), state(&still));
+}
+
+fn inspected_from_threads<M>()
+where
+    M: Memory<Addr = u32> + Default + Sync,
+{
+    let mut m = machine::<M>();
+    m.run_for(PASS * (PASSES as u64 / 2)).unwrap();
+    let expected = state(&m);
+    let sums = expected.1.iter().filter(|&&b| b != 0).count();
+    assert!(0 < sums && sums < expected.1.len());
+
+    let view = m.inspect();
+    thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                for _ in 0..100 {
+                    let regs: Vec<_> = view.hart.regs().map(|(_, v)| v).collect();
+                    let sums = view.mem.slice::<u8>(DATA, 4 * PASSES as u64).unwrap();
+                    assert_eq!(regs, expected.0);
+                    assert_eq!(*sums, *expected.1);
+                }
+            });
+        }
+    });
+
+    // And it resumes where it paused.
+    m.run().unwrap();
+    assert_eq!(m.hart.get_reg(Reg::S0), PASSES * (PASSES + 1) / 2);
+}
+
+#[test]
+fn arena_moved_between_threads() {
+    moved_between_threads::<Memory32>();
+}
+
+#[test]
+fn paged_moved_between_threads() {
+    moved_between_threads::<Paged32>();
+}
+
+#[test]
+fn arena_inspected_from_threads() {
+    inspected_from_threads::<Memory32>();
+}
+
+#[test]
+fn paged_inspected_from_threads() {
+    inspected_from_threads::<Paged32>();
+}
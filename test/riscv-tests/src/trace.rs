@@ -0,0 +1,141 @@
+//! Syscall tracing: the arguments each call decodes to, read back from the
+//! guest after it ran, and the strace line they render as.
+#![cfg(test)]
+
+use std::sync::mpsc;
+
+use riscv_kernel_linux::{ArgValue, MockLinux64, SyscallEvent};
+use riscv_vm::{machine::TerminationReason, riscv_inst::Reg};
+
+use crate::guest::*;
+
+const OPENAT: u32 = 56;
+const READ: u32 = 63;
+const WRITEV: u32 = 66;
+
+const ENOENT: i32 = 2;
+
+const AT_FDCWD: u32 = -100i32 as u32;
+const O_CLOEXEC: u32 = 0o2000000;
+
+/// The paths, then the `iovec`s `writev` takes and the bytes they point
+/// at, then where `read` lands.
+const FILE: u32 = DATA as u32;
+const MISSING: u32 = FILE + 16;
+const IOVS: u32 = FILE + 32;
+const BYTES: u32 = FILE + 64;
+const BUF: u32 = FILE + 128;
+
+/// Run `text` to its `ebreak`, with `/f` holding `hello\n` and the data
+/// above, and return what the tracer saw.
+fn trace(text: &[u32]) -> Vec<SyscallEvent> {
+    let mut text = text.to_vec();
+    text.push(EBREAK);
+    let mut data = vec![0; 128];
+    data[..2].copy_from_slice(b"/f");
+    data[16..24].copy_from_slice(b"/missing");
+    for (i, (addr, len)) in [(BYTES, 2u64), (BYTES + 8, 3)].into_iter().enumerate() {
+        data[32 + 16 * i..][..8].copy_from_slice(&(addr as u64).to_le_bytes());
+        data[40 + 16 * i..][..8].copy_from_slice(&len.to_le_bytes());
+    }
+    data[64..66].copy_from_slice(b"ab");
+    data[72..75].copy_from_slice(b"cd\n");
+
+    let (tx, rx) = mpsc::channel();
+    let mut kernel = MockLinux64::new(false).with_tracer(tx);
+    kernel.fs_mut().add_file("/f", *b"hello\n");
+    let mut m = load64_with(kernel, &text, &data);
+    m.run().unwrap();
+    assert!(
+        matches!(m.termination(), Some(TerminationReason::Ebreak { .. })),
+        "{:?}",
+        m.termination()
+    );
+    rx.try_iter().collect()
+}
+
+fn symbolic(value: u32, text: &str) -> ArgValue {
+    ArgValue::Symbolic {
+        value: value as u64,
+        text: text.to_string(),
+    }
+}
+
+fn string(bytes: &[u8]) -> ArgValue {
+    ArgValue::Str {
+        bytes: bytes.to_vec(),
+        truncated: false,
+    }
+}
+
+#[test]
+fn decoded_and_rendered() {
+    let mut text = syscall(OPENAT, &[AT_FDCWD, FILE, O_CLOEXEC, 0], Reg::S1);
+    text.extend(syscall(READ, &[3, BUF, 64], Reg::S2));
+    text.extend(syscall(WRITEV, &[1, IOVS, 2], Reg::S3));
+    text.extend(syscall(OPENAT, &[AT_FDCWD, MISSING, 0, 0], Reg::S4));
+    let events = trace(&text);
+    let [openat, read, writev, missing] = &events[..] else {
+        panic!("{events:#?}");
+    };
+
+    let at_fdcwd = ArgValue::Fd(AT_FDCWD as i32);
+    assert_eq!(openat.name, Some("openat"));
+    assert_eq!(
+        openat.args,
+        [
+            at_fdcwd.clone(),
+            string(b"/f"),
+            symbolic(O_CLOEXEC, "O_RDONLY|O_CLOEXEC"),
+            symbolic(0, "0o0"),
+        ]
+    );
+    assert_eq!(openat.ret, Some(Ok(3)));
+    assert_eq!(
+        openat.to_string(),
+        r#"openat(AT_FDCWD, "/f", O_RDONLY|O_CLOEXEC, 0o0) = 3"#
+    );
+
+    // The buffer as the kernel left it, only as far as it wrote.
+    assert_eq!(
+        read.args,
+        [ArgValue::Fd(3), string(b"hello\n"), ArgValue::UInt(64)]
+    );
+    assert_eq!(read.ret, Some(Ok(6)));
+    assert_eq!(read.to_string(), r#"read(3, "hello\n", 64) = 6"#);
+
+    let iov = |bytes: &[u8]| {
+        ArgValue::Struct(vec![
+            ("iov_base", string(bytes)),
+            ("iov_len", ArgValue::UInt(bytes.len() as u64)),
+        ])
+    };
+    assert_eq!(
+        writev.args,
+        [
+            ArgValue::Fd(1),
+            ArgValue::Array(vec![iov(b"ab"), iov(b"cd\n")], false),
+            ArgValue::Int(2),
+        ]
+    );
+    assert_eq!(writev.ret, Some(Ok(5)));
+    assert_eq!(
+        writev.to_string(),
+        r#"writev(1, [{iov_base="ab", iov_len=2}, {iov_base="cd\n", iov_len=3}], 2) = 5"#
+    );
+
+    assert_eq!(
+        missing.args,
+        [
+            at_fdcwd,
+            string(b"/missing"),
+            symbolic(0, "O_RDONLY"),
+            symbolic(0, "0o0"),
+        ]
+    );
+    assert_eq!(missing.ret, Some(Err(ENOENT)));
+    assert_eq!(
+        missing.to_string(),
+        r#"openat(AT_FDCWD, "/missing", O_RDONLY, 0o0) = -1 ENOENT (No such file or directory)"#
+    );
+}
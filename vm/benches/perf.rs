@@ -66,7 +66,10 @@ fn roundtrip_setup_bench(c: &mut Criterion) {
     let file = Path::new(env!("CARGO_MANIFEST_DIR"))
         .join("../riscv/roundtrip/target/riscv32imac-unknown-linux-musl/release/roundtrip");
     let Ok(elf) = std::fs::read(&file) else {
-        eprintln!("skipping roundtrip_setup_bench: {} not built", file.display());
+        eprintln!(
+            "skipping roundtrip_setup_bench: {} not built",
+            file.display()
+        );
         return;
     };
 
@@ -88,7 +91,10 @@ fn roundtrip_exec_bench(c: &mut Criterion) {
     let file = Path::new(env!("CARGO_MANIFEST_DIR"))
         .join("../riscv/roundtrip/target/riscv32imac-unknown-linux-musl/release/roundtrip");
     let Ok(elf) = std::fs::read(&file) else {
-        eprintln!("skipping roundtrip_exec_bench: {} not built", file.display());
+        eprintln!(
+            "skipping roundtrip_exec_bench: {} not built",
+            file.display()
+        );
         return;
     };
 
@@ -0,0 +1,125 @@
+//! Async syscalls: runs a guest ELF under [`run_async`], with its reads of
+//! stdin suspended by the syscall policy and served by a host future (here a
+//! timer standing in for network or disk I/O), one argument per line.
+//!
+//! Width-generic: the ELF class byte picks the rv32 or rv64 machine.
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::{Duration, Instant};
+
+use riscv_kernel_linux::{
+    run_async, syscalls, Action, ArgMatch, KernelXlen, LinuxError, MockLinux, SyscallPolicy,
+};
+use riscv_vm::hart::{X32, X64};
+use riscv_vm::machine::{Kernel, Machine};
+use riscv_vm::memory::Memory;
+
+const EFAULT: i32 = 14;
+
+/// How long each line takes to "arrive".
+const LATENCY: Duration = Duration::from_millis(20);
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let path = args.next().expect("usage: async_stdin <elf> [line...]");
+    let mut lines: VecDeque<String> = args.collect();
+    if lines.is_empty() {
+        lines.extend(["hello".into(), "from the host".into()]);
+    }
+    let elf = std::fs::read(&path).expect("Failed to read ELF file");
+
+    match elf.get(4) {
+        Some(1) => run::<X32>(&elf, lines, syscalls::riscv32::Sysno::read),
+        Some(2) => run::<X64>(&elf, lines, syscalls::riscv64::Sysno::read),
+        other => panic!("unrecognized ELF class: {other:?}"),
+    }
+}
+
+fn run<X: KernelXlen>(elf: &[u8], mut lines: VecDeque<String>, read: X::Sysno)
+where
+    MockLinux<X>: Kernel<Xlen = X, Memory = X::Memory, Error = LinuxError>,
+{
+    let policy = SyscallPolicy::new(Action::Allow).rule_when(
+        read,
+        [ArgMatch::Eq { arg: 0, value: 0 }],
+        Action::Suspend,
+    );
+    let mut m = Machine::new(MockLinux::<X>::new(true).with_policy(policy));
+    m.kernel
+        .load_static_elf(&mut m.hart, &mut m.mem, elf, &[], &[]);
+
+    let t0 = Instant::now();
+    let mut pending = Vec::new();
+    let mut reads = 0;
+    let res = block_on(run_async(&mut m, async |m, call| {
+        reads += 1;
+        if pending.is_empty() {
+            let Some(line) = lines.pop_front() else {
+                return Ok(0);
+            };
+            sleep(LATENCY).await;
+            pending = format!("{line}\n").into_bytes();
+        }
+        let n = pending.len().min(call.args[2] as usize);
+        m.mem
+            .copy_to(call.args[1], &pending[..n])
+            .map_err(|_| EFAULT)?;
+        pending.drain(..n);
+        Ok(n as u64)
+    }));
+
+    eprintln!(
+        "{res:?}: {reads} suspended reads, {} insts, {:?} in {:.1?}",
+        m.hart.inst_count,
+        m.termination(),
+        t0.elapsed()
+    );
+}
+
+/// Ready once `after` has passed, woken by a helper thread.
+async fn sleep(after: Duration) {
+    let waker: Arc<Mutex<Option<Waker>>> = Arc::default();
+    let deadline = Instant::now() + after;
+    let wake = waker.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(after);
+        if let Some(waker) = wake.lock().unwrap().take() {
+            waker.wake();
+        }
+    });
+    std::future::poll_fn(|cx| {
+        if Instant::now() >= deadline {
+            return Poll::Ready(());
+        }
+        *waker.lock().unwrap() = Some(cx.waker().clone());
+        // Registered after the check: the helper may have missed it.
+        if Instant::now() >= deadline {
+            return Poll::Ready(());
+        }
+        Poll::Pending
+    })
+    .await
+}
+
+/// The smallest executor: poll on this thread, parking between wakeups.
+fn block_on<F: Future>(future: F) -> F::Output {
+    struct Unpark(std::thread::Thread);
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+    loop {
+        if let Poll::Ready(out) = future.as_mut().poll(&mut cx) {
+            return out;
+        }
+        std::thread::park();
+    }
+}
@@ -3,10 +3,11 @@
 //! the hart's retired-instruction counter.
 //!
 //! Width-generic: the ELF class byte picks the rv32 or rv64 machine.
+//! `BACKEND=jit` runs under [`Backend::Jit`].
 use std::time::Instant;
 
 use riscv_kernel_linux::{KernelXlen, MockLinux};
-use riscv_vm::hart::{X32, X64};
+use riscv_vm::hart::{Backend, X32, X64};
 use riscv_vm::machine::{Kernel, Machine};
 
 fn median(mut xs: Vec<f64>) -> f64 {
@@ -24,6 +25,11 @@ where
     let mut insts = 0u64;
     let mut exit_code = None;
 
+    let backend = match std::env::var("BACKEND").as_deref() {
+        Ok("jit") => Backend::Jit,
+        _ => Backend::Interpreter,
+    };
+
     let profiler = std::env::var("FLAMEGRAPH").ok().map(|out| {
         (
             pprof::ProfilerGuardBuilder::default()
@@ -37,6 +43,7 @@ where
     for _ in 0..iters {
         let t0 = Instant::now();
         let mut machine = Machine::new(MockLinux::<X>::new(false));
+        machine.hart.backend = backend;
         let t1 = Instant::now();
         machine
             .kernel
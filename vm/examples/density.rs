@@ -52,11 +52,7 @@ where
     let t1 = std::time::Instant::now();
     let mut executed = 0u64;
     for m in &mut machines {
-        for _ in 0..steps {
-            if m.step().is_err() || !m.state.is_running() {
-                break;
-            }
-        }
+        let _ = m.run_for(steps);
         executed += m.hart.inst_count;
     }
     let dt = t1.elapsed().as_secs_f64();
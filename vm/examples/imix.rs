@@ -1,15 +1,26 @@
 //! Dynamic instruction-mix probe: fraction of compressed (16-bit) vs
 //! full-width encodings actually executed, per guest. Decode cost differs
 //! between the two paths, so mix differences show up as MIPS differences.
+//! Also the most frequent back-to-back pairs of mnemonics, the candidates
+//! for the interpreter's macro-op fusion.
+use std::collections::HashMap;
+
 use riscv_kernel_linux::{KernelXlen, MockLinux};
-use riscv_vm::hart::{X32, X64, Xlen};
+use riscv_vm::hart::{X32, X64};
 use riscv_vm::machine::{Kernel, Machine};
 use riscv_vm::memory::Memory;
+use riscv_vm::riscv_inst::codegen::{rv32imasc::Rv32IMASC, rv64imasc::Rv64IMASC};
+
+/// Pairs reported.
+const TOP_PAIRS: usize = 10;
 
 fn main() {
     let mut args = std::env::args().skip(1);
     let path = args.next().expect("usage: imix <elf> [n_insts]");
-    let n: u64 = args.next().map(|s| s.parse().unwrap()).unwrap_or(20_000_000);
+    let n: u64 = args
+        .next()
+        .map(|s| s.parse().unwrap())
+        .unwrap_or(20_000_000);
     let elf = std::fs::read(&path).expect("Failed to read ELF file");
 
     match elf.get(4) {
@@ -29,11 +40,21 @@ where
 
     let mut compressed = 0u64;
     let mut executed = 0u64;
+    let mut pairs = HashMap::<(String, String), u64>::new();
+    let mut last = None;
     while executed < n && m.state.is_running() {
         let inst: u32 = m.mem.load(m.hart.pc).unwrap();
         if inst & 0b11 != 0b11 {
             compressed += 1;
         }
+        let name = match X::BITS {
+            32 => Rv32IMASC::parse(inst).map(|op| op.to_string()),
+            _ => Rv64IMASC::parse(inst).map(|op| op.to_string()),
+        }
+        .unwrap_or_else(|| "?".into());
+        if let Some(last) = last.replace(name.clone()) {
+            *pairs.entry((last, name)).or_default() += 1;
+        }
         executed += 1;
         if m.step().is_err() {
             break;
@@ -44,4 +65,12 @@ where
         X::BITS,
         compressed as f64 / executed as f64 * 100.0
     );
+    let mut pairs: Vec<_> = pairs.into_iter().collect();
+    pairs.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+    for ((a, b), count) in pairs.into_iter().take(TOP_PAIRS) {
+        println!(
+            "  {a} + {b}: {:.1}%",
+            count as f64 / executed as f64 * 100.0
+        );
+    }
 }
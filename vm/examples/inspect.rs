@@ -0,0 +1,93 @@
+//! Concurrent inspection of a paused machine: runs a guest ELF for a
+//! budget, then checksums its loaded segments a page at a time from several
+//! threads at once through [`Machine::inspect`], checking the result against
+//! one thread's.
+//!
+//! Width-generic: the ELF class byte picks the rv32 or rv64 machine.
+use riscv_kernel_linux::{KernelXlen, MockLinux};
+use riscv_vm::hart::{X32, X64};
+use riscv_vm::machine::{Inspect, Kernel, Machine};
+use riscv_vm::memory::Memory;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let path = args
+        .next()
+        .expect("usage: inspect <elf> [budget] [threads]");
+    let budget: u64 = args.next().map(|s| s.parse().unwrap()).unwrap_or(1_000_000);
+    let threads: usize = args.next().map(|s| s.parse().unwrap()).unwrap_or(4);
+    let elf = std::fs::read(&path).expect("Failed to read ELF file");
+
+    match elf.get(4) {
+        Some(1) => run::<X32>(&path, &elf, budget, threads),
+        Some(2) => run::<X64>(&path, &elf, budget, threads),
+        other => panic!("unrecognized ELF class: {other:?}"),
+    }
+}
+
+/// Bytes each checksum covers.
+const CHUNK: u64 = 4096;
+
+/// FNV-1a over guest `[start, end)`.
+fn checksum<K: Kernel>(m: Inspect<'_, K>, start: u64, end: u64) -> u64 {
+    let bytes = m
+        .mem
+        .slice::<u8>(start, end - start)
+        .expect("segment unmapped");
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |h, &b| {
+        (h ^ b as u64).wrapping_mul(0x100_0000_01b3)
+    })
+}
+
+fn run<X: KernelXlen>(path: &str, elf: &[u8], budget: u64, threads: usize)
+where
+    MockLinux<X>: Kernel<Xlen = X, Memory = X::Memory>,
+    X::Memory: Sync,
+{
+    let mut m = Machine::new(MockLinux::<X>::new(false));
+    let segments: Vec<_> = m
+        .kernel
+        .load_static_elf(&mut m.hart, &mut m.mem, elf, &[], &[])
+        .program_headers
+        .iter()
+        .filter(|ph| ph.p_type == goblin::elf::program_header::PT_LOAD && ph.p_memsz > 0)
+        .map(|ph| (ph.p_vaddr, ph.p_vaddr + ph.p_memsz))
+        .collect();
+    let _ = m.run_for(budget);
+    let chunks: Vec<_> = segments
+        .iter()
+        .flat_map(|&(start, end)| {
+            (start..end)
+                .step_by(CHUNK as usize)
+                .map(move |at| (at, end.min(at + CHUNK)))
+        })
+        .collect();
+
+    let view = m.inspect();
+    let alone: Vec<_> = chunks
+        .iter()
+        .map(|&(start, end)| checksum(view, start, end))
+        .collect();
+    let mut shared = vec![0; chunks.len()];
+    let per_thread = chunks.len().div_ceil(threads).max(1);
+    std::thread::scope(|s| {
+        for (i, sums) in shared.chunks_mut(per_thread).enumerate() {
+            let chunks = &chunks[i * per_thread..];
+            s.spawn(move || {
+                for (sum, &(start, end)) in sums.iter_mut().zip(chunks) {
+                    *sum = checksum(view, start, end);
+                }
+            });
+        }
+    });
+
+    println!(
+        "{path}: rv{} paused at pc {:#x} after {} insts ({:?}); {} chunks on {threads} threads: {}",
+        <X as riscv_vm::hart::Xlen>::BITS,
+        X::to_u64(view.hart.pc),
+        view.hart.inst_count,
+        view.state,
+        chunks.len(),
+        if shared == alone { "match" } else { "MISMATCH" },
+    );
+}
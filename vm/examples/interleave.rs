@@ -11,7 +11,10 @@ use riscv_vm::machine::Machine;
 fn main() {
     let mut args = std::env::args().skip(1);
     let path = args.next().expect("usage: interleave <elf> [n_steps]");
-    let n: u64 = args.next().map(|s| s.parse().unwrap()).unwrap_or(20_000_000);
+    let n: u64 = args
+        .next()
+        .map(|s| s.parse().unwrap())
+        .unwrap_or(20_000_000);
     let elf = std::fs::read(&path).expect("Failed to read ELF file");
 
     let mk = || {
@@ -0,0 +1,65 @@
+//! Differential check: an rv32 guest run under the JIT backend must end as
+//! it does under the interpreter -- same exit code, retired-instruction
+//! count, registers and pc. `JIT_THRESHOLD` sets [`Hart::jit_threshold`]
+//! (1 compiles every block on first entry).
+//!
+//! [`Hart::jit_threshold`]: riscv_vm::hart::Hart::jit_threshold
+use riscv_kernel_linux::MockLinux32;
+use riscv_vm::hart::Backend;
+use riscv_vm::machine::Machine;
+
+fn run(elf: &[u8], backend: Backend) -> Machine<MockLinux32> {
+    let mut machine = Machine::new(MockLinux32::new(false));
+    machine.hart.backend = backend;
+    if let Ok(threshold) = std::env::var("JIT_THRESHOLD") {
+        machine.hart.jit_threshold = threshold.parse().expect("JIT_THRESHOLD is a count");
+    }
+    machine
+        .kernel
+        .load_static_elf(&mut machine.hart, &mut machine.mem, elf, &[], &[]);
+    if let Err(e) = machine.run() {
+        println!("{backend:?}: {e}");
+    }
+    machine
+}
+
+fn main() {
+    let mut failed = false;
+    for path in std::env::args().skip(1) {
+        let elf = std::fs::read(&path).expect("Failed to read ELF file");
+        let interp = run(&elf, Backend::Interpreter);
+        let jit = run(&elf, Backend::Jit);
+
+        let mut diffs = Vec::new();
+        let (a, b) = (interp.kernel.exit_code(), jit.kernel.exit_code());
+        if a != b {
+            diffs.push(format!("exit {a:?} != {b:?}"));
+        }
+        let (a, b) = (interp.hart.inst_count, jit.hart.inst_count);
+        if a != b {
+            diffs.push(format!("insts {a} != {b}"));
+        }
+        let (a, b) = (interp.hart.pc, jit.hart.pc);
+        if a != b {
+            diffs.push(format!("pc {a:#x} != {b:#x}"));
+        }
+        for ((reg, a), (_, b)) in interp.hart.regs().zip(jit.hart.regs()) {
+            if a != b {
+                diffs.push(format!("{reg:?} {a:#x} != {b:#x}"));
+            }
+        }
+        if diffs.is_empty() {
+            println!(
+                "{path}: ok (exit={:?} insts={})",
+                interp.kernel.exit_code(),
+                interp.hart.inst_count
+            );
+        } else {
+            println!("{path}: DIFFER {}", diffs.join(", "));
+            failed = true;
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,99 @@
+//! Memory-mapped I/O: a bare-metal rv32 guest prints a string through a
+//! 16550-style UART mapped above its RAM, polling the line status register
+//! before each byte, then halts on `ecall`.
+//!
+//! The program is embedded (no ELF): it is copied to `ENTRY` and the
+//! string to `MSG`, and the UART captures what the guest transmits.
+use std::convert::Infallible;
+
+use riscv_vm::error::MachineError;
+use riscv_vm::hart::{Hart, X32};
+use riscv_vm::machine::{Kernel, Machine, StepResult};
+use riscv_vm::memory::{Device, Fault, Memory, Mmio, Window32};
+
+const RAM: u64 = 1 << 20;
+const UART: u64 = 0x1000_0000;
+const ENTRY: u64 = 0x1000;
+const MSG: u64 = 0x2000;
+
+const PROGRAM: [u32; 11] = [
+    0x1000_0537, // lui   a0, 0x10000       # UART
+    0x0000_25b7, // lui   a1, 0x2           # MSG
+    0x0005_c603, // loop: lbu a2, 0(a1)
+    0x0006_0e63, //       beqz a2, done
+    0x0055_4283, // wait: lbu t0, 5(a0)     # line status
+    0x0202_f293, //       andi t0, t0, 0x20 # transmitter empty
+    0xfe02_8ce3, //       beqz t0, wait
+    0x00c5_0023, //       sb a2, 0(a0)      # transmit
+    0x0015_8593, //       addi a1, a1, 1
+    0xfe5f_f06f, //       j loop
+    0x0000_0073, // done: ecall
+];
+
+/// Transmit holding register at 0, line status at 5; the transmitter is
+/// busy on every other status read, so the guest has to poll.
+#[derive(Default)]
+struct Uart {
+    out: Vec<u8>,
+    polls: u64,
+}
+
+impl Device for Uart {
+    fn read(&mut self, offset: u64, _size: usize) -> Result<u64, Fault> {
+        match offset {
+            5 => {
+                self.polls += 1;
+                Ok(if self.polls.is_multiple_of(2) {
+                    0x20
+                } else {
+                    0
+                })
+            }
+            0..8 => Ok(0),
+            _ => Err(Fault),
+        }
+    }
+
+    fn write(&mut self, offset: u64, _size: usize, val: u64) -> Result<(), Fault> {
+        match offset {
+            0 => self.out.push(val as u8),
+            1..8 => {}
+            _ => return Err(Fault),
+        }
+        Ok(())
+    }
+}
+
+/// No OS: `ecall` halts.
+struct BareMetal;
+
+impl Kernel for BareMetal {
+    type Xlen = X32;
+    type Memory = Mmio<Window32>;
+    type Error = Infallible;
+
+    fn syscall(
+        &mut self,
+        _hart: &mut Hart<X32>,
+        _mem: &mut Mmio<Window32>,
+    ) -> Result<StepResult, MachineError<Infallible>> {
+        Ok(StepResult::Halt)
+    }
+}
+
+fn main() {
+    let mem = Mmio::new(Window32::new(RAM)).with_device(UART, 8, Uart::default());
+    let mut m = Machine::with_memory(BareMetal, mem);
+    m.mem.copy_to(ENTRY, &PROGRAM).unwrap();
+    m.mem.copy_to(MSG, b"hello from mmio\n\0").unwrap();
+    m.hart.pc = ENTRY as u32;
+
+    m.run().expect("guest failed");
+
+    let uart = m.mem.device::<Uart>(UART).unwrap();
+    print!("{}", String::from_utf8_lossy(&uart.out));
+    println!(
+        "{} instructions, {} status polls",
+        m.hart.inst_count, uart.polls
+    );
+}
@@ -0,0 +1,66 @@
+//! The portable backend: runs a guest ELF to completion on its width's
+//! arena and again on [`Paged`] memory, and checks the two runs agree
+//! (termination, retired instructions, final pc), reporting both speeds.
+//!
+//! Width-generic: the ELF class byte picks the rv32 or rv64 machine.
+use std::time::Instant;
+
+use riscv_kernel_linux::{KernelXlen, MockLinux};
+use riscv_vm::hart::{Xlen, X32, X64};
+use riscv_vm::machine::{Kernel, Machine, MachineState};
+use riscv_vm::memory::{Memory, Paged};
+
+fn main() {
+    let path = std::env::args().nth(1).expect("usage: paged <elf>");
+    let elf = std::fs::read(&path).expect("Failed to read ELF file");
+
+    match elf.get(4) {
+        Some(1) => run::<X32>(&path, &elf, Paged::<u32>::new()),
+        Some(2) => run::<X64>(&path, &elf, Paged::<u64>::default()),
+        other => panic!("unrecognized ELF class: {other:?}"),
+    }
+}
+
+/// Run to completion, returning what the two backends must agree on and
+/// the time taken.
+fn outcome<X: KernelXlen, M: Memory>(elf: &[u8], mem: M) -> (String, f64)
+where
+    MockLinux<X, M>: Kernel<Xlen = X, Memory = M>,
+{
+    let mut m = Machine::with_memory(MockLinux::<X, M>::new(false), mem);
+    m.kernel
+        .load_static_elf(&mut m.hart, &mut m.mem, elf, &[], &[]);
+    let t0 = Instant::now();
+    let mut res = m.run();
+    while res.is_ok() && m.state == MachineState::Blocked {
+        res = m.run();
+    }
+    let dt = t0.elapsed().as_secs_f64();
+    let summary = m.summary();
+    let seen = format!(
+        "{:?} after {} insts at pc {:#x}{}",
+        summary.termination,
+        summary.instructions,
+        X::to_u64(m.hart.pc),
+        res.err().map_or(String::new(), |e| format!(" ({e})")),
+    );
+    (seen, summary.instructions as f64 / dt / 1e6)
+}
+
+fn run<X: KernelXlen>(path: &str, elf: &[u8], paged: Paged<<X as Xlen>::U>)
+where
+    MockLinux<X>: Kernel<Xlen = X, Memory = X::Memory>,
+    MockLinux<X, Paged<<X as Xlen>::U>>: Kernel<Xlen = X, Memory = Paged<<X as Xlen>::U>>,
+{
+    let (arena, arena_mips) = outcome::<X, _>(elf, X::Memory::default());
+    let (pages, paged_mips) = outcome::<X, _>(elf, paged);
+    println!(
+        "{path}: rv{} {arena}; arena {arena_mips:.1} MIPS, paged {paged_mips:.1} MIPS: {}",
+        <X as Xlen>::BITS,
+        if pages == arena {
+            "match".to_string()
+        } else {
+            format!("MISMATCH (paged: {pages})")
+        },
+    );
+}
@@ -0,0 +1,89 @@
+//! Host-parallel throughput: runs N copies of a guest ELF to completion on a
+//! [`Scheduler`], and checks each against a lone sequential run (exit code
+//! and retired instructions must match).
+//!
+//! Width-generic: the ELF class byte picks the rv32 or rv64 machine.
+//! `THREADS` and `QUANTUM` override the scheduler's defaults.
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use riscv_kernel_linux::{KernelXlen, MockLinux};
+use riscv_vm::hart::{X32, X64};
+use riscv_vm::machine::{Kernel, Machine, MachineState};
+use riscv_vm::sched::{Scheduler, SchedulerConfig};
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let path = args.next().expect("usage: pool <elf> [n]");
+    let n: usize = args.next().map(|s| s.parse().unwrap()).unwrap_or(64);
+    let elf = std::fs::read(&path).expect("Failed to read ELF file");
+
+    match elf.get(4) {
+        Some(1) => run::<X32>(&path, &elf, n),
+        Some(2) => run::<X64>(&path, &elf, n),
+        other => panic!("unrecognized ELF class: {other:?}"),
+    }
+}
+
+fn machine<X: KernelXlen>(elf: &[u8]) -> Machine<MockLinux<X>>
+where
+    MockLinux<X>: Kernel<Xlen = X, Memory = X::Memory>,
+{
+    let mut m = Machine::new(MockLinux::<X>::new(false));
+    m.kernel
+        .load_static_elf(&mut m.hart, &mut m.mem, elf, &[], &[]);
+    m
+}
+
+fn run<X: KernelXlen>(path: &str, elf: &[u8], n: usize)
+where
+    MockLinux<X>: Kernel<Xlen = X, Memory = X::Memory> + Send + 'static,
+    X::Memory: Send,
+{
+    let mut config = SchedulerConfig::default();
+    if let Some(threads) = std::env::var("THREADS").ok().and_then(|s| s.parse().ok()) {
+        config.threads = threads;
+    }
+    if let Some(quantum) = std::env::var("QUANTUM").ok().and_then(|s| s.parse().ok()) {
+        config.quantum = quantum;
+    }
+
+    let mut alone = machine::<X>(elf);
+    let t0 = Instant::now();
+    alone.run().expect("Failed to run");
+    while alone.state == MachineState::Blocked {
+        alone.run().expect("Failed to run");
+    }
+    let dt_alone = t0.elapsed().as_secs_f64();
+    let expect = (alone.kernel.exit_code(), alone.hart.inst_count);
+
+    let results = Arc::new(Mutex::new(Vec::with_capacity(n)));
+    let machines: Vec<_> = (0..n).map(|_| machine::<X>(elf)).collect();
+    let t1 = Instant::now();
+    let pool = Scheduler::new(config.clone());
+    for m in machines {
+        let results = results.clone();
+        pool.spawn(m, move |m, res| {
+            res.expect("Failed to run");
+            results
+                .lock()
+                .unwrap()
+                .push((m.kernel.exit_code(), m.hart.inst_count));
+        });
+    }
+    pool.join();
+    let dt = t1.elapsed().as_secs_f64();
+
+    let results = results.lock().unwrap();
+    let mismatched = results.iter().filter(|&&r| r != expect).count();
+    let total: u64 = results.iter().map(|r| r.1).sum();
+    println!(
+        "{path}: rv{} x{n} on {} threads (quantum {}): {:.1} MIPS aggregate, {:.2}x one machine's, {mismatched} mismatched",
+        <X as riscv_vm::hart::Xlen>::BITS,
+        config.threads,
+        config.quantum,
+        total as f64 / dt / 1e6,
+        total as f64 / dt / (expect.1 as f64 / dt_alone),
+    );
+    assert_eq!(results.len(), n, "machines lost");
+}
@@ -1,10 +1,14 @@
-use std::collections::BTreeMap;
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
 
 use clap::Parser;
-use riscv_kernel_linux::MockLinux32;
+use riscv_kernel_linux::{Entropy, Limits, MockLinux, Strace};
 use riscv_vm::{
-    machine::{Machine, MachineState},
-    memory::Memory,
+    hart::{Backend, X32},
+    machine::{Machine, MachineState, TerminationReason},
+    memory::{Memory, Memory32, Window32},
     riscv_inst::Reg,
 };
 
@@ -17,8 +21,38 @@ struct Args {
     breakpoints: Vec<u32>,
     #[clap(short, long, default_value_t = false)]
     debug: bool,
+    /// Seed guest randomness (getrandom, AT_RANDOM) for reproducible runs
+    #[clap(long)]
+    seed: Option<u64>,
+    /// Print each syscall with decoded arguments to stderr
+    #[clap(long)]
+    strace: bool,
+    /// Cap guest memory (image, heap, mappings and stack) in bytes
+    #[clap(long)]
+    max_memory: Option<u64>,
+    /// Cap open file descriptors
+    #[clap(long)]
+    max_fds: Option<u64>,
+    /// Cap the size a write may grow a file to (RLIMIT_FSIZE)
+    #[clap(long)]
+    max_file_size: Option<u64>,
+    /// Cap bytes the guest may write, across all descriptors
+    #[clap(long)]
+    max_output: Option<u64>,
+    /// Cap instructions the guest may retire
+    #[clap(long)]
+    max_instructions: Option<u64>,
+    /// Compile hot code to native code (x86_64 Linux hosts only)
+    #[clap(long)]
+    jit: bool,
+    /// Map only the low bytes of the guest address space (default: all 4 GiB)
+    #[clap(long)]
+    address_space: Option<u64>,
 }
 
+/// Longest sleep between retries of a blocked guest.
+const MAX_NAP: Duration = Duration::from_millis(10);
+
 fn maybe_hex(s: &str) -> Result<u32, std::num::ParseIntError> {
     if s.starts_with("0x") {
         u32::from_str_radix(s.trim_start_matches("0x"), 16)
@@ -34,22 +68,89 @@ fn main() {
         .init();
 
     let args = Args::parse();
-    let elf = std::fs::read(&args.elf_path).expect("Failed to read ELF file");
+    match args.address_space {
+        Some(size) => run(
+            &args,
+            Machine::with_memory(kernel(&args), Window32::new(size)),
+        ),
+        None => run(&args, Machine::new(kernel::<Memory32>(&args))),
+    }
+}
 
-    let filename = args.elf_path.split('/').last().unwrap();
+fn kernel<M: Memory>(args: &Args) -> MockLinux<X32, M> {
+    let mut kernel = MockLinux::new(true).with_limits(Limits {
+        memory: args.max_memory,
+        open_files: args.max_fds,
+        file_size: args.max_file_size,
+        output_bytes: args.max_output,
+        instructions: args.max_instructions,
+        ..Limits::default()
+    });
+    if let Some(seed) = args.seed {
+        kernel = kernel.with_entropy(Entropy::seeded(seed));
+    }
+    if args.strace {
+        kernel = kernel.with_tracer(Strace::new(std::io::stderr()));
+    }
+    kernel
+}
 
-    let mut machine = Machine::new(MockLinux32::new(true));
+fn run<M: Memory<Addr = u32>>(args: &Args, mut machine: Machine<MockLinux<X32, M>>) {
+    let elf = std::fs::read(&args.elf_path).expect("Failed to read ELF file");
+    let filename = args.elf_path.split('/').next_back().unwrap();
+
+    if args.jit {
+        machine.hart.backend = Backend::Jit;
+    }
     let elf =
         machine
             .kernel
             .load_static_elf(&mut machine.hart, &mut machine.mem, &elf, &[filename], &[]);
 
     if args.debug {
-        let mut debugger = Debugger::new(machine, elf, args.breakpoints);
+        let mut debugger = Debugger::new(machine, elf, args.breakpoints.clone());
 
         debugger.run();
     } else {
-        machine.run().expect("Failed to run");
+        let mut result = machine.run();
+        // Nothing outside the guest feeds it here, so a parked guest can
+        // only wake on a timeout: nap until then, retrying now and again.
+        while result.is_ok() && machine.state == MachineState::Blocked {
+            let nap = machine.kernel.blocked_until().map_or(MAX_NAP, |deadline| {
+                deadline
+                    .saturating_duration_since(Instant::now())
+                    .min(MAX_NAP)
+            });
+            std::thread::sleep(nap);
+            result = machine.run();
+        }
+        let summary = machine.summary();
+        tracing::info!(
+            "Instructions executed: {}, peak memory: {} KiB",
+            summary.instructions,
+            summary.peak_memory.unwrap_or(0) / 1024,
+        );
+        // Exit as a shell reports a process: its status, or 128 plus the
+        // killing signal.
+        let status = match &summary.termination {
+            Some(TerminationReason::Exited(code) | TerminationReason::ExitGroup(code)) => *code,
+            Some(reason) => {
+                eprintln!("riscuit: guest {reason}");
+                match reason {
+                    TerminationReason::Signaled(signal) => 128 + signal,
+                    _ => 1,
+                }
+            }
+            None => {
+                if let Err(e) = result {
+                    eprintln!("riscuit: {e}");
+                    1
+                } else {
+                    0
+                }
+            }
+        };
+        std::process::exit(status);
     }
 }
 
@@ -58,16 +159,20 @@ enum Mode {
     Debugging,
 }
 
-struct Debugger {
-    machine: Machine<MockLinux32>,
+struct Debugger<M: Memory<Addr = u32>> {
+    machine: Machine<MockLinux<X32, M>>,
     mode: Mode,
     syms: BTreeMap<u32, String>,
     last_sym: Option<String>,
     breakpoints: Vec<u32>,
 }
 
-impl Debugger {
-    pub fn new(machine: Machine<MockLinux32>, elf: goblin::elf::Elf, breakpoints: Vec<u32>) -> Self {
+impl<M: Memory<Addr = u32>> Debugger<M> {
+    pub fn new(
+        machine: Machine<MockLinux<X32, M>>,
+        elf: goblin::elf::Elf,
+        breakpoints: Vec<u32>,
+    ) -> Self {
         let syms = elf
             .syms
             .iter()